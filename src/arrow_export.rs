@@ -0,0 +1,192 @@
+// Standard library imports
+use std::collections::HashMap;
+use std::fs::File;
+use std::sync::Arc;
+
+// External crate imports
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema};
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::batch::rank_many;
+use crate::error::GraphManipulationError;
+use crate::graph::{GraphSingleton, GraphSnapshot};
+use crate::lib_graph::NodeId;
+use crate::loader::require_superuser;
+use crate::rating::Iterations;
+
+/// Writes a single record batch to `path` as an Arrow IPC file (Feather v2).
+fn write_ipc_file(
+    path: &str,
+    columns: Vec<(&str, DataType, ArrayRef)>,
+) -> Result<usize, GraphManipulationError> {
+    let write_failure = |e: &dyn std::fmt::Display| {
+        GraphManipulationError::FileWriteFailure(format!("{}: {}", path, e))
+    };
+
+    let schema = Arc::new(Schema::new(
+        columns
+            .iter()
+            .map(|(name, data_type, _)| Field::new(*name, data_type.clone(), false))
+            .collect::<Vec<Field>>(),
+    ));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        columns.into_iter().map(|(_, _, array)| array).collect(),
+    )
+    .map_err(|e| write_failure(&e))?;
+
+    let file = File::create(path).map_err(|e| write_failure(&e))?;
+    let mut writer = FileWriter::try_new(file, &schema).map_err(|e| write_failure(&e))?;
+    writer.write(&batch).map_err(|e| write_failure(&e))?;
+    writer.finish().map_err(|e| write_failure(&e))?;
+
+    Ok(batch.num_rows())
+}
+
+/// Returns the names of the nodes of a snapshot, by ID.
+fn names_by_id(snapshot: &GraphSnapshot) -> HashMap<NodeId, String> {
+    snapshot
+        .node_names
+        .iter()
+        .map(|(name, &id)| (id, name.clone()))
+        .collect()
+}
+
+#[pg_extern]
+/// Writes the edge list to a file on the database server in the Arrow IPC file format,
+/// which pandas (`read_feather`) and polars (`read_ipc`) load directly.
+///
+/// The file has the columns `source` and `destination` (utf8) and `weight` (float64).
+/// Only superusers may call this function, since it writes to the server's file system.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file to write, on the database server. An existing file is
+///   overwritten.
+///
+/// # Returns
+///
+/// The number of edges written.
+pub fn meritrank_export_arrow(path: &str) -> Result<i64, ErrorReport> {
+    require_superuser("meritrank_export_arrow")?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let names = names_by_id(&snapshot);
+
+    let mut edges: Vec<(&str, &str, f64)> = snapshot
+        .graph
+        .all_edges()
+        .into_iter()
+        .map(|(source, target, weight)| {
+            (
+                names.get(&source).map_or("", String::as_str),
+                names.get(&target).map_or("", String::as_str),
+                weight,
+            )
+        })
+        .collect();
+    edges.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    let sources: Vec<&str> = edges.iter().map(|edge| edge.0).collect();
+    let destinations: Vec<&str> = edges.iter().map(|edge| edge.1).collect();
+    let weights: Vec<f64> = edges.iter().map(|edge| edge.2).collect();
+
+    let rows = write_ipc_file(
+        path,
+        vec![
+            (
+                "source",
+                DataType::Utf8,
+                Arc::new(StringArray::from(sources)) as ArrayRef,
+            ),
+            (
+                "destination",
+                DataType::Utf8,
+                Arc::new(StringArray::from(destinations)) as ArrayRef,
+            ),
+            (
+                "weight",
+                DataType::Float64,
+                Arc::new(Float64Array::from(weights)) as ArrayRef,
+            ),
+        ],
+    )?;
+    Ok(rows as i64)
+}
+
+#[pg_extern]
+/// Calculates the ranks for several egos and writes them to a file on the database
+/// server in the Arrow IPC file format.
+///
+/// The file has the columns `ego` and `node` (utf8) and `rank` (float64), one row per
+/// ranked node of every ego. Only superusers may call this function, since it writes to
+/// the server's file system.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file to write, on the database server. An existing file is
+///   overwritten.
+/// * `egos` - The names of the egos to calculate ranks for.
+/// * `iterations` - The number of walks per ego. Defaults to `DEFAULT_ITERATIONS`.
+/// * `parallel` - Whether to split the walks of every ego between one thread per core,
+///   rather than `meritrank.parallelism` threads.
+///
+/// # Returns
+///
+/// The number of rows written.
+pub fn meritrank_export_ranks_arrow(
+    path: &str,
+    egos: Vec<String>,
+    iterations: default!(Option<i32>, "NULL"),
+    parallel: default!(bool, false),
+) -> Result<i64, ErrorReport> {
+    require_superuser("meritrank_export_ranks_arrow")?;
+    let iterations = Iterations::from_nullable(iterations)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_ids = egos
+        .iter()
+        .map(|ego| snapshot.node_id(ego))
+        .collect::<Result<Vec<NodeId>, GraphManipulationError>>()?;
+    let names = names_by_id(&snapshot);
+
+    let mut ego_column: Vec<&str> = Vec::new();
+    let mut node_column: Vec<&str> = Vec::new();
+    let mut rank_column: Vec<f64> = Vec::new();
+    for (ego, ranks) in rank_many(&snapshot, &ego_ids, iterations, parallel)? {
+        let ego_name = names.get(&ego).map_or("", String::as_str);
+        for (node, rank) in ranks {
+            ego_column.push(ego_name);
+            node_column.push(names.get(&node).map_or("", String::as_str));
+            rank_column.push(rank);
+        }
+    }
+
+    let rows = write_ipc_file(
+        path,
+        vec![
+            (
+                "ego",
+                DataType::Utf8,
+                Arc::new(StringArray::from(ego_column)) as ArrayRef,
+            ),
+            (
+                "node",
+                DataType::Utf8,
+                Arc::new(StringArray::from(node_column)) as ArrayRef,
+            ),
+            (
+                "rank",
+                DataType::Float64,
+                Arc::new(Float64Array::from(rank_column)) as ArrayRef,
+            ),
+        ],
+    )?;
+    Ok(rows as i64)
+}
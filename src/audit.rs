@@ -0,0 +1,71 @@
+// Library for PostgreSQL extensions
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::guc;
+use crate::notify::ChangeOp;
+
+extension_sql!(
+    r#"
+CREATE TABLE IF NOT EXISTS meritrank_audit (
+    id bigserial PRIMARY KEY,
+    op text NOT NULL,
+    subject text,
+    object text,
+    old_weight float8,
+    new_weight float8,
+    changed_by text NOT NULL DEFAULT current_user,
+    changed_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS meritrank_audit_subject_idx ON meritrank_audit (subject, changed_at);
+CREATE INDEX IF NOT EXISTS meritrank_audit_object_idx ON meritrank_audit (object, changed_at);
+SELECT pg_catalog.pg_extension_config_dump('meritrank_audit', '');
+SELECT pg_catalog.pg_extension_config_dump('meritrank_audit_id_seq', '');
+"#,
+    name = "create_audit_table"
+);
+
+/// Returns the name of the role the current statement acts for.
+///
+/// That is the current role, except inside `SECURITY DEFINER` functions such as
+/// `meritrank_vote`, whose owner the current role is while they run.
+pub fn acting_role() -> Result<String, GraphManipulationError> {
+    let role = unsafe { pg_sys::GetOuterUserId() };
+    Ok(Spi::get_one_with_args::<String>(
+        "SELECT pg_catalog.pg_get_userbyid($1)::text",
+        vec![(PgBuiltInOids::OIDOID.oid(), role.into_datum())],
+    )?
+    .unwrap_or_default())
+}
+
+/// Records a graph mutation in `meritrank_audit`, unless `meritrank.audit` is off.
+///
+/// The row is inserted in the current transaction with the acting role and time, so
+/// only committed mutations are recorded. Weights that do not apply to the operation,
+/// e.g. the old weight of a new edge, are `NULL`.
+pub fn record_change(
+    op: ChangeOp,
+    subject: Option<&str>,
+    object: Option<&str>,
+    old_weight: Option<f64>,
+    new_weight: Option<f64>,
+) -> Result<(), GraphManipulationError> {
+    if !guc::AUDIT.get() {
+        return Ok(());
+    }
+
+    Spi::run_with_args(
+        "INSERT INTO meritrank_audit (op, subject, object, old_weight, new_weight, changed_by) \
+         VALUES ($1, $2, $3, $4, $5, $6)",
+        Some(vec![
+            (PgBuiltInOids::TEXTOID.oid(), op.name().into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), subject.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), object.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), old_weight.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), new_weight.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), acting_role()?.into_datum()),
+        ]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))
+}
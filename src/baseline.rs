@@ -0,0 +1,141 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::{lock_graph, GraphSingleton};
+use crate::lib_graph::{hits, pagerank};
+use crate::lib_graph::{NodeId, Weight};
+use crate::rating::{DampingFactor, Iterations};
+
+/// Number of power iterations used when `iterations` is passed as `NULL`.
+///
+/// Also used by `hits_calculate`.
+pub const DEFAULT_PAGERANK_ITERATIONS: i32 = 100;
+
+/// Convergence threshold used when `epsilon` is passed as `NULL`.
+pub const DEFAULT_PPR_EPSILON: f64 = 1e-6;
+
+/// Resolves node IDs to names and sorts the scores in descending order.
+fn named_scores(
+    scores: impl IntoIterator<Item = (NodeId, Weight)>,
+) -> Result<Vec<(String, f64)>, GraphManipulationError> {
+    let graph = lock_graph()?;
+    let mut rows: Vec<(String, f64)> = scores
+        .into_iter()
+        .filter_map(|(node_id, score)| Some((graph.get_node_name(node_id)?.to_string(), score)))
+        .collect();
+    rows.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(rows)
+}
+
+#[pg_extern]
+/// Calculates the global (non-personalized) PageRank of every node of the in-memory
+/// graph, as a well-understood baseline to compare MeritRank against.
+///
+/// Only positive edges are followed, in proportion to their weight. The scores sum to 1.
+///
+/// # Arguments
+///
+/// * `damping` - The probability of following an edge rather than jumping to a random
+///   node. Defaults to 0.85.
+/// * `iterations` - The number of power iterations. Defaults to 100.
+///
+/// # Returns
+///
+/// The score of every node, sorted in descending order.
+pub fn pagerank_calculate(
+    damping: default!(Option<f64>, "NULL"),
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<TableIterator<'static, (name!(node, String), name!(score, f64))>, ErrorReport> {
+    // Validate arguments before touching the graph
+    let damping = DampingFactor::from_nullable(damping)?;
+    let iterations = Iterations::new(iterations.unwrap_or(DEFAULT_PAGERANK_ITERATIONS))?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let scores = pagerank::pagerank(&snapshot.graph, damping.get(), iterations.get());
+
+    Ok(TableIterator::new(named_scores(scores)?.into_iter()))
+}
+
+#[pg_extern]
+/// Calculates the personalized PageRank of the peers of `ego` with a deterministic
+/// method, to validate the Monte-Carlo MeritRank results against an exact one on the
+/// same graph.
+///
+/// Only positive edges are followed, in proportion to their weight.
+///
+/// # Arguments
+///
+/// * `ego` - The node the scores are personalized for.
+/// * `damping` - The probability of following an edge rather than restarting at `ego`.
+///   Defaults to 0.85.
+/// * `epsilon` - The residual probability below which a node is not explored further.
+///   Smaller values are more exact and slower. Defaults to 1e-6.
+///
+/// # Returns
+///
+/// The score of every node reached from `ego`, including `ego` itself, sorted in
+/// descending order.
+pub fn ppr_calculate(
+    ego: &str,
+    damping: default!(Option<f64>, "NULL"),
+    epsilon: default!(Option<f64>, "NULL"),
+) -> Result<TableIterator<'static, (name!(node, String), name!(score, f64))>, ErrorReport> {
+    // Validate arguments before touching the graph
+    let damping = DampingFactor::from_nullable(damping)?;
+    let epsilon = epsilon.unwrap_or(DEFAULT_PPR_EPSILON);
+    if !epsilon.is_finite() || epsilon <= 0.0 {
+        return Err(GraphManipulationError::InvalidEpsilon(format!(
+            "{} (must be a positive number)",
+            epsilon
+        ))
+        .into());
+    }
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+    let scores = pagerank::personalized_pagerank(&snapshot.graph, ego_id, damping.get(), epsilon)?;
+
+    Ok(TableIterator::new(named_scores(scores)?.into_iter()))
+}
+
+#[pg_extern]
+/// Calculates the HITS hub and authority score of every node of the in-memory graph.
+///
+/// A node is a good authority if good hubs vote for it, and a good hub if it votes for
+/// good authorities. Only positive edges count, in proportion to their weight. Both
+/// scores are normalized to unit Euclidean length over all nodes.
+///
+/// # Arguments
+///
+/// * `iterations` - The number of power iterations. Defaults to 100.
+///
+/// # Returns
+///
+/// The scores of every node, sorted by authority in descending order.
+pub fn hits_calculate(
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<
+    TableIterator<'static, (name!(node, String), name!(hub, f64), name!(authority, f64))>,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::new(iterations.unwrap_or(DEFAULT_PAGERANK_ITERATIONS))?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let scores = hits::hits(&snapshot.graph, iterations.get());
+
+    let graph = lock_graph()?;
+    let mut rows: Vec<(String, f64, f64)> = scores
+        .into_iter()
+        .filter_map(|(node_id, scores)| {
+            let name = graph.get_node_name(node_id)?;
+            Some((name.to_string(), scores.hub, scores.authority))
+        })
+        .collect();
+    rows.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
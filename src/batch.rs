@@ -0,0 +1,259 @@
+// Standard library imports
+use std::collections::HashMap;
+use std::thread;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::{GraphSingleton, GraphSnapshot};
+use crate::lib_graph::{MeritRank, NodeId, Weight};
+use crate::rating::{Alpha, Iterations, Limit, PenaltyFactor};
+use crate::{stats, walks};
+
+/// Calculates the ranks for each of the given egos on the same graph snapshot, with the
+/// settings `meritrank_ranks` uses by default and no limit on the number of peers.
+///
+/// When `parallel` is true, the walks of every ego are split between one thread per core
+/// rather than `meritrank.parallelism` threads. The egos are calculated on the thread of
+/// the backend either way, so queries are checked for cancellation between batches of
+/// walks.
+pub fn rank_many(
+    snapshot: &GraphSnapshot,
+    egos: &[NodeId],
+    iterations: Iterations,
+    parallel: bool,
+) -> Result<Vec<(NodeId, Vec<(NodeId, Weight)>)>, GraphManipulationError> {
+    let mut merit_rank =
+        configured_instance(snapshot, PenaltyFactor::default(), Alpha::default())?;
+    if parallel {
+        merit_rank.set_parallelism(thread::available_parallelism().map_or(1, |n| n.get()));
+    }
+    rank_egos(&mut merit_rank, egos, iterations, Limit::default())
+}
+
+/// Turns the ranks of several egos into `(ego, node, rank)` rows of node names.
+fn named_rows(
+    snapshot: &GraphSnapshot,
+    results: Vec<(NodeId, Vec<(NodeId, Weight)>)>,
+) -> Vec<(String, String, f64)> {
+    let names: HashMap<NodeId, &str> = snapshot
+        .node_names
+        .iter()
+        .map(|(name, &id)| (id, name.as_str()))
+        .collect();
+    let name = |id: &NodeId| {
+        names
+            .get(id)
+            .map_or_else(String::new, |name| name.to_string())
+    };
+
+    results
+        .into_iter()
+        .flat_map(|(ego, ranks)| {
+            let ego_name = name(&ego);
+            ranks
+                .into_iter()
+                .map(move |(node, rank)| (ego_name.clone(), name(&node), rank))
+        })
+        .collect()
+}
+
+/// Looks up the IDs of the named nodes, keeping the first of repeated names.
+fn unique_node_ids(
+    snapshot: &GraphSnapshot,
+    names: &[String],
+) -> Result<Vec<NodeId>, GraphManipulationError> {
+    let mut ids: Vec<NodeId> = Vec::with_capacity(names.len());
+    for name in names {
+        let id = snapshot.node_id(name)?;
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Returns a `MeritRank` instance on a copy of the snapshot, set up like the one
+/// `meritrank_ranks` uses.
+fn configured_instance(
+    snapshot: &GraphSnapshot,
+    penalty_factor: PenaltyFactor,
+    alpha: Alpha,
+) -> Result<MeritRank, GraphManipulationError> {
+    let mut merit_rank = MeritRank::new(snapshot.graph.clone())?;
+    walks::configure(&mut merit_rank);
+    merit_rank.set_penalty_factor(penalty_factor.get());
+    merit_rank.set_alpha(alpha.get());
+    Ok(merit_rank)
+}
+
+/// Calculates the ranks for each of the given egos on `merit_rank`.
+fn rank_egos(
+    merit_rank: &mut MeritRank,
+    egos: &[NodeId],
+    iterations: Iterations,
+    limit: Limit,
+) -> Result<Vec<(NodeId, Vec<(NodeId, Weight)>)>, GraphManipulationError> {
+    egos.iter()
+        .map(|&ego| {
+            stats::calculate(merit_rank, ego, iterations)?;
+            Ok((ego, merit_rank.get_ranks(ego, limit.get())?))
+        })
+        .collect()
+}
+
+/// Calculates the ranks for each of the given egos on a single `MeritRank` instance set
+/// up like the one `meritrank_ranks` uses.
+pub fn rank_configured(
+    snapshot: &GraphSnapshot,
+    egos: &[NodeId],
+    iterations: Iterations,
+    limit: Limit,
+    penalty_factor: PenaltyFactor,
+    alpha: Alpha,
+) -> Result<Vec<(NodeId, Vec<(NodeId, Weight)>)>, GraphManipulationError> {
+    let mut merit_rank = configured_instance(snapshot, penalty_factor, alpha)?;
+    rank_egos(&mut merit_rank, egos, iterations, limit)
+}
+
+#[pg_extern]
+/// Calculates the ranks for several egos from one shared snapshot of the graph.
+///
+/// This is much cheaper than calling `meritrank_ranks` once per ego, since the graph is
+/// copied only once. The ranks are calculated with the settings `meritrank_ranks` uses,
+/// e.g. `meritrank.seed`, as by `meritrank_calculate_many`.
+///
+/// # Arguments
+///
+/// * `egos` - The names of the egos to calculate ranks for.
+/// * `iterations` - The number of walks per ego. Defaults to `DEFAULT_ITERATIONS`.
+/// * `parallel` - Whether to split the walks of every ego between one thread per core,
+///   rather than `meritrank.parallelism` threads.
+pub fn meritrank_scores_many(
+    egos: Vec<String>,
+    iterations: default!(Option<i32>, "NULL"),
+    parallel: default!(bool, false),
+) -> Result<
+    TableIterator<'static, (name!(ego, String), name!(node, String), name!(rank, f64))>,
+    ErrorReport,
+> {
+    let iterations = Iterations::from_nullable(iterations)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_ids = egos
+        .iter()
+        .map(|ego| snapshot.node_id(ego))
+        .collect::<Result<Vec<NodeId>, GraphManipulationError>>()?;
+
+    let results = rank_many(&snapshot, &ego_ids, iterations, parallel)?;
+    Ok(TableIterator::new(
+        named_rows(&snapshot, results).into_iter(),
+    ))
+}
+
+#[pg_extern(parallel_restricted)]
+/// Calculates the ranks for several egos in one call, with the settings `meritrank_ranks`
+/// uses, e.g. `meritrank.parallelism` and `meritrank.seed`.
+///
+/// The graph is copied once and all egos are ranked on the same `MeritRank` instance,
+/// which saves the setup and locking of separate calls. Egos repeated in the array are
+/// calculated once. The rank cache is neither used nor filled.
+///
+/// # Arguments
+///
+/// * `egos` - The names of the egos to calculate ranks for.
+/// * `iterations` - The number of walks per ego. Defaults to `DEFAULT_ITERATIONS`.
+/// * `limit` - The maximum number of peers to return per ego.
+/// * `penalty_factor` - The multiplier of penalties from negative edges.
+/// * `alpha` - The probability of a walk continuing with another step.
+pub fn meritrank_calculate_many(
+    egos: Vec<String>,
+    iterations: default!(Option<i32>, "NULL"),
+    limit: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<
+    TableIterator<'static, (name!(ego, String), name!(node, String), name!(rank, f64))>,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let limit = Limit::from_nullable(limit)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_ids = unique_node_ids(&snapshot, &egos)?;
+    let results = rank_configured(
+        &snapshot,
+        &ego_ids,
+        iterations,
+        limit,
+        penalty_factor,
+        alpha,
+    )?;
+
+    Ok(TableIterator::new(
+        named_rows(&snapshot, results).into_iter(),
+    ))
+}
+
+#[pg_extern]
+/// Returns the score of every node in `nodes` from the perspective of every other, e.g.
+/// for a heatmap of mutual trust within a small group.
+///
+/// Every ego is ranked once, as by `meritrank_calculate_many`, and its row of the matrix
+/// read from its ranks, so the matrix of `n` nodes costs `n` calculations rather than
+/// `n * n` calls of `meritrank_calculate`. Targets no walk of an ego reached score 0.
+/// Nodes repeated in the array appear once.
+///
+/// # Arguments
+///
+/// * `nodes` - The names of the nodes.
+/// * `iterations` - The number of walks per ego. Defaults to `DEFAULT_ITERATIONS`.
+/// * `penalty_factor` - The multiplier of penalties from negative edges.
+/// * `alpha` - The probability of a walk continuing with another step.
+pub fn meritrank_matrix(
+    nodes: Vec<String>,
+    iterations: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<
+    TableIterator<'static, (name!(ego, String), name!(target, String), name!(score, f64))>,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let node_ids = unique_node_ids(&snapshot, &nodes)?;
+    let results = rank_configured(
+        &snapshot,
+        &node_ids,
+        iterations,
+        Limit::default(),
+        penalty_factor,
+        alpha,
+    )?;
+
+    let matrix: Vec<(NodeId, Vec<(NodeId, Weight)>)> = results
+        .into_iter()
+        .map(|(ego, ranks)| {
+            let scores: HashMap<NodeId, Weight> = ranks.into_iter().collect();
+            let row = node_ids
+                .iter()
+                .map(|&target| (target, scores.get(&target).copied().unwrap_or(0.0)))
+                .collect();
+            (ego, row)
+        })
+        .collect();
+
+    Ok(TableIterator::new(
+        named_rows(&snapshot, matrix).into_iter(),
+    ))
+}
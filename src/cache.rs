@@ -0,0 +1,296 @@
+// Standard library imports
+use std::collections::HashSet;
+use std::sync::{Mutex, MutexGuard};
+
+// External crate imports
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::GraphSingleton;
+use crate::guc;
+use crate::lib_graph::{NodeId, Weight};
+use crate::rating::{Alpha, Iterations, PenaltyFactor};
+
+// Singleton instance
+lazy_static! {
+    pub static ref RANK_CACHE: Mutex<RankCache> = Mutex::new(RankCache::new());
+}
+
+/// Identifies a cached rank vector: the ego and the parameters it was calculated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RankCacheKey {
+    pub ego: NodeId,
+    pub iterations: usize,
+    pub penalty_factor: u64,
+    pub alpha: u64,
+    pub decay_half_life: u64,
+    pub max_walk_length: i32,
+    pub connectivity_decay: u64,
+    pub connectivity_min_predecessors: i32,
+    /// Whether the ranks were calculated on the reversed graph.
+    pub reverse: bool,
+}
+
+impl RankCacheKey {
+    /// Creates a new cache key for the current `meritrank.decay_half_life`,
+    /// `meritrank.max_walk_length` and connectivity settings.
+    pub fn new(
+        ego: NodeId,
+        iterations: Iterations,
+        penalty_factor: PenaltyFactor,
+        alpha: Alpha,
+        reverse: bool,
+    ) -> Self {
+        RankCacheKey {
+            ego,
+            iterations: iterations.get(),
+            penalty_factor: penalty_factor.get().to_bits(),
+            alpha: alpha.get().to_bits(),
+            decay_half_life: guc::DECAY_HALF_LIFE.get().to_bits(),
+            max_walk_length: guc::MAX_WALK_LENGTH.get(),
+            connectivity_decay: guc::CONNECTIVITY_DECAY.get().to_bits(),
+            connectivity_min_predecessors: guc::CONNECTIVITY_MIN_PREDECESSORS.get(),
+            reverse,
+        }
+    }
+}
+
+/// A cached rank vector.
+#[derive(Debug, Clone)]
+pub struct RankCacheEntry {
+    /// All ranks of the ego, sorted in descending order.
+    pub ranks: Vec<(NodeId, Weight)>,
+    /// The generation of the graph the ranks were calculated on.
+    pub generation: u64,
+    /// The nodes visited by the ego's walks. Changes to edges leaving any other node
+    /// cannot affect the ranks.
+    visited: HashSet<NodeId>,
+}
+
+impl RankCacheEntry {
+    /// Creates a new cache entry.
+    pub fn new(ranks: Vec<(NodeId, Weight)>, generation: u64) -> Self {
+        let visited = ranks.iter().map(|&(node, _)| node).collect();
+        RankCacheEntry {
+            ranks,
+            generation,
+            visited,
+        }
+    }
+}
+
+/// A bounded, least-recently-used cache of rank vectors.
+pub struct RankCache {
+    entries: IndexMap<RankCacheKey, RankCacheEntry>,
+    hits: u64,
+    misses: u64,
+}
+
+impl RankCache {
+    /// Creates a new empty cache.
+    pub fn new() -> Self {
+        RankCache {
+            entries: IndexMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Looks up a rank vector and marks it as recently used.
+    pub fn get(&mut self, key: &RankCacheKey) -> Option<RankCacheEntry> {
+        match self.entries.shift_remove(key) {
+            Some(entry) => {
+                self.hits += 1;
+                self.entries.insert(*key, entry.clone());
+                Some(entry)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Stores a rank vector, evicting the least recently used entries above `capacity`.
+    pub fn insert(&mut self, key: RankCacheKey, entry: RankCacheEntry, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        self.entries.shift_remove(&key);
+        self.entries.insert(key, entry);
+        while self.entries.len() > capacity {
+            self.entries.shift_remove_index(0);
+        }
+    }
+
+    /// Drops every rank vector that may depend on the out-edges of `node`.
+    ///
+    /// # Returns
+    ///
+    /// The number of dropped entries.
+    pub fn invalidate_node(&mut self, node: NodeId) -> usize {
+        let before = self.entries.len();
+        self.entries
+            .retain(|key, entry| key.ego != node && !entry.visited.contains(&node));
+        before - self.entries.len()
+    }
+
+    /// Drops every rank vector that may depend on the edge from `subject` to `object`.
+    ///
+    /// # Returns
+    ///
+    /// The number of dropped entries.
+    pub fn invalidate_edge(&mut self, subject: NodeId, object: NodeId) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|key, entry| {
+            // Walks on the reversed graph traverse the edge starting from `object`
+            let source = if key.reverse { object } else { subject };
+            key.ego != source && !entry.visited.contains(&source)
+        });
+        before - self.entries.len()
+    }
+
+    /// Estimates the number of heap bytes held by the cached rank vectors.
+    pub fn memory_usage(&self) -> usize {
+        let entry_size =
+            std::mem::size_of::<RankCacheKey>() + std::mem::size_of::<RankCacheEntry>();
+        self.entries.capacity() * entry_size
+            + self
+                .entries
+                .values()
+                .map(|entry| {
+                    entry.ranks.capacity() * std::mem::size_of::<(NodeId, Weight)>()
+                        + entry.visited.capacity() * (std::mem::size_of::<NodeId>() + 1)
+                })
+                .sum::<usize>()
+    }
+
+    /// Drops all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Resets the hit and miss counters.
+    pub fn reset_stats(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    /// Returns the number of cached rank vectors.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of lookups that found an entry.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of lookups that did not find an entry.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns an iterator over the cached entries, least recently used first.
+    pub fn iter(&self) -> impl Iterator<Item = (&RankCacheKey, &RankCacheEntry)> {
+        self.entries.iter()
+    }
+}
+
+impl Default for RankCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Locks the rank cache.
+pub fn lock_cache() -> Result<MutexGuard<'static, RankCache>, GraphManipulationError> {
+    RANK_CACHE
+        .lock()
+        .map_err(|e| GraphManipulationError::MutexLockFailure(format!("Mutex lock error: {}", e)))
+}
+
+/// Returns the maximum number of cached rank vectors (`meritrank.rank_cache_size`).
+pub fn capacity() -> usize {
+    guc::RANK_CACHE_SIZE.get().max(0) as usize
+}
+
+#[pg_extern]
+/// Returns the size and hit statistics of the rank cache.
+pub fn meritrank_cache_stats() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(entries, i64),
+            name!(capacity, i64),
+            name!(hits, i64),
+            name!(misses, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let cache = lock_cache()?;
+    Ok(TableIterator::once((
+        cache.len() as i64,
+        capacity() as i64,
+        cache.hits() as i64,
+        cache.misses() as i64,
+    )))
+}
+
+#[pg_extern]
+/// Lists the cached rank vectors, least recently used first.
+pub fn meritrank_cache_entries() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(ego, String),
+            name!(iterations, i64),
+            name!(penalty_factor, f64),
+            name!(nodes, i64),
+            name!(graph_generation, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let entries: Vec<(NodeId, i64, f64, i64, i64)> = lock_cache()?
+        .iter()
+        .map(|(key, entry)| {
+            (
+                key.ego,
+                key.iterations as i64,
+                f64::from_bits(key.penalty_factor),
+                entry.ranks.len() as i64,
+                entry.generation as i64,
+            )
+        })
+        .collect();
+
+    let rows = entries
+        .into_iter()
+        .map(|(ego, iterations, penalty_factor, nodes, generation)| {
+            Ok((
+                GraphSingleton::node_id_to_name(ego)?,
+                iterations,
+                penalty_factor,
+                nodes,
+                generation,
+            ))
+        })
+        .collect::<Result<Vec<_>, GraphManipulationError>>()?;
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+#[pg_extern]
+/// Drops all cached rank vectors.
+pub fn meritrank_cache_clear() -> Result<(), ErrorReport> {
+    lock_cache()?.clear();
+    Ok(())
+}
@@ -0,0 +1,203 @@
+// Standard library imports
+use std::collections::HashMap;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::graph::GraphSingleton;
+use crate::lib_graph::{MeritRank, MeritRankError, NodeId, Weight};
+use crate::rating::{Alpha, Iterations, Limit, PenaltyFactor};
+use crate::{stats, walks};
+
+/// The number of standard errors on either side of a score that make up its 95%
+/// confidence interval.
+const CONFIDENCE_Z: f64 = 1.96;
+
+/// Returns the sum of the absolute changes of all scores between two rank vectors.
+/// Nodes missing from one of the vectors count as a score of 0 there.
+pub fn l1_change(previous: &HashMap<NodeId, Weight>, current: &HashMap<NodeId, Weight>) -> f64 {
+    let changed: f64 = current
+        .iter()
+        .map(|(node, score)| (score - previous.get(node).copied().unwrap_or(0.0)).abs())
+        .sum();
+    let dropped: f64 = previous
+        .iter()
+        .filter(|(node, _)| !current.contains_key(node))
+        .map(|(_, score)| score.abs())
+        .sum();
+    changed + dropped
+}
+
+/// Returns Kendall's tau between the order of `top` and the order the same nodes had in
+/// `previous`, from -1 (reversed) to 1 (unchanged).
+///
+/// Nodes missing from `previous` count as a score of 0 there, and tied pairs are neither
+/// concordant nor discordant. Returns `None` for fewer than two nodes.
+pub fn kendall_tau(previous: &HashMap<NodeId, Weight>, top: &[(NodeId, Weight)]) -> Option<f64> {
+    let mut pairs = 0;
+    let mut balance = 0i64;
+    for (i, &(first, first_score)) in top.iter().enumerate() {
+        for &(second, second_score) in &top[i + 1..] {
+            pairs += 1;
+            let now = first_score - second_score;
+            let before = previous.get(&first).copied().unwrap_or(0.0)
+                - previous.get(&second).copied().unwrap_or(0.0);
+            let agreement = now * before;
+            if agreement > 0.0 {
+                balance += 1;
+            } else if agreement < 0.0 {
+                balance -= 1;
+            }
+        }
+    }
+    (pairs > 0).then(|| balance as f64 / pairs as f64)
+}
+
+#[pg_extern]
+/// Calculates the ranks of `ego` in batches of walks and reports how much they changed
+/// after every batch, to help choose the number of iterations for a graph.
+///
+/// Every row describes the ranks after a batch: the number of walks so far, the L1 change
+/// of all scores since the previous batch, and Kendall's tau between the order of the
+/// `top_k` nodes and their order after the previous batch. Both are `NULL` for the first
+/// batch. The walks are not stored, and early termination is not applied.
+///
+/// # Arguments
+///
+/// * `ego` - The name of the ego.
+/// * `iterations` - The total number of walks. Defaults to `DEFAULT_ITERATIONS`.
+/// * `batch_size` - The number of walks per batch.
+/// * `top_k` - The number of top-ranked nodes Kendall's tau is computed over.
+pub fn meritrank_calculate_verbose(
+    ego: &str,
+    iterations: default!(Option<i32>, "NULL"),
+    batch_size: default!(i32, 1000),
+    top_k: default!(i32, 10),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(batch, i32),
+            name!(walks, i64),
+            name!(l1_change, Option<f64>),
+            name!(kendall_tau, Option<f64>),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let batch_size = Iterations::new(batch_size)?;
+    let top_k = Limit::new(top_k)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+
+    let mut merit_rank = MeritRank::new(snapshot.graph.clone())?;
+    walks::configure(&mut merit_rank);
+    merit_rank.set_penalty_factor(PenaltyFactor::default().get());
+
+    let mut rows: Vec<(i32, i64, Option<f64>, Option<f64>)> = Vec::new();
+    let mut previous: Option<HashMap<NodeId, Weight>> = None;
+    merit_rank.calculate_in_batches(
+        ego_id,
+        iterations.get(),
+        batch_size.get(),
+        |merit_rank, walks| {
+            let ranks = merit_rank.get_ranks(ego_id, None)?;
+            let current: HashMap<NodeId, Weight> = ranks.iter().copied().collect();
+            let top = &ranks[..ranks.len().min(top_k.get().unwrap_or(ranks.len()))];
+
+            let (l1, tau) = match previous {
+                Some(ref previous) => (
+                    Some(l1_change(previous, &current)),
+                    kendall_tau(previous, top),
+                ),
+                None => (None, None),
+            };
+            rows.push((rows.len() as i32 + 1, walks as i64, l1, tau));
+            previous = Some(current);
+            Ok(false)
+        },
+    )?;
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+#[pg_extern]
+/// Returns the ranks of the peers of `ego` like `meritrank_ranks`, together with the
+/// standard error of every rank and its 95% confidence interval.
+///
+/// The ranks are estimated from random walks, so two ranks whose intervals overlap may
+/// differ only by sampling noise; more iterations narrow the intervals. The error treats
+/// the walks reaching a peer as binomial and leaves out the penalties of negative edges.
+/// The calculation runs on its own copy of the graph and neither uses nor fills the rank
+/// cache.
+///
+/// # Arguments
+///
+/// * `ego` - The name of the ego.
+/// * `iterations` - The number of walks. Defaults to `DEFAULT_ITERATIONS`.
+/// * `limit` - The maximum number of peers to return.
+/// * `penalty_factor` - The multiplier of penalties from negative edges.
+/// * `alpha` - The probability of a walk continuing with another step.
+pub fn meritrank_ranks_with_error(
+    ego: &str,
+    iterations: default!(Option<i32>, "NULL"),
+    limit: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(node, String),
+            name!(rank, f64),
+            name!(std_error, f64),
+            name!(ci_low, f64),
+            name!(ci_high, f64),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let limit = Limit::from_nullable(limit)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+
+    let mut merit_rank = MeritRank::new(snapshot.graph.clone())?;
+    walks::configure(&mut merit_rank);
+    merit_rank.set_penalty_factor(penalty_factor.get());
+    merit_rank.set_alpha(alpha.get());
+    stats::calculate(&mut merit_rank, ego_id, iterations)?;
+
+    let names: HashMap<NodeId, &str> = snapshot
+        .node_names
+        .iter()
+        .map(|(name, &id)| (id, name.as_str()))
+        .collect();
+    let rows = merit_rank
+        .get_ranks(ego_id, limit.get())?
+        .into_iter()
+        .map(|(node, rank)| {
+            let std_error = merit_rank.get_node_score_std_error(ego_id, node)?;
+            Ok((
+                names
+                    .get(&node)
+                    .map_or_else(String::new, |name| name.to_string()),
+                rank,
+                std_error,
+                rank - CONFIDENCE_Z * std_error,
+                rank + CONFIDENCE_Z * std_error,
+            ))
+        })
+        .collect::<Result<Vec<_>, MeritRankError>>()?;
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
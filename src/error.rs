@@ -1,3 +1,6 @@
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::PgSqlErrorCode;
+
 use crate::lib_graph::MeritRankError;
 
 #[allow(dead_code)]
@@ -76,7 +79,201 @@ pub enum GraphManipulationError {
     #[error(transparent)]
     MeritRankFailure(#[from] MeritRankError),
 
+    /// Error when the current user is not allowed to perform an operation
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    /// Error when failing to read a file on the server
+    #[error("Failed to read file: {0}")]
+    FileReadFailure(String),
+
+    /// Error when failing to write a file on the server
+    #[error("Failed to write file: {0}")]
+    FileWriteFailure(String),
+
     /// Error when failing to lock a mutex for concurrent operations
     #[error("Failed to lock mutex: {0}")]
     MutexLockFailure(String),
+
+    /// Error when the requested number of iterations is not valid
+    #[error("Invalid iterations: {0}")]
+    InvalidIterations(String),
+
+    /// Error when the requested result limit is not valid
+    #[error("Invalid limit: {0}")]
+    InvalidLimit(String),
+
+    /// Error when the requested penalty factor is not valid
+    #[error("Invalid penalty factor: {0}")]
+    InvalidPenaltyFactor(String),
+
+    /// Error when the requested damping factor is not valid
+    #[error("Invalid damping factor: {0}")]
+    InvalidDampingFactor(String),
+
+    /// Error when the requested convergence threshold is not valid
+    #[error("Invalid epsilon: {0}")]
+    InvalidEpsilon(String),
+
+    /// Error when the requested change threshold is not valid
+    #[error("Invalid threshold: {0}")]
+    InvalidThreshold(String),
+
+    /// Error when the requested walk continuation probability is not valid
+    #[error("Invalid alpha: {0}")]
+    InvalidAlpha(String),
+
+    /// Error when the requested time budget is not valid
+    #[error("Invalid time budget: {0}")]
+    InvalidTimeBudget(String),
+
+    /// Error when a node name is empty or too long
+    #[error("Invalid node name: {0}")]
+    InvalidNodeName(String),
+
+    /// Error when a batch of edges is malformed
+    #[error("Invalid batch: {0}")]
+    InvalidBatch(String),
+
+    /// Error when a mutation would exceed the configured node budget
+    #[error("Node budget exceeded: {0}")]
+    NodeBudgetExceeded(String),
+
+    /// Error when a mutation would exceed the configured edge budget
+    #[error("Edge budget exceeded: {0}")]
+    EdgeBudgetExceeded(String),
+
+    /// Error when a named graph snapshot does not exist
+    #[error("Snapshot not found: {0}")]
+    SnapshotNotFound(String),
+
+    /// Error when a queued calculation does not exist
+    #[error("Job not found: {0}")]
+    JobNotFound(String),
+
+    /// Error when a column of a vote table does not exist
+    #[error("Column not found: {0}")]
+    ColumnNotFound(String),
+
+    /// Error when ranks are requested for a node that has no outgoing edges
+    #[error("Node has no outgoing edges: {0}")]
+    NoOutgoingEdges(String),
+}
+
+impl GraphManipulationError {
+    /// Returns the SQLSTATE the error is reported with.
+    pub fn sqlstate(&self) -> PgSqlErrorCode {
+        match self {
+            GraphManipulationError::NodeNotFound(_)
+            | GraphManipulationError::NodeNameNotFound(_)
+            | GraphManipulationError::SnapshotNotFound(_)
+            | GraphManipulationError::JobNotFound(_) => PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT,
+            GraphManipulationError::ColumnNotFound(_) => PgSqlErrorCode::ERRCODE_UNDEFINED_COLUMN,
+            GraphManipulationError::DataExtractionFailure(_)
+            | GraphManipulationError::WeightExtractionFailure(_)
+            | GraphManipulationError::RecordsExtractionFailure(_)
+            | GraphManipulationError::FetchRecordsFailure(_) => {
+                PgSqlErrorCode::ERRCODE_DATA_EXCEPTION
+            }
+            GraphManipulationError::InvalidIterations(_)
+            | GraphManipulationError::InvalidLimit(_)
+            | GraphManipulationError::InvalidPenaltyFactor(_)
+            | GraphManipulationError::InvalidDampingFactor(_)
+            | GraphManipulationError::InvalidEpsilon(_)
+            | GraphManipulationError::InvalidThreshold(_)
+            | GraphManipulationError::InvalidAlpha(_)
+            | GraphManipulationError::InvalidTimeBudget(_)
+            | GraphManipulationError::InvalidBatch(_)
+            | GraphManipulationError::InvalidNodeName(_) => {
+                PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE
+            }
+            GraphManipulationError::PermissionDenied(_) => {
+                PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE
+            }
+            GraphManipulationError::FileReadFailure(_)
+            | GraphManipulationError::FileWriteFailure(_) => PgSqlErrorCode::ERRCODE_IO_ERROR,
+            GraphManipulationError::NodeBudgetExceeded(_)
+            | GraphManipulationError::EdgeBudgetExceeded(_) => {
+                PgSqlErrorCode::ERRCODE_PROGRAM_LIMIT_EXCEEDED
+            }
+            GraphManipulationError::NoOutgoingEdges(_) => {
+                PgSqlErrorCode::ERRCODE_OBJECT_NOT_IN_PREREQUISITE_STATE
+            }
+            GraphManipulationError::MeritRankFailure(e) => match e {
+                MeritRankError::NodeDoesNotExist | MeritRankError::InvalidNode => {
+                    PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT
+                }
+                MeritRankError::SelfReferenceNotAllowed
+                | MeritRankError::NodeIdParseError
+                | MeritRankError::InvalidWalkLength
+                | MeritRankError::InvalidNodeType => {
+                    PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE
+                }
+                MeritRankError::NodeDoesNotCalculated => {
+                    PgSqlErrorCode::ERRCODE_OBJECT_NOT_IN_PREREQUISITE_STATE
+                }
+                _ => PgSqlErrorCode::ERRCODE_INTERNAL_ERROR,
+            },
+            _ => PgSqlErrorCode::ERRCODE_INTERNAL_ERROR,
+        }
+    }
+
+    /// Returns additional detail about the error, if any.
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            GraphManipulationError::MutexLockFailure(_) => {
+                Some("The in-memory graph was poisoned by a panic in an earlier call.".to_string())
+            }
+            GraphManipulationError::SpiFailure(e) => Some(format!("{:?}", e)),
+            _ => None,
+        }
+    }
+
+    /// Returns a suggestion on how to resolve the error, if any.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            GraphManipulationError::NodeNotFound(_)
+            | GraphManipulationError::NodeNameNotFound(_) => {
+                Some("Nodes are created by adding edges with meritrank_add().")
+            }
+            GraphManipulationError::SnapshotNotFound(_) => {
+                Some("Snapshots are taken with meritrank_snapshot() and kept per backend.")
+            }
+            GraphManipulationError::NoOutgoingEdges(_) => Some(
+                "Ranks can only be calculated for nodes that have voted; \
+                 check with meritrank_can_rank() first.",
+            ),
+            GraphManipulationError::NodeBudgetExceeded(_) => Some(
+                "Raise meritrank.max_nodes, or set meritrank.node_budget_policy to evict or warn.",
+            ),
+            GraphManipulationError::EdgeBudgetExceeded(_) => Some(
+                "Raise meritrank.max_edges, or set meritrank.node_budget_policy to evict or warn.",
+            ),
+            GraphManipulationError::MutexLockFailure(_) => {
+                Some("Reconnect to start over with a fresh backend.")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<GraphManipulationError> for ErrorReport {
+    /// Converts the error into a report carrying its SQLSTATE, detail and hint, so that
+    /// SQL functions raise it with a proper error code.
+    fn from(error: GraphManipulationError) -> Self {
+        let mut report = ErrorReport::new(error.sqlstate(), error.to_string(), "pg_meritrank");
+        if let Some(detail) = error.detail() {
+            report = report.set_detail(detail);
+        }
+        if let Some(hint) = error.hint() {
+            report = report.set_hint(hint);
+        }
+        report
+    }
+}
+
+impl From<MeritRankError> for ErrorReport {
+    fn from(error: MeritRankError) -> Self {
+        GraphManipulationError::from(error).into()
+    }
 }
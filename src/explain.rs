@@ -0,0 +1,253 @@
+// Standard library imports
+use std::collections::{HashMap, HashSet};
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::GraphSingleton;
+use crate::lib_graph::{MeritRank, NodeId};
+use crate::rating::{Iterations, PenaltyFactor};
+use crate::{stats, walks};
+
+#[pg_extern]
+/// Explains the rank of `target` from the perspective of `ego`.
+///
+/// Returns one row per distinct path by which the walks of `ego` first reached `target`:
+/// the names along the path, the number of walks that took it, the summed weight of its
+/// edges and the share of the target's score it accounts for.
+pub fn meritrank_explain(
+    ego: &str,
+    target: &str,
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(path, Vec<String>),
+            name!(walks, i64),
+            name!(accumulated_weight, f64),
+            name!(contribution, f64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let iterations = Iterations::from_nullable(iterations)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+    let target_id = snapshot.node_id(target)?;
+
+    let names: HashMap<NodeId, String> = snapshot
+        .node_names
+        .iter()
+        .map(|(name, &id)| (id, name.clone()))
+        .collect();
+
+    let mut merit_rank = MeritRank::new(snapshot.graph.clone())?;
+    stats::calculate(&mut merit_rank, ego_id, iterations)?;
+
+    let total_hits = merit_rank.get_total_hits(ego_id)?;
+    let graph = merit_rank.get_graph();
+
+    let rows: Vec<(Vec<String>, i64, f64, f64)> = merit_rank
+        .get_path_contributions(ego_id, target_id)?
+        .into_iter()
+        .map(|(path, walks)| {
+            let accumulated_weight = path
+                .windows(2)
+                .filter_map(|step| graph.edge_weight(step[0], step[1]))
+                .sum();
+            let contribution = if total_hits > 0.0 {
+                walks as f64 / total_hits
+            } else {
+                0.0
+            };
+            let path = path
+                .iter()
+                .map(|node| names.get(node).cloned().unwrap_or_default())
+                .collect();
+            (path, walks as i64, accumulated_weight, contribution)
+        })
+        .collect();
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+#[pg_extern]
+/// Reports the nodes whose rank from the perspective of `ego` depends on a single edge,
+/// the pattern of a bridge attack: one edge from a trusted node into a ring of accounts
+/// that pass the reputation on among themselves.
+///
+/// For every ranked node, the walks of `ego` that reached it are cut at its first visit,
+/// and the share of them crossing each edge is counted. Edges leaving `ego` are its own
+/// votes and are not considered. A node is reported when one edge carries at least
+/// `min_share` of its walks; that edge is returned as the bridge, preferring the edge
+/// closest to `ego` among equal shares. Rows are sorted by descending rank.
+///
+/// # Arguments
+///
+/// * `ego` - The name of the ego.
+/// * `min_share` - The smallest share of walks, in `(0, 1]`, an edge must carry.
+/// * `iterations` - The number of walks. Defaults to `DEFAULT_ITERATIONS`.
+pub fn meritrank_detect_bridges(
+    ego: &str,
+    min_share: default!(f64, 0.9),
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(node, String),
+            name!(rank, f64),
+            name!(bridge_source, String),
+            name!(bridge_target, String),
+            name!(share, f64),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    if !(min_share > 0.0 && min_share <= 1.0) {
+        return Err(GraphManipulationError::InvalidThreshold(format!(
+            "{} (must be greater than 0 and at most 1)",
+            min_share
+        ))
+        .into());
+    }
+    let iterations = Iterations::from_nullable(iterations)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+
+    let names: HashMap<NodeId, String> = snapshot
+        .node_names
+        .iter()
+        .map(|(name, &id)| (id, name.clone()))
+        .collect();
+
+    let mut merit_rank = MeritRank::new(snapshot.graph.clone())?;
+    walks::configure(&mut merit_rank);
+    stats::calculate(&mut merit_rank, ego_id, iterations)?;
+
+    let mut rows: Vec<(String, f64, String, String, f64)> = Vec::new();
+    for (node, rank) in merit_rank.get_ranks(ego_id, None)? {
+        if node == ego_id {
+            continue;
+        }
+
+        // Walks crossing every edge, and the first position the edge was seen at
+        let mut crossings: HashMap<(NodeId, NodeId), (usize, usize)> = HashMap::new();
+        let mut total = 0;
+        for (path, walks) in merit_rank.get_path_contributions(ego_id, node)? {
+            total += walks;
+            let mut seen = HashSet::new();
+            for (position, step) in path.windows(2).enumerate() {
+                let edge = (step[0], step[1]);
+                if edge.0 == ego_id || !seen.insert(edge) {
+                    continue;
+                }
+                let entry = crossings.entry(edge).or_insert((0, position));
+                entry.0 += walks;
+                entry.1 = entry.1.min(position);
+            }
+        }
+        if total == 0 {
+            continue;
+        }
+
+        let bridge =
+            crossings
+                .into_iter()
+                .max_by(|(edge1, (walks1, pos1)), (edge2, (walks2, pos2))| {
+                    walks1
+                        .cmp(walks2)
+                        .then_with(|| pos2.cmp(pos1))
+                        .then_with(|| edge2.cmp(edge1))
+                });
+        if let Some(((source, target), (walks, _))) = bridge {
+            let share = walks as f64 / total as f64;
+            if share >= min_share {
+                let name = |node: NodeId| names.get(&node).cloned().unwrap_or_default();
+                rows.push((name(node), rank, name(source), name(target), share));
+            }
+        }
+    }
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+#[pg_extern]
+/// Returns the raw hit counts behind the ranks of the peers of `ego`, before normalization.
+///
+/// `hits` is the number of walks of `ego` that visited the node, `negative_hits` the
+/// penalty the walks collected for it after crossing negative edges of `ego`, and
+/// `total_hits` the sum of the hits of all nodes. The rank is
+/// `(hits + penalty_factor * negative_hits) / total_hits`, further discounted by
+/// `meritrank.connectivity_decay` if set. Rows are sorted by descending rank.
+///
+/// # Arguments
+///
+/// * `ego` - The name of the ego.
+/// * `iterations` - The number of walks. Defaults to `DEFAULT_ITERATIONS`.
+/// * `penalty_factor` - The multiplier of penalties from negative edges.
+pub fn meritrank_hits(
+    ego: &str,
+    iterations: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(node, String),
+            name!(hits, i64),
+            name!(negative_hits, f64),
+            name!(total_hits, i64),
+            name!(rank, f64),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+
+    let names: HashMap<NodeId, String> = snapshot
+        .node_names
+        .iter()
+        .map(|(name, &id)| (id, name.clone()))
+        .collect();
+
+    let mut merit_rank = MeritRank::new(snapshot.graph.clone())?;
+    walks::configure(&mut merit_rank);
+    merit_rank.set_penalty_factor(penalty_factor.get());
+    stats::calculate(&mut merit_rank, ego_id, iterations)?;
+
+    let total_hits = merit_rank.get_total_hits(ego_id)? as i64;
+    let counter = &merit_rank.get_personal_hits()[&ego_id];
+    let negative_hits = merit_rank.get_neg_hits().get(&ego_id);
+
+    let rows: Vec<(String, i64, f64, i64, f64)> = merit_rank
+        .get_ranks(ego_id, None)?
+        .into_iter()
+        .map(|(node, rank)| {
+            (
+                names.get(&node).cloned().unwrap_or_default(),
+                counter.get_count(&node).copied().unwrap_or(0.0) as i64,
+                negative_hits
+                    .and_then(|hits| hits.get(&node))
+                    .copied()
+                    .unwrap_or(0.0),
+                total_hits,
+                rank,
+            )
+        })
+        .collect();
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
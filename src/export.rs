@@ -0,0 +1,148 @@
+// Standard library imports
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::graph::{lock_graph, GraphSingleton};
+use crate::lib_graph::{NodeId, Weight};
+use crate::rating::Iterations;
+use crate::stats;
+
+/// Escapes the characters that are not allowed inside XML text and attribute values.
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Serializes the graph into GraphML.
+///
+/// Nodes are identified by their internal id and carry their name as a `name` attribute;
+/// edges carry their weight as a `weight` attribute.
+pub fn to_graphml(graph: &GraphSingleton) -> String {
+    let names = graph.node_ids_to_names();
+    let mut out = String::new();
+
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    out.push_str("  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>\n");
+    out.push_str("  <graph id=\"meritrank\" edgedefault=\"directed\">\n");
+
+    for node_id in graph.borrow_graph().node_ids() {
+        let name = names.get(&node_id).map(String::as_str).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "    <node id=\"n{}\"><data key=\"name\">{}</data></node>",
+            node_id,
+            escape_xml(name)
+        );
+    }
+
+    for (source, target, weight) in graph.borrow_graph().all_edges() {
+        let _ = writeln!(
+            out,
+            "    <edge source=\"n{}\" target=\"n{}\"><data key=\"weight\">{}</data></edge>",
+            source, target, weight
+        );
+    }
+
+    out.push_str("  </graph>\n");
+    out.push_str("</graphml>\n");
+    out
+}
+
+/// Escapes the characters that are not allowed inside a quoted DOT string.
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Serializes the graph into Graphviz DOT.
+///
+/// When `ranks` is given, every ranked node is labelled with its rank and filled with a color
+/// whose saturation is proportional to the rank (green for positive, red for negative ranks).
+pub fn to_dot(graph: &GraphSingleton, ranks: Option<&HashMap<NodeId, Weight>>) -> String {
+    let names = graph.node_ids_to_names();
+    let max_rank = ranks
+        .map(|ranks| {
+            ranks
+                .values()
+                .fold(0.0, |max: Weight, rank| max.max(rank.abs()))
+        })
+        .unwrap_or(0.0);
+    let mut out = String::new();
+
+    out.push_str("digraph meritrank {\n");
+
+    for node_id in graph.borrow_graph().node_ids() {
+        let name = names.get(&node_id).map(String::as_str).unwrap_or_default();
+        match ranks.and_then(|ranks| ranks.get(&node_id)) {
+            Some(&rank) => {
+                let hue = if rank < 0.0 { 0.0 } else { 0.33 };
+                let saturation = if max_rank > 0.0 {
+                    rank.abs() / max_rank
+                } else {
+                    0.0
+                };
+                let _ = writeln!(
+                    out,
+                    "  n{} [label=\"{}\\n{:.4}\", style=filled, fillcolor=\"{:.3} {:.3} 1.000\"];",
+                    node_id,
+                    escape_dot(name),
+                    rank,
+                    hue,
+                    saturation
+                );
+            }
+            None => {
+                let _ = writeln!(out, "  n{} [label=\"{}\"];", node_id, escape_dot(name));
+            }
+        }
+    }
+
+    for (source, target, weight) in graph.borrow_graph().all_edges() {
+        let _ = writeln!(out, "  n{} -> n{} [label=\"{}\"];", source, target, weight);
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+#[pg_extern]
+/// Exports the in-memory graph in Graphviz DOT format.
+///
+/// When `ego` is given, ranks are calculated from its perspective and every ranked node
+/// is annotated with its rank.
+pub fn meritrank_export_dot(ego: default!(Option<&str>, "NULL")) -> Result<String, ErrorReport> {
+    let ranks = match ego {
+        Some(ego) => {
+            let ego_id = GraphSingleton::node_name_to_id(ego)?;
+            let mut merit_rank = GraphSingleton::get_rank()?;
+            stats::calculate(&mut merit_rank, ego_id, Iterations::default())?;
+            Some(
+                merit_rank
+                    .get_ranks(ego_id, None)?
+                    .into_iter()
+                    .collect::<HashMap<NodeId, Weight>>(),
+            )
+        }
+        None => None,
+    };
+
+    let graph = lock_graph()?;
+    Ok(to_dot(&graph, ranks.as_ref()))
+}
+
+#[pg_extern]
+/// Exports the in-memory graph as a GraphML document.
+pub fn meritrank_export_graphml() -> Result<String, ErrorReport> {
+    let graph = lock_graph()?;
+    Ok(to_graphml(&graph))
+}
@@ -0,0 +1,241 @@
+// Standard library imports
+use std::collections::HashSet;
+
+// External crate imports
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::add_edges;
+
+/// Model of a synthetic graph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphModel {
+    /// Every possible edge exists independently with the given probability (Erdős–Rényi).
+    Random { edge_probability: f64 },
+    /// Every new node votes for `attachments` existing nodes, preferring nodes that
+    /// already have many edges, which produces hubs (Barabási–Albert).
+    BarabasiAlbert { attachments: usize },
+    /// A ring where every node votes for its `neighbors` nearest nodes, with every edge
+    /// rewired to a random node with the given probability (Watts–Strogatz).
+    WattsStrogatz {
+        neighbors: usize,
+        rewire_probability: f64,
+    },
+}
+
+impl GraphModel {
+    /// Generates the edges of a graph of `nodes` nodes, as pairs of node positions.
+    /// The edges are directed and contain neither self-references nor duplicates.
+    pub fn generate(&self, nodes: usize, rng: &mut impl Rng) -> Vec<(usize, usize)> {
+        match *self {
+            GraphModel::Random { edge_probability } => random(nodes, edge_probability, rng),
+            GraphModel::BarabasiAlbert { attachments } => barabasi_albert(nodes, attachments, rng),
+            GraphModel::WattsStrogatz {
+                neighbors,
+                rewire_probability,
+            } => watts_strogatz(nodes, neighbors, rewire_probability, rng),
+        }
+    }
+}
+
+fn random(nodes: usize, edge_probability: f64, rng: &mut impl Rng) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    for source in 0..nodes {
+        for target in 0..nodes {
+            if source != target && rng.gen_bool(edge_probability) {
+                edges.push((source, target));
+            }
+        }
+    }
+    edges
+}
+
+fn barabasi_albert(nodes: usize, attachments: usize, rng: &mut impl Rng) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    // Every node appears once per edge it has, so sampling from this list picks nodes
+    // in proportion to their degree
+    let mut endpoints: Vec<usize> = Vec::new();
+
+    for node in attachments.min(nodes)..nodes {
+        let mut targets: Vec<usize> = Vec::with_capacity(attachments);
+        if endpoints.is_empty() {
+            // The first node votes for all the initial nodes
+            targets.extend(0..node);
+        }
+        while targets.len() < attachments {
+            let target = endpoints[rng.gen_range(0..endpoints.len())];
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        for target in targets {
+            edges.push((node, target));
+            endpoints.push(node);
+            endpoints.push(target);
+        }
+    }
+    edges
+}
+
+fn watts_strogatz(
+    nodes: usize,
+    neighbors: usize,
+    rewire_probability: f64,
+    rng: &mut impl Rng,
+) -> Vec<(usize, usize)> {
+    let half = neighbors / 2;
+    let mut edges: HashSet<(usize, usize)> = HashSet::new();
+    for source in 0..nodes {
+        for offset in 1..=half {
+            edges.insert((source, (source + offset) % nodes));
+            edges.insert((source, (source + nodes - offset) % nodes));
+        }
+    }
+
+    let mut ring: Vec<(usize, usize)> = edges.iter().copied().collect();
+    ring.sort_unstable();
+    for (source, target) in ring {
+        if !rng.gen_bool(rewire_probability) {
+            continue;
+        }
+        let rewired = rng.gen_range(0..nodes);
+        if rewired != source && !edges.contains(&(source, rewired)) {
+            edges.remove(&(source, target));
+            edges.insert((source, rewired));
+        }
+    }
+
+    let mut edges: Vec<(usize, usize)> = edges.into_iter().collect();
+    edges.sort_unstable();
+    edges
+}
+
+/// Parses the model name and its parameters, validating them.
+fn parse_model(
+    model: &str,
+    nodes: usize,
+    edge_probability: f64,
+    attachments: i32,
+    neighbors: i32,
+    rewire_probability: f64,
+) -> Result<GraphModel, GraphManipulationError> {
+    let invalid = GraphManipulationError::GraphGenerationFailure;
+    let probability = |name: &str, value: f64| {
+        if (0.0..=1.0).contains(&value) {
+            Ok(value)
+        } else {
+            Err(invalid(format!(
+                "{} must be between 0 and 1, got {}",
+                name, value
+            )))
+        }
+    };
+
+    match model.to_ascii_lowercase().as_str() {
+        "random" | "erdos_renyi" => Ok(GraphModel::Random {
+            edge_probability: probability("edge_probability", edge_probability)?,
+        }),
+        "barabasi_albert" => {
+            if attachments <= 0 || attachments as usize >= nodes {
+                return Err(invalid(format!(
+                    "attachments must be positive and less than the number of nodes, got {}",
+                    attachments
+                )));
+            }
+            Ok(GraphModel::BarabasiAlbert {
+                attachments: attachments as usize,
+            })
+        }
+        "watts_strogatz" => {
+            if neighbors < 2 || neighbors % 2 != 0 || neighbors as usize >= nodes {
+                return Err(invalid(format!(
+                    "neighbors must be an even number of at least 2 and less than the number \
+                     of nodes, got {}",
+                    neighbors
+                )));
+            }
+            Ok(GraphModel::WattsStrogatz {
+                neighbors: neighbors as usize,
+                rewire_probability: probability("rewire_probability", rewire_probability)?,
+            })
+        }
+        _ => Err(invalid(format!(
+            "unknown model '{}' (expected random, barabasi_albert or watts_strogatz)",
+            model
+        ))),
+    }
+}
+
+#[pg_extern]
+/// Adds a synthetic graph to the in-memory graph, e.g. for load tests.
+///
+/// The nodes are named `prefix` followed by their number, and every edge has a weight
+/// of 1. The edges are applied as by `meritrank_add_batch`, so they are written to the
+/// `graph` table and announced as well, unless `meritrank.persist_changes` is off.
+///
+/// # Arguments
+///
+/// * `nodes` - The number of nodes.
+/// * `model` - `random` (uniform random edges), `barabasi_albert` (preferential
+///   attachment, with hubs) or `watts_strogatz` (small world).
+/// * `edge_probability` - For `random`, the probability of every possible edge.
+/// * `attachments` - For `barabasi_albert`, the number of edges of every new node.
+/// * `neighbors` - For `watts_strogatz`, the even number of nearest nodes on the ring
+///   every node votes for.
+/// * `rewire_probability` - For `watts_strogatz`, the probability of rewiring an edge.
+/// * `seed` - Seed for a reproducible graph. `NULL` generates a different graph each time.
+/// * `prefix` - The prefix of the node names.
+///
+/// # Returns
+///
+/// The number of edges added.
+pub fn meritrank_generate_graph(
+    nodes: i32,
+    model: default!(&str, "'random'"),
+    edge_probability: default!(f64, 0.1),
+    attachments: default!(i32, 2),
+    neighbors: default!(i32, 4),
+    rewire_probability: default!(f64, 0.1),
+    seed: default!(Option<i64>, "NULL"),
+    prefix: default!(&str, "'G'"),
+) -> Result<i64, ErrorReport> {
+    if nodes <= 0 {
+        return Err(GraphManipulationError::GraphGenerationFailure(format!(
+            "nodes must be a positive integer, got {}",
+            nodes
+        ))
+        .into());
+    }
+    let nodes = nodes as usize;
+    let model = parse_model(
+        model,
+        nodes,
+        edge_probability,
+        attachments,
+        neighbors,
+        rewire_probability,
+    )?;
+
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed as u64),
+        None => StdRng::from_entropy(),
+    };
+    let edges = model.generate(nodes, &mut rng);
+
+    let names: Vec<String> = (0..nodes)
+        .map(|node| format!("{}{}", prefix, node))
+        .collect();
+    let edges: Vec<(&str, &str, f64)> = edges
+        .iter()
+        .map(|&(source, target)| (names[source].as_str(), names[target].as_str(), 1.0))
+        .collect();
+    add_edges(&edges)?;
+
+    Ok(edges.len() as i64)
+}
@@ -1,12 +1,18 @@
 // Standard library imports
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 // External crate imports
 use lazy_static::lazy_static;
 // use petgraph::visit::Walker;
 
 // Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
 use pgx::*;
 // use pgx::pg_sys::Datum;
 // use pgx::prelude::*;
@@ -17,19 +23,392 @@ pub use crate::error::GraphManipulationError;
 // use crate::logger::Logger;
 
 // Current crate (`crate::`) imports
+use crate::audit;
+use crate::cache::{self, RankCacheEntry, RankCacheKey};
+use crate::guc::{self, NodeBudgetPolicy};
+#[cfg(feature = "profiling")]
+use crate::lib_graph::profile;
 pub use crate::lib_graph::NodeId;
-use crate::lib_graph::{MeritRank, MyGraph};
+use crate::lib_graph::{
+    CsrGraph, MeritRank, MeritRankError, MyGraph, Node, NodeType, Timestamp, Weight,
+};
+use crate::loader;
+use crate::logging::{log_info, log_warning};
+use crate::node_types;
+use crate::notify::{self, ChangeOp};
+use crate::outbox;
+use crate::persist;
+use crate::rating::{Alpha, Freshness, Iterations, Limit, PenaltyFactor, TimeBudget};
+use crate::stats;
+use crate::walks;
 
 // Singleton instance
 lazy_static! {
     pub static ref GRAPH: Arc<Mutex<GraphSingleton>> = Arc::new(Mutex::new(GraphSingleton::new()));
 }
 
+// Generation of the graph in `GRAPH`, readable without locking it
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // Last snapshot taken by this thread, reused until the generation of the graph advances
+    static SNAPSHOT: RefCell<Option<Arc<GraphSnapshot>>> = RefCell::new(None);
+}
+
+/// Longest node name accepted, in bytes. Keys of the `graph` table hold two names, and
+/// btree index entries are limited to about a third of a page, so longer names would only
+/// fail once the edge is written.
+pub const MAX_NODE_NAME_BYTES: usize = 1024;
+
+// Whether the graph has been loaded from the `graph` table in this backend
+pub(crate) static HYDRATED: AtomicBool = AtomicBool::new(false);
+
+// Whether the graph was loaded during a parallel query, before `meritrank_config` could
+// be applied
+static CONFIG_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Whether this backend is running a parallel query, during which settings and tables
+/// cannot be written.
+pub(crate) fn in_parallel_mode() -> bool {
+    unsafe { pg_sys::IsInParallelMode() }
+}
+
+/// Loads the `graph` table into the in-memory graph on first use, unless
+/// `meritrank.auto_load` is off.
+///
+/// The settings in `meritrank_config` are applied first, or on the next use outside of a
+/// parallel query. The table is read at most once per backend; a failed load is retried
+/// on the next use.
+pub fn ensure_hydrated() -> Result<(), GraphManipulationError> {
+    if CONFIG_PENDING.load(Ordering::SeqCst) && !in_parallel_mode() {
+        persist::apply_config()?;
+        CONFIG_PENDING.store(false, Ordering::SeqCst);
+    }
+    if !guc::AUTO_LOAD.get() || HYDRATED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    let apply_config = || {
+        if in_parallel_mode() {
+            CONFIG_PENDING.store(true, Ordering::SeqCst);
+            return Ok(0);
+        }
+        persist::apply_config()
+    };
+    match apply_config().and_then(|_| load_graph_table()) {
+        Ok(loaded) => {
+            log_info!("meritrank: loaded {} edges from the graph table", loaded);
+            Ok(())
+        }
+        Err(e) => {
+            HYDRATED.store(false, Ordering::SeqCst);
+            Err(e)
+        }
+    }
+}
+
+/// Loads the edges of the `graph` table into the in-memory graph, remembering the
+/// version of the table it reflects. Returns the number of edges loaded.
+///
+/// A partitioned table is read one partition at a time.
+pub fn load_graph_table() -> Result<i64, GraphManipulationError> {
+    // Read the version first: changes committed while loading only cause another reload.
+    // It is stored before loading, so that locking the graph while loading does not
+    // mistake the graph for stale.
+    persist::KNOWN_VERSION.store(persist::table_version()?, Ordering::SeqCst);
+    let load = || -> Result<i64, GraphManipulationError> {
+        let mut loaded = 0;
+        for partition in loader::graph_table_partitions()? {
+            loaded += loader::load_edges(
+                &format!(
+                    "SELECT source, destination, weight::float8, updated_at FROM {}",
+                    partition
+                ),
+                &partition,
+                false,
+            )?;
+        }
+        Ok(loaded)
+    };
+    load().map_err(|e| {
+        persist::KNOWN_VERSION.store(-1, Ordering::SeqCst);
+        e
+    })
+}
+
+// OID of the database the in-memory state belongs to, 0 before first use
+pub(crate) static DATABASE: AtomicU32 = AtomicU32::new(0);
+
+/// Makes sure the in-memory state belongs to the database this backend is connected to.
+///
+/// A regular backend serves a single database, but the state lives in process-wide
+/// statics, so it is tagged with the database's OID and discarded if it was built for
+/// another one. The graph is then loaded again from this database's `graph` table.
+pub fn ensure_database() -> Result<(), GraphManipulationError> {
+    let database = unsafe { pg_sys::MyDatabaseId }.as_u32();
+    let previous = DATABASE.swap(database, Ordering::SeqCst);
+    if previous == 0 || previous == database {
+        return Ok(());
+    }
+
+    log_info!(
+        "meritrank: discarding the in-memory graph of database {} in database {}",
+        previous,
+        database
+    );
+    GraphSingleton::clear_graph(true)?;
+    walks::lock_store()?.clear();
+    HYDRATED.store(false, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Discards the in-memory graph if other backends changed the `graph` table since it was
+/// loaded, unless `meritrank.sync_backends` is off, or if a transaction that changed it
+/// aborted. `ensure_hydrated` then loads it again.
+///
+/// Changes of this backend that are still pending are written to the table first, so
+/// they are part of the reloaded graph. During a parallel query, which cannot write them,
+/// the graph is checked on the next use instead.
+pub fn ensure_current() -> Result<(), GraphManipulationError> {
+    if !guc::AUTO_LOAD.get() || !HYDRATED.load(Ordering::SeqCst) || in_parallel_mode() {
+        return Ok(());
+    }
+    if persist::take_rolled_back() {
+        log_info!("meritrank: reloading the graph after its changes were rolled back");
+    } else if guc::SYNC_BACKENDS.get() && guc::PERSIST_CHANGES.get() && persist::is_stale()? {
+        log_info!("meritrank: reloading the graph changed by other backends");
+    } else {
+        return Ok(());
+    }
+
+    persist::flush()?;
+    GraphSingleton::clear_graph(false)?;
+    walks::lock_store()?.clear();
+    HYDRATED.store(false, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Makes sure the in-memory graph belongs to this database, is current and is loaded,
+/// without holding `GRAPH` afterwards.
+fn ensure_loaded() -> Result<(), GraphManipulationError> {
+    ensure_database()?;
+    ensure_current()?;
+    ensure_hydrated()
+}
+
+/// Locks the graph, loading it from the `graph` table first if it has not been yet.
+pub fn lock_graph() -> Result<MutexGuard<'static, GraphSingleton>, GraphManipulationError> {
+    ensure_loaded()?;
+    GRAPH
+        .lock()
+        .map_err(|e| GraphManipulationError::MutexLockFailure(format!("Mutex lock error: {}", e)))
+}
+
+/// Returns the current time as an edge timestamp.
+pub fn current_timestamp() -> Timestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as Timestamp)
+}
+
+/// Number of seconds between the Unix epoch and the Postgres epoch (2000-01-01).
+const POSTGRES_EPOCH_OFFSET: i64 = 946_684_800;
+
+/// Converts a Postgres timestamp into an edge timestamp.
+pub fn timestamp_from_pg(timestamp: TimestampWithTimeZone) -> Timestamp {
+    let micros: i64 = timestamp.into();
+    micros.div_euclid(1_000_000) + POSTGRES_EPOCH_OFFSET
+}
+
+/// Converts an edge timestamp into a Postgres timestamp.
+pub fn timestamp_to_pg(timestamp: Timestamp) -> Option<TimestampWithTimeZone> {
+    TimestampWithTimeZone::try_from((timestamp - POSTGRES_EPOCH_OFFSET) * 1_000_000).ok()
+}
+
+/// A consistent copy of the graph and its node names, taken under a single lock.
+#[derive(Clone)]
+pub struct GraphSnapshot {
+    pub graph: MyGraph,
+    pub node_names: HashMap<String, NodeId>,
+    pub generation: u64,
+}
+
+impl GraphSnapshot {
+    /// Returns the ID of the node with the given name.
+    pub fn node_id(&self, node_name: &str) -> Result<NodeId, GraphManipulationError> {
+        self.node_names.get(node_name).copied().ok_or_else(|| {
+            GraphManipulationError::NodeNotFound(format!("Node not found: {}", node_name))
+        })
+    }
+}
+
+/// A mutation of an edge between named nodes, to be mirrored to the `graph` table and
+/// announced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeMutation {
+    pub op: ChangeOp,
+    pub subject: String,
+    pub object: String,
+    pub weight: Option<Weight>,
+    /// The weight of the edge before the mutation, if it existed.
+    pub previous_weight: Option<Weight>,
+}
+
+/// Native key of a node, for applications that identify nodes by UUIDs or integer IDs
+/// rather than by names.
+///
+/// Such nodes are named after their key in canonical form, so that the `graph` table and
+/// the functions taking names see the same nodes, but `GraphSingleton` indexes them by
+/// key as well: they are looked up and reported without formatting or parsing names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKey {
+    Uuid([u8; 16]),
+    Number(i64),
+}
+
+impl NodeKey {
+    /// Returns the key a node name stands for, if it is one in canonical form: a UUID in
+    /// lowercase hyphenated form, or an integer in decimal form without leading zeros.
+    pub fn from_name(name: &str) -> Option<NodeKey> {
+        let digits = name.strip_prefix('-').unwrap_or(name);
+        if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            if (digits.len() > 1 && digits.starts_with('0')) || name == "-0" {
+                return None;
+            }
+            return name.parse().ok().map(NodeKey::Number);
+        }
+
+        if name.len() != uuid::fmt::Hyphenated::LENGTH
+            || name.bytes().any(|byte| byte.is_ascii_uppercase())
+        {
+            return None;
+        }
+        let parsed = uuid::Uuid::parse_str(name).ok()?;
+        Some(NodeKey::Uuid(parsed.into_bytes()))
+    }
+
+    /// Returns the name of the node with this key.
+    pub fn name(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for NodeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeKey::Uuid(bytes) => uuid::Uuid::from_bytes(*bytes).hyphenated().fmt(f),
+            NodeKey::Number(number) => number.fmt(f),
+        }
+    }
+}
+
+/// A node given by its name or by its native key.
+#[derive(Debug, Clone, Copy)]
+pub enum NodeRef<'a> {
+    Name(&'a str),
+    Key(NodeKey),
+}
+
+impl NodeRef<'_> {
+    /// Returns the name of the node.
+    pub fn name(&self) -> Cow<'_, str> {
+        match self {
+            NodeRef::Name(name) => Cow::Borrowed(name),
+            NodeRef::Key(key) => Cow::Owned(key.name()),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for NodeRef<'a> {
+    fn from(name: &'a str) -> Self {
+        NodeRef::Name(name)
+    }
+}
+
+impl<'a> From<&'a String> for NodeRef<'a> {
+    fn from(name: &'a String) -> Self {
+        NodeRef::Name(name)
+    }
+}
+
+impl From<NodeKey> for NodeRef<'_> {
+    fn from(key: NodeKey) -> Self {
+        NodeRef::Key(key)
+    }
+}
+
+impl fmt::Display for NodeRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeRef::Name(name) => name.fmt(f),
+            NodeRef::Key(key) => key.fmt(f),
+        }
+    }
+}
+
+/// Mirrors edge changes to the `graph` table, appends them to the outbox and the audit log,
+/// and announces them. Drops the stored walks if a mutation exceeded the graph budget under
+/// the `spill` policy.
+///
+/// Must be called without holding `GRAPH`.
+pub(crate) fn publish_changes(
+    changes: &[EdgeMutation],
+    generation: u64,
+) -> Result<(), GraphManipulationError> {
+    walks::spill_if_requested()?;
+    for change in changes {
+        persist::record_change(
+            change.op,
+            Some(&change.subject),
+            Some(&change.object),
+            change.weight,
+        )?;
+        outbox::record_change(
+            change.op,
+            Some(&change.subject),
+            Some(&change.object),
+            change.weight,
+        )?;
+        audit::record_change(
+            change.op,
+            Some(&change.subject),
+            Some(&change.object),
+            change.previous_weight,
+            change.weight,
+        )?;
+        notify::notify_change(
+            change.op,
+            Some(&change.subject),
+            Some(&change.object),
+            change.weight,
+            generation,
+        )?;
+    }
+    Ok(())
+}
+
 #[allow(dead_code)]
 // GraphSingleton structure
 pub struct GraphSingleton {
     graph: MyGraph,
     node_names: HashMap<String, NodeId>,
+    // Reverse index of `node_names`, kept in sync with it
+    node_ids: HashMap<NodeId, String>,
+    // Nodes whose names are native keys, by key, and the reverse index, kept in sync with
+    // `node_names`
+    keyed_nodes: HashMap<NodeKey, NodeId>,
+    node_keys: HashMap<NodeId, NodeKey>,
+    generation: u64,
+    next_node_id: usize,
+    evicted_nodes: u64,
+    // Names of the evicted nodes, whose edges are left to the `graph` table: they are not
+    // loaded again and `meritrank_sync` ignores them
+    evicted_names: HashSet<String>,
+    node_properties: HashMap<NodeId, HashMap<String, String>>,
+    // Whether walks step through a frozen CSR copy of the graph, and the copy itself
+    // with the generation it was built from
+    frozen: bool,
+    csr: Option<(Arc<CsrGraph>, u64)>,
 }
 
 #[allow(dead_code)]
@@ -39,35 +418,180 @@ impl GraphSingleton {
         GraphSingleton {
             graph: MyGraph::new(),
             node_names: HashMap::new(),
+            node_ids: HashMap::new(),
+            keyed_nodes: HashMap::new(),
+            node_keys: HashMap::new(),
+            generation: 0,
+            next_node_id: 1,
+            evicted_nodes: 0,
+            evicted_names: HashSet::new(),
+            node_properties: HashMap::new(),
+            frozen: false,
+            csr: None,
         }
     }
 
     /// Get MeritRank object
     pub fn get_rank() -> Result<MeritRank, GraphManipulationError> {
-        match GRAPH.lock() {
-            Ok(graph) => {
-                let merit_rank = MeritRank::new(graph.graph.clone())?;
-                Ok(merit_rank)
+        Self::get_rank_with_generation().map(|(merit_rank, _)| merit_rank)
+    }
+
+    /// Get MeritRank object together with the generation of the graph it was built from
+    pub fn get_rank_with_generation() -> Result<(MeritRank, u64), GraphManipulationError> {
+        let mut graph = lock_graph()?;
+        let mut merit_rank = MeritRank::new(graph.graph.clone())?;
+        merit_rank.set_csr(graph.current_csr());
+        Ok((merit_rank, graph.generation))
+    }
+
+    /// Takes a snapshot of the graph and its node names.
+    ///
+    /// Each thread keeps the last snapshot it took, tagged with the generation of the
+    /// graph, and reuses it without locking anything until the generation advances. Only
+    /// then is `GRAPH` locked to copy the graph, so read-mostly workloads neither copy the
+    /// graph on every call nor contend for its lock.
+    pub fn get_snapshot() -> Result<Arc<GraphSnapshot>, GraphManipulationError> {
+        ensure_loaded()?;
+        let generation = GENERATION.load(Ordering::Acquire);
+        let cached = SNAPSHOT.with(|cached| {
+            cached
+                .borrow()
+                .as_ref()
+                .filter(|snapshot| snapshot.generation == generation)
+                .cloned()
+        });
+        if let Some(snapshot) = cached {
+            stats::record_snapshot(true);
+            return Ok(snapshot);
+        }
+
+        stats::record_snapshot(false);
+        let snapshot = {
+            let graph = GRAPH.lock().map_err(|e| {
+                GraphManipulationError::MutexLockFailure(format!("Mutex lock error: {}", e))
+            })?;
+            Arc::new(GraphSnapshot {
+                graph: graph.graph.clone(),
+                node_names: graph.node_names.clone(),
+                generation: graph.generation,
+            })
+        };
+        SNAPSHOT.with(|cached| *cached.borrow_mut() = Some(snapshot.clone()));
+        Ok(snapshot)
+    }
+
+    /// Returns the current generation of the graph.
+    ///
+    /// The generation is bumped on every mutation, so results computed from the same
+    /// generation are based on an identical graph.
+    pub fn get_generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Marks the graph as mutated.
+    fn bump_generation(&mut self) {
+        self.generation += 1;
+        GENERATION.store(self.generation, Ordering::Release);
+    }
+
+    /// Builds a CSR copy of the graph for walks to step through, and keeps it current from
+    /// now on: after a mutation, it is rebuilt when the next walks are generated.
+    pub fn freeze(&mut self) -> Arc<CsrGraph> {
+        self.frozen = true;
+        self.current_csr().expect("frozen graph has a CSR copy")
+    }
+
+    /// Drops the CSR copy of the graph, so that walks step through the graph itself.
+    ///
+    /// Returns whether the graph was frozen.
+    pub fn thaw(&mut self) -> bool {
+        self.csr = None;
+        std::mem::replace(&mut self.frozen, false)
+    }
+
+    /// Returns the CSR copy of the current graph if it is frozen, rebuilding the copy if
+    /// the graph changed since it was built.
+    pub fn current_csr(&mut self) -> Option<Arc<CsrGraph>> {
+        if !self.frozen {
+            return None;
+        }
+        match self.csr {
+            Some((ref csr, generation)) if generation == self.generation => Some(csr.clone()),
+            _ => {
+                let csr = Arc::new(CsrGraph::from_graph(&self.graph));
+                self.csr = Some((csr.clone(), self.generation));
+                Some(csr)
             }
-            Err(e) => Err(GraphManipulationError::MutexLockFailure(format!(
-                "Mutex lock error: {}",
-                e
-            ))),
         }
     }
 
+    /// Estimates the number of heap bytes held by the CSR copy of the graph.
+    pub fn csr_memory_usage(&self) -> usize {
+        self.csr.as_ref().map_or(0, |(csr, _)| csr.memory_usage())
+    }
+
+    /// Returns the number of nodes evicted to stay within `meritrank.max_nodes`.
+    pub fn get_evicted_nodes(&self) -> u64 {
+        self.evicted_nodes
+    }
+
+    /// Estimates the number of heap bytes held by the node name maps.
+    pub fn node_names_memory_usage(&self) -> usize {
+        let entry_size = std::mem::size_of::<String>() + std::mem::size_of::<NodeId>() + 1;
+        let key_entry_size = std::mem::size_of::<NodeKey>() + std::mem::size_of::<NodeId>() + 1;
+        (self.node_names.capacity() + self.node_ids.capacity()) * entry_size
+            + (self.keyed_nodes.capacity() + self.node_keys.capacity()) * key_entry_size
+            + 2 * self
+                .node_names
+                .keys()
+                .map(|name| name.capacity())
+                .sum::<usize>()
+    }
+
     /// Borrow Node Names
     pub fn borrow_node_names(&self) -> &HashMap<String, NodeId> {
         &self.node_names
     }
 
+    /// Builds a map from node IDs to node names.
+    pub fn node_ids_to_names(&self) -> HashMap<NodeId, String> {
+        self.node_ids.clone()
+    }
+
+    /// Builds a map from `(source, destination)` node names to the weight of every edge.
+    pub fn named_edges(&self) -> HashMap<(String, String), Weight> {
+        self.graph
+            .all_edges()
+            .into_iter()
+            .filter_map(|(source, target, weight)| {
+                let source = self.get_node_name(source)?;
+                let target = self.get_node_name(target)?;
+                Some(((source.to_string(), target.to_string()), weight))
+            })
+            .collect()
+    }
+
+    /// Whether the edge touches a node that was evicted to stay within the node or edge
+    /// budget, so that the in-memory graph no longer reflects it.
+    pub fn is_evicted_edge(&self, source: &str, destination: &str) -> bool {
+        self.evicted_names.contains(source) || self.evicted_names.contains(destination)
+    }
+
+    /// Returns the name of the node with the given ID.
+    pub fn get_node_name(&self, node_id: NodeId) -> Option<&str> {
+        self.node_ids.get(&node_id).map(String::as_str)
+    }
+
     /// Borrow Graph
     pub fn borrow_graph(&self) -> &MyGraph {
         &self.graph
     }
 
     /// Borrow Graph Mut
+    ///
+    /// The graph may be changed through the reference, so it counts as a mutation.
     pub fn borrow_graph_mut(&mut self) -> &mut MyGraph {
+        self.bump_generation();
         &mut self.graph
     }
 
@@ -84,13 +608,7 @@ impl GraphSingleton {
     ///
     /// Returns a `GraphManipulationError::MutexLockFailure()` if the mutex lock fails.
     pub fn add_node(node_name: &str) -> Result<NodeId, GraphManipulationError> {
-        match GRAPH.lock() {
-            Ok(mut graph) => graph.get_node_id(node_name),
-            Err(e) => Err(GraphManipulationError::MutexLockFailure(format!(
-                "Mutex lock error: {}",
-                e
-            ))),
-        }
+        lock_graph()?.get_node_id(node_name)
     }
 
     // This method remains largely the same, it's already well structured
@@ -98,148 +616,2139 @@ impl GraphSingleton {
         if let Some(&node_id) = self.node_names.get(node_name) {
             Ok(node_id)
         } else {
-            let new_node_id = self.graph.node_count() + 1;
-            let node_id = NodeId::UInt(new_node_id);
-            self.node_names.insert(node_name.to_string(), node_id);
-            self.graph.add_node(node_id.into());
-            Ok(node_id)
+            self.check_node_names(&[node_name])?;
+            self.reserve_nodes(&[node_name])?;
+            Ok(self.create_node(node_name))
         }
     }
 
-    /// Returns the name of the node with the given ID.
-    pub fn node_name_to_id(node_name: &str) -> Result<NodeId, GraphManipulationError> {
-        match GRAPH.lock() {
-            Ok(graph) => {
-                if let Some(&node_id) = graph.node_names.get(node_name) {
-                    Ok(node_id)
-                } else {
-                    Err(GraphManipulationError::NodeNotFound(format!(
-                        "Node not found: {}",
-                        node_name
-                    )))
-                }
-            }
-            Err(e) => Err(GraphManipulationError::MutexLockFailure(format!(
-                "Mutex lock error: {}",
-                e
+    /// Checks that the named nodes exist when `meritrank.strict_nodes` is on.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::NodeNotFound()` for the first node that does not exist.
+    pub fn require_nodes(&self, node_names: &[&str]) -> Result<(), GraphManipulationError> {
+        if !guc::STRICT_NODES.get() {
+            return Ok(());
+        }
+        match node_names
+            .iter()
+            .find(|node_name| !self.node_names.contains_key(**node_name))
+        {
+            Some(node_name) => Err(GraphManipulationError::NodeNotFound(format!(
+                "{} (meritrank.strict_nodes is on; register it with meritrank_add_node())",
+                node_name
             ))),
+            None => Ok(()),
         }
     }
 
-    /// Returns the ID of the node with the given name.
-    pub fn node_id_to_name(node_id: NodeId) -> Result<String, GraphManipulationError> {
-        match GRAPH.lock() {
-            Ok(graph) => {
-                for (name, id) in graph.node_names.iter() {
-                    if *id == node_id {
-                        return Ok(name.to_string());
-                    }
-                }
-                Err(GraphManipulationError::NodeNotFound(format!(
-                    "Node not found: {}",
-                    node_id
-                )))
+    /// Checks that the names of nodes to be created are neither empty nor longer than
+    /// `MAX_NODE_NAME_BYTES`. Names of existing nodes are not checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::InvalidNodeName()` for the first invalid name.
+    fn check_node_names(&self, node_names: &[&str]) -> Result<(), GraphManipulationError> {
+        for node_name in node_names {
+            if self.node_names.contains_key(*node_name) {
+                continue;
+            }
+            if node_name.is_empty() {
+                return Err(GraphManipulationError::InvalidNodeName(
+                    "node names must not be empty".to_string(),
+                ));
+            }
+            if node_name.len() > MAX_NODE_NAME_BYTES {
+                return Err(GraphManipulationError::InvalidNodeName(format!(
+                    "name of {} bytes exceeds the limit of {} bytes",
+                    node_name.len(),
+                    MAX_NODE_NAME_BYTES
+                )));
             }
-            Err(e) => Err(GraphManipulationError::MutexLockFailure(format!(
-                "Mutex lock error: {}",
-                e
-            ))),
         }
+        Ok(())
     }
 
-    pub fn clear_graph() -> Result<(), GraphManipulationError> {
-        match GRAPH.lock() {
-            Ok(mut graph) => {
-                graph.graph.clear();
-                graph.node_names.clear();
+    /// Returns the ID of the named node, creating it without checking the node budget.
+    fn get_or_create_node(&mut self, node_name: &str) -> NodeId {
+        match self.node_names.get(node_name) {
+            Some(&node_id) => node_id,
+            None => self.create_node(node_name),
+        }
+    }
+
+    /// Creates a new node. IDs are never reused, since evicted nodes leave gaps in the graph.
+    fn create_node(&mut self, node_name: &str) -> NodeId {
+        self.create_keyed_node(node_name, NodeKey::from_name(node_name))
+    }
+
+    /// Creates a new node whose name is the given native key, if any.
+    fn create_keyed_node(&mut self, node_name: &str, key: Option<NodeKey>) -> NodeId {
+        let node_id = NodeId::UInt(self.next_node_id);
+        self.next_node_id += 1;
+        self.node_names.insert(node_name.to_string(), node_id);
+        self.node_ids.insert(node_id, node_name.to_string());
+        if let Some(key) = key {
+            self.keyed_nodes.insert(key, node_id);
+            self.node_keys.insert(node_id, key);
+        }
+        self.graph
+            .add_node(Node::with_type(node_id, NodeType::from_name(node_name)));
+        self.bump_generation();
+        node_id
+    }
+
+    /// Makes sure the named nodes that do not exist yet fit into `meritrank.max_nodes`,
+    /// applying `meritrank.node_budget_policy` if they do not.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::NodeBudgetExceeded()` if the policy is `reject`,
+    /// or if not enough nodes can be evicted.
+    fn reserve_nodes(&mut self, node_names: &[&str]) -> Result<(), GraphManipulationError> {
+        let max_nodes = guc::MAX_NODES.get();
+        if max_nodes <= 0 {
+            return Ok(());
+        }
+        let max_nodes = max_nodes as usize;
+
+        let missing = node_names
+            .iter()
+            .filter(|name| !self.node_names.contains_key(**name))
+            .collect::<HashSet<_>>()
+            .len();
+        let required = self.graph.node_count() + missing;
+        if required <= max_nodes {
+            return Ok(());
+        }
+
+        match guc::NODE_BUDGET_POLICY.get() {
+            NodeBudgetPolicy::Reject => Err(GraphManipulationError::NodeBudgetExceeded(format!(
+                "{} nodes would exceed meritrank.max_nodes = {}",
+                required, max_nodes
+            ))),
+            NodeBudgetPolicy::Warn => {
+                log_warning!(
+                    "meritrank: graph has {} nodes, exceeding meritrank.max_nodes = {}",
+                    required,
+                    max_nodes
+                );
                 Ok(())
             }
-            Err(e) => Err(GraphManipulationError::MutexLockFailure(format!(
-                "Mutex lock error: {}",
-                e
+            NodeBudgetPolicy::Spill => {
+                log_warning!(
+                    "meritrank: graph has {} nodes, exceeding meritrank.max_nodes = {}; \
+                     dropping stored walks",
+                    required,
+                    max_nodes
+                );
+                self.spill()
+            }
+            NodeBudgetPolicy::Evict => self.evict_nodes(required - max_nodes, node_names),
+        }
+    }
+
+    /// Makes sure a new edge between the named nodes fits into `meritrank.max_edges`,
+    /// applying `meritrank.node_budget_policy` if it does not.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::EdgeBudgetExceeded()` if the policy is `reject`,
+    /// or if not enough edges can be evicted.
+    fn reserve_edge(
+        &mut self,
+        subject: &str,
+        object: &str,
+    ) -> Result<(), GraphManipulationError> {
+        let max_edges = guc::MAX_EDGES.get();
+        if max_edges <= 0 || self.contains_edge(subject, object) {
+            return Ok(());
+        }
+        let max_edges = max_edges as usize;
+
+        let required = self.graph.edge_count() + 1;
+        if required <= max_edges {
+            return Ok(());
+        }
+
+        match guc::NODE_BUDGET_POLICY.get() {
+            NodeBudgetPolicy::Reject => Err(GraphManipulationError::EdgeBudgetExceeded(format!(
+                "{} edges would exceed meritrank.max_edges = {}",
+                required, max_edges
             ))),
+            NodeBudgetPolicy::Warn => {
+                log_warning!(
+                    "meritrank: graph has {} edges, exceeding meritrank.max_edges = {}",
+                    required,
+                    max_edges
+                );
+                Ok(())
+            }
+            NodeBudgetPolicy::Spill => {
+                log_warning!(
+                    "meritrank: graph has {} edges, exceeding meritrank.max_edges = {}; \
+                     dropping stored walks",
+                    required,
+                    max_edges
+                );
+                self.spill()
+            }
+            NodeBudgetPolicy::Evict => self.evict_edges(required - max_edges, &[subject, object]),
         }
     }
-}
 
-#[pg_extern]
-pub fn meritrank_add(
-    subject: &str,
-    object: &str,
-    amount: f64,
-) -> Result<(), GraphManipulationError> {
-    match GRAPH.lock() {
-        Ok(mut graph) => {
-            let subject_id = graph.get_node_id(subject)?;
-            let object_id = graph.get_node_id(object)?;
+    /// Drops the cached ranks and the graph snapshot of this thread, and has the stored
+    /// walks dropped once `GRAPH` is released.
+    fn spill(&mut self) -> Result<(), GraphManipulationError> {
+        cache::lock_cache()?.clear();
+        SNAPSHOT.with(|cached| cached.borrow_mut().take());
+        walks::request_spill();
+        Ok(())
+    }
 
-            graph
-                .borrow_graph_mut()
-                .add_edge(subject_id.into(), object_id.into(), amount)?;
-            Ok(())
+    /// Returns the nodes other than the named ones with their degree, fewest edges first.
+    fn eviction_candidates(&self, protected: &[&str]) -> Vec<(usize, NodeId)> {
+        let protected: HashSet<NodeId> = protected
+            .iter()
+            .filter_map(|name| self.node_names.get(*name).copied())
+            .collect();
+
+        let mut candidates: Vec<(usize, NodeId)> = self
+            .graph
+            .node_ids()
+            .into_iter()
+            .filter(|node_id| !protected.contains(node_id))
+            .map(|node_id| (self.graph.degree(node_id), node_id))
+            .collect();
+        candidates.sort_by_key(|&(degree, _)| degree);
+        candidates
+    }
+
+    /// Removes the `count` nodes with the fewest edges, sparing the named nodes.
+    fn evict_nodes(
+        &mut self,
+        count: usize,
+        protected: &[&str],
+    ) -> Result<(), GraphManipulationError> {
+        let candidates = self.eviction_candidates(protected);
+        if candidates.len() < count {
+            return Err(GraphManipulationError::NodeBudgetExceeded(format!(
+                "cannot evict {} nodes to stay within meritrank.max_nodes",
+                count
+            )));
         }
-        Err(e) => Err(GraphManipulationError::MutexLockFailure(format!(
-            "Mutex lock error: {}",
-            e
-        ))),
+
+        let evicted: HashSet<NodeId> = candidates
+            .into_iter()
+            .take(count)
+            .map(|(_, node_id)| node_id)
+            .collect();
+        self.remove_evicted(evicted)
     }
-}
 
-#[pg_extern]
-pub fn meritrank_calculate(
-    subject: &str,
-    object: &str,
-    iterations: i32,
-) -> Result<f64, GraphManipulationError> {
-    // Convert the subject string into a NodeId
-    let subject_id = GraphSingleton::node_name_to_id(subject)?;
+    /// Removes the nodes with the fewest edges until at least `count` edges are gone,
+    /// sparing the named nodes.
+    fn evict_edges(
+        &mut self,
+        count: usize,
+        protected: &[&str],
+    ) -> Result<(), GraphManipulationError> {
+        let mut evicted = HashSet::new();
+        let mut removed = 0;
+        for (degree, node_id) in self.eviction_candidates(protected) {
+            if removed >= count {
+                break;
+            }
+            // Edges to nodes that are already evicted are counted once
+            let shared: usize = evicted
+                .iter()
+                .map(|&other| {
+                    self.graph.contains_edge(node_id, other) as usize
+                        + self.graph.contains_edge(other, node_id) as usize
+                })
+                .sum();
+            if degree > 0 {
+                evicted.insert(node_id);
+                removed += degree - shared.min(degree);
+            }
+        }
+        if removed < count {
+            return Err(GraphManipulationError::EdgeBudgetExceeded(format!(
+                "cannot evict {} edges to stay within meritrank.max_edges",
+                count
+            )));
+        }
+        self.remove_evicted(evicted)
+    }
 
-    // Initialize a new graph and merit rank object
-    let mut merit_rank = GraphSingleton::get_rank()?;
+    /// Removes evicted nodes with their edges and metadata.
+    ///
+    /// Evictions only bound the memory of this backend, so they are not written to the
+    /// `graph` table. The names are remembered instead, so that the edges of the evicted
+    /// nodes are not mistaken for deleted ones.
+    fn remove_evicted(&mut self, evicted: HashSet<NodeId>) -> Result<(), GraphManipulationError> {
+        for &node_id in evicted.iter() {
+            self.graph.remove_node(node_id);
+            if let Some(name) = self.node_ids.get(&node_id) {
+                self.evicted_names.insert(name.clone());
+            }
+        }
+        self.node_names
+            .retain(|_, node_id| !evicted.contains(node_id));
+        self.node_ids
+            .retain(|node_id, _| !evicted.contains(node_id));
+        self.keyed_nodes
+            .retain(|_, node_id| !evicted.contains(node_id));
+        self.node_keys
+            .retain(|node_id, _| !evicted.contains(node_id));
+        self.node_properties
+            .retain(|node_id, _| !evicted.contains(node_id));
+        self.evicted_nodes += evicted.len() as u64;
+        self.bump_generation();
 
-    // Attempt to calculate merit ranks
-    merit_rank.calculate(subject_id, iterations as usize)?;
+        // Removed edges may affect any cached rank vector
+        cache::lock_cache()?.clear();
+        Ok(())
+    }
 
-    // Get ranks and handle potential error
-    let peer_scores = merit_rank.get_ranks(subject_id, None)?;
+    /// Adds an edge between two named nodes, creating the nodes if necessary.
+    ///
+    /// Adding an existing edge replaces its weight. A weight of zero removes the edge,
+    /// as in the reference MeritRank implementation, and does not create any nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `subject` - The name of the source node.
+    /// * `object` - The name of the destination node.
+    /// * `amount` - The weight of the edge.
+    pub fn add_edge(
+        &mut self,
+        subject: &str,
+        object: &str,
+        amount: f64,
+    ) -> Result<(), GraphManipulationError> {
+        self.add_edge_at(subject, object, amount, current_timestamp())
+    }
 
-    // Find the rank for our object
-    let object_id = GraphSingleton::node_name_to_id(object)?;
+    /// Sets the weight of an edge like `add_edge`, describing the resulting change.
+    ///
+    /// Returns `None` if nothing changed, i.e. for a zero weight on a missing edge.
+    pub fn set_edge(
+        &mut self,
+        subject: &str,
+        object: &str,
+        amount: f64,
+    ) -> Result<Option<EdgeMutation>, GraphManipulationError> {
+        self.set_edge_at(subject, object, amount, current_timestamp())
+    }
 
-    // Convert Vec<(NodeId, f64)> to HashMap<NodeId, f64> if needed, or find directly in the Vec
-    let rank = peer_scores.into_iter()
-        .find(|(node_id, _)| node_id == &object_id)
-        .map(|(_, rank)| rank)
-        .ok_or_else(|| GraphManipulationError::NodeNotFound(format!(
-            "Rank not found for node: {}",
-            object
-        )))?;
+    /// Checks that an edge is not a self-reference and that its weight is a finite number.
+    fn check_edge(subject: &str, object: &str, amount: f64) -> Result<(), GraphManipulationError> {
+        if subject == object {
+            return Err(GraphManipulationError::EdgeCreationFailure(format!(
+                "self-reference of node {}",
+                subject
+            )));
+        }
+        if !amount.is_finite() {
+            return Err(GraphManipulationError::WeightExtractionFailure(format!(
+                "weight of edge {} -> {} is not a finite number",
+                subject, object
+            )));
+        }
+        Ok(())
+    }
 
-    Ok(rank)
-}
+    /// Sets the weight of an edge like `set_edge`, for an edge last updated at `timestamp`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::EdgeCreationFailure()` for a self-reference, and a
+    /// `GraphManipulationError::WeightExtractionFailure()` for a weight that is not finite.
+    pub fn set_edge_at(
+        &mut self,
+        subject: &str,
+        object: &str,
+        amount: f64,
+        timestamp: Timestamp,
+    ) -> Result<Option<EdgeMutation>, GraphManipulationError> {
+        Self::check_edge(subject, object, amount)?;
+        let op = match (self.contains_edge(subject, object), amount == 0.0) {
+            // A zero weight removes the edge
+            (true, true) => ChangeOp::Delete,
+            (false, true) => return Ok(None),
+            (true, false) => ChangeOp::Update,
+            (false, false) => ChangeOp::Add,
+        };
+        let previous_weight = self.edge_weight(subject, object);
+        self.add_edge_at(subject, object, amount, timestamp)?;
+        Ok(Some(EdgeMutation {
+            op,
+            subject: subject.to_string(),
+            object: object.to_string(),
+            weight: Some(amount).filter(|_| op != ChangeOp::Delete),
+            previous_weight,
+        }))
+    }
 
-#[pg_extern]
-pub fn meritrank_delete(subject: &str, object: &str) -> Result<(), GraphManipulationError> {
-    match GRAPH.lock() {
-        Ok(mut graph) => {
-            let subject_id = graph.get_node_id(subject)?;
-            let object_id = graph.get_node_id(object)?;
-
-            graph
-                .borrow_graph_mut()
-                .remove_edge(subject_id.into(), object_id.into());
-            Ok(())
+    /// Sets the weights of a batch of edges like `set_edge_at`, all of them or none.
+    ///
+    /// If an edge cannot be set, e.g. because it would exceed `meritrank.max_edges` under
+    /// the `reject` policy, the edges set before it are restored and the nodes created for
+    /// them are removed again, so that no change is left unpublished in the graph. Cached
+    /// ranks and stored walks are dropped, since they may have seen the restored edges.
+    ///
+    /// # Returns
+    ///
+    /// The changes, in the order of the edges.
+    pub fn set_edges_at(
+        &mut self,
+        edges: &[(&str, &str, f64, Timestamp)],
+    ) -> Result<Vec<EdgeMutation>, GraphManipulationError> {
+        let next_node_id = self.next_node_id;
+        let mut changes = Vec::with_capacity(edges.len());
+        let mut previous_timestamps = Vec::with_capacity(edges.len());
+        for &(subject, object, amount, timestamp) in edges {
+            let previous_timestamp = self.edge_timestamp(subject, object);
+            match self.set_edge_at(subject, object, amount, timestamp) {
+                Ok(Some(change)) => {
+                    changes.push(change);
+                    previous_timestamps.push(previous_timestamp);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.undo_changes(&changes, &previous_timestamps, next_node_id)?;
+                    return Err(e);
+                }
+            }
         }
-        Err(e) => Err(GraphManipulationError::MutexLockFailure(format!(
-            "Mutex lock error: {}",
-            e
-        ))),
+        Ok(changes)
     }
+
+    /// Reverts changes made by `set_edges_at`, latest first, and removes the nodes with IDs
+    /// from `next_node_id` on, which were created for them.
+    fn undo_changes(
+        &mut self,
+        changes: &[EdgeMutation],
+        previous_timestamps: &[Option<Timestamp>],
+        next_node_id: usize,
+    ) -> Result<(), GraphManipulationError> {
+        for (change, previous_timestamp) in changes.iter().zip(previous_timestamps).rev() {
+            // Nodes evicted by a later edge took their edges with them
+            let (subject_id, object_id) = match (
+                self.node_names.get(&change.subject),
+                self.node_names.get(&change.object),
+            ) {
+                (Some(&subject_id), Some(&object_id)) => (subject_id, object_id),
+                _ => continue,
+            };
+            match change.previous_weight {
+                Some(weight) => {
+                    self.graph
+                        .add_edge(subject_id.into(), object_id.into(), weight)?;
+                    if let Some(timestamp) = *previous_timestamp {
+                        self.graph
+                            .set_edge_timestamp(subject_id, object_id, timestamp);
+                    }
+                }
+                None => self.graph.remove_edge(subject_id.into(), object_id.into()),
+            }
+        }
+
+        let created: HashSet<NodeId> = self
+            .node_ids
+            .keys()
+            .filter(|node_id| matches!(node_id, NodeId::UInt(id) if *id >= next_node_id))
+            .copied()
+            .collect();
+        for &node_id in created.iter() {
+            self.graph.remove_node(node_id);
+            if let Some(name) = self.node_ids.remove(&node_id) {
+                self.node_names.remove(&name);
+            }
+            if let Some(key) = self.node_keys.remove(&node_id) {
+                self.keyed_nodes.remove(&key);
+            }
+            self.node_properties.remove(&node_id);
+        }
+        self.bump_generation();
+        self.spill()
+    }
+
+    /// Adds an edge that was last updated at `timestamp`.
+    pub fn add_edge_at(
+        &mut self,
+        subject: &str,
+        object: &str,
+        amount: f64,
+        timestamp: Timestamp,
+    ) -> Result<(), GraphManipulationError> {
+        if amount == 0.0 {
+            if self.contains_edge(subject, object) {
+                self.remove_edge(subject, object)?;
+            }
+            return Ok(());
+        }
+
+        // Reserve room for both nodes at once, so that creating one cannot evict the other
+        self.check_node_names(&[subject, object])?;
+        self.reserve_nodes(&[subject, object])?;
+        self.reserve_edge(subject, object)?;
+        let subject_id = self.get_or_create_node(subject);
+        let object_id = self.get_or_create_node(object);
+
+        self.graph
+            .add_edge(subject_id.into(), object_id.into(), amount)?;
+        self.graph
+            .set_edge_timestamp(subject_id, object_id, timestamp);
+        self.bump_generation();
+        cache::lock_cache()?.invalidate_edge(subject_id, object_id);
+        Ok(())
+    }
+
+    /// Sets a property of the named node, creating the node if necessary.
+    /// A `None` value removes the property.
+    ///
+    /// Properties are metadata for applications and do not affect ranks.
+    pub fn set_node_property(
+        &mut self,
+        node_name: &str,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<(), GraphManipulationError> {
+        let node_id = self.get_node_id(node_name)?;
+        match value {
+            Some(value) => {
+                self.node_properties
+                    .entry(node_id)
+                    .or_insert_with(HashMap::new)
+                    .insert(key.to_string(), value.to_string());
+            }
+            None => {
+                if let Some(properties) = self.node_properties.get_mut(&node_id) {
+                    properties.remove(key);
+                    if properties.is_empty() {
+                        self.node_properties.remove(&node_id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Estimates the number of heap bytes held by node properties.
+    pub fn node_properties_memory_usage(&self) -> usize {
+        let entry_size = 2 * std::mem::size_of::<String>() + 1;
+        self.node_properties
+            .values()
+            .map(|properties| {
+                properties.capacity() * entry_size
+                    + properties
+                        .iter()
+                        .map(|(key, value)| key.capacity() + value.capacity())
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// Returns the properties of the node with the given ID.
+    pub fn get_node_properties(&self, node_id: NodeId) -> Option<&HashMap<String, String>> {
+        self.node_properties.get(&node_id)
+    }
+
+    /// Returns the type of the named node.
+    pub fn get_node_type(&self, node_name: &str) -> Result<NodeType, GraphManipulationError> {
+        self.node_names
+            .get(node_name)
+            .and_then(|&node_id| self.graph.node_type(node_id))
+            .ok_or_else(|| {
+                GraphManipulationError::NodeNotFound(format!("Node not found: {}", node_name))
+            })
+    }
+
+    /// Overrides the type of the named node, creating the node if necessary.
+    pub fn set_node_type(
+        &mut self,
+        node_name: &str,
+        node_type: NodeType,
+    ) -> Result<(), GraphManipulationError> {
+        let node_id = self.get_node_id(node_name)?;
+        self.graph.set_node_type(node_id, node_type);
+        self.bump_generation();
+        Ok(())
+    }
+
+    /// Returns the ID of the named node without creating it.
+    fn existing_node_id(&self, node_name: &str) -> Result<NodeId, GraphManipulationError> {
+        self.node_names.get(node_name).copied().ok_or_else(|| {
+            GraphManipulationError::NodeNotFound(format!("Node not found: {}", node_name))
+        })
+    }
+
+    /// Returns the name of the node with the given ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::NodeNotFound()` if the node has no name, e.g.
+    /// because it was evicted.
+    fn existing_node_name(&self, node_id: NodeId) -> Result<String, GraphManipulationError> {
+        self.node_ids.get(&node_id).cloned().ok_or_else(|| {
+            GraphManipulationError::NodeNotFound(format!("Node not found: {:?}", node_id))
+        })
+    }
+
+    /// Returns the ID of the node with the given native key, if it exists.
+    pub fn keyed_node_id(&self, key: NodeKey) -> Option<NodeId> {
+        self.keyed_nodes.get(&key).copied()
+    }
+
+    /// Returns the native key of the node with the given ID, if its name is one.
+    pub fn node_key(&self, node_id: NodeId) -> Option<NodeKey> {
+        self.node_keys.get(&node_id).copied()
+    }
+
+    /// Returns the ID of a node given by name or by native key, if it exists.
+    pub fn find_node(&self, node: NodeRef) -> Option<NodeId> {
+        match node {
+            NodeRef::Name(name) => self.node_names.get(name).copied(),
+            NodeRef::Key(key) => self.keyed_node_id(key),
+        }
+    }
+
+    /// Checks that the nodes with the given native keys exist when `meritrank.strict_nodes`
+    /// is on, as `require_nodes` does for names.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::NodeNotFound()` for the first node that does not exist.
+    pub fn require_keyed_nodes(&self, keys: &[NodeKey]) -> Result<(), GraphManipulationError> {
+        if !guc::STRICT_NODES.get() {
+            return Ok(());
+        }
+        match keys.iter().find(|key| !self.keyed_nodes.contains_key(key)) {
+            Some(key) => Err(GraphManipulationError::NodeNotFound(format!(
+                "{} (meritrank.strict_nodes is on; register it with meritrank_add_node())",
+                key
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the IDs of the nodes with the given native keys, creating the missing ones
+    /// within `meritrank.max_nodes` as `add_edge` does. Only new nodes have their names
+    /// formatted.
+    pub fn get_keyed_node_ids(
+        &mut self,
+        keys: &[NodeKey],
+    ) -> Result<Vec<NodeId>, GraphManipulationError> {
+        let missing: Vec<String> = keys
+            .iter()
+            .filter(|key| !self.keyed_nodes.contains_key(key))
+            .map(NodeKey::name)
+            .collect();
+        if !missing.is_empty() {
+            // Reserve room for all nodes at once, so that creating one cannot evict another
+            let names: Vec<&str> = missing.iter().map(String::as_str).collect();
+            self.reserve_nodes(&names)?;
+        }
+
+        Ok(keys
+            .iter()
+            .map(|&key| match self.keyed_node_id(key) {
+                Some(node_id) => node_id,
+                None => self.create_keyed_node(&key.name(), Some(key)),
+            })
+            .collect())
+    }
+
+    /// Sets the weight of the edge between two existing nodes like `set_edge_at`, without
+    /// looking them up by name. The names are only copied to describe the change.
+    pub fn set_edge_between(
+        &mut self,
+        subject_id: NodeId,
+        object_id: NodeId,
+        amount: f64,
+        timestamp: Timestamp,
+    ) -> Result<Option<EdgeMutation>, GraphManipulationError> {
+        if subject_id == object_id || !amount.is_finite() {
+            Self::check_edge(
+                &self.existing_node_name(subject_id)?,
+                &self.existing_node_name(object_id)?,
+                amount,
+            )?;
+        }
+        let previous_weight = self.graph.edge_weight(subject_id, object_id);
+        let op = match (previous_weight.is_some(), amount == 0.0) {
+            // A zero weight removes the edge
+            (true, true) => ChangeOp::Delete,
+            (false, true) => return Ok(None),
+            (true, false) => ChangeOp::Update,
+            (false, false) => ChangeOp::Add,
+        };
+        let subject = self.existing_node_name(subject_id)?;
+        let object = self.existing_node_name(object_id)?;
+
+        if op == ChangeOp::Delete {
+            self.graph.remove_edge(subject_id.into(), object_id.into());
+        } else {
+            self.reserve_edge(&subject, &object)?;
+            self.graph
+                .add_edge(subject_id.into(), object_id.into(), amount)?;
+            self.graph
+                .set_edge_timestamp(subject_id, object_id, timestamp);
+        }
+        self.bump_generation();
+        cache::lock_cache()?.invalidate_edge(subject_id, object_id);
+
+        Ok(Some(EdgeMutation {
+            op,
+            subject,
+            object,
+            weight: Some(amount).filter(|_| op != ChangeOp::Delete),
+            previous_weight,
+        }))
+    }
+
+    /// Returns the edges leaving or entering a node.
+    fn edges_of(&self, node_id: NodeId) -> Vec<(NodeId, NodeId, Weight)> {
+        self.graph
+            .all_edges()
+            .into_iter()
+            .filter(|&(source, target, _)| source == node_id || target == node_id)
+            .collect()
+    }
+
+    /// Renames a node. The node keeps its ID, type, properties and edges.
+    ///
+    /// # Returns
+    ///
+    /// The edges of the node, deleted under the old name and added under the new one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::NodeNotFound()` if `old_name` does not exist, and
+    /// a `GraphManipulationError::NodeCreationFailure()` if `new_name` already exists.
+    pub fn rename_node(
+        &mut self,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<Vec<EdgeMutation>, GraphManipulationError> {
+        let node_id = self.existing_node_id(old_name)?;
+        if self.node_names.contains_key(new_name) {
+            return Err(GraphManipulationError::NodeCreationFailure(format!(
+                "{} already exists",
+                new_name
+            )));
+        }
+        self.check_node_names(&[new_name])?;
+
+        let name = |node: NodeId, renamed: &str| -> Result<String, GraphManipulationError> {
+            if node == node_id {
+                Ok(renamed.to_string())
+            } else {
+                self.existing_node_name(node)
+            }
+        };
+        let mut changes = Vec::new();
+        for (source, target, weight) in self.edges_of(node_id) {
+            changes.push(EdgeMutation {
+                op: ChangeOp::Delete,
+                subject: name(source, old_name)?,
+                object: name(target, old_name)?,
+                weight: None,
+                previous_weight: Some(weight),
+            });
+            changes.push(EdgeMutation {
+                op: ChangeOp::Add,
+                subject: name(source, new_name)?,
+                object: name(target, new_name)?,
+                weight: Some(weight),
+                previous_weight: None,
+            });
+        }
+
+        self.node_names.remove(old_name);
+        self.node_names.insert(new_name.to_string(), node_id);
+        self.node_ids.insert(node_id, new_name.to_string());
+        if let Some(key) = self.node_keys.remove(&node_id) {
+            self.keyed_nodes.remove(&key);
+        }
+        if let Some(key) = NodeKey::from_name(new_name) {
+            self.keyed_nodes.insert(key, node_id);
+            self.node_keys.insert(node_id, key);
+        }
+        self.bump_generation();
+        Ok(changes)
+    }
+
+    /// Merges the node `from` into the node `into`, e.g. to consolidate duplicate accounts.
+    ///
+    /// Every edge of `from` is re-pointed to `into`; where `into` already has an edge to or
+    /// from the same node, the weights are summed, and a sum of zero removes the edge.
+    /// Edges between the two nodes are dropped, since nodes cannot vote for themselves.
+    /// Properties of `from` that `into` does not have are kept. `from` is then removed.
+    ///
+    /// # Returns
+    ///
+    /// The resulting edge changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::NodeNotFound()` if either node does not exist.
+    pub fn merge_nodes(
+        &mut self,
+        from: &str,
+        into: &str,
+    ) -> Result<Vec<EdgeMutation>, GraphManipulationError> {
+        let from_id = self.existing_node_id(from)?;
+        let into_id = self.existing_node_id(into)?;
+        if from_id == into_id {
+            return Err(MeritRankError::SelfReferenceNotAllowed.into());
+        }
+
+        let mut changes = Vec::new();
+        for (source, target, weight) in self.edges_of(from_id) {
+            changes.push(EdgeMutation {
+                op: ChangeOp::Delete,
+                subject: self.existing_node_name(source)?,
+                object: self.existing_node_name(target)?,
+                weight: None,
+                previous_weight: Some(weight),
+            });
+
+            let repoint = |node: NodeId| if node == from_id { into_id } else { node };
+            let (source, target) = (repoint(source), repoint(target));
+            if source == target {
+                continue;
+            }
+
+            // The merge removes more than it adds, so the edges are set directly rather
+            // than through the node and edge budget, whose evictions could remove either
+            // node halfway through
+            let previous_weight = self.graph.edge_weight(source, target);
+            let combined = previous_weight.unwrap_or(0.0) + weight;
+            let op = match (previous_weight, combined == 0.0) {
+                (None, true) => continue,
+                (Some(_), true) => ChangeOp::Delete,
+                (Some(_), false) => ChangeOp::Update,
+                (None, false) => ChangeOp::Add,
+            };
+            if op == ChangeOp::Delete {
+                self.graph.remove_edge(source.into(), target.into());
+            } else {
+                self.graph.add_edge(source.into(), target.into(), combined)?;
+                self.graph
+                    .set_edge_timestamp(source, target, current_timestamp());
+            }
+            changes.push(EdgeMutation {
+                op,
+                subject: self.existing_node_name(source)?,
+                object: self.existing_node_name(target)?,
+                weight: Some(combined).filter(|_| op != ChangeOp::Delete),
+                previous_weight,
+            });
+        }
+
+        self.graph.remove_node(from_id);
+        self.node_names.remove(from);
+        self.node_ids.remove(&from_id);
+        if let Some(key) = self.node_keys.remove(&from_id) {
+            self.keyed_nodes.remove(&key);
+        }
+        if let Some(properties) = self.node_properties.remove(&from_id) {
+            let kept = self.node_properties.entry(into_id).or_default();
+            for (key, value) in properties {
+                kept.entry(key).or_insert(value);
+            }
+        }
+        self.bump_generation();
+
+        // Removed edges may affect any cached rank vector
+        cache::lock_cache()?.clear();
+        Ok(changes)
+    }
+
+    /// Renumbers the nodes contiguously from 1 in the order of their current IDs, rebuilds
+    /// the graph indices and the name maps, and drops names of nodes that no longer exist.
+    ///
+    /// Node IDs held elsewhere become stale, so the rank cache and snapshot are dropped;
+    /// the caller has to drop the stored walks once `GRAPH` is released.
+    ///
+    /// # Returns
+    ///
+    /// The number of nodes, the number of nodes whose ID changed, the number of dropped
+    /// names, and the number of bytes reclaimed.
+    pub fn compact(&mut self) -> Result<(usize, usize, usize, usize), GraphManipulationError> {
+        let before = self.graph.memory_usage()
+            + self.node_names_memory_usage()
+            + self.node_properties_memory_usage();
+
+        let mut node_ids = self.graph.node_ids();
+        node_ids.sort();
+        let ids: HashMap<NodeId, NodeId> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(position, &node_id)| (node_id, NodeId::UInt(position + 1)))
+            .collect();
+        let renumbered = ids.iter().filter(|(old, new)| old != new).count();
+        let ghosts = self
+            .node_names
+            .values()
+            .filter(|node_id| !ids.contains_key(node_id))
+            .count();
+
+        self.graph = self.graph.renumbered(&ids);
+        self.node_names = self
+            .node_names
+            .drain()
+            .filter_map(|(name, node_id)| ids.get(&node_id).map(|&node_id| (name, node_id)))
+            .collect();
+        self.node_ids = self
+            .node_names
+            .iter()
+            .map(|(name, &node_id)| (node_id, name.clone()))
+            .collect();
+        self.node_keys = self
+            .node_keys
+            .drain()
+            .filter_map(|(node_id, key)| ids.get(&node_id).map(|&node_id| (node_id, key)))
+            .collect();
+        self.keyed_nodes = self
+            .node_keys
+            .iter()
+            .map(|(&node_id, &key)| (key, node_id))
+            .collect();
+        self.node_properties = self
+            .node_properties
+            .drain()
+            .filter_map(|(node_id, mut properties)| {
+                properties.shrink_to_fit();
+                ids.get(&node_id).map(|&node_id| (node_id, properties))
+            })
+            .collect();
+        self.next_node_id = ids.len() + 1;
+        self.bump_generation();
+
+        cache::lock_cache()?.clear();
+        SNAPSHOT.with(|cached| cached.borrow_mut().take());
+
+        let after = self.graph.memory_usage()
+            + self.node_names_memory_usage()
+            + self.node_properties_memory_usage();
+        Ok((ids.len(), renumbered, ghosts, before.saturating_sub(after)))
+    }
+
+    /// Returns the weight of the edge between two named nodes, if there is one.
+    pub fn edge_weight(&self, subject: &str, object: &str) -> Option<Weight> {
+        match (self.node_names.get(subject), self.node_names.get(object)) {
+            (Some(&subject_id), Some(&object_id)) => self.graph.edge_weight(subject_id, object_id),
+            _ => None,
+        }
+    }
+
+    /// Returns when the edge between two named nodes was last updated, if there is one.
+    pub fn edge_timestamp(&self, subject: &str, object: &str) -> Option<Timestamp> {
+        match (self.node_names.get(subject), self.node_names.get(object)) {
+            (Some(&subject_id), Some(&object_id)) => {
+                self.graph.edge_timestamp(subject_id, object_id)
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks if an edge exists between two named nodes.
+    pub fn contains_edge(&self, subject: &str, object: &str) -> bool {
+        match (self.node_names.get(subject), self.node_names.get(object)) {
+            (Some(&subject_id), Some(&object_id)) => self
+                .graph
+                .contains_edge(subject_id.into(), object_id.into()),
+            _ => false,
+        }
+    }
+
+    /// Removes the edge between two named nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `subject` - The name of the source node.
+    /// * `object` - The name of the destination node.
+    pub fn remove_edge(
+        &mut self,
+        subject: &str,
+        object: &str,
+    ) -> Result<(), GraphManipulationError> {
+        let subject_id = self.get_node_id(subject)?;
+        let object_id = self.get_node_id(object)?;
+
+        self.graph.remove_edge(subject_id.into(), object_id.into());
+        self.bump_generation();
+        cache::lock_cache()?.invalidate_edge(subject_id, object_id);
+        Ok(())
+    }
+
+    /// Returns the ID of the node with the given name.
+    pub fn node_name_to_id(node_name: &str) -> Result<NodeId, GraphManipulationError> {
+        let graph = lock_graph()?;
+        if let Some(&node_id) = graph.node_names.get(node_name) {
+            Ok(node_id)
+        } else {
+            Err(GraphManipulationError::NodeNotFound(format!(
+                "Node not found: {}",
+                node_name
+            )))
+        }
+    }
+
+    /// Returns the ID of a node given by name or by native key.
+    pub fn node_ref_to_id<'a>(
+        node: impl Into<NodeRef<'a>>,
+    ) -> Result<NodeId, GraphManipulationError> {
+        let node = node.into();
+        lock_graph()?.find_node(node).ok_or_else(|| {
+            GraphManipulationError::NodeNotFound(format!("Node not found: {}", node))
+        })
+    }
+
+    /// Returns the name of the node with the given ID.
+    pub fn node_id_to_name(node_id: NodeId) -> Result<String, GraphManipulationError> {
+        lock_graph()?
+            .get_node_name(node_id)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                GraphManipulationError::NodeNotFound(format!("Node not found: {}", node_id))
+            })
+    }
+
+    /// Looks up a node that ranks are to be calculated for.
+    ///
+    /// Returns its ID, whether it has any edges to walk, and the current generation.
+    /// Walks follow the outgoing edges of the ego, or its incoming edges when `reverse`.
+    pub fn ego_precheck<'a>(
+        ego: impl Into<NodeRef<'a>>,
+        reverse: bool,
+    ) -> Result<(NodeId, bool, u64), GraphManipulationError> {
+        let ego = ego.into();
+        let graph = lock_graph()?;
+        let ego_id = graph.find_node(ego).ok_or_else(|| {
+            GraphManipulationError::NodeNotFound(format!("Node not found: {}", ego))
+        })?;
+        let has_out_edges = if reverse {
+            !graph.graph.incoming_edges(ego_id).is_empty()
+        } else {
+            !graph.graph.neighbors(ego_id).is_empty()
+        };
+        Ok((ego_id, has_out_edges, graph.generation))
+    }
+
+    /// Empties the graph. The names of evicted nodes are kept when the graph is only
+    /// discarded to be loaded again from the `graph` table, unless `forget_evicted`.
+    pub fn clear_graph(forget_evicted: bool) -> Result<(), GraphManipulationError> {
+        match GRAPH.lock() {
+            Ok(mut graph) => {
+                if forget_evicted {
+                    graph.evicted_names.clear();
+                }
+                graph.graph.clear();
+                graph.node_names.clear();
+                graph.node_ids.clear();
+                graph.keyed_nodes.clear();
+                graph.node_keys.clear();
+                graph.node_properties.clear();
+                graph.next_node_id = 1;
+                graph.bump_generation();
+                cache::lock_cache()?.clear();
+                Ok(())
+            }
+            Err(e) => Err(GraphManipulationError::MutexLockFailure(format!(
+                "Mutex lock error: {}",
+                e
+            ))),
+        }
+    }
+}
+
+#[pg_extern]
+/// Sets the weight of the edge from `subject` to `object`. A weight of zero removes the
+/// edge, so retracting a vote does not need a separate `meritrank_delete` call.
+///
+/// # Returns
+///
+/// What changed, so applications can log it: the weight of the edge before the call and
+/// after it, `NULL` where there was or is no edge, and whether the edge was created.
+pub fn meritrank_add(
+    subject: &str,
+    object: &str,
+    amount: f64,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(previous_weight, Option<f64>),
+            name!(weight, Option<f64>),
+            name!(created, bool),
+        ),
+    >,
+    ErrorReport,
+> {
+    let (change, generation) = {
+        let mut graph = lock_graph()?;
+        graph.require_nodes(&[subject, object])?;
+        let change = graph.set_edge(subject, object, amount)?;
+        (change, graph.get_generation())
+    };
+    publish_changes(change.as_slice(), generation)?;
+
+    let previous_weight = change.and_then(|change| change.previous_weight);
+    let weight = Some(amount).filter(|&amount| amount != 0.0);
+    let created = previous_weight.is_none() && weight.is_some();
+    Ok(TableIterator::once((previous_weight, weight, created)))
+}
+
+#[pg_extern]
+/// Sets the weights of many edges at once, given as parallel arrays, for drivers that
+/// cannot easily build arrays of composite values:
+///
+/// ```sql
+/// SELECT meritrank_add_batch(ARRAY['U1', 'U2'], ARRAY['U2', 'U3'], ARRAY[1.0, 0.5]);
+/// ```
+///
+/// Every edge is applied as by `meritrank_add`, in order, under a single lock of the
+/// graph. The edges are validated first, so a malformed edge leaves the graph unchanged.
+///
+/// # Returns
+///
+/// The number of edges that were added, updated or removed.
+pub fn meritrank_add_batch(
+    subjects: Vec<String>,
+    objects: Vec<String>,
+    amounts: Vec<f64>,
+) -> Result<i64, ErrorReport> {
+    if subjects.len() != objects.len() || subjects.len() != amounts.len() {
+        return Err(GraphManipulationError::InvalidBatch(format!(
+            "arrays of different lengths ({} subjects, {} objects, {} amounts)",
+            subjects.len(),
+            objects.len(),
+            amounts.len()
+        ))
+        .into());
+    }
+    let edges: Vec<(&str, &str, f64)> = subjects
+        .iter()
+        .zip(objects.iter())
+        .zip(amounts.iter())
+        .map(|((subject, object), &amount)| (subject.as_str(), object.as_str(), amount))
+        .collect();
+
+    Ok(add_edges(&edges)? as i64)
+}
+
+#[pg_extern]
+/// Sets the weights of many edges at once, given as a JSON array such as
+/// `[{"src": "U1", "dst": "U2", "w": 1.0}, ...]`, so that application services can push
+/// edges in one round trip without defining SQL types.
+///
+/// Every edge is applied as by `meritrank_add`, in order, under a single lock of the
+/// graph. The edges are validated first, so a malformed edge leaves the graph unchanged.
+///
+/// # Returns
+///
+/// The number of edges that were added, updated or removed.
+pub fn meritrank_add_jsonb(edges: JsonB) -> Result<i64, ErrorReport> {
+    let items = edges.0.as_array().ok_or_else(|| {
+        GraphManipulationError::InvalidBatch("expected a JSON array of edges".to_string())
+    })?;
+    let edges = items
+        .iter()
+        .enumerate()
+        .map(|(position, item)| {
+            let field = |key: &str| item.get(key).filter(|value| !value.is_null());
+            match (
+                field("src").and_then(|value| value.as_str()),
+                field("dst").and_then(|value| value.as_str()),
+                field("w").and_then(|value| value.as_f64()),
+            ) {
+                (Some(subject), Some(object), Some(amount)) => Ok((subject, object, amount)),
+                _ => Err(GraphManipulationError::InvalidBatch(format!(
+                    "edge {} needs string \"src\" and \"dst\" and a numeric \"w\", got {}",
+                    position + 1,
+                    item
+                ))),
+            }
+        })
+        .collect::<Result<Vec<(&str, &str, f64)>, GraphManipulationError>>()?;
+
+    Ok(add_edges(&edges)? as i64)
+}
+
+/// Applies a batch of edges as by `meritrank_add` under a single lock of the graph,
+/// after validating all of them. If an edge still fails, e.g. on `meritrank.max_edges`,
+/// none of them are applied.
+///
+/// # Returns
+///
+/// The number of edges that were added, updated or removed.
+pub fn add_edges(edges: &[(&str, &str, f64)]) -> Result<usize, GraphManipulationError> {
+    let (changes, generation) = {
+        let mut graph = lock_graph()?;
+        // Self-references and weights that are not finite are refused by `set_edges_at`
+        for &(subject, object, _) in edges {
+            graph.require_nodes(&[subject, object])?;
+            graph.check_node_names(&[subject, object])?;
+        }
+
+        let timestamp = current_timestamp();
+        let edges: Vec<(&str, &str, f64, Timestamp)> = edges
+            .iter()
+            .map(|&(subject, object, amount)| (subject, object, amount, timestamp))
+            .collect();
+        let changes = graph.set_edges_at(&edges)?;
+        (changes, graph.get_generation())
+    };
+
+    publish_changes(&changes, generation)?;
+    Ok(changes.len())
+}
+
+/// Sets the weight of the edge between two nodes given by native keys as `meritrank_add`
+/// does for names, and publishes the change. A weight of zero removes the edge without
+/// creating any nodes.
+///
+/// # Returns
+///
+/// The change, or `None` if nothing changed.
+pub fn set_keyed_edge(
+    subject: NodeKey,
+    object: NodeKey,
+    amount: f64,
+) -> Result<Option<EdgeMutation>, GraphManipulationError> {
+    let (change, generation) = {
+        let mut graph = lock_graph()?;
+        graph.require_keyed_nodes(&[subject, object])?;
+        let node_ids = if amount == 0.0 {
+            match (graph.keyed_node_id(subject), graph.keyed_node_id(object)) {
+                (Some(subject_id), Some(object_id)) => Some((subject_id, object_id)),
+                _ => None,
+            }
+        } else {
+            let node_ids = graph.get_keyed_node_ids(&[subject, object])?;
+            Some((node_ids[0], node_ids[1]))
+        };
+        let change = match node_ids {
+            Some((subject_id, object_id)) => {
+                graph.set_edge_between(subject_id, object_id, amount, current_timestamp())?
+            }
+            None => None,
+        };
+        (change, graph.get_generation())
+    };
+    publish_changes(change.as_slice(), generation)?;
+    Ok(change)
+}
+
+#[pg_extern]
+/// Registers a node without any edges, which is required before it can be connected
+/// when `meritrank.strict_nodes` is on.
+///
+/// Only edges are stored in the `graph` table, so a node stays registered in the
+/// backends that registered it, or that loaded an edge of it.
+///
+/// # Returns
+///
+/// `true` if the node was created, `false` if it already existed.
+pub fn meritrank_add_node(node: &str) -> Result<bool, ErrorReport> {
+    let mut graph = lock_graph()?;
+    if graph.borrow_node_names().contains_key(node) {
+        return Ok(false);
+    }
+    graph.get_node_id(node)?;
+    Ok(true)
+}
+
+#[pg_extern]
+/// Renames a node, e.g. when a user changes their username. The node keeps its edges,
+/// type and properties, so its ranks are unaffected.
+pub fn meritrank_rename_node(old_name: &str, new_name: &str) -> Result<(), ErrorReport> {
+    let (changes, generation) = {
+        let mut graph = lock_graph()?;
+        let changes = graph.rename_node(old_name, new_name)?;
+        (changes, graph.get_generation())
+    };
+
+    Ok(publish_changes(&changes, generation)?)
+}
+
+#[pg_extern]
+/// Merges the node `from` into the node `into`, e.g. when duplicate accounts are
+/// consolidated. Edges of `from` are re-pointed to `into`, summing the weights of edges
+/// the two nodes share, and `from` is removed. Stored walks are recalculated on the next
+/// rank calculation.
+///
+/// # Returns
+///
+/// The number of edges of `from` that were re-pointed or dropped.
+pub fn meritrank_merge_nodes(from: &str, into: &str) -> Result<i64, ErrorReport> {
+    let (changes, generation) = {
+        let mut graph = lock_graph()?;
+        let changes = graph.merge_nodes(from, into)?;
+        (changes, graph.get_generation())
+    };
+
+    publish_changes(&changes, generation)?;
+    Ok(changes
+        .iter()
+        .filter(|change| change.subject == from || change.object == from)
+        .count() as i64)
+}
+
+#[pg_extern]
+/// Renumbers the nodes after mass deletions, so that node IDs and graph indices are
+/// contiguous again and the name maps hold only existing nodes. Stored walks and cached
+/// ranks are dropped, since they refer to the old IDs.
+///
+/// # Returns
+///
+/// The number of nodes, the number of nodes whose ID changed, the number of names of
+/// missing nodes that were dropped, and an estimate of the bytes reclaimed.
+pub fn meritrank_compact() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(nodes, i64),
+            name!(renumbered_nodes, i64),
+            name!(dropped_names, i64),
+            name!(reclaimed_bytes, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let (nodes, renumbered, ghosts, reclaimed) = lock_graph()?.compact()?;
+    walks::lock_store()?.clear();
+
+    Ok(TableIterator::once((
+        nodes as i64,
+        renumbered as i64,
+        ghosts as i64,
+        reclaimed as i64,
+    )))
+}
+
+#[pg_extern]
+/// Builds an immutable compressed sparse row (CSR) copy of the graph that walks step
+/// through instead of the graph itself, which is considerably faster for large graphs
+/// that rarely change. Writes stay possible: they mark the copy as stale, and it is
+/// rebuilt when walks are next generated. The copy is not used while edge decay is on.
+///
+/// # Returns
+///
+/// The number of nodes with positive out-edges and the number of positive edges in the
+/// copy, and an estimate of its size in bytes.
+pub fn meritrank_freeze() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(nodes, i64),
+            name!(edges, i64),
+            name!(memory_bytes, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let (csr, generation) = {
+        let mut graph = lock_graph()?;
+        (graph.freeze(), graph.get_generation())
+    };
+    // Stored walks of the same generation may switch to the copy right away
+    if let Some(merit_rank) = walks::lock_store()?.current(generation) {
+        merit_rank.set_csr(Some(csr.clone()));
+    }
+
+    Ok(TableIterator::once((
+        csr.node_count() as i64,
+        csr.edge_count() as i64,
+        csr.memory_usage() as i64,
+    )))
+}
+
+#[pg_extern]
+/// Drops the CSR copy of the graph built by `meritrank_freeze`, so that walks step
+/// through the graph itself again. Returns whether the graph was frozen.
+pub fn meritrank_thaw() -> Result<bool, ErrorReport> {
+    let was_frozen = lock_graph()?.thaw();
+    if let Some(merit_rank) = walks::lock_store()?.get_mut() {
+        merit_rank.set_csr(None);
+    }
+    Ok(was_frozen)
+}
+
+/// Calculates the ranks of the peers of `ego` on a snapshot of the graph.
+///
+/// Results are served from and stored in the rank cache; freshly computed results are
+/// also stored in `meritrank_current_ranks`. An ego without outgoing edges
+/// has no ranks, so it gets an empty result without running any walks.
+/// When `node_type` is given, only peers of that type are kept before the limit is applied.
+///
+/// When `reverse` is true, the walks run on the reversed graph, so the ranks measure how
+/// much the peers trust `ego` rather than how much `ego` trusts them. These walks are not
+/// kept between calls, and their ranks are not stored in `meritrank_current_ranks`.
+///
+/// A calculation that runs out of its time `budget` returns the ranks of the walks
+/// generated so far; these are neither cached nor stored.
+///
+/// Returns the ranks sorted in descending order together with the generation of the snapshot.
+fn calculate_ranks<'a>(
+    ego: impl Into<NodeRef<'a>>,
+    iterations: Iterations,
+    limit: Limit,
+    penalty_factor: PenaltyFactor,
+    alpha: Alpha,
+    reverse: bool,
+    node_type: Option<NodeType>,
+    budget: TimeBudget,
+) -> Result<(Vec<(NodeId, f64)>, u64), GraphManipulationError> {
+    // Convert the ego into a NodeId
+    let ego = ego.into();
+    let (ego_id, has_out_edges, generation) = GraphSingleton::ego_precheck(ego, reverse)?;
+    if !has_out_edges {
+        return Ok((Vec::new(), generation));
+    }
+
+    let cache_key = RankCacheKey::new(ego_id, iterations, penalty_factor, alpha, reverse);
+
+    let deadline = budget.deadline();
+    let out_of_time = || deadline.map_or(false, |deadline| Instant::now() >= deadline);
+
+    let cached = cache::lock_cache()?.get(&cache_key);
+    let entry = match cached {
+        Some(entry) => {
+            // The ranks were computed from the stored walks, which count as used again
+            if !reverse {
+                walks::mark_used(ego_id)?;
+            }
+            entry
+        }
+        None if reverse => {
+            let snapshot = GraphSingleton::get_snapshot()?;
+            let mut merit_rank = MeritRank::new(snapshot.graph.reversed())?;
+            walks::configure(&mut merit_rank);
+            merit_rank.set_penalty_factor(penalty_factor.get());
+            merit_rank.set_alpha(alpha.get());
+            merit_rank.set_deadline(deadline);
+            stats::calculate(&mut merit_rank, ego_id, iterations)?;
+
+            let entry =
+                RankCacheEntry::new(merit_rank.get_ranks(ego_id, None)?, snapshot.generation);
+            if !out_of_time() {
+                cache::lock_cache()?.insert(cache_key, entry.clone(), cache::capacity());
+            }
+            entry
+        }
+        None => {
+            let entry = walks::with_current_rank(generation, |merit_rank, generation| {
+                merit_rank.set_penalty_factor(penalty_factor.get());
+                merit_rank.set_alpha(alpha.get());
+                merit_rank.set_deadline(deadline);
+
+                // Attempt to calculate merit ranks
+                stats::calculate(merit_rank, ego_id, iterations)?;
+
+                Ok(RankCacheEntry::new(
+                    merit_rank.get_ranks(ego_id, None)?,
+                    generation,
+                ))
+            })?;
+            // Results cut short by the time budget are partial
+            if !out_of_time() {
+                persist::store_ranks(&ego.name(), &entry.ranks)?;
+                cache::lock_cache()?.insert(cache_key, entry.clone(), cache::capacity());
+            }
+            entry
+        }
+    };
+
+    let ranks = match node_type {
+        Some(node_type) => {
+            let graph = lock_graph()?;
+            entry
+                .ranks
+                .into_iter()
+                .filter(|(node_id, _)| graph.graph.node_type(*node_id) == Some(node_type))
+                .collect()
+        }
+        None => entry.ranks,
+    };
+
+    let limit = limit.get().unwrap_or(ranks.len());
+    Ok((ranks.into_iter().take(limit).collect(), entry.generation))
+}
+
+/// Calculates the rank of `object` from the perspective of `subject`.
+///
+/// Returns the rank together with the generation of the graph snapshot it was computed on.
+pub(crate) fn calculate_rank<'a, 'b>(
+    subject: impl Into<NodeRef<'a>>,
+    object: impl Into<NodeRef<'b>>,
+    iterations: Iterations,
+    penalty_factor: PenaltyFactor,
+    alpha: Alpha,
+    reverse: bool,
+    budget: TimeBudget,
+) -> Result<(f64, u64), GraphManipulationError> {
+    let (subject, object) = (subject.into(), object.into());
+    let (_, has_out_edges, _) = GraphSingleton::ego_precheck(subject, reverse)?;
+    if !has_out_edges {
+        return Err(GraphManipulationError::NoOutgoingEdges(if reverse {
+            format!("{} (no incoming edges to walk in reverse)", subject)
+        } else {
+            subject.to_string()
+        }));
+    }
+
+    // Get ranks and handle potential error
+    let (peer_scores, generation) = calculate_ranks(
+        subject,
+        iterations,
+        Limit::default(),
+        penalty_factor,
+        alpha,
+        reverse,
+        None,
+        budget,
+    )?;
+
+    // Find the rank for our object
+    let object_id = GraphSingleton::node_ref_to_id(object)?;
+
+    let rank = peer_scores
+        .into_iter()
+        .find(|(node_id, _)| node_id == &object_id)
+        .map(|(_, rank)| rank)
+        .ok_or_else(|| {
+            GraphManipulationError::NodeNotFound(format!("Rank not found for node: {}", object))
+        })?;
+
+    Ok((rank, generation))
+}
+
+/// Calculates the ranks of the peers of an ego given by native key, keeping the peers
+/// that have a native key of the same kind, e.g. integer IDs for an integer ego.
+///
+/// The peers are filtered before `limit` is applied, so that up to `limit` of them are
+/// returned. Returns the ranks sorted in descending order.
+pub(crate) fn calculate_keyed_ranks(
+    ego: NodeKey,
+    iterations: Iterations,
+    limit: Limit,
+    penalty_factor: PenaltyFactor,
+    alpha: Alpha,
+) -> Result<Vec<(NodeKey, f64)>, GraphManipulationError> {
+    let (ranks, _) = calculate_ranks(
+        ego,
+        iterations,
+        Limit::default(),
+        penalty_factor,
+        alpha,
+        false,
+        None,
+        TimeBudget::default(),
+    )?;
+
+    let graph = lock_graph()?;
+    let keyed = ranks.into_iter().filter_map(|(node_id, rank)| {
+        graph
+            .node_key(node_id)
+            .filter(|key| std::mem::discriminant(key) == std::mem::discriminant(&ego))
+            .map(|key| (key, rank))
+    });
+    Ok(match limit.get() {
+        Some(limit) => keyed.take(limit).collect(),
+        None => keyed.collect(),
+    })
+}
+
+#[pg_extern]
+/// Checks cheaply whether ranks can be calculated for `ego`: it must exist and have at
+/// least one outgoing edge.
+pub fn meritrank_can_rank(ego: &str) -> Result<bool, ErrorReport> {
+    match GraphSingleton::ego_precheck(ego, false) {
+        Ok((_, has_out_edges, _)) => Ok(has_out_edges),
+        Err(GraphManipulationError::NodeNotFound(_)) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[pg_extern]
+/// Returns the generation of the in-memory graph of this backend.
+///
+/// The generation increases with every mutation of the graph, including clearing it and
+/// reloading it after other backends changed the `graph` table, and never decreases while
+/// the backend runs. Results tagged with the same generation, e.g. by
+/// `meritrank_ranks(..., with_freshness => true)`, were computed on an identical graph,
+/// so caches outside the database can be invalidated when it changes rather than on a
+/// timer. Generations start at 0 in every backend and are not comparable between them.
+pub fn meritrank_generation() -> Result<i64, ErrorReport> {
+    Ok(lock_graph()?.get_generation() as i64)
+}
+
+#[pg_extern(parallel_restricted)]
+/// Calculates the rank of `object` from the perspective of `subject`. `max_ms` limits the
+/// time spent on walks as in `meritrank_ranks`.
+pub fn meritrank_calculate(
+    subject: &str,
+    object: &str,
+    iterations: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+    reverse: default!(bool, false),
+    max_ms: default!(Option<i32>, "NULL"),
+) -> Result<f64, ErrorReport> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+    let budget = TimeBudget::from_nullable(max_ms)?;
+
+    let (rank, _) = calculate_rank(
+        subject,
+        object,
+        iterations,
+        penalty_factor,
+        alpha,
+        reverse,
+        budget,
+    )?;
+    Ok(rank)
+}
+
+#[pg_extern]
+/// Calculates the rank of `target` from the perspective of `ego`, considering only the
+/// edges last updated within `[since, until)`, e.g. for reputation over the last 90 days:
+///
+/// ```sql
+/// SELECT meritrank_calculate_window('U1', 'U2', now() - interval '90 days');
+/// ```
+///
+/// A `NULL` bound leaves the window open on that side. The calculation runs on its own
+/// copy of the graph and neither uses nor fills the rank cache.
+pub fn meritrank_calculate_window(
+    ego: &str,
+    target: &str,
+    since: default!(Option<TimestampWithTimeZone>, "NULL"),
+    until: default!(Option<TimestampWithTimeZone>, "NULL"),
+    iterations: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<f64, ErrorReport> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+    let since = since.map(timestamp_from_pg);
+    let until = until.map(timestamp_from_pg);
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+    let target_id = snapshot.node_id(target)?;
+
+    let mut merit_rank = MeritRank::new(snapshot.graph.window(since, until))?;
+    if merit_rank.get_graph().neighbors(ego_id).is_empty() {
+        return Err(GraphManipulationError::NoOutgoingEdges(format!(
+            "{} (no edges within the requested window)",
+            ego
+        ))
+        .into());
+    }
+
+    merit_rank.set_penalty_factor(penalty_factor.get());
+    merit_rank.set_alpha(alpha.get());
+    stats::calculate(&mut merit_rank, ego_id, iterations)?;
+    Ok(merit_rank.get_node_score(ego_id, target_id)?)
+}
+
+#[pg_extern]
+/// Same as `meritrank_calculate`, but also returns when the rank was computed and the
+/// generation of the graph it was computed on.
+pub fn meritrank_calculate_with_freshness(
+    subject: &str,
+    object: &str,
+    iterations: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+    reverse: default!(bool, false),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(rank, f64),
+            name!(computed_at, Option<TimestampWithTimeZone>),
+            name!(graph_generation, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let (rank, generation) = calculate_rank(
+        subject,
+        object,
+        iterations,
+        penalty_factor,
+        alpha,
+        reverse,
+        TimeBudget::default(),
+    )?;
+    let freshness = Freshness::now(generation);
+
+    Ok(TableIterator::once((
+        rank,
+        freshness.computed_at,
+        freshness.graph_generation,
+    )))
+}
+
+#[pg_extern(parallel_restricted)]
+/// Returns the ranks of the peers of `ego`, sorted in descending order.
+///
+/// When `with_freshness` is true, every row also carries when the ranks were computed and
+/// the generation of the graph they were computed on; otherwise these columns are `NULL`.
+///
+/// When `node_type` is given (`user`, `beacon`, `comment` or `other`), only peers of that
+/// type are returned and `limit` applies to the filtered result.
+///
+/// `alpha` is the probability of a walk continuing with another step; lower values favour
+/// close peers. `NULL` uses the default of 0.85.
+///
+/// When `reverse` is true, the walks follow the edges backwards, so the ranks measure how
+/// much every peer trusts `ego` ("who trusts this node") instead of how much `ego` trusts
+/// them.
+///
+/// When `max_ms` is given, the calculation stops once that many milliseconds have passed
+/// and returns best-effort ranks from the walks generated so far. Cancelling the query or
+/// `statement_timeout` stops it as well.
+pub fn meritrank_ranks(
+    ego: &str,
+    iterations: default!(Option<i32>, "NULL"),
+    limit: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    with_freshness: default!(bool, false),
+    node_type: default!(Option<&str>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+    reverse: default!(bool, false),
+    max_ms: default!(Option<i32>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(node, String),
+            name!(rank, f64),
+            name!(computed_at, Option<TimestampWithTimeZone>),
+            name!(graph_generation, Option<i64>),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let limit = Limit::from_nullable(limit)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let node_type = node_type.map(node_types::parse_node_type).transpose()?;
+    let alpha = Alpha::from_nullable(alpha)?;
+    let budget = TimeBudget::from_nullable(max_ms)?;
+
+    let (ranks, generation) = calculate_ranks(
+        ego,
+        iterations,
+        limit,
+        penalty_factor,
+        alpha,
+        reverse,
+        node_type,
+        budget,
+    )?;
+
+    let (computed_at, graph_generation) = if with_freshness {
+        let freshness = Freshness::now(generation);
+        (freshness.computed_at, Some(freshness.graph_generation))
+    } else {
+        (None, None)
+    };
+
+    // Resolve all names under a single lock
+    let graph = lock_graph()?;
+    let ranks = ranks
+        .into_iter()
+        .map(|(node_id, rank)| {
+            let name = graph.get_node_name(node_id).ok_or_else(|| {
+                GraphManipulationError::NodeNotFound(format!("Node not found: {}", node_id))
+            })?;
+            Ok((name.to_string(), rank, computed_at, graph_generation))
+        })
+        .collect::<Result<Vec<_>, GraphManipulationError>>()?;
+
+    Ok(TableIterator::new(ranks.into_iter()))
+}
+
+#[pg_extern]
+/// Returns all ranks of the peers of `ego` as a single JSON object mapping node names to
+/// ranks, e.g. `{"U2": 0.41, "U3": 0.12}`, which is convenient for REST backends such as
+/// PostgREST.
+pub fn meritrank_ranks_json(
+    ego: &str,
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<JsonB, ErrorReport> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+
+    let (ranks, _) = calculate_ranks(
+        ego,
+        iterations,
+        Limit::default(),
+        PenaltyFactor::default(),
+        Alpha::default(),
+        false,
+        None,
+        TimeBudget::default(),
+    )?;
+
+    // Resolve all names under a single lock
+    let graph = lock_graph()?;
+    let scores = ranks
+        .into_iter()
+        .map(|(node_id, rank)| {
+            let name = graph.get_node_name(node_id).ok_or_else(|| {
+                GraphManipulationError::NodeNotFound(format!("Node not found: {}", node_id))
+            })?;
+            Ok((name.to_string(), serde_json::Value::from(rank)))
+        })
+        .collect::<Result<serde_json::Map<String, serde_json::Value>, GraphManipulationError>>()?;
+
+    Ok(JsonB(serde_json::Value::Object(scores)))
+}
+
+/// Returns the cosine similarity of two rank vectors, or `None` if either is all zeros.
+fn cosine_similarity(a: &HashMap<NodeId, f64>, b: &HashMap<NodeId, f64>) -> Option<f64> {
+    let dot: f64 = a
+        .iter()
+        .filter_map(|(node_id, rank)| b.get(node_id).map(|other| rank * other))
+        .sum();
+    let norm = |ranks: &HashMap<NodeId, f64>| ranks.values().map(|rank| rank * rank).sum::<f64>();
+    let (norm_a, norm_b) = (norm(a), norm(b));
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return None;
+    }
+    Some(dot / (norm_a * norm_b).sqrt())
+}
+
+/// Returns one minus the Jensen-Shannon divergence, in bits, of two rank vectors taken as
+/// distributions over their positive ranks, or `None` if either has no positive rank.
+fn jensen_shannon_similarity(a: &HashMap<NodeId, f64>, b: &HashMap<NodeId, f64>) -> Option<f64> {
+    let total = |ranks: &HashMap<NodeId, f64>| ranks.values().filter(|&&r| r > 0.0).sum::<f64>();
+    let (total_a, total_b) = (total(a), total(b));
+    if total_a == 0.0 || total_b == 0.0 {
+        return None;
+    }
+    let share = |ranks: &HashMap<NodeId, f64>, total: f64, node_id: &NodeId| {
+        ranks.get(node_id).map_or(0.0, |&rank| rank.max(0.0) / total)
+    };
+
+    let mut divergence = 0.0;
+    for node_id in a.keys().chain(b.keys().filter(|node_id| !a.contains_key(node_id))) {
+        let (p, q) = (share(a, total_a, node_id), share(b, total_b, node_id));
+        let m = (p + q) / 2.0;
+        for x in [p, q] {
+            if x > 0.0 {
+                divergence += x * (x / m).log2() / 2.0;
+            }
+        }
+    }
+    Some((1.0 - divergence).clamp(0.0, 1.0))
+}
+
+#[pg_extern]
+/// Compares the rank vectors of two egos, e.g. to suggest people with similar taste.
+///
+/// Ranks are calculated as by `meritrank_ranks`, so results in the rank cache are
+/// reused. The ranks the egos give each other and themselves are left out, so that
+/// each ego's own weight does not dominate the comparison.
+///
+/// # Returns
+///
+/// * `cosine` - The cosine similarity of the rank vectors, from -1 to 1.
+/// * `jensen_shannon` - One minus the Jensen-Shannon divergence of the positive ranks,
+///   each normalized to sum to 1, from 0 (disjoint) to 1 (identical).
+/// * `shared_peers` - The number of peers ranked by both egos.
+///
+/// The similarities are `NULL` when an ego has no ranks to compare, e.g. no outgoing edges.
+pub fn meritrank_similarity(
+    ego_a: &str,
+    ego_b: &str,
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(cosine, Option<f64>),
+            name!(jensen_shannon, Option<f64>),
+            name!(shared_peers, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let egos = [
+        GraphSingleton::node_name_to_id(ego_a)?,
+        GraphSingleton::node_name_to_id(ego_b)?,
+    ];
+
+    let rank_vector = |ego: &str| -> Result<HashMap<NodeId, f64>, GraphManipulationError> {
+        let (ranks, _) = calculate_ranks(
+            ego,
+            iterations,
+            Limit::default(),
+            PenaltyFactor::default(),
+            Alpha::default(),
+            false,
+            None,
+            TimeBudget::default(),
+        )?;
+        Ok(ranks
+            .into_iter()
+            .filter(|(node_id, _)| !egos.contains(node_id))
+            .collect())
+    };
+    let ranks_a = rank_vector(ego_a)?;
+    let ranks_b = rank_vector(ego_b)?;
+
+    let shared_peers = ranks_a
+        .keys()
+        .filter(|node_id| ranks_b.contains_key(node_id))
+        .count() as i64;
+    Ok(TableIterator::once((
+        cosine_similarity(&ranks_a, &ranks_b),
+        jensen_shannon_similarity(&ranks_a, &ranks_b),
+        shared_peers,
+    )))
+}
+
+#[pg_extern]
+/// Calculates the ranks of frequently queried egos ahead of time, e.g. after a nightly
+/// reload, so that the first requests for them are served from the rank cache.
+///
+/// The ranks are calculated as by `meritrank_ranks` with the default penalty factor and
+/// alpha, so only calls with the same `iterations` and those defaults hit the cache.
+/// Their walks are kept like those of any calculation, within `meritrank.max_walk_memory`.
+/// Names not in the graph are skipped with a warning.
+///
+/// # Returns
+///
+/// A row for every ego warmed up, with the number of its peers and the milliseconds
+/// spent on it, which is close to 0 if its ranks were already cached.
+pub fn meritrank_warmup(
+    egos: Vec<String>,
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<
+    TableIterator<'static, (name!(ego, String), name!(peers, i64), name!(elapsed_ms, f64))>,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+
+    let mut rows = Vec::with_capacity(egos.len());
+    for ego in egos {
+        let start = Instant::now();
+        let ranks = match calculate_ranks(
+            &ego,
+            iterations,
+            Limit::default(),
+            PenaltyFactor::default(),
+            Alpha::default(),
+            false,
+            None,
+            TimeBudget::default(),
+        ) {
+            Ok((ranks, _)) => ranks,
+            Err(GraphManipulationError::NodeNotFound(_)) => {
+                log_warning!("meritrank: skipping warm-up of unknown ego {}", ego);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+        rows.push((ego, ranks.len() as i64, elapsed_ms));
+    }
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+#[pg_extern]
+/// Returns the nodes whose rank from the perspective of `ego` changed by more than
+/// `threshold` since an earlier computation, e.g. to notify users about meaningful
+/// changes of their reputation.
+///
+/// The current ranks are compared against the latest vector of `ego` kept in
+/// `meritrank_rank_history` (see `meritrank.rank_history`) that was computed at version
+/// `since_version` of the `graph` table or earlier. The version is shared by all backends,
+/// so it may come from another connection. Nodes missing on one side count as a rank of 0
+/// and have a `NULL` rank there; without any earlier vector, every ranked node is returned.
+///
+/// # Arguments
+///
+/// * `ego` - The name of the ego.
+/// * `since_version` - The `graph_version` of the earlier computation.
+/// * `threshold` - The smallest absolute change that is returned.
+/// * `iterations` - The number of walks. Defaults to `DEFAULT_ITERATIONS`.
+pub fn meritrank_rank_deltas(
+    ego: &str,
+    since_version: i64,
+    threshold: default!(f64, 0.01),
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(node, String),
+            name!(previous_rank, Option<f64>),
+            name!(rank, Option<f64>),
+            name!(delta, f64),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    if !(threshold >= 0.0 && threshold.is_finite()) {
+        return Err(GraphManipulationError::InvalidThreshold(format!(
+            "{} (must be a finite number of at least 0)",
+            threshold
+        ))
+        .into());
+    }
+    let iterations = Iterations::from_nullable(iterations)?;
+
+    let (ranks, _) = calculate_ranks(
+        ego,
+        iterations,
+        Limit::default(),
+        PenaltyFactor::default(),
+        Alpha::default(),
+        false,
+        None,
+        TimeBudget::default(),
+    )?;
+    let mut previous = persist::ranks_since(ego, since_version)?;
+
+    let mut deltas: Vec<(String, Option<f64>, Option<f64>, f64)> = {
+        let graph = lock_graph()?;
+        let mut deltas = Vec::new();
+        for (node_id, rank) in ranks {
+            let name = graph.get_node_name(node_id).ok_or_else(|| {
+                GraphManipulationError::NodeNotFound(format!("Node not found: {}", node_id))
+            })?;
+            let previous_rank = previous.remove(name);
+            deltas.push((
+                name.to_string(),
+                previous_rank,
+                Some(rank),
+                rank - previous_rank.unwrap_or(0.0),
+            ));
+        }
+        deltas
+    };
+    // Nodes that are no longer ranked
+    deltas.extend(
+        previous
+            .into_iter()
+            .map(|(name, previous_rank)| (name, Some(previous_rank), None, -previous_rank)),
+    );
+
+    deltas.retain(|&(_, _, _, delta)| delta.abs() > threshold);
+    deltas.sort_by(|a, b| b.3.abs().total_cmp(&a.3.abs()).then_with(|| a.0.cmp(&b.0)));
+    Ok(TableIterator::new(deltas.into_iter()))
+}
+
+#[pg_extern]
+/// Removes the edge from `subject` to `object`, as `meritrank_add` with a weight of zero
+/// does. Deleting an edge that does not exist changes nothing and records nothing.
+pub fn meritrank_delete(subject: &str, object: &str) -> Result<(), ErrorReport> {
+    let (change, generation) = {
+        let mut graph = lock_graph()?;
+        graph.require_nodes(&[subject, object])?;
+        let change = graph.set_edge(subject, object, 0.0)?;
+        (change, graph.get_generation())
+    };
+
+    Ok(publish_changes(change.as_slice(), generation)?)
+}
+
+#[pg_extern]
+/// Clears the in-memory graph and walks of the current backend.
+///
+/// By default only memory is cleared: the `graph` table is left untouched and nothing is
+/// recorded, so other backends and the next reload still see every edge. With
+/// `truncate_table`, the clear is written to the `graph` table as well, and recorded in the
+/// outbox and the audit log and announced like any other change, which deletes every edge.
+pub fn meritrank_clear(truncate_table: default!(bool, false)) -> Result<(), ErrorReport> {
+    // A cleared graph must not be hydrated from the table afterwards
+    HYDRATED.store(true, Ordering::SeqCst);
+    GraphSingleton::clear_graph(true)?;
+    walks::lock_store()?.clear();
+    if !truncate_table {
+        return Ok(());
+    }
+    // The emptied table matches the emptied graph, whatever version it was loaded at
+    if guc::PERSIST_CHANGES.get() {
+        persist::KNOWN_VERSION.store(persist::table_version()?, Ordering::SeqCst);
+    }
+
+    let generation = GRAPH
+        .lock()
+        .map_err(|e| GraphManipulationError::MutexLockFailure(format!("Mutex lock error: {}", e)))?
+        .get_generation();
+    persist::record_change(ChangeOp::Clear, None, None, None)?;
+    outbox::record_change(ChangeOp::Clear, None, None, None)?;
+    audit::record_change(ChangeOp::Clear, None, None, None, None)?;
+    Ok(notify::notify_change(
+        ChangeOp::Clear,
+        None,
+        None,
+        None,
+        generation,
+    )?)
+}
+
+#[cfg(feature = "profiling")]
+#[pg_extern]
+/// Returns the number of calls and the accumulated time (in milliseconds) spent
+/// in each instrumented hot path section since the last reset.
+pub fn meritrank_profile() -> TableIterator<
+    'static,
+    (
+        name!(section, String),
+        name!(calls, i64),
+        name!(total_ms, f64),
+    ),
+> {
+    let rows: Vec<(String, i64, f64)> = profile::snapshot()
+        .into_iter()
+        .map(|(section, calls, nanos)| {
+            (
+                section.name().to_string(),
+                calls as i64,
+                nanos as f64 / 1_000_000.0,
+            )
+        })
+        .collect();
+    TableIterator::new(rows.into_iter())
 }
 
+#[cfg(feature = "profiling")]
 #[pg_extern]
-pub fn meritrank_clear() -> Result<(), GraphManipulationError> {
-    GraphSingleton::clear_graph()
+/// Resets the profiling counters.
+pub fn meritrank_profile_reset() {
+    profile::reset();
 }
 
 // TODO: Finish implementing this
@@ -248,7 +2757,7 @@ pub fn meritrank_clear() -> Result<(), GraphManipulationError> {
 // use crate::edge::GraphEdge;
 
 // #[pg_extern]
-// pub fn meritrank_update_graph(edges: AnyArray) -> Result<(), GraphManipulationError> {
+// pub fn meritrank_update_graph(edges: AnyArray) -> Result<(), ErrorReport> {
 //     let graph_edges_datum: Datum = edges.datum();
 //
 //     let array_datum: Array<Datum>;
@@ -0,0 +1,162 @@
+// Standard library imports
+use std::collections::HashMap;
+
+// External crate imports
+use serde::{Deserialize, Serialize};
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::lib_graph::{MeritRank, MyGraph, NodeId, Weight};
+use crate::rating::{Alpha, Iterations, PenaltyFactor};
+use crate::{stats, walks};
+
+/// A self-contained graph stored in a table column, e.g. a small graph per tenant.
+///
+/// Values are stored in a compact binary form in a varlena, so large graphs are
+/// compressed and moved out of line by TOAST like any other value. The text form is
+/// JSON, e.g. `{"edges":[["a","b",1.0]]}`. Ranks are calculated on a copy built for the
+/// call; the in-memory graph of the backend is neither read nor changed.
+#[derive(PostgresType, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct MeritGraph {
+    edges: Vec<(String, String, Weight)>,
+}
+
+impl MeritGraph {
+    /// Builds a graph from `(source, destination, weight)` edges. Of edges repeated in
+    /// the input, the last one wins, as in the in-memory graph.
+    pub fn new(
+        edges: impl IntoIterator<Item = (String, String, Weight)>,
+    ) -> Result<Self, GraphManipulationError> {
+        let mut positions: HashMap<(String, String), usize> = HashMap::new();
+        let mut unique: Vec<(String, String, Weight)> = Vec::new();
+        for (source, destination, weight) in edges {
+            if source == destination {
+                return Err(GraphManipulationError::EdgeCreationFailure(format!(
+                    "self-reference of node {}",
+                    source
+                )));
+            }
+            if !weight.is_finite() {
+                return Err(GraphManipulationError::WeightExtractionFailure(format!(
+                    "weight of edge {} -> {} is not a finite number",
+                    source, destination
+                )));
+            }
+            match positions.get(&(source.clone(), destination.clone())) {
+                Some(&position) => unique[position].2 = weight,
+                None => {
+                    positions.insert((source.clone(), destination.clone()), unique.len());
+                    unique.push((source, destination, weight));
+                }
+            }
+        }
+        Ok(MeritGraph { edges: unique })
+    }
+
+    /// Builds the graph for a calculation, together with the IDs of the node names.
+    fn to_graph(&self) -> Result<(MyGraph, HashMap<&str, NodeId>), GraphManipulationError> {
+        let mut graph = MyGraph::new();
+        let mut ids: HashMap<&str, NodeId> = HashMap::new();
+        for (source, destination, weight) in &self.edges {
+            for name in [source, destination] {
+                if !ids.contains_key(name.as_str()) {
+                    let id = NodeId::UInt(ids.len() + 1);
+                    graph.add_node(id.into());
+                    ids.insert(name, id);
+                }
+            }
+            graph.add_edge(ids[source.as_str()], ids[destination.as_str()], *weight)?;
+        }
+        Ok((graph, ids))
+    }
+}
+
+#[pg_extern]
+/// Builds a `MeritGraph` value from parallel arrays of edges, e.g. in an aggregate:
+///
+/// ```sql
+/// SELECT meritrank_graph(array_agg(source), array_agg(destination), array_agg(weight))
+/// FROM tenant_votes GROUP BY tenant;
+/// ```
+///
+/// Of edges repeated in the arrays, the last one wins.
+pub fn meritrank_graph(
+    sources: Vec<String>,
+    destinations: Vec<String>,
+    weights: Vec<f64>,
+) -> Result<MeritGraph, ErrorReport> {
+    if sources.len() != destinations.len() || sources.len() != weights.len() {
+        return Err(GraphManipulationError::DataExtractionFailure(format!(
+            "arrays of {} sources, {} destinations and {} weights differ in length",
+            sources.len(),
+            destinations.len(),
+            weights.len()
+        ))
+        .into());
+    }
+
+    Ok(MeritGraph::new(
+        sources
+            .into_iter()
+            .zip(destinations)
+            .zip(weights)
+            .map(|((source, destination), weight)| (source, destination, weight)),
+    )?)
+}
+
+#[pg_extern]
+/// Calculates the rank of `object` from the perspective of `subject` in a `MeritGraph`
+/// value, like `meritrank_calculate` does in the in-memory graph.
+///
+/// # Arguments
+///
+/// * `graph` - The graph, e.g. a column of a table.
+/// * `subject` - The name of the ego.
+/// * `object` - The name of the node to rank.
+/// * `iterations` - The number of walks. Defaults to `DEFAULT_ITERATIONS`.
+/// * `penalty_factor` - The multiplier of penalties from negative edges.
+/// * `alpha` - The probability of a walk continuing with another step.
+pub fn meritrank_calculate_on(
+    graph: MeritGraph,
+    subject: &str,
+    object: &str,
+    iterations: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<f64, ErrorReport> {
+    // Validate arguments before building the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let (graph, ids) = graph.to_graph()?;
+    let node_id = |name: &str| {
+        ids.get(name).copied().ok_or_else(|| {
+            GraphManipulationError::NodeNotFound(format!("Node not found: {}", name))
+        })
+    };
+    let subject_id = node_id(subject)?;
+    let object_id = node_id(object)?;
+
+    let mut merit_rank = MeritRank::new(graph)?;
+    if merit_rank.get_graph().neighbors(subject_id).is_empty() {
+        return Err(GraphManipulationError::NoOutgoingEdges(subject.to_string()).into());
+    }
+    walks::configure(&mut merit_rank);
+    merit_rank.set_penalty_factor(penalty_factor.get());
+    merit_rank.set_alpha(alpha.get());
+    stats::calculate(&mut merit_rank, subject_id, iterations)?;
+
+    Ok(merit_rank
+        .get_ranks(subject_id, None)?
+        .into_iter()
+        .find(|(node_id, _)| *node_id == object_id)
+        .map(|(_, rank)| rank)
+        .ok_or_else(|| {
+            GraphManipulationError::NodeNotFound(format!("Rank not found for node: {}", object))
+        })?)
+}
@@ -0,0 +1,528 @@
+// Library for PostgreSQL extensions
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::lib_graph::constants::DEFAULT_ALPHA;
+
+/// Default multiplier applied to penalties from negative edges (`meritrank.penalty_factor`).
+pub static PENALTY_FACTOR: GucSetting<f64> = GucSetting::new(1.0);
+
+/// Half-life of edge weights in days (`meritrank.decay_half_life`). 0 disables decay.
+pub static DECAY_HALF_LIFE: GucSetting<f64> = GucSetting::new(0.0);
+
+/// Default probability of a walk continuing with another step (`meritrank.alpha`).
+pub static ALPHA: GucSetting<f64> = GucSetting::new(DEFAULT_ALPHA);
+
+/// Maximum number of nodes of a walk, including the ego (`meritrank.max_walk_length`).
+/// 0 means unlimited.
+pub static MAX_WALK_LENGTH: GucSetting<i32> = GucSetting::new(0);
+
+/// Share of the score removed from nodes reached through few distinct predecessors
+/// (`meritrank.connectivity_decay`). 0 disables the discount.
+pub static CONNECTIVITY_DECAY: GucSetting<f64> = GucSetting::new(0.0);
+
+/// Number of distinct predecessors below which a score is discounted
+/// (`meritrank.connectivity_min_predecessors`).
+pub static CONNECTIVITY_MIN_PREDECESSORS: GucSetting<i32> = GucSetting::new(2);
+
+/// Memory the stored walks of a backend may take, in megabytes (`meritrank.max_walk_memory`).
+/// 0 means unlimited.
+pub static MAX_WALK_MEMORY: GucSetting<i32> = GucSetting::new(0);
+
+/// Maximum number of rank vectors kept in the rank cache (`meritrank.rank_cache_size`).
+pub static RANK_CACHE_SIZE: GucSetting<i32> = GucSetting::new(100);
+
+/// Whether graph mutations are announced with `pg_notify` (`meritrank.notify_changes`).
+pub static NOTIFY_CHANGES: GucSetting<bool> = GucSetting::new(false);
+
+/// Whether the graph is loaded from the `graph` table on first use (`meritrank.auto_load`).
+pub static AUTO_LOAD: GucSetting<bool> = GucSetting::new(true);
+
+/// Whether graph mutations are mirrored to the `graph` table (`meritrank.persist_changes`).
+pub static PERSIST_CHANGES: GucSetting<bool> = GucSetting::new(true);
+
+/// Whether backends reload the graph after other backends changed it (`meritrank.sync_backends`).
+pub static SYNC_BACKENDS: GucSetting<bool> = GucSetting::new(true);
+
+/// Whether computed ranks are stored in `meritrank_current_ranks` (`meritrank.store_ranks`).
+pub static STORE_RANKS: GucSetting<bool> = GucSetting::new(true);
+
+/// Whether graph mutations are appended to `meritrank_outbox` (`meritrank.outbox`).
+pub static OUTBOX: GucSetting<bool> = GucSetting::new(false);
+
+/// Whether graph mutations are recorded in `meritrank_audit` (`meritrank.audit`).
+pub static AUDIT: GucSetting<bool> = GucSetting::new(false);
+
+/// CSV file of edges loaded into the graph at postmaster start (`meritrank.preload_file`).
+pub static PRELOAD_FILE: GucSetting<Option<&'static str>> = GucSetting::new(None);
+
+/// Database whose `meritrank_jobs` queue a background worker processes
+/// (`meritrank.queue_database`). No worker is started when unset.
+pub static QUEUE_DATABASE: GucSetting<Option<&'static str>> = GucSetting::new(None);
+
+/// Milliseconds the queue worker sleeps when the queue is empty (`meritrank.queue_naptime`).
+pub static QUEUE_NAPTIME: GucSetting<i32> = GucSetting::new(1000);
+
+/// Whether stored ranks are also appended to `meritrank_rank_history` (`meritrank.rank_history`).
+pub static RANK_HISTORY: GucSetting<bool> = GucSetting::new(false);
+
+/// Setting that holds the name `meritrank_vote` votes as (`meritrank.voter_setting`).
+/// `None` means the name of the current role.
+pub static VOTER_SETTING: GucSetting<Option<&'static str>> = GucSetting::new(None);
+
+/// `LIKE` pattern of the vote tables `meritrank_bootstrap` discovers
+/// (`meritrank.vote_table_pattern`).
+pub static VOTE_TABLE_PATTERN: GucSetting<Option<&'static str>> = GucSetting::new(Some("vote_%"));
+
+/// Number of top-ranked nodes whose stable order ends a calculation early
+/// (`meritrank.convergence_top_k`). 0 always generates the requested number of walks.
+pub static CONVERGENCE_TOP_K: GucSetting<i32> = GucSetting::new(0);
+
+/// Number of walks generated between two convergence checks (`meritrank.convergence_batch_size`).
+pub static CONVERGENCE_BATCH_SIZE: GucSetting<i32> = GucSetting::new(1000);
+
+/// Number of threads random walks are generated on (`meritrank.parallelism`).
+pub static PARALLELISM: GucSetting<i32> = GucSetting::new(1);
+
+/// Number of background workers the walks of a calculation are split between
+/// (`meritrank.parallel_workers`). 0 generates them in the backend.
+pub static PARALLEL_WORKERS: GucSetting<i32> = GucSetting::new(0);
+
+/// Seed for random walks (`meritrank.seed`). -1 means non-deterministic walks.
+pub static SEED: GucSetting<i32> = GucSetting::new(-1);
+
+/// Whether edges may only connect registered nodes (`meritrank.strict_nodes`).
+pub static STRICT_NODES: GucSetting<bool> = GucSetting::new(false);
+
+/// Maximum number of nodes in the in-memory graph (`meritrank.max_nodes`). 0 means unlimited.
+pub static MAX_NODES: GucSetting<i32> = GucSetting::new(0);
+
+/// Maximum number of edges in the in-memory graph (`meritrank.max_edges`). 0 means unlimited.
+pub static MAX_EDGES: GucSetting<i32> = GucSetting::new(0);
+
+/// Number of hash partitions the `graph` table is created with
+/// (`meritrank.graph_partitions`). 0 creates an ordinary table.
+pub static GRAPH_PARTITIONS: GucSetting<i32> = GucSetting::new(0);
+
+/// What happens when a new node or edge would exceed `meritrank.max_nodes` or
+/// `meritrank.max_edges`.
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeBudgetPolicy {
+    /// Refuse to create the node with an error.
+    Reject,
+    /// Evict the nodes with the fewest edges to make room.
+    Evict,
+    /// Create the node anyway and emit a warning.
+    Warn,
+    /// Create the node anyway, emit a warning and drop the stored walks and cached ranks
+    /// to free memory.
+    Spill,
+}
+
+/// Behavior when the node budget is exhausted (`meritrank.node_budget_policy`).
+pub static NODE_BUDGET_POLICY: GucSetting<NodeBudgetPolicy> =
+    GucSetting::new(NodeBudgetPolicy::Reject);
+
+/// Verbosity of the extension's own diagnostics, from least to most verbose.
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+    /// Emit nothing.
+    Off,
+    /// Emit warnings only.
+    Warning,
+    /// Also write progress messages to the server log.
+    Info,
+    /// Also emit `DEBUG1` diagnostics.
+    Debug,
+}
+
+/// Verbosity of the extension's diagnostics (`meritrank.log_level`).
+pub static LOG_LEVEL: GucSetting<LogLevel> = GucSetting::new(LogLevel::Info);
+
+/// Registers the extension's configuration parameters.
+///
+/// Must be called from `_PG_init`.
+pub fn init() {
+    GucRegistry::define_float_guc(
+        "meritrank.penalty_factor",
+        "Multiplier applied to penalties from negative edges.",
+        "Scales how strongly distrust propagates to nodes encountered after a negative edge. \
+         0 disables penalties, 1 applies them at full edge weight.",
+        &PENALTY_FACTOR,
+        0.0,
+        1000.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "meritrank.decay_half_life",
+        "Half-life of edge weights, in days.",
+        "Every edge remembers when it was last added or updated, and its weight halves with \
+         every half-life of age when walks are generated. Ages are measured up to the time \
+         the walks were generated, i.e. the last change of the graph. 0 disables decay.",
+        &DECAY_HALF_LIFE,
+        0.0,
+        36500.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "meritrank.alpha",
+        "Default probability of a walk continuing with another step.",
+        "The transitivity decay of the MeritRank paper: every step multiplies the share of \
+         trust passed on by alpha, so lower values favour close peers and limit how much \
+         reputation a chain of accounts can relay. Used when alpha is passed as NULL.",
+        &ALPHA,
+        0.01,
+        0.99,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.max_walk_length",
+        "Maximum number of nodes of a walk, including the ego.",
+        "Walks are cut off after this many nodes, which bounds how many hits a walk that \
+         enters a densely connected ring of accounts can spend inside it. 0 lets walks end \
+         only by chance, as controlled by alpha.",
+        &MAX_WALK_LENGTH,
+        0,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "meritrank.connectivity_decay",
+        "Share of the score removed from nodes reached through few distinct predecessors.",
+        "The connectivity decay of the MeritRank paper: when the walks of an ego entered a \
+         node from fewer than meritrank.connectivity_min_predecessors distinct nodes, its \
+         score is multiplied by 1 minus this value, so a single account cannot amplify the \
+         nodes behind it. 0 disables the discount.",
+        &CONNECTIVITY_DECAY,
+        0.0,
+        1.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.connectivity_min_predecessors",
+        "Number of distinct predecessors below which a score is discounted.",
+        "Only used when meritrank.connectivity_decay is set.",
+        &CONNECTIVITY_MIN_PREDECESSORS,
+        1,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.max_walk_memory",
+        "Memory the stored walks of a backend may take, in megabytes.",
+        "When a calculation leaves the stored walks and hit counters larger than this, the \
+         walks of the egos used least recently are dropped until they fit. The walks \
+         of the latest ego are always kept. 0 means unlimited.",
+        &MAX_WALK_MEMORY,
+        0,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.rank_cache_size",
+        "Maximum number of rank vectors kept in the rank cache.",
+        "Rank vectors are cached per ego and calculation parameters, and dropped when an edge \
+         they may depend on changes. 0 disables the cache.",
+        &RANK_CACHE_SIZE,
+        0,
+        100_000,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.max_nodes",
+        "Maximum number of nodes in the in-memory graph.",
+        "Guards the backend against runaway ingestion. 0 means unlimited.",
+        &MAX_NODES,
+        0,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.max_edges",
+        "Maximum number of edges in the in-memory graph.",
+        "Guards the backend against runaway ingestion. 0 means unlimited.",
+        &MAX_EDGES,
+        0,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        "meritrank.node_budget_policy",
+        "What happens when a new node or edge would exceed meritrank.max_nodes or \
+         meritrank.max_edges.",
+        "reject refuses the mutation, evict removes the nodes with the fewest edges, \
+         warn accepts the mutation and emits a warning, spill also drops the stored walks \
+         and cached ranks, which usually hold most of the memory.",
+        &NODE_BUDGET_POLICY,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        "meritrank.log_level",
+        "Verbosity of the extension's diagnostics.",
+        "off emits nothing, warning emits warnings only, info also writes progress of bulk \
+         operations to the server log, debug also emits DEBUG1 diagnostics.",
+        &LOG_LEVEL,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.notify_changes",
+        "Announces graph mutations on the meritrank_changes channel.",
+        "Edges added, updated or deleted with meritrank_add, meritrank_add_vote and \
+         meritrank_delete, and meritrank_clear(true), send a JSON payload with pg_notify. \
+         Every edge of a batch is announced on its own, so the setting is off by \
+         default, and bulk loaders do not notify.",
+        &NOTIFY_CHANGES,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.auto_load",
+        "Loads the graph table into the in-memory graph on first use.",
+        "The first function that reads or changes the graph in a backend loads the edges \
+         of the graph table first. Turn off to populate the graph explicitly, e.g. with \
+         meritrank_load_from.",
+        &AUTO_LOAD,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.persist_changes",
+        "Mirrors graph mutations to the graph table.",
+        "Edges added, updated or deleted with meritrank_add, meritrank_add_vote and \
+         meritrank_delete, and meritrank_clear(true), are written to the graph table in batches \
+         and at commit. Turn off for ephemeral graphs. Only superusers can change it, so \
+         that roles allowed to vote cannot keep their votes out of the table.",
+        &PERSIST_CHANGES,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.graph_partitions",
+        "Number of hash partitions the graph table is created with.",
+        "Only read by CREATE EXTENSION. Values above 1 partition the graph table by hash of \
+         source into tables graph_p0, graph_p1 and so on, which the graph is then loaded \
+         from one at a time. 0 and 1 create an ordinary table.",
+        &GRAPH_PARTITIONS,
+        0,
+        1024,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.sync_backends",
+        "Reloads the graph after other backends changed the graph table.",
+        "Every write to the graph table bumps the version in meritrank_graph_version. \
+         Once per statement, a backend compares it with the version its in-memory graph \
+         was loaded at, and reloads the table when another backend committed changes. \
+         Only applies while meritrank.auto_load and meritrank.persist_changes are on.",
+        &SYNC_BACKENDS,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.store_ranks",
+        "Stores computed ranks in the meritrank_current_ranks table.",
+        "Whenever the ranks of an ego are computed rather than served from the rank cache, \
+         its rows in meritrank_current_ranks are replaced. Nothing is stored in read-only \
+         transactions.",
+        &STORE_RANKS,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.rank_history",
+        "Keeps previous rank vectors in the meritrank_rank_history table.",
+        "Whenever ranks are stored in meritrank_current_ranks, they are also appended to \
+         meritrank_rank_history with their graph generation, so meritrank_rank_deltas can \
+         compare against them later. Requires meritrank.store_ranks.",
+        &RANK_HISTORY,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.outbox",
+        "Appends graph mutations to the meritrank_outbox table.",
+        "Edges added, updated or deleted with meritrank_add, meritrank_add_vote and \
+         meritrank_delete, and meritrank_clear(true), are inserted into meritrank_outbox in the \
+         same transaction, so downstream consumers can read an ordered stream of them or \
+         subscribe to the table with logical replication. Bulk loaders do not record. \
+         Only superusers can change it, so that roles allowed to vote cannot hide their votes \
+         from consumers.",
+        &OUTBOX,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.audit",
+        "Whether graph mutations are recorded in meritrank_audit.",
+        "Every edge added, updated or deleted, and every clear of the graph, is recorded \
+         with the old and new weight, the current role and the time, to trace reputation \
+         changes. Only superusers may change it, so that it cannot be turned off by the \
+         roles it audits. Bulk loaders do not record.",
+        &AUDIT,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        "meritrank.preload_file",
+        "CSV file of edges loaded into the graph at postmaster start.",
+        "Only used when pg_meritrank is in shared_preload_libraries. The file holds \
+         source,destination,weight rows, written with meritrank_write_preload_file(). Every \
+         backend starts with a copy of the preloaded graph instead of reading the graph \
+         table, until the table changes.",
+        &PRELOAD_FILE,
+        GucContext::Postmaster,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        "meritrank.queue_database",
+        "Database whose meritrank_jobs queue a background worker processes.",
+        "Only used when pg_meritrank is in shared_preload_libraries. The worker calculates \
+         the ranks of egos queued with meritrank_enqueue and stores them in \
+         meritrank_current_ranks. No worker is started when unset.",
+        &QUEUE_DATABASE,
+        GucContext::Postmaster,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.queue_naptime",
+        "Milliseconds the queue worker sleeps when the queue is empty.",
+        "The worker processes queued jobs one transaction each until the queue is empty, \
+         then checks again after this long.",
+        &QUEUE_NAPTIME,
+        10,
+        3_600_000,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        "meritrank.voter_setting",
+        "Setting that holds the node name meritrank_vote votes as.",
+        "E.g. request.jwt.claims.sub when requests are authenticated by PostgREST. When \
+         empty, meritrank_vote votes as the current role. Only superusers may change it, \
+         but any role can change the setting it names, so name one only when a trusted \
+         layer sets it and application roles cannot run SET.",
+        &VOTER_SETTING,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        "meritrank.vote_table_pattern",
+        "LIKE pattern of the vote tables meritrank_bootstrap discovers.",
+        "Tables whose name matches the pattern and that have subject, object and amount \
+         columns get the sync trigger and are loaded into the graph.",
+        &VOTE_TABLE_PATTERN,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.convergence_top_k",
+        "Number of top-ranked nodes whose stable order ends a calculation early.",
+        "Walks are generated in batches of meritrank.convergence_batch_size, and the \
+         calculation stops once the order of this many top-ranked nodes stayed the same \
+         for two batches. The number of iterations becomes a maximum. 0 disables early \
+         termination.",
+        &CONVERGENCE_TOP_K,
+        0,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.convergence_batch_size",
+        "Number of walks generated between two convergence checks.",
+        "Only used when meritrank.convergence_top_k is set.",
+        &CONVERGENCE_BATCH_SIZE,
+        1,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.parallelism",
+        "Number of threads random walks are generated on.",
+        "Walks of a single calculation are split between this many threads. \
+         1 generates them on the backend's own thread.",
+        &PARALLELISM,
+        1,
+        256,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.parallel_workers",
+        "Number of background workers random walks are generated on.",
+        "Calculations of at least 2000 walks are split between up to this many worker \
+         processes, 1000 walks or more each, limited by max_worker_processes. \
+         0 generates them in the backend.",
+        &PARALLEL_WORKERS,
+        0,
+        1024,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "meritrank.seed",
+        "Seed for random walks.",
+        "With a seed, a calculation on the same graph always produces the same ranks, \
+         regardless of meritrank.parallelism. -1 means non-deterministic walks.",
+        &SEED,
+        -1,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "meritrank.strict_nodes",
+        "Refuses edges between nodes that do not exist yet.",
+        "When on, meritrank_add and meritrank_delete fail for unknown nodes instead of \
+         creating them, which catches misspelled names. Nodes are registered with \
+         meritrank_add_node. Bulk loaders still create nodes.",
+        &STRICT_NODES,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+}
@@ -0,0 +1,104 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::graph::{self, NodeKey};
+use crate::rating::{Alpha, Iterations, Limit, PenaltyFactor, TimeBudget};
+
+#[pg_extern]
+/// Sets the weight of the edge between two nodes identified by integer IDs, as
+/// `meritrank_add` does for their decimal form, and returns what changed.
+///
+/// The nodes are looked up by their IDs rather than by name, so nodes added by ID and
+/// by the decimal form of the ID are the same nodes.
+pub fn meritrank_add_id(
+    subject: i64,
+    object: i64,
+    amount: f64,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(previous_weight, Option<f64>),
+            name!(weight, Option<f64>),
+            name!(created, bool),
+        ),
+    >,
+    ErrorReport,
+> {
+    let change = graph::set_keyed_edge(NodeKey::Number(subject), NodeKey::Number(object), amount)?;
+
+    let previous_weight = change.and_then(|change| change.previous_weight);
+    let weight = Some(amount).filter(|&amount| amount != 0.0);
+    let created = previous_weight.is_none() && weight.is_some();
+    Ok(TableIterator::once((previous_weight, weight, created)))
+}
+
+#[pg_extern]
+/// Removes the edge between two nodes identified by integer IDs.
+pub fn meritrank_delete_id(subject: i64, object: i64) -> Result<(), ErrorReport> {
+    graph::set_keyed_edge(NodeKey::Number(subject), NodeKey::Number(object), 0.0)?;
+    Ok(())
+}
+
+#[pg_extern]
+/// Calculates the rank of `object` from the perspective of `subject`, both identified
+/// by integer IDs.
+pub fn meritrank_calculate_id(
+    subject: i64,
+    object: i64,
+    iterations: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<f64, ErrorReport> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let (rank, _) = graph::calculate_rank(
+        NodeKey::Number(subject),
+        NodeKey::Number(object),
+        iterations,
+        penalty_factor,
+        alpha,
+        false,
+        TimeBudget::default(),
+    )?;
+    Ok(rank)
+}
+
+#[pg_extern]
+/// Returns the ranks of the peers of `ego`, identified by integer IDs, sorted in
+/// descending order. Peers whose names are not integers are left out before `limit` is
+/// applied.
+pub fn meritrank_ranks_id(
+    ego: i64,
+    iterations: default!(Option<i32>, "NULL"),
+    limit: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<TableIterator<'static, (name!(node, i64), name!(rank, f64))>, ErrorReport> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let limit = Limit::from_nullable(limit)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let ranks: Vec<(i64, f64)> = graph::calculate_keyed_ranks(
+        NodeKey::Number(ego),
+        iterations,
+        limit,
+        penalty_factor,
+        alpha,
+    )?
+    .into_iter()
+    .filter_map(|(key, rank)| match key {
+        NodeKey::Number(number) => Some((number, rank)),
+        _ => None,
+    })
+    .collect();
+
+    Ok(TableIterator::new(ranks.into_iter()))
+}
@@ -0,0 +1,37 @@
+// Library for PostgreSQL extensions
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::lib_graph::constants::SCORING_VERSION;
+use crate::rating::{Alpha, PenaltyFactor, DEFAULT_ITERATIONS};
+
+/// Name of the ranking algorithm.
+pub const ALGORITHM: &str = "meritrank";
+
+#[pg_extern]
+/// Describes the ranking algorithm and the parameters calculations use by default.
+///
+/// `scoring_version` changes whenever an upgrade alters the computed scores, so stored
+/// or materialized scores can be tagged with it and recomputed when it differs.
+/// `alpha` and `penalty_factor` reflect the current `meritrank.alpha` and
+/// `meritrank.penalty_factor` settings.
+pub fn meritrank_algorithm_info() -> TableIterator<
+    'static,
+    (
+        name!(algorithm, String),
+        name!(extension_version, String),
+        name!(scoring_version, i32),
+        name!(alpha, f64),
+        name!(iterations, i64),
+        name!(penalty_factor, f64),
+    ),
+> {
+    TableIterator::once((
+        ALGORITHM.to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+        SCORING_VERSION,
+        Alpha::default().get(),
+        DEFAULT_ITERATIONS as i64,
+        PenaltyFactor::default().get(),
+    ))
+}
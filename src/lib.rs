@@ -1,7 +1,40 @@
 // Importing modules for the library
 // mod edge; // This module contains edge related operations and data structures
+#[cfg(feature = "arrow")]
+mod arrow_export; // This module contains the export of the graph and ranks to Apache Arrow files
+mod audit; // This module contains the audit log of graph mutations
+mod baseline; // This module contains classic scoring algorithms to compare MeritRank against
+mod batch; // This module contains rank calculations for several egos at once
+mod cache; // This module contains the cache of calculated rank vectors
+mod convergence; // This module contains diagnostics of the sampling accuracy of calculations
 mod error; // This module contains error types and handling logic
+mod explain; // This module contains explanations of calculated ranks
+mod export; // This module contains serialization of the graph to external formats
+mod generate; // This module contains generators of synthetic graphs
 mod graph; // This module is for graph related operations
+mod graph_type; // This module contains the graph type stored in table columns
+mod guc; // This module contains the extension's configuration parameters
+mod id_nodes; // This module contains the API for nodes identified by integer IDs
+mod info; // This module contains the description of the algorithm and its defaults
+mod loader; // This module contains bulk loading of edges from other sources
+mod logging; // This module contains diagnostics routed through the Postgres log
+mod node_rating; // This module contains the node_rating type for materialized rank results
+mod node_types; // This module contains the classification of nodes into users and content
+mod notify; // This module contains change notifications for graph mutations
+mod outbox; // This module contains the ordered log of graph mutations for downstream consumers
+mod parallel; // This module contains the generation of walks on background workers
+mod persist; // This module contains the mirroring of in-memory state to tables
+mod properties; // This module contains key-value metadata attached to nodes
+mod queue; // This module contains the queue of calculations processed in the background
+mod rating; // This module contains validated calculation parameters
+mod snapshots; // This module contains named copies of the graph and their comparison
+mod stats; // This module contains runtime statistics of the extension
+mod structure; // This module contains structural queries over the in-memory graph
+mod triggers; // This module contains the triggers that mirror vote tables to the graph
+mod uuid_nodes; // This module contains overloads of the API for nodes identified by UUIDs
+mod vote; // This module contains write functions that act on behalf of the current user
+mod walks; // This module contains the random walks kept between calculations
+mod weights; // This module contains the translation of vote values to edge weights
 // #[cfg(feature = "shared")]
 // mod shared; // This module contains shared data structures
 mod lib_graph; // This module contains graph related operations and data structures
@@ -15,6 +48,20 @@ use graph::{GraphManipulationError, GraphSingleton}; // Importing types from the
 // pgx specific macros
 pg_module_magic!();
 
+#[pg_guard]
+/// Called by Postgres when the extension library is loaded.
+///
+/// When the library is in `shared_preload_libraries`, this runs once in the postmaster,
+/// which warms the graph from `meritrank.preload_file` before any backend is forked and
+/// registers the queue worker if `meritrank.queue_database` is set.
+pub extern "C" fn _PG_init() {
+    guc::init();
+    if unsafe { pg_sys::process_shared_preload_libraries_in_progress } {
+        loader::preload();
+        queue::register_worker();
+    }
+}
+
 // The postgres external function to return a greeting message.
 #[pg_extern]
 /// Returns a static greeting message.
@@ -31,13 +78,18 @@ fn hello_hello_world() -> &'static str {
 /// * `destination` - A string slice holding the destination node's name.
 /// * `weight` - A float64 holding the weight of the edge.
 fn insert_and_trigger(source: &str, destination: &str, weight: f64) {
-    let insert_sql = format!(
-        "INSERT INTO graph (source, destination, weight) VALUES ('{}', '{}', {});",
-        source, destination, weight
+    let inserted = Spi::run_with_args(
+        "INSERT INTO graph (source, destination, weight) VALUES ($1, $2, $3) \
+         ON CONFLICT (source, destination) DO UPDATE SET weight = EXCLUDED.weight",
+        Some(vec![
+            (PgBuiltInOids::TEXTOID.oid(), source.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), destination.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), weight.into_datum()),
+        ]),
     );
-    match Spi::run(&insert_sql) {
-        Ok(_) => println!("Inserted record into graph table successfully."),
-        Err(err) => println!("Error inserting record into graph table: {}", err),
+    match inserted {
+        Ok(_) => logging::log_debug!("Inserted record into graph table successfully."),
+        Err(err) => logging::log_warning!("Error inserting record into graph table: {}", err),
     }
 }
 
@@ -1,3 +1,14 @@
 pub const ASSERT: bool = false;
 pub const VERBOSE: bool = false;
 pub const OPTIMIZE_INVALIDATION: bool = true;
+
+/// Number of walks generated between two checks for query cancellation or an exceeded
+/// deadline, unless a convergence rule sets the batch size.
+pub const INTERRUPT_BATCH_SIZE: usize = 1000;
+
+/// Walk continuation probability used unless set otherwise.
+pub const DEFAULT_ALPHA: f64 = 0.85;
+
+/// Version of the scoring semantics. Bump it whenever a change alters the computed
+/// scores, so that stored scores can be recognized as stale after an upgrade.
+pub const SCORING_VERSION: i32 = 1;
@@ -16,6 +16,12 @@ impl Counter {
         }
     }
 
+    /// Estimates the number of heap bytes held by the counter.
+    pub fn memory_usage(&self) -> usize {
+        self.counter.capacity()
+            * (std::mem::size_of::<NodeId>() + std::mem::size_of::<Weight>() + 1)
+    }
+
     pub fn keys(&self) -> Vec<NodeId> {
         self.counter.keys().cloned().collect()
     }
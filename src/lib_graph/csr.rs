@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::lib_graph::graph::MyGraph;
+use crate::lib_graph::node::{NodeId, Weight};
+use crate::lib_graph::walk::AliasTable;
+
+/// An immutable compressed sparse row (CSR) copy of the positive edges of a graph,
+/// laid out for sampling walk steps.
+///
+/// The out-edges of every node occupy one contiguous row of the edge arrays, together
+/// with the alias table for choosing among them, so a step reads a single cache-friendly
+/// slice instead of following the adjacency lists of the graph. Nodes without positive
+/// out-edges have no row. The structure does not follow changes of the graph it was
+/// built from; it has to be rebuilt instead.
+pub struct CsrGraph {
+    rows: HashMap<NodeId, usize>,
+    offsets: Vec<usize>,
+    targets: Vec<NodeId>,
+    probabilities: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl CsrGraph {
+    /// Builds the structure from the positive edges of `graph`.
+    ///
+    /// The targets of a row are sorted, so seeded walks take the same steps as over the
+    /// graph itself.
+    pub fn from_graph(graph: &MyGraph) -> Self {
+        let mut out_edges: HashMap<NodeId, Vec<(NodeId, Weight)>> = HashMap::new();
+        for (source, target, weight) in graph.all_edges() {
+            if weight > 0.0 {
+                out_edges.entry(source).or_default().push((target, weight));
+            }
+        }
+        let mut sources: Vec<NodeId> = out_edges.keys().copied().collect();
+        sources.sort();
+
+        let edge_count = out_edges.values().map(Vec::len).sum();
+        let mut csr = CsrGraph {
+            rows: HashMap::with_capacity(sources.len()),
+            offsets: Vec::with_capacity(sources.len() + 1),
+            targets: Vec::with_capacity(edge_count),
+            probabilities: Vec::with_capacity(edge_count),
+            aliases: Vec::with_capacity(edge_count),
+        };
+        csr.offsets.push(0);
+        for source in sources {
+            let mut edges = out_edges.remove(&source).unwrap_or_default();
+            edges.sort_by_key(|&(target, _)| target);
+            let (targets, weights): (Vec<_>, Vec<_>) = edges.into_iter().unzip();
+            if let Some(table) = AliasTable::new(targets, &weights) {
+                let (targets, probabilities, aliases) = table.into_parts();
+                csr.rows.insert(source, csr.offsets.len() - 1);
+                csr.targets.extend(targets);
+                csr.probabilities.extend(probabilities);
+                csr.aliases.extend(aliases);
+                csr.offsets.push(csr.targets.len());
+            }
+        }
+        csr
+    }
+
+    /// Draws the next step of a walk leaving `node`, or returns `None` if the node has no
+    /// positive out-edges.
+    pub fn sample(&self, node: NodeId, rng: &mut impl Rng) -> Option<NodeId> {
+        let row = *self.rows.get(&node)?;
+        let (start, end) = (self.offsets[row], self.offsets[row + 1]);
+        let bucket = start + rng.gen_range(0..end - start);
+        if rng.gen::<f64>() < self.probabilities[bucket] {
+            Some(self.targets[bucket])
+        } else {
+            Some(self.targets[start + self.aliases[bucket]])
+        }
+    }
+
+    /// Checks if `node` has positive out-edges.
+    pub fn has_out_edges(&self, node: NodeId) -> bool {
+        self.rows.contains_key(&node)
+    }
+
+    /// Returns the number of nodes with positive out-edges.
+    pub fn node_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns the number of positive edges.
+    pub fn edge_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Estimates the number of heap bytes held by the structure.
+    pub fn memory_usage(&self) -> usize {
+        self.rows.capacity() * (std::mem::size_of::<NodeId>() + std::mem::size_of::<usize>() + 1)
+            + self.offsets.capacity() * std::mem::size_of::<usize>()
+            + self.targets.capacity() * std::mem::size_of::<NodeId>()
+            + self.probabilities.capacity() * std::mem::size_of::<f64>()
+            + self.aliases.capacity() * std::mem::size_of::<usize>()
+    }
+}
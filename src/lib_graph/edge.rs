@@ -1,3 +1,47 @@
-use crate::lib_graph::node::NodeId;
+use crate::lib_graph::node::{NodeId, Weight};
 
 pub type EdgeId = (NodeId, NodeId);
+
+/// Time of the last update of an edge, in seconds since the Unix epoch.
+pub type Timestamp = i64;
+
+/// Exponential decay of edge weights with the age of the edge.
+///
+/// An edge loses half of its weight every `half_life` seconds, counted from its last
+/// update up to `reference_time`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeDecay {
+    half_life: f64,
+    reference_time: Timestamp,
+}
+
+impl EdgeDecay {
+    /// Creates a decay model. Returns `None` unless the half-life is a positive finite number.
+    pub fn new(half_life: f64, reference_time: Timestamp) -> Option<Self> {
+        if half_life.is_finite() && half_life > 0.0 {
+            Some(EdgeDecay {
+                half_life,
+                reference_time,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the half-life in seconds.
+    pub fn get_half_life(&self) -> f64 {
+        self.half_life
+    }
+
+    /// Returns the time ages are measured up to.
+    pub fn get_reference_time(&self) -> Timestamp {
+        self.reference_time
+    }
+
+    /// Returns the effective weight of an edge last updated at `timestamp`.
+    /// Edges from the future do not gain weight.
+    pub fn apply(&self, weight: Weight, timestamp: Timestamp) -> Weight {
+        let age = (self.reference_time - timestamp).max(0) as f64;
+        weight * 0.5f64.powf(age / self.half_life)
+    }
+}
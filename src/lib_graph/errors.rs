@@ -9,6 +9,8 @@ pub enum MeritRankError {
     NodeDoesNotCalculated,
     InvalidWalkLength,
     InvalidNode,
+    WalkWorkerPanicked,
+    InvalidNodeType,
 }
 
 use std::error::Error;
@@ -25,6 +27,8 @@ impl Display for MeritRankError {
             MeritRankError::NodeDoesNotCalculated => write!(f, "Node does not calculated"),
             MeritRankError::InvalidWalkLength => write!(f, "Invalid walk length"),
             MeritRankError::InvalidNode => write!(f, "Invalid node"),
+            MeritRankError::WalkWorkerPanicked => write!(f, "Walk worker thread panicked"),
+            MeritRankError::InvalidNodeType => write!(f, "Invalid node type"),
         }
     }
 }
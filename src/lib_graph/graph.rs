@@ -1,15 +1,18 @@
 use std::collections::HashMap;
 
+use indexmap::IndexMap;
 use petgraph::algo::has_path_connecting;
 use petgraph::graph::DiGraph;
 use petgraph::prelude::NodeIndex;
+use petgraph::unionfind::UnionFind;
 
 #[allow(unused_imports)]
 use petgraph::visit::EdgeRef;
 
 // use crate::lib_graph::{MeritRankError, NodeId, Weight, Node};
+use crate::lib_graph::edge::{EdgeId, Timestamp};
 use crate::lib_graph::errors::MeritRankError;
-use crate::lib_graph::node::{Node, NodeId, Weight};
+use crate::lib_graph::node::{Node, NodeId, NodeType, Weight};
 
 pub type MyDiGraph = DiGraph<Node, Weight>;
 
@@ -17,6 +20,7 @@ pub type MyDiGraph = DiGraph<Node, Weight>;
 pub struct MyGraph {
     graph: MyDiGraph,
     nodes: HashMap<NodeId, NodeIndex>,
+    edge_timestamps: HashMap<EdgeId, Timestamp>,
 }
 
 #[allow(dead_code)]
@@ -27,6 +31,7 @@ impl MyGraph {
         MyGraph {
             graph: MyDiGraph::new(),
             nodes: HashMap::new(),
+            edge_timestamps: HashMap::new(),
         }
     }
 
@@ -85,7 +90,8 @@ impl MyGraph {
         }
     }
 
-    /// Adds an edge between the two given nodes in the graph.
+    /// Adds an edge between the two given nodes in the graph, or replaces the weight of
+    /// an existing one.
     pub fn add_edge(
         &mut self,
         source: NodeId,
@@ -96,8 +102,9 @@ impl MyGraph {
         if let (Some(source_index), Some(target_index)) =
             (self.get_node_index(source), self.get_node_index(target))
         {
-            // Add an edge between the source and target NodeIndices with the given weight
-            self.graph.add_edge(source_index, target_index, weight);
+            // Add an edge between the source and target NodeIndices with the given weight,
+            // without creating a parallel edge when it already exists
+            self.graph.update_edge(source_index, target_index, weight);
             Ok(())
         } else {
             Err(MeritRankError::InvalidNode)
@@ -115,6 +122,123 @@ impl MyGraph {
                 self.graph.remove_edge(edge_index);
             }
         }
+        self.edge_timestamps.remove(&(source, target));
+    }
+
+    /// Records when the edge between the two given nodes was last updated.
+    pub fn set_edge_timestamp(&mut self, source: NodeId, target: NodeId, timestamp: Timestamp) {
+        self.edge_timestamps.insert((source, target), timestamp);
+    }
+
+    /// Returns when the edge between the two given nodes was last updated,
+    /// or `None` if the edge carries no timestamp.
+    pub fn edge_timestamp(&self, source: NodeId, target: NodeId) -> Option<Timestamp> {
+        self.edge_timestamps.get(&(source, target)).copied()
+    }
+
+    /// Returns the type of a node, or `None` if the node does not exist.
+    pub fn node_type(&self, node_id: NodeId) -> Option<NodeType> {
+        self.get_node_index(node_id)
+            .map(|index| self.graph[index].get_type())
+    }
+
+    /// Sets the type of a node. Returns `false` if the node does not exist.
+    pub fn set_node_type(&mut self, node_id: NodeId, node_type: NodeType) -> bool {
+        match self.get_node_index(node_id) {
+            Some(index) => {
+                self.graph[index].set_type(node_type);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns a copy of the graph that keeps only the edges last updated within
+    /// `[since, until)`. An open bound is `None`; edges without a timestamp are dropped
+    /// unless both bounds are open.
+    pub fn window(&self, since: Option<Timestamp>, until: Option<Timestamp>) -> MyGraph {
+        let mut window = self.clone();
+        if since.is_none() && until.is_none() {
+            return window;
+        }
+
+        for (source, target, _) in self.all_edges() {
+            let inside = self
+                .edge_timestamp(source, target)
+                .map_or(false, |timestamp| {
+                    since.map_or(true, |since| timestamp >= since)
+                        && until.map_or(true, |until| timestamp < until)
+                });
+            if !inside {
+                window.remove_edge(source, target);
+            }
+        }
+        window
+    }
+
+    /// Returns a copy of the graph with the direction of every edge reversed, so that walks
+    /// follow votes backwards from the voted-for node to the voters.
+    pub fn reversed(&self) -> MyGraph {
+        let mut reversed = self.clone();
+        reversed.graph.reverse();
+        reversed.edge_timestamps = self
+            .edge_timestamps
+            .iter()
+            .map(|(&(source, target), &timestamp)| ((target, source), timestamp))
+            .collect();
+        reversed
+    }
+
+    /// Returns a copy of the graph with its nodes renumbered according to `ids`, keeping
+    /// node types, edge weights and timestamps. Nodes missing from `ids` are dropped with
+    /// their edges. The copy is allocated to fit, without spare capacity.
+    pub fn renumbered(&self, ids: &HashMap<NodeId, NodeId>) -> MyGraph {
+        let mut renumbered = MyGraph {
+            graph: MyDiGraph::with_capacity(ids.len(), self.graph.edge_count()),
+            nodes: HashMap::with_capacity(ids.len()),
+            edge_timestamps: HashMap::with_capacity(self.edge_timestamps.len()),
+        };
+        for index in self.graph.node_indices() {
+            let node = &self.graph[index];
+            if let Some(&node_id) = ids.get(&node.get_id()) {
+                renumbered.add_node(Node::with_type(node_id, node.get_type()));
+            }
+        }
+        for (source, target, weight) in self.all_edges() {
+            if let (Some(&new_source), Some(&new_target)) = (ids.get(&source), ids.get(&target)) {
+                let _ = renumbered.add_edge(new_source, new_target, weight);
+                if let Some(timestamp) = self.edge_timestamp(source, target) {
+                    renumbered.set_edge_timestamp(new_source, new_target, timestamp);
+                }
+            }
+        }
+        renumbered
+    }
+
+    /// Removes a node and all of its edges from the graph.
+    pub fn remove_node(&mut self, node_id: NodeId) {
+        if let Some(index) = self.get_node_index(node_id) {
+            // Removing a node invalidates the index of the last node, so rebuild the mapping
+            self.graph.remove_node(index);
+            self.update_index();
+        }
+        self.edge_timestamps
+            .retain(|&(source, target), _| source != node_id && target != node_id);
+    }
+
+    /// Returns the number of incoming and outgoing edges of a node.
+    pub fn degree(&self, node_id: NodeId) -> usize {
+        self.get_node_index(node_id)
+            .map(|index| {
+                self.graph
+                    .neighbors_directed(index, petgraph::Direction::Outgoing)
+                    .count()
+                    + self
+                        .graph
+                        .neighbors_directed(index, petgraph::Direction::Incoming)
+                        .count()
+            })
+            .unwrap_or(0)
     }
 
     /// Retrieves the neighboring nodes of a given node.
@@ -177,6 +301,19 @@ impl MyGraph {
         })
     }
 
+    /// Retrieves the edges pointing to the given node, as pairs of the source node and the
+    /// weight of the edge. A node that does not exist has no incoming edges.
+    pub fn incoming_edges(&self, node: NodeId) -> Vec<(NodeId, Weight)> {
+        self.get_node_index(node)
+            .map(|index| {
+                self.graph
+                    .edges_directed(index, petgraph::Direction::Incoming)
+                    .map(|edge| (self.graph[edge.source()].get_id(), *edge.weight()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     /// Checks if there is a path between the two given nodes.
     pub fn is_connecting(&self, source: NodeId, target: NodeId) -> bool {
         // Check if the source and target nodes have valid NodeIndices in the graph
@@ -223,10 +360,79 @@ impl MyGraph {
         self.graph.node_count()
     }
 
+    /// Returns the number of edges in the graph
+    pub fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    /// Retrieves the ids of all nodes in the graph.
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        self.graph
+            .node_indices()
+            .map(|index| self.graph[index].get_id())
+            .collect()
+    }
+
+    /// Retrieves all edges of the graph.
+    ///
+    /// Each tuple contains the source node, destination node, and weight of the edge.
+    pub fn all_edges(&self) -> Vec<(NodeId, NodeId, Weight)> {
+        self.graph
+            .edge_references()
+            .map(|edge| {
+                (
+                    self.graph[edge.source()].get_id(),
+                    self.graph[edge.target()].get_id(),
+                    *edge.weight(),
+                )
+            })
+            .collect()
+    }
+
+    /// Labels the weakly connected components of the graph, ignoring edge direction.
+    ///
+    /// Components are numbered from 1 by descending size, so component 1 is the largest.
+    pub fn connected_components(&self) -> HashMap<NodeId, usize> {
+        let mut sets = UnionFind::new(self.graph.node_count());
+        for edge in self.graph.edge_references() {
+            sets.union(edge.source().index(), edge.target().index());
+        }
+
+        // Group the nodes by the representative of their set, in order of appearance
+        let mut groups: IndexMap<usize, Vec<NodeId>> = IndexMap::new();
+        for index in self.graph.node_indices() {
+            groups
+                .entry(sets.find(index.index()))
+                .or_insert_with(Vec::new)
+                .push(self.graph[index].get_id());
+        }
+
+        let mut groups: Vec<Vec<NodeId>> = groups.into_values().collect();
+        // A stable sort keeps components of equal size in order of appearance
+        groups.sort_by_key(|nodes| std::cmp::Reverse(nodes.len()));
+        groups
+            .into_iter()
+            .enumerate()
+            .flat_map(|(component, nodes)| nodes.into_iter().map(move |node| (node, component + 1)))
+            .collect()
+    }
+
+    /// Estimates the number of heap bytes held by the graph, including allocated spare capacity.
+    pub fn memory_usage(&self) -> usize {
+        let (node_capacity, edge_capacity) = self.graph.capacity();
+        node_capacity * std::mem::size_of::<petgraph::graph::Node<Node>>()
+            + edge_capacity * std::mem::size_of::<petgraph::graph::Edge<Weight>>()
+            + self.nodes.capacity()
+                * (std::mem::size_of::<NodeId>() + std::mem::size_of::<NodeIndex>() + 1)
+            + self.edge_timestamps.capacity()
+                * (std::mem::size_of::<EdgeId>() + std::mem::size_of::<Timestamp>() + 1)
+    }
+
     /// Clears the graph.
     pub fn clear(&mut self) {
         self.graph.clear();
         self.nodes.clear();
+        self.edge_timestamps.clear();
     }
 }
 
@@ -269,4 +475,4 @@ impl PartialEq for MyGraph {
 
         true
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use crate::lib_graph::graph::MyGraph;
+use crate::lib_graph::node::{NodeId, Weight};
+
+/// Hub and authority scores of a node.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HitsScores {
+    pub hub: Weight,
+    pub authority: Weight,
+}
+
+/// Scales `scores` to unit Euclidean length, unless they are all zero.
+fn normalize(scores: &mut [Weight]) {
+    let norm = scores
+        .iter()
+        .map(|score| score * score)
+        .sum::<Weight>()
+        .sqrt();
+    if norm > 0.0 {
+        for score in scores.iter_mut() {
+            *score /= norm;
+        }
+    }
+}
+
+/// Calculates the HITS hub and authority scores of every node of `graph`.
+///
+/// A node is a good authority if good hubs point to it, and a good hub if it points to
+/// good authorities. Only positive edges count, in proportion to their weight. Both
+/// score vectors are normalized to unit Euclidean length after every iteration.
+pub fn hits(graph: &MyGraph, iterations: usize) -> HashMap<NodeId, HitsScores> {
+    let nodes = graph.node_ids();
+    let positions: HashMap<NodeId, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(position, &node)| (node, position))
+        .collect();
+    let edges: Vec<(usize, usize, Weight)> = graph
+        .all_edges()
+        .into_iter()
+        .filter(|&(_, _, weight)| weight > 0.0)
+        .map(|(source, target, weight)| (positions[&source], positions[&target], weight))
+        .collect();
+
+    let n = nodes.len();
+    let mut hubs = vec![1.0; n];
+    let mut authorities = vec![1.0; n];
+    normalize(&mut hubs);
+    normalize(&mut authorities);
+
+    for _ in 0..iterations {
+        let mut next_authorities = vec![0.0; n];
+        for &(source, target, weight) in edges.iter() {
+            next_authorities[target] += weight * hubs[source];
+        }
+        normalize(&mut next_authorities);
+
+        let mut next_hubs = vec![0.0; n];
+        for &(source, target, weight) in edges.iter() {
+            next_hubs[source] += weight * next_authorities[target];
+        }
+        normalize(&mut next_hubs);
+
+        hubs = next_hubs;
+        authorities = next_authorities;
+    }
+
+    nodes
+        .into_iter()
+        .enumerate()
+        .map(|(position, node)| {
+            (
+                node,
+                HitsScores {
+                    hub: hubs[position],
+                    authority: authorities[position],
+                },
+            )
+        })
+        .collect()
+}
@@ -1,21 +1,27 @@
 pub mod common;
 pub mod constants;
 pub mod counter;
+pub mod csr;
 pub mod debug;
 pub mod display;
 pub mod edge;
 pub mod errors;
 pub mod graph;
+pub mod hits;
 pub mod node;
+pub mod pagerank;
+pub mod profile;
 pub mod rank;
+pub mod simd;
 pub mod storage;
 pub mod walk;
 
 pub use counter::{Counter, CounterIterator};
-pub use edge::EdgeId;
+pub use csr::CsrGraph;
+pub use edge::{EdgeDecay, EdgeId, Timestamp};
 pub use errors::MeritRankError;
 pub use graph::{MyDiGraph, MyGraph};
-pub use node::{Node, NodeId, Weight};
-pub use rank::MeritRank;
-pub use walk::{WalkId, WalkIdGenerator, PosWalk, RandomWalk};
+pub use node::{Node, NodeId, NodeType, Weight};
+pub use rank::{Connectivity, Convergence, MeritRank, WalkOffload};
+pub use walk::{WalkId, WalkIdGenerator, WalkParams, PosWalk, RandomWalk};
 pub use storage::WalkStorage;
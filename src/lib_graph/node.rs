@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use crate::lib_graph::MeritRankError;
 
 // use uuid::Uuid;
@@ -49,22 +51,100 @@ impl NodeId {
     }
 }
 
+/// The class of a node. Users vote, while beacons and comments are content being voted on.
+#[derive(Debug, Hash, Default, PartialOrd, Ord, Clone, Copy, PartialEq, Eq)]
+pub enum NodeType {
+    User,
+    Beacon,
+    Comment,
+    #[default]
+    Other,
+}
+
+impl NodeType {
+    /// All node types, in reporting order.
+    pub const ALL: [NodeType; 4] = [
+        NodeType::User,
+        NodeType::Beacon,
+        NodeType::Comment,
+        NodeType::Other,
+    ];
+
+    /// Derives the type of a node from the prefix of its name, following the convention of
+    /// other MeritRank implementations: `U123` is a user, `B123` a beacon, `C123` a comment.
+    /// The prefix must be followed by a digit; any other name is of type `Other`.
+    pub fn from_name(name: &str) -> Self {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(prefix), Some(next)) if next.is_ascii_digit() => match prefix {
+                'U' => NodeType::User,
+                'B' => NodeType::Beacon,
+                'C' => NodeType::Comment,
+                _ => NodeType::Other,
+            },
+            _ => NodeType::Other,
+        }
+    }
+
+    /// Returns the name of the node type as used in SQL.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeType::User => "user",
+            NodeType::Beacon => "beacon",
+            NodeType::Comment => "comment",
+            NodeType::Other => "other",
+        }
+    }
+}
+
+impl FromStr for NodeType {
+    type Err = MeritRankError;
+
+    /// Parses a node type from its SQL name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NodeType::ALL
+            .iter()
+            .copied()
+            .find(|node_type| node_type.name().eq_ignore_ascii_case(s))
+            .ok_or(MeritRankError::InvalidNodeType)
+    }
+}
+
 /// Represents a node in the MeritRank graph.
 #[derive(Debug, Hash, Default, PartialOrd, Ord, Clone, PartialEq, Eq)]
 pub struct Node {
     id: NodeId,
+    node_type: NodeType,
 }
 
 impl Node {
     /// Creates a new Node with the specified id.
     pub fn new(id: NodeId) -> Self {
-        Node { id }
+        Node {
+            id,
+            node_type: NodeType::default(),
+        }
+    }
+
+    /// Creates a new Node with the specified id and type.
+    pub fn with_type(id: NodeId, node_type: NodeType) -> Self {
+        Node { id, node_type }
     }
 
     /// Returns the id of the node.
     pub fn get_id(&self) -> NodeId {
         self.id
     }
+
+    /// Returns the type of the node.
+    pub fn get_type(&self) -> NodeType {
+        self.node_type
+    }
+
+    /// Sets the type of the node.
+    pub fn set_type(&mut self, node_type: NodeType) {
+        self.node_type = node_type;
+    }
 }
 
 impl<T> From<T> for Node
@@ -73,7 +153,7 @@ where
 {
     /// Converts the value into a Node using the provided id.
     fn from(id: T) -> Self {
-        Node { id: id.into() }
+        Node::new(id.into())
     }
 }
 
@@ -116,8 +196,6 @@ impl From<Weight> for NodeId {
     }
 }
 
-use std::str::FromStr;
-
 impl FromStr for NodeId {
     type Err = MeritRankError;
 
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::lib_graph::errors::MeritRankError;
+use crate::lib_graph::graph::MyGraph;
+use crate::lib_graph::node::{NodeId, Weight};
+
+/// Row-stochastic transition structure of a graph.
+///
+/// Only positive edges are followed; the probability of moving along an edge is
+/// proportional to its weight. Nodes without positive outgoing edges are dangling.
+pub struct Transitions {
+    /// The nodes of the graph; positions in this vector index the other fields.
+    pub nodes: Vec<NodeId>,
+    /// For every node, its successors and the probability of moving to each of them.
+    pub successors: Vec<Vec<(usize, Weight)>>,
+}
+
+impl Transitions {
+    /// Builds the transition structure of `graph`.
+    pub fn new(graph: &MyGraph) -> Self {
+        let nodes = graph.node_ids();
+        let positions: HashMap<NodeId, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(position, &node)| (node, position))
+            .collect();
+
+        let mut successors: Vec<Vec<(usize, Weight)>> = vec![Vec::new(); nodes.len()];
+        for (source, target, weight) in graph.all_edges() {
+            if weight > 0.0 {
+                successors[positions[&source]].push((positions[&target], weight));
+            }
+        }
+        for edges in successors.iter_mut() {
+            let total: Weight = edges.iter().map(|&(_, weight)| weight).sum();
+            for (_, weight) in edges.iter_mut() {
+                *weight /= total;
+            }
+        }
+
+        Transitions { nodes, successors }
+    }
+
+    /// Returns the number of nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the position of a node, or `None` if it is not in the graph.
+    pub fn position(&self, node: NodeId) -> Option<usize> {
+        self.nodes.iter().position(|&n| n == node)
+    }
+
+    /// Pairs the nodes with their scores.
+    pub fn scores(&self, scores: Vec<Weight>) -> HashMap<NodeId, Weight> {
+        self.nodes.iter().copied().zip(scores).collect()
+    }
+}
+
+/// Calculates the global (non-personalized) PageRank of every node of `graph` by power
+/// iteration.
+///
+/// With probability `damping` a surfer follows a positive edge, otherwise it jumps to a
+/// uniformly random node; dangling nodes jump uniformly as well. The scores sum to 1.
+pub fn pagerank(graph: &MyGraph, damping: f64, iterations: usize) -> HashMap<NodeId, Weight> {
+    let transitions = Transitions::new(graph);
+    let n = transitions.len();
+    if n == 0 {
+        return HashMap::new();
+    }
+
+    let uniform = 1.0 / n as Weight;
+    let mut scores = vec![uniform; n];
+    for _ in 0..iterations {
+        let mut next = vec![(1.0 - damping) * uniform; n];
+        let mut dangling = 0.0;
+        for (node, edges) in transitions.successors.iter().enumerate() {
+            if edges.is_empty() {
+                dangling += scores[node];
+            }
+            for &(successor, probability) in edges {
+                next[successor] += damping * scores[node] * probability;
+            }
+        }
+        let share = damping * dangling * uniform;
+        for score in next.iter_mut() {
+            *score += share;
+        }
+        scores = next;
+    }
+
+    transitions.scores(scores)
+}
+
+/// Calculates the personalized PageRank of every node from the perspective of `ego`
+/// with the deterministic forward-push method.
+///
+/// With probability `damping` a surfer follows a positive edge, otherwise it restarts
+/// at `ego`; dangling nodes restart as well. Residual probability is pushed until no
+/// node holds more than `epsilon` of it, so every score is exact to within `epsilon`
+/// times the number of nodes. Nodes that are never reached are omitted.
+pub fn personalized_pagerank(
+    graph: &MyGraph,
+    ego: NodeId,
+    damping: f64,
+    epsilon: f64,
+) -> Result<HashMap<NodeId, Weight>, MeritRankError> {
+    let transitions = Transitions::new(graph);
+    let ego = transitions
+        .position(ego)
+        .ok_or(MeritRankError::NodeDoesNotExist)?;
+
+    let n = transitions.len();
+    let mut scores: Vec<Weight> = vec![0.0; n];
+    let mut residuals: Vec<Weight> = vec![0.0; n];
+    let mut queued = vec![false; n];
+    let mut queue = VecDeque::new();
+
+    residuals[ego] = 1.0;
+    queued[ego] = true;
+    queue.push_back(ego);
+
+    while let Some(node) = queue.pop_front() {
+        queued[node] = false;
+        let residual = std::mem::take(&mut residuals[node]);
+        scores[node] += (1.0 - damping) * residual;
+
+        let pushed = damping * residual;
+        let edges = &transitions.successors[node];
+        let targets: Vec<(usize, Weight)> = if edges.is_empty() {
+            vec![(ego, pushed)]
+        } else {
+            edges
+                .iter()
+                .map(|&(successor, probability)| (successor, pushed * probability))
+                .collect()
+        };
+        for (target, amount) in targets {
+            residuals[target] += amount;
+            if residuals[target] > epsilon && !queued[target] {
+                queued[target] = true;
+                queue.push_back(target);
+            }
+        }
+    }
+
+    Ok(transitions
+        .nodes
+        .iter()
+        .copied()
+        .zip(scores)
+        .filter(|&(_, score)| score > 0.0)
+        .collect())
+}
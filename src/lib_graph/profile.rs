@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "profiling")]
+use std::time::Instant;
+
+/// Hot path sections instrumented when the `profiling` feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    WalkGeneration,
+    CounterUpdate,
+    Normalization,
+}
+
+impl Section {
+    /// All instrumented sections, in reporting order.
+    pub const ALL: [Section; 3] = [
+        Section::WalkGeneration,
+        Section::CounterUpdate,
+        Section::Normalization,
+    ];
+
+    /// Returns the name of the section as reported to SQL.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::WalkGeneration => "walk_generation",
+            Section::CounterUpdate => "counter_update",
+            Section::Normalization => "normalization",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+static CALLS: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+static NANOS: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+
+/// Measures the time spent in a section until it is dropped.
+///
+/// Without the `profiling` feature this is a zero-sized no-op.
+pub struct ProfileScope {
+    #[cfg(feature = "profiling")]
+    section: Section,
+    #[cfg(feature = "profiling")]
+    start: Instant,
+}
+
+impl ProfileScope {
+    /// Starts measuring the given section.
+    #[inline]
+    #[allow(unused_variables)]
+    pub fn new(section: Section) -> Self {
+        ProfileScope {
+            #[cfg(feature = "profiling")]
+            section,
+            #[cfg(feature = "profiling")]
+            start: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "profiling")]
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        let index = self.section.index();
+        CALLS[index].fetch_add(1, Ordering::Relaxed);
+        NANOS[index].fetch_add(self.start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of calls and the accumulated nanoseconds for every section.
+pub fn snapshot() -> Vec<(Section, u64, u64)> {
+    Section::ALL
+        .iter()
+        .map(|section| {
+            (
+                *section,
+                CALLS[section.index()].load(Ordering::Relaxed),
+                NANOS[section.index()].load(Ordering::Relaxed),
+            )
+        })
+        .collect()
+}
+
+/// Resets all section counters to zero.
+pub fn reset() {
+    for section in Section::ALL.iter() {
+        CALLS[section.index()].store(0, Ordering::Relaxed);
+        NANOS[section.index()].store(0, Ordering::Relaxed);
+    }
+}
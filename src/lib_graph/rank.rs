@@ -1,25 +1,83 @@
 use rand::distributions::WeightedIndex;
 use rand::prelude::*;
+use rand::rngs::StdRng;
 
 use indexmap::IndexMap;
 use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use crate::lib_graph::common::sign;
-use crate::lib_graph::constants::{ASSERT, VERBOSE, OPTIMIZE_INVALIDATION};
+use crate::lib_graph::constants::{
+    ASSERT, DEFAULT_ALPHA, INTERRUPT_BATCH_SIZE, OPTIMIZE_INVALIDATION, VERBOSE,
+};
 use crate::lib_graph::counter::Counter;
+use crate::lib_graph::csr::CsrGraph;
+use crate::lib_graph::edge::EdgeDecay;
 use crate::lib_graph::errors::MeritRankError;
 use crate::lib_graph::graph::MyGraph;
 use crate::lib_graph::node::{NodeId, Node, Weight};
+use crate::lib_graph::profile::{ProfileScope, Section};
+use crate::lib_graph::simd;
 use crate::lib_graph::storage::WalkStorage;
-use crate::lib_graph::walk::{PosWalk, RandomWalk, WalkId};
+use crate::lib_graph::walk::{AliasTable, PosWalk, RandomWalk, WalkId, WalkParams};
+use crate::logging::log_debug;
+
+/// Rule for stopping a calculation early once the top of the ranking has stabilized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Convergence {
+    /// Number of walks generated between two checks.
+    pub batch_size: usize,
+    /// Number of top-ranked nodes whose order is compared.
+    pub top_k: usize,
+    /// Number of consecutive checks the order must stay the same for.
+    pub stable_checks: usize,
+}
+
+/// Rule for discounting the scores of nodes the walks of an ego reached through only a few
+/// distinct predecessors, which limits how much a single account can amplify others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Connectivity {
+    /// Number of distinct predecessors below which a score is discounted.
+    pub min_predecessors: usize,
+    /// Share of the score removed from such nodes, from 0 (none) to 1 (all).
+    pub decay: Weight,
+}
 
+/// Generates the walks with the given indices starting from an ego in place of the
+/// instance's own threads, e.g. in other processes. It must return the same walks, in the
+/// same order, as `MeritRank::generate_local_walks`, and check for interrupts itself.
+pub type WalkOffload =
+    fn(&MeritRank, NodeId, Range<usize>) -> Result<Vec<RandomWalk>, MeritRankError>;
 
 pub struct MeritRank {
     graph: MyGraph,
     walks: WalkStorage,
     personal_hits: HashMap<NodeId, Counter>,
+    // For every ego and node, the walks that first entered the node from each predecessor
+    predecessors: HashMap<NodeId, HashMap<NodeId, Counter>>,
     neg_hits: HashMap<NodeId, HashMap<NodeId, Weight>>,
     alpha: Weight,
+    decay: Option<EdgeDecay>,
+    max_walk_length: Option<usize>,
+    penalty_factor: Weight,
+    parallelism: usize,
+    seed: Option<u64>,
+    convergence: Option<Convergence>,
+    connectivity: Option<Connectivity>,
+    // Calculations stop once this moment has passed, keeping the walks generated so far
+    deadline: Option<Instant>,
+    // Called between batches of walks; may abort the calculation by panicking
+    interrupt_check: Option<fn()>,
+    // Generates walks instead of the threads of the instance
+    walk_offload: Option<WalkOffload>,
+    // Alias tables of the positive out-edges, built lazily when a walk first leaves a node.
+    // `None` marks a node without positive out-edges.
+    alias_tables: RwLock<HashMap<NodeId, Option<Arc<AliasTable<NodeId>>>>>,
+    // Frozen copy of the graph that walks step through instead, dropped on any change
+    csr: Option<Arc<CsrGraph>>,
 }
 
 // #[allow(dead_code)]
@@ -43,8 +101,21 @@ impl MeritRank {
             graph,
             walks: WalkStorage::new(),
             personal_hits: HashMap::new(),
+            predecessors: HashMap::new(),
             neg_hits: HashMap::new(),
-            alpha: 0.85,
+            alpha: DEFAULT_ALPHA,
+            decay: None,
+            max_walk_length: None,
+            penalty_factor: 1.0,
+            parallelism: 1,
+            seed: None,
+            convergence: None,
+            connectivity: None,
+            deadline: None,
+            interrupt_check: None,
+            walk_offload: None,
+            alias_tables: RwLock::new(HashMap::new()),
+            csr: None,
         })
     }
 
@@ -56,6 +127,11 @@ impl MeritRank {
         self.walks.get_walks()
     }
 
+    /// Returns the storage of the walks.
+    pub fn get_walk_storage(&self) -> &WalkStorage {
+        &self.walks
+    }
+
     pub fn get_neg_hits(&self) -> &HashMap<NodeId, HashMap<NodeId, Weight>> {
         &self.neg_hits
     }
@@ -69,15 +145,339 @@ impl MeritRank {
     }
 
     pub fn get_graph_mut(&mut self) -> &mut MyGraph {
+        // The caller may change any edge
+        self.csr = None;
+        self.alias_tables
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
         &mut self.graph
     }
 
+    /// Returns the alias table for sampling the next step of a walk leaving `node`,
+    /// or `None` if the node has no positive out-edges.
+    fn positive_alias_table(
+        &self,
+        node: NodeId,
+    ) -> Result<Option<Arc<AliasTable<NodeId>>>, MeritRankError> {
+        if let Some(table) = self
+            .alias_tables
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&node)
+        {
+            return Ok(table.clone());
+        }
+
+        let table = match self.neighbors_weighted(node, true) {
+            Some(neighbors) => {
+                // Sort the neighbors, so that seeded walks do not depend on hash order
+                let mut neighbors: Vec<(NodeId, Weight)> = neighbors.into_iter().collect();
+                neighbors.sort_by_key(|&(peer, _)| peer);
+                let (peers, weights): (Vec<_>, Vec<_>) = neighbors.into_iter().unzip();
+                let table =
+                    AliasTable::new(peers, &weights).ok_or(MeritRankError::RandomChoiceError)?;
+                Some(Arc::new(table))
+            }
+            None => None,
+        };
+
+        self.alias_tables
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(node, table.clone());
+        Ok(table)
+    }
+
+    /// Drops the alias table of `node` after its positive out-edges changed.
+    fn invalidate_alias_table(&mut self, node: NodeId) {
+        self.alias_tables
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&node);
+    }
+
     pub fn get_alpha(&self) -> Weight {
         self.alpha
     }
 
+    /// Sets the walk continuation probability.
+    ///
+    /// Walks generated with a different alpha are no longer valid, so the walks and hits of
+    /// every ego calculated with another parameter set are dropped and must be recalculated.
     pub fn set_alpha(&mut self, alpha: Weight) {
+        if self.alpha == alpha {
+            return;
+        }
         self.alpha = alpha;
+
+        for ego in self.walks.evict_incompatible(self.get_walk_params().fingerprint()) {
+            self.personal_hits.remove(&ego);
+            self.predecessors.remove(&ego);
+            self.neg_hits.remove(&ego);
+        }
+    }
+
+    pub fn get_decay(&self) -> Option<EdgeDecay> {
+        self.decay
+    }
+
+    /// Sets the decay applied to edge weights at walk time, or `None` to use the weights as is.
+    ///
+    /// Walks generated with a different decay are no longer valid, so the walks and hits of
+    /// every ego calculated with another parameter set are dropped and must be recalculated.
+    pub fn set_decay(&mut self, decay: Option<EdgeDecay>) {
+        if self.decay == decay {
+            return;
+        }
+        self.decay = decay;
+        self.alias_tables
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+
+        for ego in self.walks.evict_incompatible(self.get_walk_params().fingerprint()) {
+            self.personal_hits.remove(&ego);
+            self.predecessors.remove(&ego);
+            self.neg_hits.remove(&ego);
+        }
+    }
+
+    pub fn get_max_walk_length(&self) -> Option<usize> {
+        self.max_walk_length
+    }
+
+    /// Sets the maximum number of nodes of a walk, including the ego, or `None` for walks
+    /// that only end by chance.
+    ///
+    /// Capping the length bounds how many hits a walk that enters a densely connected ring
+    /// of nodes can spend inside it. Walks generated with a different maximum length are no
+    /// longer valid, so the walks and hits of every ego calculated with another parameter
+    /// set are dropped and must be recalculated.
+    pub fn set_max_walk_length(&mut self, max_walk_length: Option<usize>) {
+        if self.max_walk_length == max_walk_length {
+            return;
+        }
+        self.max_walk_length = max_walk_length;
+
+        for ego in self.walks.evict_incompatible(self.get_walk_params().fingerprint()) {
+            self.personal_hits.remove(&ego);
+            self.predecessors.remove(&ego);
+            self.neg_hits.remove(&ego);
+        }
+    }
+
+    /// Returns the parameters walks are currently generated with.
+    pub fn get_walk_params(&self) -> WalkParams {
+        WalkParams {
+            alpha: self.alpha,
+            decay: self.decay,
+            max_length: self.max_walk_length,
+        }
+    }
+
+    /// Estimates the number of heap bytes held by the stored walks and hit counters.
+    pub fn walks_memory_usage(&self) -> usize {
+        self.walks.memory_usage()
+            + self
+                .personal_hits
+                .values()
+                .map(|counter| counter.memory_usage())
+                .sum::<usize>()
+            + self
+                .predecessors
+                .values()
+                .flat_map(|targets| targets.values())
+                .map(|counter| counter.memory_usage())
+                .sum::<usize>()
+            + self
+                .neg_hits
+                .values()
+                .map(|hits| {
+                    hits.capacity()
+                        * (std::mem::size_of::<NodeId>() + std::mem::size_of::<Weight>() + 1)
+                })
+                .sum::<usize>()
+    }
+
+    pub fn get_parallelism(&self) -> usize {
+        self.parallelism
+    }
+
+    /// Sets the number of threads walks are generated on. Values below 1 are treated as 1.
+    pub fn set_parallelism(&mut self, parallelism: usize) {
+        self.parallelism = parallelism.max(1);
+    }
+
+    pub fn get_seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Sets the seed walks are generated from, or `None` for non-deterministic walks.
+    ///
+    /// With a seed, the walks of a calculation do not depend on the parallelism.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    pub fn get_convergence(&self) -> Option<Convergence> {
+        self.convergence
+    }
+
+    /// Sets the rule for stopping calculations early, or `None` to always generate the
+    /// requested number of walks.
+    pub fn set_convergence(&mut self, convergence: Option<Convergence>) {
+        self.convergence = convergence;
+    }
+
+    pub fn get_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Sets the moment after which calculations stop and keep the walks generated so far,
+    /// or `None` to always generate the requested number of walks.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Sets a function called between batches of walks, e.g. to let a host abort a long
+    /// calculation, or `None` to not check.
+    pub fn set_interrupt_check(&mut self, interrupt_check: Option<fn()>) {
+        self.interrupt_check = interrupt_check;
+    }
+
+    /// Sets a function that generates walks instead of the threads of the instance, or
+    /// `None` to generate them on `parallelism` threads.
+    pub fn set_walk_offload(&mut self, walk_offload: Option<WalkOffload>) {
+        self.walk_offload = walk_offload;
+    }
+
+    pub fn get_connectivity(&self) -> Option<Connectivity> {
+        self.connectivity
+    }
+
+    /// Sets the rule for discounting the scores of poorly connected nodes, or `None` to
+    /// score nodes by their hits alone.
+    ///
+    /// The predecessors are counted while the walks are accumulated, so the rule applies to
+    /// stored walks right away.
+    pub fn set_connectivity(&mut self, connectivity: Option<Connectivity>) {
+        self.connectivity = connectivity;
+    }
+
+    pub fn get_csr(&self) -> Option<&Arc<CsrGraph>> {
+        self.csr.as_ref()
+    }
+
+    /// Sets a frozen CSR copy of the graph for walks to step through, or `None` to walk
+    /// the graph itself. The copy must have been built from the current graph; it is
+    /// dropped as soon as the graph changes. It is not used while edge decay is set.
+    pub fn set_csr(&mut self, csr: Option<Arc<CsrGraph>>) {
+        self.csr = csr;
+    }
+
+    /// Returns the number of distinct nodes the walks of `ego` entered `target` from.
+    pub fn count_predecessors(&self, ego: NodeId, target: NodeId) -> usize {
+        self.predecessors
+            .get(&ego)
+            .and_then(|targets| targets.get(&target))
+            .map_or(0, |counter| {
+                counter.count_values().filter(|&&count| count > 0.0).count()
+            })
+    }
+
+    /// Counts the predecessors of the nodes that the given nodes of a walk of `ego` visit
+    /// for the first time, adding `delta` walks to each.
+    fn count_first_visits(
+        &mut self,
+        ego: NodeId,
+        walk: &[NodeId],
+        nodes: &HashSet<NodeId>,
+        delta: Weight,
+    ) {
+        let targets = self.predecessors.entry(ego).or_insert_with(HashMap::new);
+        let mut visited = HashSet::new();
+        for step in walk.windows(2) {
+            visited.insert(step[0]);
+            if nodes.contains(&step[1]) && visited.insert(step[1]) {
+                let counter = targets.entry(step[1]).or_insert_with(Counter::new);
+                *counter.get_mut_count(&step[0]) += delta;
+            }
+        }
+    }
+
+    /// Returns the egos that have walks stored.
+    pub fn get_calculated_egos(&self) -> Vec<NodeId> {
+        self.walks.get_egos()
+    }
+
+    /// Drops the stored walks and hits of the egos that were used least recently, until the
+    /// stored walks and hit counters take at most `max_bytes`.
+    ///
+    /// Walks are used when they are generated, and when ranks computed from them are
+    /// served again, see `mark_used`. The ego used most recently is always kept.
+    ///
+    /// # Returns
+    ///
+    /// The number of egos whose walks were dropped, and the number of dropped walks.
+    pub fn evict_walks(&mut self, max_bytes: usize) -> (usize, usize) {
+        let mut usage = self.walks_memory_usage();
+        if usage <= max_bytes {
+            return (0, 0);
+        }
+
+        let mut egos: Vec<(SystemTime, NodeId)> = self
+            .get_calculated_egos()
+            .into_iter()
+            .map(|ego| {
+                let last_used = self.walks.get_last_used(ego).unwrap_or(UNIX_EPOCH);
+                (last_used, ego)
+            })
+            .collect();
+        egos.sort();
+        egos.pop();
+
+        let (mut evicted_egos, mut evicted_walks) = (0, 0);
+        for (_, ego) in egos {
+            if usage <= max_bytes {
+                break;
+            }
+            evicted_walks += self.drop_walks(ego);
+            evicted_egos += 1;
+            self.walks.shrink_to_fit();
+            usage = self.walks_memory_usage();
+        }
+        (evicted_egos, evicted_walks)
+    }
+
+    /// Records that the stored walks of `ego` were used now, e.g. because ranks computed
+    /// from them were served from a cache, so that they are evicted after those of egos
+    /// used less recently.
+    pub fn mark_used(&mut self, ego: NodeId) {
+        self.walks.mark_used(ego);
+    }
+
+    /// Drops the stored walks and hits of `ego`.
+    ///
+    /// # Returns
+    ///
+    /// The number of dropped walks.
+    pub fn drop_walks(&mut self, ego: NodeId) -> usize {
+        let dropped = self.walks.count_walks_from_node(ego);
+        self.walks.drop_walks_from_node(ego);
+        self.personal_hits.remove(&ego);
+        self.predecessors.remove(&ego);
+        self.neg_hits.remove(&ego);
+        dropped
+    }
+
+    pub fn get_penalty_factor(&self) -> Weight {
+        self.penalty_factor
+    }
+
+    /// Sets the multiplier applied to penalties from negative edges when scoring.
+    pub fn set_penalty_factor(&mut self, penalty_factor: Weight) {
+        self.penalty_factor = penalty_factor;
     }
 
     // Get the hit count for a specific node
@@ -91,6 +491,7 @@ impl MeritRank {
     /// Retrieves the weighted neighbors of a node.
     ///
     /// This method returns a hashmap of the neighbors of the specified `node`, along with their weights.
+    /// If a decay is set, the weights of edges with a timestamp are decayed by their age.
     /// Only neighbors with positive weights are returned if `positive` is `true`, and only neighbors with negative
     /// weights are returned if `positive` is `false`.
     ///
@@ -132,7 +533,12 @@ impl MeritRank {
             .neighbors(node)
             .into_iter()
             .filter_map(|nbr| {
-                let weight = self.graph.edge_weight(node, nbr).unwrap_or_else(|| 0.0);
+                let mut weight = self.graph.edge_weight(node, nbr).unwrap_or_else(|| 0.0);
+                if let Some(decay) = self.decay {
+                    if let Some(timestamp) = self.graph.edge_timestamp(node, nbr) {
+                        weight = decay.apply(weight, timestamp);
+                    }
+                }
                 if (positive && weight > 0.0) || (!positive && weight < 0.0) {
                     Some((nbr, weight))
                 } else {
@@ -155,11 +561,13 @@ impl MeritRank {
     /// # Arguments
     ///
     /// * `ego` - The source node to calculate the MeritRank for.
-    /// * `num_walks` - The number of walks that should be used.
+    /// * `num_walks` - The number of walks that should be used. With a convergence rule set,
+    ///   this is the maximum, and fewer walks are used once the top of the ranking is stable.
+    ///   With a deadline set, fewer walks are used once it has passed.
     ///
     /// # Returns
     ///
-    /// * `Result<(), MeritRankError>` - A `Result` indicating success (`Ok`) or an error (`Err`) if the node does not exist.
+    /// * `Result<usize, MeritRankError>` - The number of walks used, or an error (`Err`) if the node does not exist.
     ///
     /// # Errors
     ///
@@ -185,36 +593,210 @@ impl MeritRank {
     ///     _ => {}}
     /// }
     /// ```
-    pub fn calculate(&mut self, ego: NodeId, num_walks: usize) -> Result<(), MeritRankError> {
+    pub fn calculate(&mut self, ego: NodeId, num_walks: usize) -> Result<usize, MeritRankError> {
+        // An offload checks for interrupts while it waits, so it gets all walks at once
+        let batch_size = match self.convergence {
+            Some(convergence) => convergence.batch_size,
+            None if self.deadline.is_some() => INTERRUPT_BATCH_SIZE,
+            None if self.interrupt_check.is_some() && self.walk_offload.is_none() => {
+                INTERRUPT_BATCH_SIZE
+            }
+            None => num_walks,
+        };
+
+        let mut top: Vec<NodeId> = Vec::new();
+        let mut stable_checks = 0;
+        self.calculate_in_batches(ego, num_walks, batch_size, |merit_rank, _| {
+            if let Some(interrupt_check) = merit_rank.interrupt_check {
+                interrupt_check();
+            }
+            if merit_rank.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Ok(true);
+            }
+            let convergence = match merit_rank.convergence {
+                Some(convergence) => convergence,
+                None => return Ok(false),
+            };
+
+            let current: Vec<NodeId> = merit_rank
+                .get_ranks(ego, Some(convergence.top_k))?
+                .into_iter()
+                .map(|(node, _)| node)
+                .collect();
+            if current == top {
+                stable_checks += 1;
+            } else {
+                stable_checks = 0;
+                top = current;
+            }
+            Ok(stable_checks >= convergence.stable_checks)
+        })
+    }
+
+    /// Calculates the MeritRank like `calculate`, generating the walks in batches.
+    ///
+    /// After every batch, `after_batch` is called with the instance and the number of walks
+    /// generated so far; returning `true` stops the calculation. The convergence rule,
+    /// deadline and interrupt check of the instance are not applied. Returns the number of
+    /// walks used.
+    pub fn calculate_in_batches<F>(
+        &mut self,
+        ego: NodeId,
+        num_walks: usize,
+        batch_size: usize,
+        mut after_batch: F,
+    ) -> Result<usize, MeritRankError>
+    where
+        F: FnMut(&MeritRank, usize) -> Result<bool, MeritRankError>,
+    {
         if !self.graph.contains_node(ego) {
             return Err(MeritRankError::NodeDoesNotExist);
         }
 
         self.walks.drop_walks_from_node(ego);
+        self.walks
+            .set_params(ego, self.get_walk_params().fingerprint());
 
         let mut negs = self
             .neighbors_weighted(ego, false)
             .unwrap_or(HashMap::new());
 
         self.personal_hits.insert(ego, Counter::new());
+        self.predecessors.insert(ego, HashMap::new());
+
+        let batch_size = batch_size.max(1);
+        let mut generated = 0;
+
+        while generated < num_walks {
+            let batch = generated..(generated + batch_size).min(num_walks);
+            generated = batch.end;
+
+            let walks = {
+                let _scope = ProfileScope::new(Section::WalkGeneration);
+                self.generate_walks(ego, batch)?
+            };
+
+            for walk in walks {
+                if VERBOSE {
+                    log_debug!("Walk: {:?}", walk.iter().cloned().collect::<Vec<NodeId>>());
+                }
+
+                // Every walk counts each node it visits once
+                let visited: HashSet<NodeId> = walk.iter().copied().collect();
+                {
+                    let _scope = ProfileScope::new(Section::CounterUpdate);
+                    self.personal_hits
+                        .entry(ego)
+                        .and_modify(|counter| counter.increment_counts(visited.iter().copied()));
+                }
+                self.count_first_visits(ego, walk.get_nodes(), &visited, 1.0);
 
-        for _ in 0..num_walks {
-            let walk = self.perform_walk(ego)?;
-            let walk_steps = walk.iter().cloned();
+                self.update_negative_hits(&walk, &mut negs, false);
+                self.add_walk(walk, 0);
+            }
 
-            if VERBOSE {
-                println!("Walk: {:?}", walk.iter().cloned().collect::<Vec<NodeId>>());
+            if after_batch(self, generated)? {
+                break;
             }
+        }
 
-            self.personal_hits
-                .entry(ego)
-                .and_modify(|counter| counter.increment_unique_counts(walk_steps));
+        Ok(generated)
+    }
 
-            self.update_negative_hits(&walk, &mut negs, false);
-            self.add_walk(walk, 0);
+    /// Generates the walks with the given indices starting from `ego`, with the walk
+    /// offload if one is set.
+    fn generate_walks(
+        &self,
+        ego: NodeId,
+        indices: Range<usize>,
+    ) -> Result<Vec<RandomWalk>, MeritRankError> {
+        match self.walk_offload {
+            Some(walk_offload) => walk_offload(self, ego, indices),
+            None => self.generate_local_walks(ego, indices),
         }
+    }
 
-        Ok(())
+    /// Generates the walks with the given indices starting from `ego`, split between
+    /// `parallelism` threads.
+    ///
+    /// The walks are returned in the same order regardless of the number of threads, and walk
+    /// `i` is generated from `seed + i` when a seed is set, so seeded results are deterministic.
+    pub fn generate_local_walks(
+        &self,
+        ego: NodeId,
+        indices: Range<usize>,
+    ) -> Result<Vec<RandomWalk>, MeritRankError> {
+        let num_walks = indices.len();
+        let threads = self.parallelism.min(num_walks);
+        if threads <= 1 {
+            return indices
+                .map(|index| self.perform_indexed_walk(ego, index))
+                .collect();
+        }
+
+        let chunk_size = (num_walks + threads - 1) / threads;
+        thread::scope(|scope| {
+            let handles: Vec<_> = indices
+                .clone()
+                .step_by(chunk_size)
+                .map(|start| {
+                    let end = (start + chunk_size).min(indices.end);
+                    scope.spawn(move || {
+                        (start..end)
+                            .map(|index| self.perform_indexed_walk(ego, index))
+                            .collect::<Result<Vec<RandomWalk>, MeritRankError>>()
+                    })
+                })
+                .collect();
+
+            let mut walks = Vec::with_capacity(num_walks);
+            for handle in handles {
+                let chunk = handle.join().map_err(|_| MeritRankError::WalkWorkerPanicked)??;
+                walks.extend(chunk);
+            }
+            Ok(walks)
+        })
+    }
+
+    /// Returns the positive out-edges walks from `ego` can follow, with the weights they
+    /// are drawn with, grouped by source and sorted by target.
+    ///
+    /// An instance with these edges as its graph, and the same alpha, maximum walk length
+    /// and seed, generates the same walks from `ego`.
+    pub fn walk_edges(&self, ego: NodeId) -> Vec<(NodeId, Vec<(NodeId, Weight)>)> {
+        let mut rows = Vec::new();
+        let mut visited: HashSet<NodeId> = HashSet::from([ego]);
+        let mut queue = vec![ego];
+        while let Some(node) = queue.pop() {
+            let mut edges: Vec<(NodeId, Weight)> = match self.neighbors_weighted(node, true) {
+                Some(neighbors) => neighbors.into_iter().collect(),
+                None => continue,
+            };
+            edges.sort_by_key(|&(target, _)| target);
+            for &(target, _) in edges.iter() {
+                if visited.insert(target) {
+                    queue.push(target);
+                }
+            }
+            rows.push((node, edges));
+        }
+        rows.sort_by_key(|&(source, _)| source);
+        rows
+    }
+
+    /// Performs the `index`-th walk of a calculation, seeded if a seed is set.
+    fn perform_indexed_walk(
+        &self,
+        start_node: NodeId,
+        index: usize,
+    ) -> Result<RandomWalk, MeritRankError> {
+        match self.seed {
+            Some(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed.wrapping_add(index as u64));
+                self.perform_walk_with_rng(start_node, &mut rng)
+            }
+            None => self.perform_walk(start_node),
+        }
     }
 
     /// Updates the negative hits based on a random walk and negative penalties.
@@ -250,7 +832,8 @@ impl MeritRank {
 
     /// Retrieves the MeritRank score for a target node from the perspective of the ego node.
     ///
-    /// The score is calculated based on the accumulated hits and penalized by negative hits.
+    /// The score is calculated based on the accumulated hits and penalized by negative hits,
+    /// scaled by the penalty factor.
     ///
     /// # Arguments
     ///
@@ -298,9 +881,45 @@ impl MeritRank {
 
         let binding = HashMap::new();
         let neg_hits = self.neg_hits.get(&ego).unwrap_or(&binding);
-        let hits_penalized = hits + neg_hits.get(&target).copied().unwrap_or(0.0);
+        let hits_penalized =
+            hits + self.penalty_factor * neg_hits.get(&target).copied().unwrap_or(0.0);
+        let score = hits_penalized / counter.total_count();
+
+        match self.connectivity {
+            Some(connectivity)
+                if target != ego
+                    && self.count_predecessors(ego, target) < connectivity.min_predecessors =>
+            {
+                Ok(score * (1.0 - connectivity.decay))
+            }
+            _ => Ok(score),
+        }
+    }
 
-        Ok(hits_penalized / counter.total_count())
+    /// Estimates the standard error of the score of the target node from the perspective
+    /// of the ego node, caused by the random sampling of the walks.
+    ///
+    /// Every walk from the ego counts the target at most once, so its hits are treated as
+    /// binomial over the stored walks: the error is `sqrt(hits * (1 - hits / walks))`
+    /// divided by the total hits. The penalties of negative edges are not included.
+    pub fn get_node_score_std_error(
+        &self,
+        ego: NodeId,
+        target: NodeId,
+    ) -> Result<Weight, MeritRankError> {
+        let counter = self
+            .personal_hits
+            .get(&ego)
+            .ok_or(MeritRankError::NodeDoesNotCalculated)?;
+
+        let walks = self.walks.count_walks_from_node(ego) as Weight;
+        let total = counter.total_count();
+        if walks == 0.0 || total == 0.0 {
+            return Ok(0.0);
+        }
+
+        let hits = counter.get_count(&target).copied().unwrap_or(0.0).min(walks);
+        Ok((hits * (1.0 - hits / walks)).sqrt() / total)
     }
 
     /// Returns the ranks of peers for the given ego node.
@@ -327,11 +946,41 @@ impl MeritRank {
             .get(&ego)
             .ok_or(MeritRankError::NodeDoesNotExist)?;
 
-        let mut peer_scores: Vec<(NodeId, Weight)> = counter
-            .keys()
+        let _scope = ProfileScope::new(Section::Normalization);
+
+        // Scores are computed as in `get_node_score`, but over contiguous vectors, so that
+        // the total is summed once and the normalization is vectorized
+        let peers = counter.keys();
+        let mut scores: Vec<Weight> = peers
             .iter()
-            .map(|&peer| Ok((peer, self.get_node_score(ego, peer)?)))
-            .collect::<Result<Vec<(NodeId, Weight)>, MeritRankError>>()?;
+            .map(|peer| counter.get_count(peer).copied().unwrap_or(0.0))
+            .collect();
+        if ASSERT {
+            for (&peer, &hits) in peers.iter().zip(&scores) {
+                if hits > 0.0 && !self.graph.is_connecting(ego, peer) {
+                    return Err(MeritRankError::NoPathExists);
+                }
+            }
+        }
+        let total = simd::sum(&scores);
+        let penalties: Vec<Weight> = match self.neg_hits.get(&ego) {
+            Some(neg_hits) => peers
+                .iter()
+                .map(|peer| neg_hits.get(peer).copied().unwrap_or(0.0))
+                .collect(),
+            None => vec![0.0; peers.len()],
+        };
+        simd::penalize_and_normalize(&mut scores, &penalties, self.penalty_factor, total);
+
+        if let Some(connectivity) = self.connectivity {
+            for (&peer, score) in peers.iter().zip(scores.iter_mut()) {
+                if peer != ego && self.count_predecessors(ego, peer) < connectivity.min_predecessors
+                {
+                    *score *= 1.0 - connectivity.decay;
+                }
+            }
+        }
+        let mut peer_scores: Vec<(NodeId, Weight)> = peers.into_iter().zip(scores).collect();
 
         peer_scores.sort_unstable_by(|(_, score1), (_, score2)| {
             score2
@@ -345,6 +994,56 @@ impl MeritRank {
         Ok(peer_scores)
     }
 
+    /// Groups the walks of the ego node that reach the target by the path leading to the target.
+    ///
+    /// Every walk is cut at its first visit of the target, so walks sharing the same prefix
+    /// are counted together. This explains which paths the target's hits came from.
+    ///
+    /// # Arguments
+    ///
+    /// * `ego` - The ego node from whose perspective the score is calculated.
+    /// * `target` - The target node whose score should be explained.
+    ///
+    /// # Returns
+    ///
+    /// A vector of `(path, walk count)` pairs sorted by descending walk count.
+    pub fn get_path_contributions(
+        &self,
+        ego: NodeId,
+        target: NodeId,
+    ) -> Result<Vec<(Vec<NodeId>, usize)>, MeritRankError> {
+        if !self.personal_hits.contains_key(&ego) {
+            return Err(MeritRankError::NodeDoesNotCalculated);
+        }
+
+        let mut paths: HashMap<Vec<NodeId>, usize> = HashMap::new();
+        for walk in self
+            .walks
+            .get_walks_through_node(target, |pos_walk| {
+                pos_walk.get_walk().first_node() == Some(ego)
+            })
+        {
+            if let Some(pos) = walk.iter().position(|&node| node == target) {
+                *paths.entry(walk.get_nodes()[..=pos].to_vec()).or_insert(0) += 1;
+            }
+        }
+
+        let mut paths: Vec<(Vec<NodeId>, usize)> = paths.into_iter().collect();
+        paths.sort_unstable_by(|(path1, count1), (path2, count2)| {
+            count2.cmp(count1).then_with(|| path1.cmp(path2))
+        });
+
+        Ok(paths)
+    }
+
+    /// Returns the total number of hits of the ego node, used to normalize scores.
+    pub fn get_total_hits(&self, ego: NodeId) -> Result<Weight, MeritRankError> {
+        self.personal_hits
+            .get(&ego)
+            .map(|counter| counter.total_count())
+            .ok_or(MeritRankError::NodeDoesNotCalculated)
+    }
+
     /// Performs a random walk starting from the specified node.
     ///
     /// This method generates a random walk starting from the `start_node` by iteratively selecting neighbors
@@ -378,9 +1077,21 @@ impl MeritRank {
     /// }
     /// ```
     pub fn perform_walk(&self, start_node: NodeId) -> Result<RandomWalk, MeritRankError> {
+        self.perform_walk_with_rng(start_node, &mut thread_rng())
+    }
+
+    /// Performs a random walk starting from the specified node, drawing from the given generator.
+    pub fn perform_walk_with_rng(
+        &self,
+        start_node: NodeId,
+        rng: &mut impl Rng,
+    ) -> Result<RandomWalk, MeritRankError> {
         let mut walk = RandomWalk::new();
         walk.push(start_node);
-        let new_segment = self.generate_walk_segment(start_node, false)?;
+        let mut new_segment = self.generate_walk_segment_with_rng(start_node, false, rng)?;
+        if let Some(max_walk_length) = self.max_walk_length {
+            new_segment.truncate(max_walk_length.saturating_sub(walk.len()));
+        }
         walk.extend(&new_segment);
         Ok(walk)
     }
@@ -424,18 +1135,46 @@ impl MeritRank {
         &self,
         start_node: NodeId,
         skip_alpha_on_first_step: bool,
+    ) -> Result<Vec<NodeId>, MeritRankError> {
+        self.generate_walk_segment_with_rng(
+            start_node,
+            skip_alpha_on_first_step,
+            &mut thread_rng(),
+        )
+    }
+
+    /// Generates a walk segment for the specified start node, drawing from the given generator.
+    pub fn generate_walk_segment_with_rng(
+        &self,
+        start_node: NodeId,
+        skip_alpha_on_first_step: bool,
+        rng: &mut impl Rng,
     ) -> Result<Vec<NodeId>, MeritRankError> {
         let mut node = start_node;
         let mut segment = Vec::new();
-        let mut rng = thread_rng();
         let mut skip_alpha_on_first_step = skip_alpha_on_first_step;
 
-        while let Some(neighbors) = self.neighbors_weighted(node, true) {
+        // The frozen copy holds raw weights, so it cannot serve decayed edges
+        if let Some(csr) = self.csr.as_ref().filter(|_| self.decay.is_none()) {
+            while csr.has_out_edges(node) {
+                if skip_alpha_on_first_step || rng.gen::<f64>() <= self.alpha {
+                    skip_alpha_on_first_step = false;
+                    let next_step = csr
+                        .sample(node, rng)
+                        .ok_or(MeritRankError::RandomChoiceError)?;
+                    segment.push(next_step);
+                    node = next_step;
+                } else {
+                    break;
+                }
+            }
+            return Ok(segment);
+        }
+
+        while let Some(table) = self.positive_alias_table(node)? {
             if skip_alpha_on_first_step || rng.gen::<f64>() <= self.alpha {
                 skip_alpha_on_first_step = false;
-                let (peers, weights): (Vec<_>, Vec<_>) = neighbors.iter().unzip();
-                let next_step = Self::random_choice(&peers, &weights, &mut rng)
-                    .ok_or(MeritRankError::RandomChoiceError)?;
+                let next_step = table.sample(rng);
                 segment.push(next_step);
                 node = next_step;
             } else {
@@ -546,17 +1285,21 @@ impl MeritRank {
         // Get the starting node (ego) of the invalidated walk
         let ego = walk.first_node().unwrap();
 
-        // Get or insert the hit counter for the starting node
-        let counter: &mut Counter = self.personal_hits.entry(ego).or_insert_with(Counter::new);
-
         // Subtract the nodes in the invalidated segment from the hit counter
-        let to_remove: HashSet<&NodeId> = invalidated_segment
-            .into_iter()
+        let to_remove: HashSet<NodeId> = invalidated_segment
+            .iter()
             .filter(|node| !walk.contains(node))
+            .copied()
             .collect();
 
+        let nodes: Vec<NodeId> = walk.iter().chain(invalidated_segment).copied().collect();
+        self.count_first_visits(ego, &nodes, &to_remove, -1.0);
+
+        // Get or insert the hit counter for the starting node
+        let counter: &mut Counter = self.personal_hits.entry(ego).or_insert_with(Counter::new);
+
         if to_remove.len() > 0 {
-            for node_to_remove in to_remove {
+            for node_to_remove in &to_remove {
                 *counter.get_mut_count(node_to_remove) -= 1.0;
             }
 
@@ -635,6 +1378,9 @@ impl MeritRank {
         if let Some(force_first_step) = force_first_step {
             new_segment.insert(0, force_first_step);
         }
+        if let Some(max_walk_length) = self.max_walk_length {
+            new_segment.truncate(max_walk_length.saturating_sub(new_segment_start));
+        }
 
         // Update the personal hits counter for the new segment
         let counter: &mut Counter = self.personal_hits.entry(ego).or_insert_with(Counter::new);
@@ -647,6 +1393,7 @@ impl MeritRank {
 
         // Extend the walk with the new segment
         walk.extend(&new_segment);
+        self.count_first_visits(ego, walk.get_nodes(), &diff, 1.0);
 
         // Add the updated walk to the collection of walks
         self.add_walk(walk.clone(), new_segment_start);
@@ -655,6 +1402,7 @@ impl MeritRank {
     }
 
     pub fn add_node(&mut self, node: NodeId) {
+        self.csr = None;
         self.graph.add_node(Node::new(node));
     }
 
@@ -676,6 +1424,7 @@ impl MeritRank {
         if src == dest {
             panic!("Self reference not allowed");
         }
+        self.csr = None;
 
         let old_weight = self.graph.edge_weight(src, dest).unwrap_or(0.0);
 
@@ -731,7 +1480,7 @@ impl MeritRank {
             for (_, hits) in &self.personal_hits {
                 for (peer, count) in hits {
                     let walks = self.walks.get_walks_through_node(*peer, |_| true);
-                    println!("Peer: {:?}, Count: {:?}, Walks: {:?}", *peer, *count as usize, walks.len());
+                    log_debug!("Peer: {:?}, Count: {:?}, Walks: {:?}", *peer, *count as usize, walks.len());
                 }
             }
         }
@@ -760,6 +1509,8 @@ impl MeritRank {
         } else {
             self.graph.add_edge(src, dest, weight);
         }
+        // Only positive edges are walked, so only this change affects the alias tables
+        self.invalidate_alias_table(src);
 
         for (walk_mut, invalidated_segment) in &mut invalidated_walks {
             let first_node = walk_mut.first_node().unwrap();
@@ -798,7 +1549,7 @@ impl MeritRank {
                 for (peer, count) in hits {
                     let walks = self.walks.get_walks_through_node(*peer, |_| true);
                     if VERBOSE {
-                        println!("Peer: {:?}, Count: {:?}, Walks: {:?}", *peer, *count as usize, walks.len());
+                        log_debug!("Peer: {:?}, Count: {:?}, Walks: {:?}", *peer, *count as usize, walks.len());
                     }
                     if walks.len() != *count as usize {
                         assert!(false);
@@ -0,0 +1,49 @@
+use crate::lib_graph::node::Weight;
+
+/// The number of scores processed together by the kernels.
+///
+/// Every kernel keeps one independent accumulator per lane, so the compiler can map the
+/// lanes to vector registers on stable Rust without changing results between targets.
+pub const LANES: usize = 8;
+
+/// Returns the sum of the values, accumulated lane-wise.
+///
+/// The order of additions differs from a sequential sum, so the result may differ from
+/// it in the last bits.
+pub fn sum(values: &[Weight]) -> Weight {
+    let mut lanes = [0.0; LANES];
+    let chunks = values.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+    for chunk in chunks {
+        for (lane, &value) in lanes.iter_mut().zip(chunk) {
+            *lane += value;
+        }
+    }
+    lanes.iter().sum::<Weight>() + remainder.iter().sum::<Weight>()
+}
+
+/// Turns hit counts into scores in place: every count is penalized by `penalty_factor`
+/// times the matching penalty and divided by `total`.
+///
+/// # Panics
+///
+/// Panics if the slices have different lengths.
+pub fn penalize_and_normalize(
+    hits: &mut [Weight],
+    penalties: &[Weight],
+    penalty_factor: Weight,
+    total: Weight,
+) {
+    assert_eq!(hits.len(), penalties.len());
+    let mut hit_chunks = hits.chunks_exact_mut(LANES);
+    let mut penalty_chunks = penalties.chunks_exact(LANES);
+    for (hits, penalties) in (&mut hit_chunks).zip(&mut penalty_chunks) {
+        for (hit, &penalty) in hits.iter_mut().zip(penalties) {
+            *hit = (*hit + penalty_factor * penalty) / total;
+        }
+    }
+    let remainder = penalty_chunks.remainder();
+    for (hit, &penalty) in hit_chunks.into_remainder().iter_mut().zip(remainder) {
+        *hit = (*hit + penalty_factor * penalty) / total;
+    }
+}
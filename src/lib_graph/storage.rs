@@ -1,5 +1,6 @@
 use indexmap::IndexMap;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
 
 use rand::prelude::*;
 
@@ -11,6 +12,9 @@ use crate::lib_graph::walk::{PosWalk, RandomWalk, WalkId};
 /// Represents a storage container for walks in the MeritRank graph.
 pub struct WalkStorage {
     walks: IndexMap<NodeId, IndexMap<WalkId, PosWalk>>,
+    params: HashMap<NodeId, u64>,
+    generated_at: HashMap<NodeId, SystemTime>,
+    last_used: HashMap<NodeId, SystemTime>,
 }
 
 impl WalkStorage {
@@ -24,6 +28,9 @@ impl WalkStorage {
     pub fn new() -> Self {
         WalkStorage {
             walks: IndexMap::new(),
+            params: HashMap::new(),
+            generated_at: HashMap::new(),
+            last_used: HashMap::new(),
         }
     }
 
@@ -31,6 +38,112 @@ impl WalkStorage {
         &self.walks
     }
 
+    /// Records the fingerprint of the parameters the walks starting from `ego` were generated with,
+    /// along with the time they were generated, which is also the time they were last used.
+    pub fn set_params(&mut self, ego: NodeId, fingerprint: u64) {
+        let now = SystemTime::now();
+        self.params.insert(ego, fingerprint);
+        self.generated_at.insert(ego, now);
+        self.last_used.insert(ego, now);
+    }
+
+    /// Records that the walks starting from `ego` were used now, if there are any.
+    pub fn mark_used(&mut self, ego: NodeId) {
+        if let Some(last_used) = self.last_used.get_mut(&ego) {
+            *last_used = SystemTime::now();
+        }
+    }
+
+    /// Returns the fingerprint of the parameters the walks starting from `ego` were generated with.
+    pub fn get_params(&self, ego: NodeId) -> Option<u64> {
+        self.params.get(&ego).copied()
+    }
+
+    /// Returns the time the walks starting from `ego` were generated.
+    pub fn get_generated_at(&self, ego: NodeId) -> Option<SystemTime> {
+        self.generated_at.get(&ego).copied()
+    }
+
+    /// Returns the time the walks starting from `ego` were last used.
+    pub fn get_last_used(&self, ego: NodeId) -> Option<SystemTime> {
+        self.last_used.get(&ego).copied()
+    }
+
+    /// Returns the average number of nodes of the stored walks starting from `ego`, or `None`
+    /// if there are no such walks.
+    pub fn average_walk_length(&self, ego: NodeId) -> Option<f64> {
+        let lengths: Vec<usize> = self
+            .walks
+            .get(&ego)?
+            .values()
+            .filter(|pos_walk| pos_walk.get_pos() == 0)
+            .map(|pos_walk| pos_walk.get_walk().len())
+            .collect();
+        if lengths.is_empty() {
+            return None;
+        }
+        Some(lengths.iter().sum::<usize>() as f64 / lengths.len() as f64)
+    }
+
+    /// Estimates the number of heap bytes held by the stored walks.
+    ///
+    /// Every node a walk passes through holds its own copy of the walk.
+    pub fn memory_usage(&self) -> usize {
+        let entry_size = std::mem::size_of::<WalkId>() + std::mem::size_of::<PosWalk>();
+        self.walks
+            .values()
+            .map(|pos_walks| {
+                pos_walks.capacity() * entry_size
+                    + pos_walks
+                        .values()
+                        .map(|pos_walk| pos_walk.get_walk().len() * std::mem::size_of::<NodeId>())
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// Returns the egos that have walks stored.
+    pub fn get_egos(&self) -> Vec<NodeId> {
+        self.params.keys().copied().collect()
+    }
+
+    /// Returns the number of stored walks starting from `ego`.
+    pub fn count_walks_from_node(&self, ego: NodeId) -> usize {
+        self.walks
+            .get(&ego)
+            .map(|pos_walks| {
+                pos_walks
+                    .values()
+                    .filter(|pos_walk| pos_walk.get_pos() == 0)
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Drops the walks of every ego whose walks were generated with a different parameter set.
+    ///
+    /// # Arguments
+    ///
+    /// * `fingerprint` - The fingerprint of the parameter set that remains valid.
+    ///
+    /// # Returns
+    ///
+    /// The egos whose walks were evicted.
+    pub fn evict_incompatible(&mut self, fingerprint: u64) -> Vec<NodeId> {
+        let evicted: Vec<NodeId> = self
+            .params
+            .iter()
+            .filter(|(_, &params)| params != fingerprint)
+            .map(|(&ego, _)| ego)
+            .collect();
+
+        for &ego in &evicted {
+            self.drop_walks_from_node(ego);
+        }
+
+        evicted
+    }
+
     /// Adds a walk to the storage.
     ///
     /// This method adds a `RandomWalk` object to the storage, starting from the specified `start_pos`.
@@ -288,6 +401,10 @@ impl WalkStorage {
     /// storage.drop_walks_from_node(node);
     /// ```
     pub fn drop_walks_from_node(&mut self, node: NodeId) {
+        self.params.remove(&node);
+        self.generated_at.remove(&node);
+        self.last_used.remove(&node);
+
         for (_, pos_walks) in &mut self.walks {
             pos_walks.retain(|_, pos_walk| pos_walk.get_walk().first_node().unwrap() != node);
         }
@@ -299,6 +416,14 @@ impl WalkStorage {
         self.walks.retain(|_, pos_walks| !pos_walks.is_empty());
     }
 
+    /// Releases the memory left unused by dropped walks.
+    pub fn shrink_to_fit(&mut self) {
+        for (_, pos_walks) in &mut self.walks {
+            pos_walks.shrink_to_fit();
+        }
+        self.walks.shrink_to_fit();
+    }
+
     /// Returns the walks passing through the specified node based on a given filter.
     ///
     /// This method retrieves the walks passing through the given `node` that satisfy the provided `filter`.
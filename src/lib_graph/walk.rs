@@ -21,10 +21,119 @@ impl WalkIdGenerator {
     }
 }
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
+use crate::lib_graph::edge::EdgeDecay;
 use crate::lib_graph::node::{NodeId, Weight};
 
+/// The parameters that determine how random walks are generated.
+///
+/// Walks generated with one parameter set must not be reused for another, so stored walks
+/// are tagged with the fingerprint of the parameters they were generated with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WalkParams {
+    pub alpha: Weight,
+    pub decay: Option<EdgeDecay>,
+    pub max_length: Option<usize>,
+}
+
+impl WalkParams {
+    /// Returns a hash identifying this parameter set.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.alpha.to_bits().hash(&mut hasher);
+        if let Some(decay) = self.decay {
+            decay.get_half_life().to_bits().hash(&mut hasher);
+            decay.get_reference_time().hash(&mut hasher);
+        }
+        if let Some(max_length) = self.max_length {
+            max_length.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// Alias table for sampling from a discrete weighted distribution in constant time
+/// (Walker's alias method, built with Vose's algorithm).
+#[derive(Debug, Clone)]
+pub struct AliasTable<T> {
+    values: Vec<T>,
+    probabilities: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl<T: Copy> AliasTable<T> {
+    /// Builds an alias table for the given values and their weights.
+    ///
+    /// Returns `None` if there are no values, the lengths differ, a weight is negative,
+    /// or the weights do not sum up to a positive finite number.
+    pub fn new(values: Vec<T>, weights: &[f64]) -> Option<Self> {
+        let n = values.len();
+        let total: f64 = weights.iter().sum();
+        if n == 0
+            || weights.len() != n
+            || !total.is_finite()
+            || total <= 0.0
+            || weights.iter().any(|&weight| weight < 0.0)
+        {
+            return None;
+        }
+
+        // Scale the weights so that the average bucket holds exactly 1.0
+        let mut scaled: Vec<f64> = weights.iter().map(|&w| w * n as f64 / total).collect();
+        let mut probabilities = vec![1.0; n];
+        let mut aliases: Vec<usize> = (0..n).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| scaled[i] < 1.0);
+
+        while let (Some(s), Some(l)) = (small.pop(), large.pop()) {
+            probabilities[s] = scaled[s];
+            aliases[s] = l;
+            scaled[l] += scaled[s] - 1.0;
+            if scaled[l] < 1.0 {
+                small.push(l);
+            } else {
+                large.push(l);
+            }
+        }
+        // Whatever remains is full up to rounding errors
+
+        Some(AliasTable {
+            values,
+            probabilities,
+            aliases,
+        })
+    }
+
+    /// Draws a value.
+    pub fn sample(&self, rng: &mut impl Rng) -> T {
+        let bucket = rng.gen_range(0..self.values.len());
+        if rng.gen::<f64>() < self.probabilities[bucket] {
+            self.values[bucket]
+        } else {
+            self.values[self.aliases[bucket]]
+        }
+    }
+
+    /// Returns the number of values in the table.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Checks if the table has no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Splits the table into its values, their probabilities and their aliases.
+    pub fn into_parts(self) -> (Vec<T>, Vec<f64>, Vec<usize>) {
+        (self.values, self.probabilities, self.aliases)
+    }
+}
+
 /// Represents a random walk through a graph.
 #[derive(Clone)]
 pub struct RandomWalk {
@@ -214,7 +323,7 @@ impl RandomWalk {
     ///     println!("Node ID: {:?}", node_id);
     /// }
     /// ```
-    pub fn iter(&self) -> impl Iterator<Item=&NodeId> {
+    pub fn iter(&self) -> impl Iterator<Item = &NodeId> {
         self.nodes.iter()
     }
 
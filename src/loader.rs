@@ -0,0 +1,799 @@
+// Standard library imports
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::sync::atomic::Ordering;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::{
+    add_edges, current_timestamp, lock_graph, publish_changes, timestamp_from_pg, DATABASE, GRAPH,
+    HYDRATED,
+};
+use crate::guc;
+use crate::lib_graph::Timestamp;
+use crate::logging::log_info;
+use crate::persist;
+
+/// Number of rows fetched from the cursor per batch.
+pub const LOAD_BATCH_SIZE: i64 = 10_000;
+
+/// Quotes an SQL identifier.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quotes an SQL identifier, keeping an optional schema qualifier (`schema.table`).
+pub fn quote_qualified_identifier(name: &str) -> String {
+    name.split('.')
+        .map(quote_identifier)
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/// Quotes an SQL string literal.
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[pg_extern]
+/// Loads edges from an arbitrary relation into the graph.
+///
+/// The relation is read in batches with `load_edges`; rows with `NULL` values or a
+/// self-reference are skipped. The edges are applied as by `meritrank_add`, so they are
+/// written to the `graph` table and recorded in the outbox and the audit log as well, and
+/// survive reloads.
+///
+/// # Arguments
+///
+/// * `table` - The name of the relation, optionally schema-qualified.
+/// * `source_col` - The column holding the source node's name.
+/// * `dest_col` - The column holding the destination node's name.
+/// * `weight_col` - The column holding the weight of the edge.
+/// * `timestamp_col` - The column holding when the edge was last updated. When `NULL`,
+///   or for rows where the column is `NULL`, the edges are stamped with the current time.
+///
+/// # Returns
+///
+/// The number of edges loaded into the graph.
+pub fn meritrank_load_from(
+    table: &str,
+    source_col: &str,
+    dest_col: &str,
+    weight_col: &str,
+    timestamp_col: default!(Option<&str>, "NULL"),
+) -> Result<i64, ErrorReport> {
+    let query = format!(
+        "SELECT {}::text, {}::text, {}::float8, {}::timestamptz FROM {}",
+        quote_identifier(source_col),
+        quote_identifier(dest_col),
+        quote_identifier(weight_col),
+        timestamp_col.map_or_else(|| "NULL".to_string(), quote_identifier),
+        quote_qualified_identifier(table),
+    );
+
+    Ok(load_edges(&query, table, true)?)
+}
+
+/// Loads the edges returned by `query` into the in-memory graph.
+///
+/// The query must return `(source text, destination text, weight float8,
+/// updated_at timestamptz)` rows. They are read through an SPI cursor in batches of
+/// `LOAD_BATCH_SIZE` rows; rows with `NULL` values, a weight that is not finite or a
+/// self-reference are skipped, as are the edges of nodes that were evicted to stay within
+/// `meritrank.max_nodes` or `meritrank.max_edges`. Every batch is applied as a whole: if an edge fails, e.g. on
+/// `meritrank.max_edges`, none of its batch is left in the graph.
+/// `relation` names where the edges come from in progress messages.
+///
+/// With `publish`, the changes of every batch are published like those of `meritrank_add`;
+/// without it, only the in-memory graph is changed, e.g. when loading the `graph` table.
+pub fn load_edges(
+    query: &str,
+    relation: &str,
+    publish: bool,
+) -> Result<i64, GraphManipulationError> {
+    Spi::connect(|client| -> Result<i64, GraphManipulationError> {
+        let mut cursor = client.open_cursor(query, None);
+        let mut loaded: i64 = 0;
+        let mut skipped: i64 = 0;
+
+        loop {
+            let batch = cursor
+                .fetch(LOAD_BATCH_SIZE)
+                .map_err(|e| GraphManipulationError::FetchRecordsFailure(e.to_string()))?;
+            if batch.is_empty() {
+                break;
+            }
+
+            // Lock the graph once per batch rather than once per row
+            let mut graph = lock_graph()?;
+            let mut rows = Vec::with_capacity(batch.len());
+
+            for row in batch {
+                let source = row.get::<String>(1)?;
+                let destination = row.get::<String>(2)?;
+                let weight = row.get::<f64>(3)?;
+                let timestamp = row
+                    .get::<TimestampWithTimeZone>(4)?
+                    .map_or_else(current_timestamp, timestamp_from_pg);
+
+                match (source, destination, weight) {
+                    (Some(source), Some(destination), Some(weight))
+                        if source != destination
+                            && weight.is_finite()
+                            && !graph.is_evicted_edge(&source, &destination) =>
+                    {
+                        rows.push((source, destination, weight, timestamp));
+                    }
+                    _ => skipped += 1,
+                }
+            }
+
+            // A batch is applied as a whole, so that a failing edge leaves none of the batch
+            // in the graph
+            let edges: Vec<(&str, &str, f64, Timestamp)> = rows
+                .iter()
+                .map(|(source, destination, weight, timestamp)| {
+                    (source.as_str(), destination.as_str(), *weight, *timestamp)
+                })
+                .collect();
+            let changes = graph.set_edges_at(&edges)?;
+            loaded += edges.len() as i64;
+
+            if publish {
+                let generation = graph.get_generation();
+                drop(graph);
+                publish_changes(&changes, generation)?;
+            }
+
+            log_info!(
+                "meritrank: {} rows loaded, {} skipped so far from {}",
+                loaded,
+                skipped,
+                relation
+            );
+        }
+
+        Ok(loaded)
+    })
+}
+
+extension_sql!(
+    r#"
+-- Creates the graph table unless one exists, e.g. one made by hand or by an earlier version
+-- of the extension. An existing table stays out of the extension, so that dropping the
+-- extension keeps it; meritrank_migrate_graph_table upgrades it. Returns whether the table
+-- was created.
+--
+-- meritrank.graph_partitions may be set before CREATE EXTENSION, when the library that
+-- defines it is not necessarily loaded yet, so it is read as a placeholder
+CREATE FUNCTION meritrank_create_graph_table() RETURNS bool
+LANGUAGE plpgsql AS $function$
+DECLARE
+    partitions int := coalesce(
+        nullif(current_setting('meritrank.graph_partitions', true), ''), '0'
+    )::int;
+BEGIN
+    IF to_regclass('graph') IS NOT NULL THEN
+        RETURN false;
+    END IF;
+    IF partitions > 1 THEN
+        CREATE TABLE graph (
+            source text NOT NULL,
+            destination text NOT NULL,
+            weight float8 NOT NULL,
+            updated_at timestamptz NOT NULL DEFAULT now(),
+            PRIMARY KEY (source, destination)
+        ) PARTITION BY HASH (source);
+        FOR remainder IN 0 .. partitions - 1 LOOP
+            EXECUTE format(
+                'CREATE TABLE %I PARTITION OF graph FOR VALUES WITH (MODULUS %s, REMAINDER %s)',
+                'graph_p' || remainder, partitions, remainder
+            );
+            PERFORM pg_catalog.pg_extension_config_dump(
+                format('%I', 'graph_p' || remainder)::regclass, ''
+            );
+        END LOOP;
+    ELSE
+        CREATE TABLE graph (
+            source text NOT NULL,
+            destination text NOT NULL,
+            weight float8 NOT NULL,
+            updated_at timestamptz NOT NULL DEFAULT now(),
+            PRIMARY KEY (source, destination)
+        );
+    END IF;
+    -- The primary key serves lookups by source and by pair; this one serves lookups of the
+    -- voters for a node
+    CREATE INDEX graph_destination_source_idx ON graph (destination, source);
+    PERFORM pg_catalog.pg_extension_config_dump('graph', '');
+    RETURN true;
+END
+$function$;
+REVOKE EXECUTE ON FUNCTION meritrank_create_graph_table() FROM PUBLIC;
+SELECT meritrank_create_graph_table();
+"#,
+    name = "create_graph_table"
+);
+
+#[pg_extern]
+/// Upgrades a `graph` table created by an earlier version of the extension to the current
+/// schema. Weights stored as `numeric`, e.g. `NUMERIC(10,5)`, are rounded and converted on
+/// every load, so the column is changed to `float8`, which matches the in-memory weights.
+/// Node names limited in length, e.g. `VARCHAR(32)`, are changed to `text`.
+/// Tables without the primary key on `(source, destination)` get it, after duplicate rows
+/// of an edge are removed, keeping the most recently updated one; writes to the table
+/// rely on the key. Missing indexes are created.
+///
+/// Only superusers may upgrade the table. The steps already applied are skipped, so the
+/// function can be called repeatedly.
+///
+/// # Returns
+///
+/// A description of every step applied.
+pub fn meritrank_migrate_graph_table() -> Result<SetOfIterator<'static, String>, ErrorReport> {
+    require_superuser("meritrank_migrate_graph_table")?;
+    let mut steps = Vec::new();
+
+    let weight_type = Spi::get_one::<String>(
+        "SELECT format_type(atttypid, atttypmod) FROM pg_attribute \
+         WHERE attrelid = 'graph'::regclass AND attname = 'weight' AND NOT attisdropped",
+    )
+    .map_err(GraphManipulationError::from)?
+    .unwrap_or_default();
+    if weight_type != "double precision" {
+        Spi::run("ALTER TABLE graph ALTER COLUMN weight TYPE float8 USING weight::float8")
+            .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+        steps.push(format!("weight: {} -> double precision", weight_type));
+    }
+
+    // Earlier schemas limited names, e.g. to VARCHAR(32), which the in-memory graph does not
+    for column in ["source", "destination"] {
+        let name_type = Spi::get_one_with_args::<String>(
+            "SELECT format_type(atttypid, atttypmod) FROM pg_attribute \
+             WHERE attrelid = 'graph'::regclass AND attname = $1 AND NOT attisdropped",
+            vec![(PgBuiltInOids::TEXTOID.oid(), column.into_datum())],
+        )
+        .map_err(GraphManipulationError::from)?
+        .unwrap_or_default();
+        if name_type != "text" {
+            Spi::run(&format!(
+                "ALTER TABLE graph ALTER COLUMN {} TYPE text",
+                quote_identifier(column)
+            ))
+            .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+            steps.push(format!("{}: {} -> text", column, name_type));
+        }
+    }
+
+    let has_primary_key = Spi::get_one::<bool>(
+        "SELECT EXISTS ( \
+             SELECT 1 FROM pg_index WHERE indrelid = 'graph'::regclass AND indisprimary \
+         )",
+    )
+    .map_err(GraphManipulationError::from)?
+    .unwrap_or(false);
+    if !has_primary_key {
+        let duplicates = Spi::get_one::<i64>(
+            "WITH removed AS ( \
+                 DELETE FROM graph a USING graph b \
+                 WHERE a.source = b.source AND a.destination = b.destination \
+                   AND (a.updated_at < b.updated_at \
+                        OR (a.updated_at = b.updated_at AND a.ctid < b.ctid)) \
+                 RETURNING 1 \
+             ) SELECT count(*) FROM removed",
+        )
+        .map_err(GraphManipulationError::from)?
+        .unwrap_or(0);
+        Spi::run("ALTER TABLE graph ADD PRIMARY KEY (source, destination)")
+            .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+        steps.push(format!(
+            "primary key (source, destination), {} duplicate rows removed",
+            duplicates
+        ));
+    }
+
+    let has_destination_index =
+        Spi::get_one::<bool>("SELECT to_regclass('graph_destination_source_idx') IS NOT NULL")
+            .map_err(GraphManipulationError::from)?
+            .unwrap_or(false);
+    if !has_destination_index {
+        Spi::run("CREATE INDEX graph_destination_source_idx ON graph (destination, source)")
+            .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+        steps.push("index graph_destination_source_idx".to_string());
+    }
+
+    Ok(SetOfIterator::new(steps.into_iter()))
+}
+
+#[pg_extern]
+/// Rebuilds the indexes of the `graph` table, e.g. after a bulk import bloated them.
+/// A partitioned table is reindexed one partition at a time.
+///
+/// Only superusers may rebuild the indexes.
+///
+/// # Returns
+///
+/// The number of indexes rebuilt.
+pub fn meritrank_reindex() -> Result<i64, ErrorReport> {
+    require_superuser("meritrank_reindex")?;
+    // Partitioned tables cannot be reindexed as a whole before PostgreSQL 14
+    let mut rebuilt = 0;
+    for partition in graph_table_partitions()? {
+        Spi::run(&format!("REINDEX TABLE {}", partition))
+            .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+        rebuilt += Spi::get_one_with_args::<i64>(
+            "SELECT count(*) FROM pg_index WHERE indrelid = $1::regclass",
+            vec![(PgBuiltInOids::TEXTOID.oid(), partition.into_datum())],
+        )
+        .map_err(GraphManipulationError::from)?
+        .unwrap_or(0);
+    }
+    Ok(rebuilt)
+}
+
+/// Returns an error unless the current user is a superuser.
+pub fn require_superuser(operation: &str) -> Result<(), GraphManipulationError> {
+    if unsafe { pg_sys::superuser() } {
+        Ok(())
+    } else {
+        Err(GraphManipulationError::PermissionDenied(format!(
+            "only superusers may call {}",
+            operation
+        )))
+    }
+}
+
+/// Returns the tables holding the rows of the `graph` table: its leaf partitions when it
+/// is partitioned, and the table itself otherwise.
+///
+/// Every partition can be scanned on its own, so large graphs are read in chunks of one
+/// partition rather than by a single scan of the whole table.
+pub fn graph_table_partitions() -> Result<Vec<String>, GraphManipulationError> {
+    Spi::connect(|client| -> Result<_, GraphManipulationError> {
+        let rows = client
+            .select(
+                "WITH RECURSIVE tree (relid) AS ( \
+                     SELECT 'graph'::regclass::oid \
+                     UNION ALL \
+                     SELECT inhrelid FROM pg_inherits JOIN tree ON inhparent = tree.relid \
+                 ) \
+                 SELECT relid::regclass::text FROM tree JOIN pg_class ON pg_class.oid = relid \
+                 WHERE relkind = 'r' ORDER BY relname",
+                None,
+                None,
+            )
+            .map_err(|e| GraphManipulationError::GraphReadFailure(e.to_string()))?;
+
+        let mut partitions = Vec::new();
+        for row in rows {
+            if let Some(partition) = row.get::<String>(1)? {
+                partitions.push(partition);
+            }
+        }
+        Ok(partitions)
+    })
+}
+
+/// Inserts a batch of edges into the `graph` table with a single statement.
+///
+/// Edges that are already in the table get the new weight. Of edges repeated within the
+/// batch, the last one wins, as in the in-memory graph.
+pub fn persist_edges(
+    sources: Vec<String>,
+    destinations: Vec<String>,
+    weights: Vec<f64>,
+) -> Result<(), GraphManipulationError> {
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    Spi::run_with_args(
+        "INSERT INTO graph (source, destination, weight) \
+         SELECT DISTINCT ON (source, destination) source, destination, weight \
+         FROM unnest($1::text[], $2::text[], $3::float8[]) \
+             WITH ORDINALITY AS edges (source, destination, weight, position) \
+         ORDER BY source, destination, position DESC \
+         ON CONFLICT (source, destination) \
+         DO UPDATE SET weight = EXCLUDED.weight, updated_at = now()",
+        Some(vec![
+            (PgBuiltInOids::TEXTARRAYOID.oid(), sources.into_datum()),
+            (PgBuiltInOids::TEXTARRAYOID.oid(), destinations.into_datum()),
+            (PgBuiltInOids::FLOAT8ARRAYOID.oid(), weights.into_datum()),
+        ]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))
+}
+
+/// Splits CSV text into records of fields, as described in RFC 4180.
+///
+/// Fields may be enclosed in double quotes, and then contain the delimiter, line breaks
+/// and double quotes written as `""`. Unquoted fields are trimmed, and so is the space
+/// around quoted ones. Lines may end with `\r\n`.
+struct CsvRecords<R> {
+    lines: Lines<R>,
+    delimiter: char,
+}
+
+impl<R: BufRead> CsvRecords<R> {
+    fn new(reader: R, delimiter: char) -> Self {
+        CsvRecords {
+            lines: reader.lines(),
+            delimiter,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CsvRecords<R> {
+    type Item = std::io::Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = match self.lines.next()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        // Whether the current field is quoted, and whether its closing quote is still ahead
+        let mut quoted = false;
+        let mut in_quotes = false;
+        loop {
+            let mut chars = line.trim_end_matches('\r').chars().peekable();
+            while let Some(c) = chars.next() {
+                if in_quotes {
+                    match c {
+                        '"' if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        '"' => in_quotes = false,
+                        _ => field.push(c),
+                    }
+                } else if c == self.delimiter {
+                    fields.push(finish_csv_field(std::mem::take(&mut field), quoted));
+                    quoted = false;
+                } else if c == '"' && !quoted && field.trim().is_empty() {
+                    field.clear();
+                    quoted = true;
+                    in_quotes = true;
+                } else if !quoted || !c.is_whitespace() {
+                    field.push(c);
+                }
+            }
+            if !in_quotes {
+                break;
+            }
+
+            // The quoted field continues on the next line
+            line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            };
+            field.push('\n');
+        }
+        fields.push(finish_csv_field(field, quoted));
+        Some(Ok(fields))
+    }
+}
+
+fn finish_csv_field(field: String, quoted: bool) -> String {
+    if quoted {
+        field
+    } else {
+        field.trim().to_string()
+    }
+}
+
+/// Parses the fields of a CSV record into a `(source, destination, weight)` edge.
+///
+/// Returns `None` if the record does not contain exactly three fields, a name is empty,
+/// the weight is not a finite number, or the edge is a self-reference.
+fn parse_csv_edge(fields: &[String]) -> Option<(String, String, f64)> {
+    match fields {
+        [source, destination, weight] => {
+            let weight = weight.trim().parse::<f64>().ok().filter(|w| w.is_finite())?;
+            if source.is_empty() || destination.is_empty() || source == destination {
+                return None;
+            }
+            Some((source.to_string(), destination.to_string(), weight))
+        }
+        _ => None,
+    }
+}
+
+/// Start of the first line of preload files written by `meritrank_write_preload_file`,
+/// which goes on with the OID of the database and the version of its `graph` table.
+const PRELOAD_HEADER: &str = "# meritrank preload";
+
+/// Parses the header of a preload file into the database OID and table version.
+fn parse_preload_header(record: &[String]) -> Option<(u32, i64)> {
+    let mut database = None;
+    let mut version = None;
+    for field in record.first()?.strip_prefix(PRELOAD_HEADER)?.split_whitespace() {
+        match field.split_once('=')? {
+            ("database", value) => database = value.parse().ok(),
+            ("version", value) => version = value.parse().ok(),
+            _ => {}
+        }
+    }
+    Some((database?, version?))
+}
+
+/// Loads `meritrank.preload_file` into the in-memory graph while the postmaster starts.
+///
+/// Backends are forked from the postmaster and inherit the loaded graph, so it is marked
+/// as hydrated and the `graph` table is not read again on first use. There is no
+/// database connection yet, so the file is read directly and edges are not mirrored.
+/// Failures are logged rather than raised, so that a bad file cannot stop the server.
+pub fn preload() {
+    let path = match guc::PRELOAD_FILE.get() {
+        Some(path) if !path.is_empty() => path,
+        _ => return,
+    };
+
+    match preload_file(&path) {
+        Ok(loaded) => {
+            log!("meritrank: preloaded {} edges from {}", loaded, path);
+        }
+        Err(e) => {
+            warning!("meritrank: failed to preload {}: {}", path, e);
+        }
+    }
+}
+
+/// Loads a preload file into the in-memory graph and marks it as hydrated.
+///
+/// Files written by `meritrank_write_preload_file` name the database and the version of
+/// the `graph` table they were copied from, so backends keep the graph until the table
+/// changes, and discard it in other databases. The graph of other files is taken to
+/// reflect the table as the first backend finds it. Returns the number of edges loaded.
+pub fn preload_file(path: &str) -> Result<i64, GraphManipulationError> {
+    let file = File::open(path)
+        .map_err(|e| GraphManipulationError::FileReadFailure(format!("{}: {}", path, e)))?;
+
+    let mut origin = None;
+    let mut loaded: i64 = 0;
+    {
+        let mut graph = GRAPH.lock().map_err(|e| {
+            GraphManipulationError::MutexLockFailure(format!("Mutex lock error: {}", e))
+        })?;
+        for (position, record) in CsvRecords::new(BufReader::new(file), ',').enumerate() {
+            let record = record
+                .map_err(|e| GraphManipulationError::FileReadFailure(format!("{}: {}", path, e)))?;
+            if position == 0 {
+                origin = parse_preload_header(&record);
+            }
+            if let Some((source, destination, weight)) = parse_csv_edge(&record) {
+                graph.add_edge(&source, &destination, weight)?;
+                loaded += 1;
+            }
+        }
+    }
+
+    HYDRATED.store(true, Ordering::SeqCst);
+    match origin {
+        Some((database, version)) => {
+            DATABASE.store(database, Ordering::SeqCst);
+            persist::KNOWN_VERSION.store(version, Ordering::SeqCst);
+        }
+        None => persist::ADOPT_VERSION.store(true, Ordering::SeqCst),
+    }
+    Ok(loaded)
+}
+
+/// Quotes a CSV field, doubling the quotes it contains.
+fn quote_csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[pg_extern]
+/// Writes the `graph` table of this database to a preload file for
+/// `meritrank.preload_file`, so that backends start with a graph that matches the table.
+///
+/// The file starts with a header naming the database and the version of the table, so
+/// backends reload the graph only once the table changed after the file was written.
+/// Changes pending in this backend are written to the table first. The file is replaced
+/// atomically. Only superusers may write server files.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file on the database server. Defaults to
+///   `meritrank.preload_file`.
+///
+/// # Returns
+///
+/// The number of edges written.
+pub fn meritrank_write_preload_file(
+    path: default!(Option<&str>, "NULL"),
+) -> Result<i64, ErrorReport> {
+    require_superuser("meritrank_write_preload_file")?;
+    let path = path.map(str::to_string);
+    let path = match path.or_else(|| guc::PRELOAD_FILE.get().map(|path| path.to_string())) {
+        Some(path) if !path.is_empty() => path,
+        _ => {
+            return Err(GraphManipulationError::FileWriteFailure(
+                "no path given and meritrank.preload_file is not set".to_string(),
+            )
+            .into())
+        }
+    };
+    let write_failure =
+        |e: std::io::Error| GraphManipulationError::FileWriteFailure(format!("{}: {}", path, e));
+
+    persist::flush()?;
+    // The version is read first: edges committed while writing only cause a reload
+    let version = persist::table_version()?;
+    let database = unsafe { pg_sys::MyDatabaseId }.as_u32();
+
+    let temporary = format!("{}.tmp", path);
+    let mut file = BufWriter::new(File::create(&temporary).map_err(write_failure)?);
+    writeln!(file, "{} database={} version={}", PRELOAD_HEADER, database, version)
+        .map_err(write_failure)?;
+
+    let written = Spi::connect(|client| -> Result<i64, GraphManipulationError> {
+        let mut cursor = client.open_cursor(
+            "SELECT source, destination, weight::float8 FROM graph WHERE weight <> 0",
+            None,
+        );
+        let mut written: i64 = 0;
+        loop {
+            let batch = cursor
+                .fetch(LOAD_BATCH_SIZE)
+                .map_err(|e| GraphManipulationError::FetchRecordsFailure(e.to_string()))?;
+            if batch.is_empty() {
+                break;
+            }
+            for row in batch {
+                if let (Some(source), Some(destination), Some(weight)) = (
+                    row.get::<String>(1)?,
+                    row.get::<String>(2)?,
+                    row.get::<f64>(3)?,
+                ) {
+                    writeln!(
+                        file,
+                        "{},{},{}",
+                        quote_csv_field(&source),
+                        quote_csv_field(&destination),
+                        weight
+                    )
+                    .map_err(write_failure)?;
+                    written += 1;
+                }
+            }
+        }
+        Ok(written)
+    })?;
+
+    file.flush().map_err(write_failure)?;
+    std::fs::rename(&temporary, &path).map_err(write_failure)?;
+    Ok(written)
+}
+
+/// Applies a batch of imported edges as by `meritrank_add` and empties it.
+fn import_batch(batch: &mut Vec<(String, String, f64)>) -> Result<(), GraphManipulationError> {
+    let edges: Vec<(&str, &str, f64)> = batch
+        .iter()
+        .map(|(source, destination, weight)| (source.as_str(), destination.as_str(), *weight))
+        .collect();
+    add_edges(&edges)?;
+    batch.clear();
+    Ok(())
+}
+
+#[pg_extern]
+/// Imports edges from a server-side CSV file of `(source, destination, weight)` rows.
+///
+/// Fields may be quoted as described in RFC 4180, e.g. to contain the delimiter. Valid
+/// rows are applied as by `meritrank_add`, so they are written to the `graph` table and
+/// recorded in the outbox and the audit log as well; invalid rows (including a header
+/// line) are skipped. Only superusers may read server files.
+///
+/// # Arguments
+///
+/// * `path` - The path of the CSV file on the database server.
+/// * `delimiter` - A single-character field delimiter. Defaults to `,`.
+///
+/// # Returns
+///
+/// A single row with the number of loaded and skipped rows.
+pub fn meritrank_import_csv(
+    path: &str,
+    delimiter: default!(&str, "','"),
+) -> Result<TableIterator<'static, (name!(loaded, i64), name!(skipped, i64))>, ErrorReport> {
+    require_superuser("meritrank_import_csv")?;
+
+    let mut chars = delimiter.chars();
+    let delimiter = match (chars.next(), chars.next()) {
+        (Some(delimiter), None) => delimiter,
+        _ => {
+            return Err(GraphManipulationError::DataExtractionFailure(format!(
+                "delimiter must be a single character, got '{}'",
+                delimiter
+            ))
+            .into())
+        }
+    };
+
+    let file = File::open(path)
+        .map_err(|e| GraphManipulationError::FileReadFailure(format!("{}: {}", path, e)))?;
+
+    let mut loaded: i64 = 0;
+    let mut skipped: i64 = 0;
+    let mut batch = Vec::new();
+
+    for record in CsvRecords::new(BufReader::new(file), delimiter) {
+        let record = record
+            .map_err(|e| GraphManipulationError::FileReadFailure(format!("{}: {}", path, e)))?;
+        if record.len() == 1 && record[0].is_empty() {
+            continue;
+        }
+
+        match parse_csv_edge(&record) {
+            Some(edge) => {
+                batch.push(edge);
+                loaded += 1;
+            }
+            None => skipped += 1,
+        }
+
+        if batch.len() as i64 >= LOAD_BATCH_SIZE {
+            import_batch(&mut batch)?;
+            log_info!(
+                "meritrank_import_csv: {} rows loaded, {} skipped so far from {}",
+                loaded,
+                skipped,
+                path
+            );
+        }
+    }
+
+    import_batch(&mut batch)?;
+
+    Ok(TableIterator::once((loaded, skipped)))
+}
+
+/// Aggregate that feeds every aggregated row into the in-memory graph as an edge.
+///
+/// ```sql
+/// SELECT meritrank_build_agg(subject, object, amount) FROM vote_user WHERE amount <> 0;
+/// ```
+///
+/// Rows with `NULL` values or a self-reference are skipped. Each edge is applied and
+/// recorded as by `meritrank_add`. The result is the number of edges added.
+pub struct MeritRankBuildAgg;
+
+#[pg_aggregate]
+impl Aggregate for MeritRankBuildAgg {
+    const NAME: &'static str = "meritrank_build_agg";
+    const INITIAL_CONDITION: Option<&'static str> = Some("0");
+
+    type State = i64;
+    type Args = (
+        name!(source, Option<String>),
+        name!(destination, Option<String>),
+        name!(weight, Option<f64>),
+    );
+
+    fn state(
+        current: Self::State,
+        (source, destination, weight): Self::Args,
+        _fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        match (source, destination, weight) {
+            (Some(source), Some(destination), Some(weight)) if source != destination => {
+                // Each row is published like a call of meritrank_add, so the edges reach the
+                // graph table, the outbox, the audit log and notifications
+                if let Err(e) = add_edges(&[(&source, &destination, weight)]) {
+                    error!("meritrank_build_agg: {}", e);
+                }
+                current + 1
+            }
+            _ => current,
+        }
+    }
+}
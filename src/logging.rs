@@ -0,0 +1,37 @@
+// Current crate (`crate::`) imports
+use crate::guc::{self, LogLevel};
+
+/// Returns whether messages of `level` are emitted at the current `meritrank.log_level`.
+pub fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && guc::LOG_LEVEL.get() >= level
+}
+
+/// Emits a `WARNING`, which is also sent to the client, unless `meritrank.log_level = off`.
+macro_rules! log_warning {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::guc::LogLevel::Warning) {
+            pgx::warning!($($arg)*);
+        }
+    };
+}
+
+/// Writes an informational message to the server log at `LOG` level when
+/// `meritrank.log_level` is `info` or `debug`.
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::guc::LogLevel::Info) {
+            pgx::log!($($arg)*);
+        }
+    };
+}
+
+/// Writes a diagnostic message at `DEBUG1` level when `meritrank.log_level = debug`.
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::guc::LogLevel::Debug) {
+            pgx::debug1!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {log_debug, log_info, log_warning};
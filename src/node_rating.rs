@@ -0,0 +1,71 @@
+// Library for PostgreSQL extensions
+use pgx::*;
+
+// Current crate (`crate::`) imports
+#[allow(unused_imports)]
+use crate::graph::meritrank_ranks;
+
+// A rank of a node as a single value, ordered by rank and then by node name, so that
+// `ORDER BY rating USING >` lists the best-ranked nodes first and materialized rank
+// tables can be indexed on it. The generic record operators would order by name first.
+extension_sql!(
+    r#"
+CREATE TYPE node_rating AS (node text, rank float8);
+
+CREATE FUNCTION node_rating_cmp(a node_rating, b node_rating) RETURNS integer
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE
+    AS $$ SELECT coalesce(nullif(btfloat8cmp(a.rank, b.rank), 0), bttextcmp(a.node, b.node)) $$;
+CREATE FUNCTION node_rating_lt(a node_rating, b node_rating) RETURNS boolean
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$ SELECT node_rating_cmp(a, b) < 0 $$;
+CREATE FUNCTION node_rating_le(a node_rating, b node_rating) RETURNS boolean
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$ SELECT node_rating_cmp(a, b) <= 0 $$;
+CREATE FUNCTION node_rating_eq(a node_rating, b node_rating) RETURNS boolean
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$ SELECT node_rating_cmp(a, b) = 0 $$;
+CREATE FUNCTION node_rating_ne(a node_rating, b node_rating) RETURNS boolean
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$ SELECT node_rating_cmp(a, b) <> 0 $$;
+CREATE FUNCTION node_rating_ge(a node_rating, b node_rating) RETURNS boolean
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$ SELECT node_rating_cmp(a, b) >= 0 $$;
+CREATE FUNCTION node_rating_gt(a node_rating, b node_rating) RETURNS boolean
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$ SELECT node_rating_cmp(a, b) > 0 $$;
+
+CREATE OPERATOR < (
+    LEFTARG = node_rating, RIGHTARG = node_rating, FUNCTION = node_rating_lt,
+    COMMUTATOR = >, NEGATOR = >=, RESTRICT = scalarltsel, JOIN = scalarltjoinsel
+);
+CREATE OPERATOR <= (
+    LEFTARG = node_rating, RIGHTARG = node_rating, FUNCTION = node_rating_le,
+    COMMUTATOR = >=, NEGATOR = >, RESTRICT = scalarlesel, JOIN = scalarlejoinsel
+);
+CREATE OPERATOR = (
+    LEFTARG = node_rating, RIGHTARG = node_rating, FUNCTION = node_rating_eq,
+    COMMUTATOR = =, NEGATOR = <>, RESTRICT = eqsel, JOIN = eqjoinsel, MERGES
+);
+CREATE OPERATOR <> (
+    LEFTARG = node_rating, RIGHTARG = node_rating, FUNCTION = node_rating_ne,
+    COMMUTATOR = <>, NEGATOR = =, RESTRICT = neqsel, JOIN = neqjoinsel
+);
+CREATE OPERATOR >= (
+    LEFTARG = node_rating, RIGHTARG = node_rating, FUNCTION = node_rating_ge,
+    COMMUTATOR = <=, NEGATOR = <, RESTRICT = scalargesel, JOIN = scalargejoinsel
+);
+CREATE OPERATOR > (
+    LEFTARG = node_rating, RIGHTARG = node_rating, FUNCTION = node_rating_gt,
+    COMMUTATOR = <, NEGATOR = <=, RESTRICT = scalargtsel, JOIN = scalargtjoinsel
+);
+
+CREATE OPERATOR CLASS node_rating_ops DEFAULT FOR TYPE node_rating USING btree AS
+    OPERATOR 1 <,
+    OPERATOR 2 <=,
+    OPERATOR 3 =,
+    OPERATOR 4 >=,
+    OPERATOR 5 >,
+    FUNCTION 1 node_rating_cmp(node_rating, node_rating);
+
+CREATE FUNCTION meritrank_ratings(ego text, iterations integer DEFAULT NULL)
+    RETURNS SETOF node_rating
+    LANGUAGE sql VOLATILE
+    AS $$ SELECT node, rank FROM meritrank_ranks(ego, iterations) $$;
+"#,
+    name = "create_node_rating_type",
+    requires = [meritrank_ranks]
+);
@@ -0,0 +1,41 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::lock_graph;
+use crate::lib_graph::NodeType;
+
+/// Parses a node type passed from SQL.
+pub fn parse_node_type(node_type: &str) -> Result<NodeType, GraphManipulationError> {
+    node_type.parse::<NodeType>().map_err(|_| {
+        GraphManipulationError::DataExtractionFailure(format!(
+            "unknown node type '{}' (expected one of: user, beacon, comment, other)",
+            node_type
+        ))
+    })
+}
+
+#[pg_extern]
+/// Returns the type of a node: `user`, `beacon`, `comment` or `other`.
+///
+/// The type is derived from the node name when the node is created (`U…` users,
+/// `B…` beacons, `C…` comments) and can be overridden with `meritrank_set_node_type`.
+pub fn meritrank_node_type(node: &str) -> Result<String, ErrorReport> {
+    let graph = lock_graph()?;
+    Ok(graph.get_node_type(node)?.name().to_string())
+}
+
+#[pg_extern]
+/// Sets the type of a node explicitly, creating the node if necessary.
+///
+/// # Arguments
+///
+/// * `node` - The name of the node.
+/// * `node_type` - One of `user`, `beacon`, `comment` or `other`.
+pub fn meritrank_set_node_type(node: &str, node_type: &str) -> Result<(), ErrorReport> {
+    let node_type = parse_node_type(node_type)?;
+    let mut graph = lock_graph()?;
+    Ok(graph.set_node_type(node, node_type)?)
+}
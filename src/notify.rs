@@ -0,0 +1,66 @@
+// Library for PostgreSQL extensions
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::guc;
+
+/// Channel on which graph mutations are announced.
+pub const CHANGES_CHANNEL: &str = "meritrank_changes";
+
+/// Kind of a graph mutation announced on `CHANGES_CHANNEL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOp {
+    Add,
+    Update,
+    Delete,
+    Clear,
+}
+
+impl ChangeOp {
+    /// Returns the name of the operation as sent in the payload.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeOp::Add => "add",
+            ChangeOp::Update => "update",
+            ChangeOp::Delete => "delete",
+            ChangeOp::Clear => "clear",
+        }
+    }
+}
+
+/// Sends a `pg_notify` on `CHANGES_CHANNEL` describing a graph mutation, unless
+/// `meritrank.notify_changes` is off.
+///
+/// The payload is a JSON object with the keys `op`, `subject`, `object`, `weight`
+/// and `generation`; keys that do not apply to the operation are `null`.
+/// Like any notification, it is delivered only when the transaction commits.
+pub fn notify_change(
+    op: ChangeOp,
+    subject: Option<&str>,
+    object: Option<&str>,
+    weight: Option<f64>,
+    generation: u64,
+) -> Result<(), GraphManipulationError> {
+    if !guc::NOTIFY_CHANGES.get() {
+        return Ok(());
+    }
+
+    Spi::run_with_args(
+        "SELECT pg_notify($1, json_build_object( \
+             'op', $2, 'subject', $3, 'object', $4, 'weight', $5, 'generation', $6 \
+         )::text)",
+        Some(vec![
+            (PgBuiltInOids::TEXTOID.oid(), CHANGES_CHANNEL.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), op.name().into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), subject.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), object.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), weight.into_datum()),
+            (
+                PgBuiltInOids::INT8OID.oid(),
+                (generation as i64).into_datum(),
+            ),
+        ]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))
+}
@@ -0,0 +1,79 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::guc;
+use crate::notify::ChangeOp;
+use crate::persist;
+
+extension_sql!(
+    r#"
+CREATE TABLE IF NOT EXISTS meritrank_outbox (
+    id bigserial PRIMARY KEY,
+    op text NOT NULL,
+    subject text,
+    object text,
+    weight float8,
+    graph_version bigint,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+SELECT pg_catalog.pg_extension_config_dump('meritrank_outbox', '');
+SELECT pg_catalog.pg_extension_config_dump('meritrank_outbox_id_seq', '');
+"#,
+    name = "create_outbox_table"
+);
+
+/// Appends a graph mutation to `meritrank_outbox`, unless `meritrank.outbox` is off.
+///
+/// The row is inserted in the current transaction, so it becomes visible exactly when
+/// the mutation commits and disappears if it aborts. Rows are numbered in the order the
+/// mutations were made; columns that do not apply to the operation are `NULL`.
+/// `graph_version` is the version of the `graph` table the mutation was made on top of.
+pub fn record_change(
+    op: ChangeOp,
+    subject: Option<&str>,
+    object: Option<&str>,
+    weight: Option<f64>,
+) -> Result<(), GraphManipulationError> {
+    if !guc::OUTBOX.get() {
+        return Ok(());
+    }
+
+    Spi::run_with_args(
+        "INSERT INTO meritrank_outbox (op, subject, object, weight, graph_version) \
+         VALUES ($1, $2, $3, $4, $5)",
+        Some(vec![
+            (PgBuiltInOids::TEXTOID.oid(), op.name().into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), subject.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), object.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), weight.into_datum()),
+            (
+                PgBuiltInOids::INT8OID.oid(),
+                persist::known_version().into_datum(),
+            ),
+        ]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))
+}
+
+#[pg_extern]
+/// Removes the outbox rows a consumer has processed.
+///
+/// # Arguments
+///
+/// * `up_to` - The highest `id` that has been processed; it and all earlier rows are removed.
+///
+/// # Returns
+///
+/// The number of rows removed.
+pub fn meritrank_outbox_prune(up_to: i64) -> Result<i64, ErrorReport> {
+    Ok(Spi::get_one_with_args::<i64>(
+        "WITH removed AS (DELETE FROM meritrank_outbox WHERE id <= $1 RETURNING 1) \
+         SELECT count(*) FROM removed",
+        vec![(PgBuiltInOids::INT8OID.oid(), up_to.into_datum())],
+    )
+    .map_err(GraphManipulationError::from)?
+    .unwrap_or(0))
+}
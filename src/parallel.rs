@@ -0,0 +1,518 @@
+// Standard library imports
+use std::ops::Range;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::time::Duration;
+
+// Library for PostgreSQL extensions
+use pgx::bgworkers::{BackgroundWorker, SignalWakeFlags};
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::guc;
+use crate::lib_graph::constants::INTERRUPT_BATCH_SIZE;
+use crate::lib_graph::{MeritRank, MeritRankError, MyGraph, Node, NodeId, RandomWalk};
+use crate::logging::log_info;
+
+/// Smallest number of walks worth handing to a worker process of its own.
+pub const MIN_WALKS_PER_WORKER: usize = 1_000;
+
+/// Size of the queue each worker sends its walks through, in bytes.
+const QUEUE_SIZE: usize = 1 << 16;
+
+/// Marks a segment laid out by `generate_on_workers`.
+const SEGMENT_MAGIC: u64 = 0x6d65_7269_7472_616e;
+
+/// Number of words before the edges in a segment: the magic, the ego, the range of walk
+/// indices, the number of workers, whether walks are seeded and the seed, alpha, the
+/// maximum walk length, and the numbers of rows and edges.
+const HEADER_WORDS: usize = 11;
+
+// Returned by dsm_create instead of raising an error when no segment slot is free
+const DSM_CREATE_NULL_IF_MAXSEGMENTS: c_int = 0x0001;
+
+// Results of shm_mq_send and shm_mq_receive
+const SHM_MQ_SUCCESS: u32 = 0;
+const SHM_MQ_WOULD_BLOCK: u32 = 1;
+
+// Dynamic shared memory segments and the queues in them (storage/dsm.h, storage/shm_mq.h)
+#[repr(C)]
+struct DsmSegment {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct ShmMq {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct ShmMqHandle {
+    _private: [u8; 0],
+}
+
+#[pg_guard]
+extern "C" {
+    fn dsm_create(size: usize, flags: c_int) -> *mut DsmSegment;
+    fn dsm_attach(handle: u32) -> *mut DsmSegment;
+    fn dsm_detach(segment: *mut DsmSegment);
+    fn dsm_segment_address(segment: *mut DsmSegment) -> *mut c_void;
+    fn dsm_segment_handle(segment: *mut DsmSegment) -> u32;
+    fn shm_mq_create(address: *mut c_void, size: usize) -> *mut ShmMq;
+    fn shm_mq_set_receiver(mq: *mut ShmMq, receiver: *mut pg_sys::PGPROC);
+    fn shm_mq_set_sender(mq: *mut ShmMq, sender: *mut pg_sys::PGPROC);
+    fn shm_mq_attach(
+        mq: *mut ShmMq,
+        segment: *mut DsmSegment,
+        handle: *mut pg_sys::BackgroundWorkerHandle,
+    ) -> *mut ShmMqHandle;
+    fn shm_mq_detach(mqh: *mut ShmMqHandle);
+    fn shm_mq_receive(
+        mqh: *mut ShmMqHandle,
+        nbytes: *mut usize,
+        data: *mut *mut c_void,
+        nowait: bool,
+    ) -> u32;
+}
+
+#[cfg(not(feature = "pg15"))]
+#[pg_guard]
+extern "C" {
+    fn shm_mq_send(mqh: *mut ShmMqHandle, nbytes: usize, data: *const c_void, nowait: bool) -> u32;
+}
+
+#[cfg(feature = "pg15")]
+#[pg_guard]
+extern "C" {
+    fn shm_mq_send(
+        mqh: *mut ShmMqHandle,
+        nbytes: usize,
+        data: *const c_void,
+        nowait: bool,
+        force_flush: bool,
+    ) -> u32;
+}
+
+/// Sends a message, waiting while the queue is full.
+unsafe fn send_message(mqh: *mut ShmMqHandle, message: &[u8]) -> u32 {
+    #[cfg(not(feature = "pg15"))]
+    return shm_mq_send(mqh, message.len(), message.as_ptr() as *const c_void, false);
+    #[cfg(feature = "pg15")]
+    return shm_mq_send(
+        mqh,
+        message.len(),
+        message.as_ptr() as *const c_void,
+        false,
+        true,
+    );
+}
+
+/// A dynamic shared memory segment this process is attached to, detached when dropped.
+struct Segment(*mut DsmSegment);
+
+impl Segment {
+    fn address(&self) -> *mut u8 {
+        unsafe { dsm_segment_address(self.0) as *mut u8 }
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        // On errors, the resource owner of the transaction detaches the segment instead
+        if !std::thread::panicking() {
+            unsafe { dsm_detach(self.0) };
+        }
+    }
+}
+
+/// Worker processes started for a calculation, stopped when dropped.
+struct Workers(Vec<*mut pg_sys::BackgroundWorkerHandle>);
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        // Workers that are done have exited already
+        for &handle in self.0.iter() {
+            unsafe { pg_sys::TerminateBackgroundWorker(handle) };
+        }
+    }
+}
+
+/// Generates the walks of a calculation on `meritrank.parallel_workers` background
+/// worker processes, falling back to the threads of this backend.
+///
+/// Only ranges of at least `MIN_WALKS_PER_WORKER` walks per worker are handed out, and
+/// only when at least two workers would get one. The walks are the same as those
+/// generated in this backend: walk `i` is still generated from `seed + i` when seeded.
+/// Walks generated in this backend are generated in batches, between which queries are
+/// checked for cancellation.
+pub fn offload_walks(
+    merit_rank: &MeritRank,
+    ego: NodeId,
+    indices: Range<usize>,
+) -> Result<Vec<RandomWalk>, MeritRankError> {
+    let workers =
+        (guc::PARALLEL_WORKERS.get().max(0) as usize).min(indices.len() / MIN_WALKS_PER_WORKER);
+    if workers >= 2 {
+        match generate_on_workers(merit_rank, ego, indices.clone(), workers) {
+            Ok(walks) => return Ok(walks),
+            Err(e) => {
+                log_info!("meritrank: generating walks in the backend instead: {}", e);
+            }
+        }
+    }
+
+    let mut walks = Vec::with_capacity(indices.len());
+    for start in indices.clone().step_by(INTERRUPT_BATCH_SIZE) {
+        check_for_interrupts!();
+        let end = (start + INTERRUPT_BATCH_SIZE).min(indices.end);
+        walks.extend(merit_rank.generate_local_walks(ego, start..end)?);
+    }
+    Ok(walks)
+}
+
+/// Returns the walk indices each of `workers` workers generates, split like the threads
+/// of `MeritRank::generate_local_walks` split them.
+fn shards(indices: &Range<usize>, workers: usize) -> Vec<Range<usize>> {
+    let chunk_size = (indices.len() + workers - 1) / workers;
+    (0..workers)
+        .map(|worker| {
+            let start = (indices.start + worker * chunk_size).min(indices.end);
+            start..(start + chunk_size).min(indices.end)
+        })
+        .collect()
+}
+
+fn node_word(node: NodeId) -> Option<u64> {
+    match node {
+        NodeId::UInt(id) => Some(id as u64),
+        _ => None,
+    }
+}
+
+/// Lays out what workers need to generate walks from `ego`: the settings the walks
+/// depend on and the positive edges reachable from `ego`, with the weights walks draw
+/// them with. Returns `None` for nodes without a numeric ID.
+fn encode(
+    merit_rank: &MeritRank,
+    ego: NodeId,
+    indices: &Range<usize>,
+    workers: usize,
+) -> Option<Vec<u64>> {
+    let rows = merit_rank.walk_edges(ego);
+    let edge_count: usize = rows.iter().map(|(_, edges)| edges.len()).sum();
+    let seed = merit_rank.get_seed();
+
+    let mut words = Vec::with_capacity(HEADER_WORDS + 2 * rows.len() + 2 * edge_count);
+    words.extend([
+        SEGMENT_MAGIC,
+        node_word(ego)?,
+        indices.start as u64,
+        indices.end as u64,
+        workers as u64,
+        seed.is_some() as u64,
+        seed.unwrap_or(0),
+        merit_rank.get_alpha().to_bits(),
+        merit_rank
+            .get_max_walk_length()
+            .map_or(u64::MAX, |max_walk_length| max_walk_length as u64),
+        rows.len() as u64,
+        edge_count as u64,
+    ]);
+
+    let mut end = 0;
+    for (source, _) in rows.iter() {
+        words.push(node_word(*source)?);
+    }
+    for (_, edges) in rows.iter() {
+        end += edges.len();
+        words.push(end as u64);
+    }
+    for (_, edges) in rows.iter() {
+        for &(target, _) in edges.iter() {
+            words.push(node_word(target)?);
+        }
+    }
+    for (_, edges) in rows.iter() {
+        words.extend(edges.iter().map(|&(_, weight)| weight.to_bits()));
+    }
+    Some(words)
+}
+
+/// What a worker reads from its segment.
+struct Shared {
+    merit_rank: MeritRank,
+    ego: NodeId,
+    indices: Range<usize>,
+    workers: usize,
+    /// Length of the laid out data, in words, after which the queues start.
+    words: usize,
+}
+
+/// Reads the data laid out by `encode` at `address`.
+unsafe fn decode(address: *const u64) -> Result<Shared, String> {
+    let header = std::slice::from_raw_parts(address, HEADER_WORDS);
+    if header[0] != SEGMENT_MAGIC {
+        return Err("the segment was not laid out for walks".to_string());
+    }
+    let (rows, edges) = (header[9] as usize, header[10] as usize);
+    let words = HEADER_WORDS + 2 * rows + 2 * edges;
+    let data = std::slice::from_raw_parts(address, words);
+    let sources = &data[HEADER_WORDS..HEADER_WORDS + rows];
+    let ends = &data[HEADER_WORDS + rows..HEADER_WORDS + 2 * rows];
+    let targets = &data[HEADER_WORDS + 2 * rows..HEADER_WORDS + 2 * rows + edges];
+    let weights = &data[HEADER_WORDS + 2 * rows + edges..words];
+
+    let ego = NodeId::UInt(header[1] as usize);
+    let mut graph = MyGraph::new();
+    let add_node = |graph: &mut MyGraph, node: NodeId| {
+        if !graph.contains_node(node) {
+            graph.add_node(Node::new(node));
+        }
+    };
+    add_node(&mut graph, ego);
+    let mut start = 0;
+    for (&source, &end) in sources.iter().zip(ends.iter()) {
+        let source = NodeId::UInt(source as usize);
+        add_node(&mut graph, source);
+        for edge in start..end as usize {
+            let target = NodeId::UInt(targets[edge] as usize);
+            add_node(&mut graph, target);
+            graph
+                .add_edge(source, target, f64::from_bits(weights[edge]))
+                .map_err(|e| e.to_string())?;
+        }
+        start = end as usize;
+    }
+
+    let mut merit_rank = MeritRank::new(graph).map_err(|e| e.to_string())?;
+    merit_rank.set_alpha(f64::from_bits(header[7]));
+    merit_rank.set_max_walk_length(
+        Some(header[8])
+            .filter(|&max_walk_length| max_walk_length != u64::MAX)
+            .map(|max_walk_length| max_walk_length as usize),
+    );
+    merit_rank.set_seed(Some(header[6]).filter(|_| header[5] != 0));
+
+    Ok(Shared {
+        merit_rank,
+        ego,
+        indices: header[2] as usize..header[3] as usize,
+        workers: header[4] as usize,
+        words,
+    })
+}
+
+/// Copies `value` into a fixed-size field of a worker entry, truncated and terminated.
+fn copy_c_string(field: &mut [c_char], value: &str) {
+    let length = value.len().min(field.len() - 1);
+    for (target, &byte) in field.iter_mut().zip(value.as_bytes()[..length].iter()) {
+        *target = byte as c_char;
+    }
+    field[length] = 0;
+}
+
+/// Starts the worker with the given index on the segment with the given handle.
+///
+/// The worker is registered directly rather than with `BackgroundWorkerBuilder`, so that
+/// running out of worker slots can be told apart and the walks generated here instead.
+fn start_worker(
+    segment: u32,
+    worker: usize,
+) -> Result<*mut pg_sys::BackgroundWorkerHandle, String> {
+    let mut entry: pg_sys::BackgroundWorker = unsafe { std::mem::zeroed() };
+    copy_c_string(
+        &mut entry.bgw_name,
+        &format!("meritrank walk worker {}", worker),
+    );
+    copy_c_string(&mut entry.bgw_type, "meritrank walk worker");
+    copy_c_string(&mut entry.bgw_library_name, "pg_meritrank");
+    copy_c_string(&mut entry.bgw_function_name, "meritrank_walk_worker_main");
+    copy_c_string(&mut entry.bgw_extra, &format!("{} {}", segment, worker));
+    entry.bgw_flags = pg_sys::BGWORKER_SHMEM_ACCESS as c_int;
+    entry.bgw_start_time = pg_sys::BgWorkerStartTime_BgWorkerStart_ConsistentState;
+    entry.bgw_restart_time = pg_sys::BGW_NEVER_RESTART as c_int;
+    entry.bgw_notify_pid = unsafe { pg_sys::MyProcPid };
+
+    let mut handle = ptr::null_mut();
+    if unsafe { pg_sys::RegisterDynamicBackgroundWorker(&mut entry, &mut handle) } {
+        Ok(handle)
+    } else {
+        Err("no background worker slot is free, see max_worker_processes".to_string())
+    }
+}
+
+/// Generates the walks with the given indices on `workers` worker processes.
+///
+/// The edges reachable from `ego` are copied into a dynamic shared memory segment, which
+/// also holds one queue per worker. Every worker generates a contiguous share of the
+/// walks and sends them through its queue, one message per walk, while this backend
+/// collects them in the order of the indices.
+fn generate_on_workers(
+    merit_rank: &MeritRank,
+    ego: NodeId,
+    indices: Range<usize>,
+    workers: usize,
+) -> Result<Vec<RandomWalk>, String> {
+    let words = encode(merit_rank, ego, &indices, workers)
+        .ok_or_else(|| "the graph has nodes without a numeric ID".to_string())?;
+    let data_size = words.len() * std::mem::size_of::<u64>();
+
+    let segment = unsafe {
+        dsm_create(
+            data_size + workers * QUEUE_SIZE,
+            DSM_CREATE_NULL_IF_MAXSEGMENTS,
+        )
+    };
+    if segment.is_null() {
+        return Err("no dynamic shared memory segment is free".to_string());
+    }
+    let segment = Segment(segment);
+    let address = segment.address();
+    unsafe { ptr::copy_nonoverlapping(words.as_ptr() as *const u8, address, data_size) };
+
+    let segment_handle = unsafe { dsm_segment_handle(segment.0) };
+    let mut started = Workers(Vec::with_capacity(workers));
+    let mut queues = Vec::with_capacity(workers);
+    for worker in 0..workers {
+        let queue = unsafe {
+            let mq = shm_mq_create(
+                address.add(data_size + worker * QUEUE_SIZE) as *mut c_void,
+                QUEUE_SIZE,
+            );
+            shm_mq_set_receiver(mq, pg_sys::MyProc);
+            mq
+        };
+        let handle = start_worker(segment_handle, worker)?;
+        started.0.push(handle);
+        // With the handle, the queue reports a worker that exits without attaching
+        queues.push(unsafe { shm_mq_attach(queue, segment.0, handle) });
+    }
+
+    let shards = shards(&indices, workers);
+    let mut walks: Vec<Vec<RandomWalk>> = shards
+        .iter()
+        .map(|shard| Vec::with_capacity(shard.len()))
+        .collect();
+    let mut done = vec![false; workers];
+    while done.iter().any(|&done| !done) {
+        check_for_interrupts!();
+        let mut received = false;
+        for worker in 0..workers {
+            if done[worker] {
+                continue;
+            }
+            let mut size = 0;
+            let mut message = ptr::null_mut();
+            match unsafe { shm_mq_receive(queues[worker], &mut size, &mut message, true) } {
+                SHM_MQ_SUCCESS => {
+                    let bytes = unsafe { std::slice::from_raw_parts(message as *const u8, size) };
+                    let nodes = bytes
+                        .chunks_exact(std::mem::size_of::<u64>())
+                        .map(|word| {
+                            let mut buffer = [0; 8];
+                            buffer.copy_from_slice(word);
+                            NodeId::UInt(u64::from_ne_bytes(buffer) as usize)
+                        })
+                        .collect();
+                    walks[worker].push(RandomWalk::from_nodes(nodes));
+                    received = true;
+                }
+                SHM_MQ_WOULD_BLOCK => {}
+                _ => {
+                    // The worker detached, after sending all of its walks unless it failed
+                    done[worker] = true;
+                    if walks[worker].len() != shards[worker].len() {
+                        return Err(format!(
+                            "walk worker {} stopped after {} of {} walks",
+                            worker,
+                            walks[worker].len(),
+                            shards[worker].len()
+                        ));
+                    }
+                }
+            }
+        }
+        if !received {
+            // Workers set the latch of this backend whenever they send a walk
+            BackgroundWorker::wait_latch(Some(Duration::from_millis(10)));
+        }
+    }
+
+    Ok(walks.into_iter().flatten().collect())
+}
+
+#[pg_guard]
+#[no_mangle]
+/// Entry point of the workers started by `offload_walks`.
+///
+/// The worker attaches to the segment named in its `bgw_extra`, generates its share of
+/// the walks on the edges found there and sends them through its queue. It stops early
+/// once the backend that started it detaches from the segment.
+pub extern "C" fn meritrank_walk_worker_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGTERM);
+    if let Err(e) = send_walks(BackgroundWorker::get_extra()) {
+        log!("meritrank: walk worker failed: {}", e);
+    }
+}
+
+/// Generates the share of walks of a worker and sends them, given the handle of the
+/// segment and the index of the worker.
+fn send_walks(arguments: &str) -> Result<(), String> {
+    let mut arguments = arguments.split_whitespace();
+    let (segment_handle, worker) = match (
+        arguments
+            .next()
+            .and_then(|handle| handle.parse::<u32>().ok()),
+        arguments
+            .next()
+            .and_then(|worker| worker.parse::<usize>().ok()),
+    ) {
+        (Some(segment_handle), Some(worker)) => (segment_handle, worker),
+        _ => return Err("invalid worker arguments".to_string()),
+    };
+
+    let segment = unsafe { dsm_attach(segment_handle) };
+    if segment.is_null() {
+        // The backend gave up on the calculation before the worker started
+        return Ok(());
+    }
+    let segment = Segment(segment);
+    let address = segment.address();
+    let shared = unsafe { decode(address as *const u64)? };
+    let shard = shards(&shared.indices, shared.workers)
+        .get(worker)
+        .cloned()
+        .ok_or_else(|| format!("no share of walks for worker {}", worker))?;
+
+    let queue = unsafe {
+        let mq = address.add(shared.words * std::mem::size_of::<u64>() + worker * QUEUE_SIZE)
+            as *mut ShmMq;
+        shm_mq_set_sender(mq, pg_sys::MyProc);
+        shm_mq_attach(mq, segment.0, ptr::null_mut())
+    };
+
+    let mut message = Vec::new();
+    for start in shard.clone().step_by(INTERRUPT_BATCH_SIZE) {
+        if BackgroundWorker::sigterm_received() {
+            return Ok(());
+        }
+        let end = (start + INTERRUPT_BATCH_SIZE).min(shard.end);
+        let walks = shared
+            .merit_rank
+            .generate_local_walks(shared.ego, start..end)
+            .map_err(|e| e.to_string())?;
+        for walk in walks {
+            message.clear();
+            for &node in walk.get_nodes() {
+                message.extend(node_word(node).unwrap_or(u64::MAX).to_ne_bytes());
+            }
+            if unsafe { send_message(queue, &message) } != SHM_MQ_SUCCESS {
+                // The backend detached, e.g. because the query was canceled
+                return Ok(());
+            }
+        }
+    }
+
+    unsafe { shm_mq_detach(queue) };
+    Ok(())
+}
@@ -0,0 +1,594 @@
+// Standard library imports
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Mutex;
+
+// External crate imports
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::{load_graph_table, lock_graph, GraphSingleton, HYDRATED};
+use crate::guc;
+use crate::lib_graph::{NodeId, Weight};
+use crate::loader::LOAD_BATCH_SIZE;
+use crate::notify::ChangeOp;
+use crate::walks;
+
+/// Number of pending edge changes after which they are written out without waiting
+/// for the end of the transaction.
+pub const PERSIST_BATCH_SIZE: usize = 1_000;
+
+/// Graph mutations waiting to be mirrored to the `graph` table.
+#[derive(Default)]
+struct WriteBehind {
+    /// Changed edges and their new weight, `None` for a removed edge. Only the last
+    /// change of an edge is kept.
+    pending: IndexMap<(String, String), Option<f64>>,
+    /// Whether the table must be emptied before the pending changes are applied.
+    truncate: bool,
+    /// Whether the pending changes are flushed when the current transaction commits.
+    registered: bool,
+}
+
+lazy_static! {
+    static ref WRITE_BEHIND: Mutex<WriteBehind> = Mutex::new(WriteBehind::default());
+}
+
+fn lock_write_behind() -> Result<std::sync::MutexGuard<'static, WriteBehind>, GraphManipulationError>
+{
+    WRITE_BEHIND
+        .lock()
+        .map_err(|e| GraphManipulationError::MutexLockFailure(format!("Mutex lock error: {}", e)))
+}
+
+// Version of the `graph` table the in-memory graph reflects, -1 before it is known
+pub(crate) static KNOWN_VERSION: AtomicI64 = AtomicI64::new(-1);
+
+// Whether the version of the table is to be taken as the one the in-memory graph
+// reflects when next checked, as for graphs preloaded from files without a version
+pub(crate) static ADOPT_VERSION: AtomicBool = AtomicBool::new(false);
+
+// Whether a transaction that changed the in-memory graph aborted, so that the graph holds
+// changes the `graph` table does not
+static ROLLED_BACK: AtomicBool = AtomicBool::new(false);
+
+// Start of the statement in which the version was last checked
+pub(crate) static VERSION_CHECKED_AT: AtomicI64 = AtomicI64::new(0);
+
+extension_sql!(
+    r#"
+CREATE TABLE IF NOT EXISTS meritrank_graph_version (
+    id boolean PRIMARY KEY DEFAULT true CHECK (id),
+    version bigint NOT NULL DEFAULT 0
+);
+INSERT INTO meritrank_graph_version DEFAULT VALUES ON CONFLICT DO NOTHING;
+SELECT pg_catalog.pg_extension_config_dump('meritrank_graph_version', '');
+
+CREATE TABLE IF NOT EXISTS meritrank_config (
+    name text PRIMARY KEY CHECK (name LIKE 'meritrank.%'),
+    value text NOT NULL
+);
+COMMENT ON TABLE meritrank_config IS
+    'Settings of the extension for this database, applied when the graph is loaded.';
+SELECT pg_catalog.pg_extension_config_dump('meritrank_config', '');
+"#,
+    name = "create_graph_version_table"
+);
+
+/// Returns the current version of the `graph` table.
+pub fn table_version() -> Result<i64, GraphManipulationError> {
+    Ok(
+        Spi::get_one::<i64>("SELECT version FROM meritrank_graph_version")
+            .map_err(|e| GraphManipulationError::GraphReadFailure(e.to_string()))?
+            .unwrap_or(0),
+    )
+}
+
+/// Returns the version of the `graph` table the in-memory graph reflects, or `None` if
+/// the graph was not loaded from the table, e.g. with `meritrank.auto_load` off.
+///
+/// Unlike the generation of the graph, the version is shared by all backends, so it can
+/// be compared across connections.
+pub fn known_version() -> Option<i64> {
+    Some(KNOWN_VERSION.load(Ordering::SeqCst)).filter(|&version| version >= 0)
+}
+
+/// Bumps the version of the `graph` table after this backend wrote to it.
+///
+/// The in-memory graph stays current only if no other backend bumped the version since
+/// it was loaded; otherwise the version it knows is left behind, so it is reloaded.
+fn bump_version() -> Result<(), GraphManipulationError> {
+    let version = Spi::get_one::<i64>(
+        "UPDATE meritrank_graph_version SET version = version + 1 RETURNING version",
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?
+    .unwrap_or(0);
+    let _ =
+        KNOWN_VERSION.compare_exchange(version - 1, version, Ordering::SeqCst, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Returns whether other backends changed the `graph` table since the in-memory graph
+/// was loaded.
+///
+/// The version is checked at most once per statement, so that functions which lock the
+/// graph repeatedly do not query it every time.
+pub fn is_stale() -> Result<bool, GraphManipulationError> {
+    let statement = unsafe { pg_sys::GetCurrentStatementStartTimestamp() };
+    if VERSION_CHECKED_AT.swap(statement, Ordering::SeqCst) == statement {
+        return Ok(false);
+    }
+    let version = table_version()?;
+    if ADOPT_VERSION.swap(false, Ordering::SeqCst)
+        && KNOWN_VERSION
+            .compare_exchange(-1, version, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    {
+        return Ok(false);
+    }
+    Ok(version != KNOWN_VERSION.load(Ordering::SeqCst))
+}
+
+/// Records a graph mutation to be mirrored to the `graph` table, unless
+/// `meritrank.persist_changes` is off.
+///
+/// Changes are buffered and written in batches: when `PERSIST_BATCH_SIZE` edges are
+/// pending, and when the transaction commits. If the transaction aborts, the pending
+/// changes are discarded together with it, see `discard_changes`.
+pub fn record_change(
+    op: ChangeOp,
+    subject: Option<&str>,
+    object: Option<&str>,
+    weight: Option<f64>,
+) -> Result<(), GraphManipulationError> {
+    if !guc::PERSIST_CHANGES.get() {
+        return Ok(());
+    }
+
+    let pending = {
+        let mut write_behind = lock_write_behind()?;
+        match (op, subject, object) {
+            (ChangeOp::Clear, _, _) => {
+                write_behind.pending.clear();
+                write_behind.truncate = true;
+            }
+            (ChangeOp::Delete, Some(subject), Some(object)) => {
+                write_behind
+                    .pending
+                    .insert((subject.to_string(), object.to_string()), None);
+            }
+            (_, Some(subject), Some(object)) => {
+                write_behind
+                    .pending
+                    .insert((subject.to_string(), object.to_string()), weight);
+            }
+            _ => {}
+        }
+
+        if !write_behind.registered {
+            register_xact_callback(PgXactCallbackEvent::PreCommit, || {
+                if let Ok(mut write_behind) = WRITE_BEHIND.lock() {
+                    write_behind.registered = false;
+                }
+                if let Err(e) = flush() {
+                    error!("meritrank: failed to persist graph changes: {}", e);
+                }
+            });
+            register_xact_callback(PgXactCallbackEvent::Abort, discard_changes);
+            write_behind.registered = true;
+        }
+
+        write_behind.pending.len()
+    };
+
+    if pending >= PERSIST_BATCH_SIZE {
+        flush()?;
+    }
+    Ok(())
+}
+
+/// Discards the pending changes of an aborted transaction.
+///
+/// Batches the transaction already wrote are rolled back with it, while the in-memory
+/// graph keeps every change, so the graph is loaded again from the table on next use.
+pub(crate) fn discard_changes() {
+    if let Ok(mut write_behind) = WRITE_BEHIND.lock() {
+        *write_behind = WriteBehind::default();
+    }
+    ROLLED_BACK.store(true, Ordering::SeqCst);
+}
+
+/// Returns whether a transaction that changed the in-memory graph aborted since last
+/// called.
+pub(crate) fn take_rolled_back() -> bool {
+    ROLLED_BACK.swap(false, Ordering::SeqCst)
+}
+
+/// Writes the pending changes to the `graph` table. Returns the number of edges written.
+pub fn flush() -> Result<usize, GraphManipulationError> {
+    let (pending, truncate) = {
+        let mut write_behind = lock_write_behind()?;
+        (
+            std::mem::take(&mut write_behind.pending),
+            std::mem::replace(&mut write_behind.truncate, false),
+        )
+    };
+
+    if truncate {
+        Spi::run("DELETE FROM graph")
+            .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+    }
+
+    let written = pending.len();
+    write_edges(pending)?;
+    if truncate || written > 0 {
+        bump_version()?;
+    }
+    Ok(written)
+}
+
+/// Applies edge changes to the `graph` table with a single statement.
+///
+/// Every edge is upserted on the `(source, destination)` key; edges with a `None` weight
+/// are deleted.
+fn write_edges(
+    changes: impl IntoIterator<Item = ((String, String), Option<f64>)>,
+) -> Result<(), GraphManipulationError> {
+    let mut sources = Vec::new();
+    let mut destinations = Vec::new();
+    let mut weights = Vec::new();
+    for ((source, destination), weight) in changes {
+        sources.push(source);
+        destinations.push(destination);
+        weights.push(weight);
+    }
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    Spi::run_with_args(
+        "WITH changes (source, destination, weight) AS ( \
+             SELECT * FROM unnest($1::text[], $2::text[], $3::float8[]) \
+         ), removed AS ( \
+             DELETE FROM graph g USING changes c \
+             WHERE g.source = c.source AND g.destination = c.destination \
+               AND c.weight IS NULL \
+         ) \
+         INSERT INTO graph (source, destination, weight) \
+         SELECT source, destination, weight FROM changes WHERE weight IS NOT NULL \
+         ON CONFLICT (source, destination) \
+         DO UPDATE SET weight = EXCLUDED.weight, updated_at = now()",
+        Some(vec![
+            (PgBuiltInOids::TEXTARRAYOID.oid(), sources.into_datum()),
+            (PgBuiltInOids::TEXTARRAYOID.oid(), destinations.into_datum()),
+            (PgBuiltInOids::FLOAT8ARRAYOID.oid(), weights.into_datum()),
+        ]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))
+}
+
+#[pg_extern]
+/// Writes the graph mutations pending for the `graph` table right away instead of at
+/// the end of the transaction.
+///
+/// # Returns
+///
+/// The number of edges written.
+pub fn meritrank_flush() -> Result<i64, ErrorReport> {
+    Ok(flush()? as i64)
+}
+
+/// Which side wins when `meritrank_sync` finds the in-memory graph and the `graph`
+/// table disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// The in-memory graph is changed to match the table.
+    TableWins,
+    /// The table is changed to match the in-memory graph.
+    MemoryWins,
+}
+
+impl SyncPolicy {
+    /// Parses a policy name: `table-wins` or `memory-wins`.
+    pub fn parse(policy: &str) -> Result<Self, GraphManipulationError> {
+        match policy.to_ascii_lowercase().as_str() {
+            "table-wins" | "table" => Ok(SyncPolicy::TableWins),
+            "memory-wins" | "memory" => Ok(SyncPolicy::MemoryWins),
+            _ => Err(GraphManipulationError::DataExtractionFailure(format!(
+                "unknown sync policy '{}' (expected table-wins or memory-wins)",
+                policy
+            ))),
+        }
+    }
+}
+
+/// Number of edges changed by `meritrank_sync`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncCounts {
+    pub added: i64,
+    pub removed: i64,
+    pub updated: i64,
+}
+
+/// Reads the edges of the `graph` table. Of duplicate rows, the last one read wins.
+fn table_edges() -> Result<HashMap<(String, String), f64>, GraphManipulationError> {
+    Spi::connect(|client| -> Result<_, GraphManipulationError> {
+        let mut cursor = client.open_cursor(
+            "SELECT source, destination, weight::float8 FROM graph",
+            None,
+        );
+        let mut edges = HashMap::new();
+
+        loop {
+            let batch = cursor
+                .fetch(LOAD_BATCH_SIZE)
+                .map_err(|e| GraphManipulationError::FetchRecordsFailure(e.to_string()))?;
+            if batch.is_empty() {
+                break;
+            }
+            for row in batch {
+                let source = row.get::<String>(1)?;
+                let destination = row.get::<String>(2)?;
+                let weight = row.get::<f64>(3)?;
+                // A zero weight means no edge, as in `meritrank_add`
+                if let (Some(source), Some(destination), Some(weight)) =
+                    (source, destination, weight.filter(|&weight| weight != 0.0))
+                {
+                    edges.insert((source, destination), weight);
+                }
+            }
+        }
+
+        Ok(edges)
+    })
+}
+
+/// Reconciles the in-memory graph and the `graph` table according to `policy`.
+///
+/// Pending changes are written to the table first, so they take part in the comparison.
+/// Returns the number of edges added, removed and updated on the losing side.
+pub fn sync(policy: SyncPolicy) -> Result<SyncCounts, GraphManipulationError> {
+    flush()?;
+    let mut table = table_edges()?;
+
+    let mut graph = lock_graph()?;
+    let mut memory = graph.named_edges();
+    // Edges of evicted nodes are missing from memory on purpose
+    memory.retain(|(source, destination), _| !graph.is_evicted_edge(source, destination));
+    table.retain(|(source, destination), _| !graph.is_evicted_edge(source, destination));
+
+    let (winner, loser) = match policy {
+        SyncPolicy::TableWins => (&table, &memory),
+        SyncPolicy::MemoryWins => (&memory, &table),
+    };
+
+    let mut counts = SyncCounts::default();
+    let mut changes: Vec<((String, String), Option<f64>)> = Vec::new();
+    for (edge, &weight) in winner {
+        match loser.get(edge) {
+            None => counts.added += 1,
+            Some(&current) if current != weight => counts.updated += 1,
+            Some(_) => continue,
+        }
+        changes.push((edge.clone(), Some(weight)));
+    }
+    for edge in loser.keys().filter(|edge| !winner.contains_key(*edge)) {
+        counts.removed += 1;
+        changes.push((edge.clone(), None));
+    }
+
+    match policy {
+        SyncPolicy::TableWins => {
+            for ((source, destination), weight) in changes {
+                match weight {
+                    Some(weight) => graph.add_edge(&source, &destination, weight)?,
+                    None => graph.remove_edge(&source, &destination)?,
+                }
+            }
+        }
+        SyncPolicy::MemoryWins => {
+            drop(graph);
+            if !changes.is_empty() {
+                write_edges(changes)?;
+                bump_version()?;
+            }
+        }
+    }
+
+    Ok(counts)
+}
+
+#[pg_extern]
+/// Reconciles the in-memory graph and the `graph` table, e.g. after triggers or
+/// `meritrank.persist_changes` were disabled for a while.
+///
+/// # Arguments
+///
+/// * `policy` - `table-wins` changes the in-memory graph to match the table,
+///   `memory-wins` changes the table to match the in-memory graph.
+///
+/// # Returns
+///
+/// A single row with the number of edges added, removed and updated.
+pub fn meritrank_sync(
+    policy: default!(&str, "'table-wins'"),
+) -> Result<
+    TableIterator<'static, (name!(added, i64), name!(removed, i64), name!(updated, i64))>,
+    ErrorReport,
+> {
+    let counts = sync(SyncPolicy::parse(policy)?)?;
+    Ok(TableIterator::once((
+        counts.added,
+        counts.removed,
+        counts.updated,
+    )))
+}
+
+/// Applies the settings stored in `meritrank_config` to this session.
+///
+/// Settings that were changed in the session, e.g. with `SET`, are left alone. Returns
+/// the number of settings applied.
+pub fn apply_config() -> Result<i64, GraphManipulationError> {
+    Ok(Spi::get_one::<i64>(
+        "SELECT count(set_config(c.name, c.value, false)) \
+         FROM meritrank_config c JOIN pg_settings s USING (name) \
+         WHERE s.source = 'default' AND s.context = 'user'",
+    )
+    .map_err(|e| GraphManipulationError::GraphReadFailure(e.to_string()))?
+    .unwrap_or(0))
+}
+
+#[pg_extern]
+/// Rebuilds the in-memory state of this backend from the extension's tables, e.g. after
+/// the database was restored with `pg_restore`.
+///
+/// The settings in `meritrank_config` are applied, and the graph is discarded and loaded
+/// again from the `graph` table, regardless of `meritrank.auto_load`. Changes that are
+/// still pending are written to the table first.
+///
+/// # Returns
+///
+/// The number of edges loaded.
+pub fn meritrank_restore() -> Result<i64, ErrorReport> {
+    flush()?;
+    apply_config()?;
+
+    // Loading must not trigger a load of its own on first use
+    HYDRATED.store(true, Ordering::SeqCst);
+    GraphSingleton::clear_graph(true)?;
+    walks::lock_store()?.clear();
+    Ok(load_graph_table()?)
+}
+
+extension_sql!(
+    r#"
+CREATE TABLE IF NOT EXISTS meritrank_current_ranks (
+    ego text NOT NULL,
+    node text NOT NULL,
+    rank float8 NOT NULL,
+    graph_version bigint,
+    computed_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (ego, node)
+);
+CREATE INDEX IF NOT EXISTS meritrank_current_ranks_node_idx ON meritrank_current_ranks (node);
+
+CREATE TABLE IF NOT EXISTS meritrank_rank_history (
+    ego text NOT NULL,
+    node text NOT NULL,
+    rank float8 NOT NULL,
+    graph_version bigint,
+    computed_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS meritrank_rank_history_ego_idx
+    ON meritrank_rank_history (ego, graph_version);
+"#,
+    name = "create_current_ranks_table"
+);
+
+/// Replaces the rows of `ego` in `meritrank_current_ranks` with freshly computed ranks,
+/// unless `meritrank.store_ranks` is off or the transaction is read-only or running a
+/// parallel query, in which tables cannot be written. With
+/// `meritrank.rank_history` on, the ranks are also appended to `meritrank_rank_history`.
+pub fn store_ranks(ego: &str, ranks: &[(NodeId, Weight)]) -> Result<(), GraphManipulationError> {
+    if !guc::STORE_RANKS.get() || unsafe { pg_sys::XactReadOnly || pg_sys::IsInParallelMode() } {
+        return Ok(());
+    }
+    write_ranks(ego, ranks)
+}
+
+/// Replaces the rows of `ego` in `meritrank_current_ranks` like `store_ranks`, regardless
+/// of `meritrank.store_ranks`.
+///
+/// The rows are tagged with the version of the `graph` table the ranks were computed on.
+/// Pending changes are written to the table first, so that the version includes them.
+pub fn write_ranks(ego: &str, ranks: &[(NodeId, Weight)]) -> Result<(), GraphManipulationError> {
+    flush()?;
+    let version = known_version();
+
+    let (nodes, scores): (Vec<String>, Vec<f64>) = {
+        let graph = lock_graph()?;
+        ranks
+            .iter()
+            .filter_map(|&(node_id, rank)| {
+                graph
+                    .get_node_name(node_id)
+                    .map(|name| (name.to_string(), rank))
+            })
+            .unzip()
+    };
+
+    Spi::run_with_args(
+        "DELETE FROM meritrank_current_ranks WHERE ego = $1",
+        Some(vec![(PgBuiltInOids::TEXTOID.oid(), ego.into_datum())]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+
+    let history = if guc::RANK_HISTORY.get() {
+        ", history AS ( \
+             INSERT INTO meritrank_rank_history (ego, node, rank, graph_version) \
+             SELECT $1, node, rank, $4 FROM ranks \
+         )"
+    } else {
+        ""
+    };
+    Spi::run_with_args(
+        &format!(
+            "WITH ranks (node, rank) AS ( \
+                 SELECT * FROM unnest($2::text[], $3::float8[]) \
+             ){} \
+             INSERT INTO meritrank_current_ranks (ego, node, rank, graph_version) \
+             SELECT $1, node, rank, $4 FROM ranks",
+            history
+        ),
+        Some(vec![
+            (PgBuiltInOids::TEXTOID.oid(), ego.into_datum()),
+            (PgBuiltInOids::TEXTARRAYOID.oid(), nodes.into_datum()),
+            (PgBuiltInOids::FLOAT8ARRAYOID.oid(), scores.into_datum()),
+            (PgBuiltInOids::INT8OID.oid(), version.into_datum()),
+        ]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))
+}
+
+/// Returns the ranks of `ego` from the latest vector in `meritrank_rank_history` that was
+/// computed at version `since_version` of the `graph` table or earlier, by node name. The
+/// result is empty if there is no such vector.
+pub fn ranks_since(
+    ego: &str,
+    since_version: i64,
+) -> Result<HashMap<String, f64>, GraphManipulationError> {
+    Spi::connect(|client| -> Result<_, GraphManipulationError> {
+        let rows = client
+            .select(
+                "WITH baseline AS ( \
+                     SELECT max(graph_version) AS version FROM meritrank_rank_history \
+                     WHERE ego = $1 AND graph_version <= $2 \
+                 ) \
+                 SELECT DISTINCT ON (h.node) h.node, h.rank \
+                 FROM meritrank_rank_history h JOIN baseline b \
+                     ON h.graph_version = b.version \
+                 WHERE h.ego = $1 \
+                 ORDER BY h.node, h.computed_at DESC",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::TEXTOID.oid(), ego.into_datum()),
+                    (PgBuiltInOids::INT8OID.oid(), since_version.into_datum()),
+                ]),
+            )
+            .map_err(|e| GraphManipulationError::GraphReadFailure(e.to_string()))?;
+
+        let mut ranks = HashMap::new();
+        for row in rows {
+            if let (Some(node), Some(rank)) = (row.get::<String>(1)?, row.get::<f64>(2)?) {
+                ranks.insert(node, rank);
+            }
+        }
+        Ok(ranks)
+    })
+}
@@ -0,0 +1,67 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::lock_graph;
+
+#[pg_extern]
+/// Attaches a key-value property to a node, creating the node if necessary.
+///
+/// Properties are kept next to the node in memory, so they can be fetched together with
+/// ranks, e.g. `SELECT node, rank, meritrank_get_node_property(node, 'display_name')
+/// FROM meritrank_ranks('U1')`.
+///
+/// # Arguments
+///
+/// * `node` - The name of the node.
+/// * `key` - The name of the property.
+/// * `value` - The value of the property. `NULL` removes the property.
+pub fn meritrank_set_node_property(
+    node: &str,
+    key: &str,
+    value: Option<&str>,
+) -> Result<(), ErrorReport> {
+    let mut graph = lock_graph()?;
+    Ok(graph.set_node_property(node, key, value)?)
+}
+
+#[pg_extern]
+/// Returns a property of a node, or `NULL` if the node or the property does not exist.
+pub fn meritrank_get_node_property(node: &str, key: &str) -> Result<Option<String>, ErrorReport> {
+    let graph = lock_graph()?;
+
+    Ok(graph
+        .borrow_node_names()
+        .get(node)
+        .and_then(|&node_id| graph.get_node_properties(node_id))
+        .and_then(|properties| properties.get(key))
+        .cloned())
+}
+
+#[pg_extern]
+/// Lists all properties of a node, sorted by key.
+pub fn meritrank_get_node_properties(
+    node: &str,
+) -> Result<TableIterator<'static, (name!(key, String), name!(value, String))>, ErrorReport> {
+    let graph = lock_graph()?;
+
+    let node_id = graph
+        .borrow_node_names()
+        .get(node)
+        .copied()
+        .ok_or_else(|| GraphManipulationError::NodeNotFound(format!("Node not found: {}", node)))?;
+    let mut properties: Vec<(String, String)> = graph
+        .get_node_properties(node_id)
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+    properties.sort();
+
+    Ok(TableIterator::new(properties.into_iter()))
+}
@@ -0,0 +1,313 @@
+// Standard library imports
+use std::time::Duration;
+
+// Library for PostgreSQL extensions
+use pgx::bgworkers::{BackgroundWorker, BackgroundWorkerBuilder, SignalWakeFlags};
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::batch;
+use crate::error::GraphManipulationError;
+use crate::graph::GraphSingleton;
+use crate::guc;
+use crate::persist;
+use crate::rating::{Alpha, Iterations, Limit, PenaltyFactor};
+
+extension_sql!(
+    r#"
+CREATE TABLE IF NOT EXISTS meritrank_jobs (
+    id bigserial PRIMARY KEY,
+    ego text NOT NULL,
+    iterations integer,
+    status text NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'running', 'done', 'failed')),
+    error text,
+    enqueued_at timestamptz NOT NULL DEFAULT now(),
+    started_at timestamptz,
+    finished_at timestamptz
+);
+CREATE INDEX IF NOT EXISTS meritrank_jobs_queued_idx ON meritrank_jobs (id)
+    WHERE status = 'queued';
+SELECT pg_catalog.pg_extension_config_dump('meritrank_jobs', '');
+SELECT pg_catalog.pg_extension_config_dump('meritrank_jobs_id_seq', '');
+"#,
+    name = "create_jobs_table"
+);
+
+/// Registers the background worker that processes the queue of `meritrank.queue_database`,
+/// if it is set. Must be called while the postmaster loads `shared_preload_libraries`.
+pub fn register_worker() {
+    if guc::QUEUE_DATABASE
+        .get()
+        .map_or(true, |database| database.is_empty())
+    {
+        return;
+    }
+
+    BackgroundWorkerBuilder::new("meritrank queue worker")
+        .set_type("meritrank queue worker")
+        .set_function("meritrank_queue_worker_main")
+        .set_library("pg_meritrank")
+        .enable_spi_access()
+        .set_restart_time(Some(Duration::from_secs(10)))
+        .load();
+}
+
+#[pg_guard]
+#[no_mangle]
+/// Entry point of the queue worker.
+///
+/// The worker has its own in-memory graph, loaded from the `graph` table and reloaded
+/// after other backends change it. It processes jobs one transaction each until the queue
+/// is empty, then sleeps for `meritrank.queue_naptime`.
+pub extern "C" fn meritrank_queue_worker_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi(guc::QUEUE_DATABASE.get(), None);
+    log!("meritrank: queue worker started");
+
+    // Jobs left running belong to a previous run of the worker that failed
+    if let Err(e) = BackgroundWorker::transaction(requeue_interrupted) {
+        warning!("meritrank: queue worker failed to requeue jobs: {}", e);
+    }
+
+    let naptime = || Duration::from_millis(guc::QUEUE_NAPTIME.get() as u64);
+    while BackgroundWorker::wait_latch(Some(naptime())) {
+        while !BackgroundWorker::sigterm_received() {
+            match BackgroundWorker::transaction(process_next) {
+                Ok(true) => continue,
+                Ok(false) => break,
+                Err(e) => {
+                    warning!("meritrank: queue worker failed to process a job: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+    log!("meritrank: queue worker stopped");
+}
+
+/// Puts jobs marked as running back into the queue.
+fn requeue_interrupted() -> Result<(), GraphManipulationError> {
+    Spi::run(
+        "UPDATE meritrank_jobs SET status = 'queued', started_at = NULL \
+         WHERE status = 'running'",
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))
+}
+
+/// Claims the oldest queued job and calculates it in the current transaction.
+///
+/// Jobs are claimed with `FOR UPDATE SKIP LOCKED`, so several backends can process the
+/// queue at once. The ranks are stored in `meritrank_current_ranks`; a calculation that
+/// fails marks its job as failed with the error instead. Returns whether a job was
+/// processed.
+pub fn process_next() -> Result<bool, GraphManipulationError> {
+    let job = Spi::connect(|mut client| -> Result<_, GraphManipulationError> {
+        let rows = client
+            .update(
+                "UPDATE meritrank_jobs SET status = 'running', started_at = now() \
+                 WHERE id = ( \
+                     SELECT id FROM meritrank_jobs WHERE status = 'queued' \
+                     ORDER BY id LIMIT 1 FOR UPDATE SKIP LOCKED \
+                 ) \
+                 RETURNING id, ego, iterations",
+                None,
+                None,
+            )
+            .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+
+        let mut job = None;
+        for row in rows {
+            if let (Some(id), Some(ego)) = (row.get::<i64>(1)?, row.get::<String>(2)?) {
+                job = Some((id, ego, row.get::<i32>(3)?));
+            }
+        }
+        Ok(job)
+    })?;
+    let (id, ego, iterations) = match job {
+        Some(job) => job,
+        None => return Ok(false),
+    };
+
+    let (status, error) = match calculate(&ego, iterations) {
+        Ok(()) => ("done", None),
+        Err(e) => ("failed", Some(e.to_string())),
+    };
+    Spi::run_with_args(
+        "UPDATE meritrank_jobs SET status = $2, error = $3, finished_at = now() WHERE id = $1",
+        Some(vec![
+            (PgBuiltInOids::INT8OID.oid(), id.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), status.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), error.into_datum()),
+        ]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+    Ok(true)
+}
+
+/// Calculates the ranks of `ego` with the default parameters and stores them.
+fn calculate(ego: &str, iterations: Option<i32>) -> Result<(), GraphManipulationError> {
+    let iterations = Iterations::from_nullable(iterations)?;
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+    let results = batch::rank_configured(
+        &snapshot,
+        &[ego_id],
+        iterations,
+        Limit::default(),
+        PenaltyFactor::default(),
+        Alpha::default(),
+    )?;
+    for (_, ranks) in results {
+        persist::write_ranks(ego, &ranks)?;
+    }
+    Ok(())
+}
+
+#[pg_extern]
+/// Queues the calculation of the ranks of `ego`, to be processed outside the current
+/// transaction, e.g. to keep heavy calculations out of web requests.
+///
+/// The queue is processed by the background worker of `meritrank.queue_database`, or by
+/// calling `meritrank_process_queue()`. The job is visible to them once the current
+/// transaction commits. Poll it with `meritrank_job_status(id)`, and read the ranks with
+/// `meritrank_result(ego)` once it is done.
+///
+/// # Arguments
+///
+/// * `ego` - The name of the ego.
+/// * `iterations` - The number of walks. Defaults to `DEFAULT_ITERATIONS` when processed.
+///
+/// # Returns
+///
+/// The ID of the job.
+pub fn meritrank_enqueue(
+    ego: &str,
+    iterations: default!(Option<i32>, "NULL"),
+) -> Result<i64, ErrorReport> {
+    // Validate arguments before queueing
+    Iterations::from_nullable(iterations)?;
+
+    Ok(Spi::get_one_with_args::<i64>(
+        "INSERT INTO meritrank_jobs (ego, iterations) VALUES ($1, $2) RETURNING id",
+        vec![
+            (PgBuiltInOids::TEXTOID.oid(), ego.into_datum()),
+            (PgBuiltInOids::INT4OID.oid(), iterations.into_datum()),
+        ],
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?
+    .unwrap_or(0))
+}
+
+#[pg_extern]
+/// Returns the state of a queued calculation: `queued`, `running`, `done` or `failed`,
+/// with the error of a failed one.
+pub fn meritrank_job_status(
+    id: i64,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(status, String),
+            name!(ego, String),
+            name!(error, Option<String>),
+            name!(enqueued_at, TimestampWithTimeZone),
+            name!(started_at, Option<TimestampWithTimeZone>),
+            name!(finished_at, Option<TimestampWithTimeZone>),
+        ),
+    >,
+    ErrorReport,
+> {
+    let job = Spi::connect(|client| -> Result<_, GraphManipulationError> {
+        let rows = client
+            .select(
+                "SELECT status, ego, error, enqueued_at, started_at, finished_at \
+                 FROM meritrank_jobs WHERE id = $1",
+                None,
+                Some(vec![(PgBuiltInOids::INT8OID.oid(), id.into_datum())]),
+            )
+            .map_err(|e| GraphManipulationError::GraphReadFailure(e.to_string()))?;
+
+        let mut job = None;
+        for row in rows {
+            if let (Some(status), Some(ego), Some(enqueued_at)) = (
+                row.get::<String>(1)?,
+                row.get::<String>(2)?,
+                row.get::<TimestampWithTimeZone>(4)?,
+            ) {
+                job = Some((
+                    status,
+                    ego,
+                    row.get::<String>(3)?,
+                    enqueued_at,
+                    row.get::<TimestampWithTimeZone>(5)?,
+                    row.get::<TimestampWithTimeZone>(6)?,
+                ));
+            }
+        }
+        Ok(job)
+    })?;
+
+    let job = job.ok_or_else(|| GraphManipulationError::JobNotFound(id.to_string()))?;
+    Ok(TableIterator::once(job))
+}
+
+#[pg_extern]
+/// Returns the ranks of `ego` stored in `meritrank_current_ranks`, e.g. by a queued
+/// calculation, sorted in descending order. The result is empty until ranks of `ego` were
+/// stored.
+pub fn meritrank_result(
+    ego: &str,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(node, String),
+            name!(rank, f64),
+            name!(computed_at, TimestampWithTimeZone),
+            name!(graph_version, Option<i64>),
+        ),
+    >,
+    ErrorReport,
+> {
+    let rows = Spi::connect(|client| -> Result<_, GraphManipulationError> {
+        let rows = client
+            .select(
+                "SELECT node, rank, computed_at, graph_version FROM meritrank_current_ranks \
+                 WHERE ego = $1 ORDER BY rank DESC, node",
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), ego.into_datum())]),
+            )
+            .map_err(|e| GraphManipulationError::GraphReadFailure(e.to_string()))?;
+
+        let mut ranks = Vec::new();
+        for row in rows {
+            if let (Some(node), Some(rank), Some(computed_at)) = (
+                row.get::<String>(1)?,
+                row.get::<f64>(2)?,
+                row.get::<TimestampWithTimeZone>(3)?,
+            ) {
+                ranks.push((node, rank, computed_at, row.get::<i64>(4)?));
+            }
+        }
+        Ok(ranks)
+    })?;
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+#[pg_extern]
+/// Processes up to `max_jobs` queued calculations in the current transaction, e.g. from a
+/// scheduled job where no background worker runs.
+///
+/// # Returns
+///
+/// The number of jobs processed, including failed ones.
+pub fn meritrank_process_queue(max_jobs: default!(i32, 100)) -> Result<i64, ErrorReport> {
+    let mut processed = 0;
+    while processed < max_jobs as i64 && process_next()? {
+        processed += 1;
+    }
+    Ok(processed)
+}
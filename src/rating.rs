@@ -0,0 +1,257 @@
+// Standard library imports
+use std::time::{Duration, Instant};
+
+// Library for PostgreSQL extensions
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::guc;
+
+/// Number of walks used when `iterations` is passed as `NULL`.
+pub const DEFAULT_ITERATIONS: usize = 1000;
+
+/// Validated number of random walks to perform for a calculation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Iterations(usize);
+
+impl Iterations {
+    /// Creates a new `Iterations` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::InvalidIterations()` if the value is zero or negative.
+    pub fn new(value: i32) -> Result<Self, GraphManipulationError> {
+        if value <= 0 {
+            return Err(GraphManipulationError::InvalidIterations(format!(
+                "{} (must be a positive integer)",
+                value
+            )));
+        }
+        Ok(Iterations(value as usize))
+    }
+
+    /// Creates a new `Iterations` value from a nullable SQL argument.
+    /// `NULL` falls back to `DEFAULT_ITERATIONS`.
+    pub fn from_nullable(value: Option<i32>) -> Result<Self, GraphManipulationError> {
+        value.map_or_else(|| Ok(Self::default()), Self::new)
+    }
+
+    /// Returns the number of walks.
+    pub fn get(&self) -> usize {
+        self.0
+    }
+}
+
+impl Default for Iterations {
+    fn default() -> Self {
+        Iterations(DEFAULT_ITERATIONS)
+    }
+}
+
+/// Validated maximum number of results to return. `None` means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limit(Option<usize>);
+
+impl Limit {
+    /// Creates a new `Limit` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::InvalidLimit()` if the value is zero or negative.
+    pub fn new(value: i32) -> Result<Self, GraphManipulationError> {
+        if value <= 0 {
+            return Err(GraphManipulationError::InvalidLimit(format!(
+                "{} (must be a positive integer)",
+                value
+            )));
+        }
+        Ok(Limit(Some(value as usize)))
+    }
+
+    /// Creates a new `Limit` value from a nullable SQL argument.
+    /// `NULL` means no limit.
+    pub fn from_nullable(value: Option<i32>) -> Result<Self, GraphManipulationError> {
+        value.map_or_else(|| Ok(Self::default()), Self::new)
+    }
+
+    /// Returns the limit, or `None` if unlimited.
+    pub fn get(&self) -> Option<usize> {
+        self.0
+    }
+}
+
+/// Validated multiplier applied to penalties from negative edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PenaltyFactor(f64);
+
+impl PenaltyFactor {
+    /// Creates a new `PenaltyFactor` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::InvalidPenaltyFactor()` if the value is negative or not finite.
+    pub fn new(value: f64) -> Result<Self, GraphManipulationError> {
+        if !value.is_finite() || value < 0.0 {
+            return Err(GraphManipulationError::InvalidPenaltyFactor(format!(
+                "{} (must be a non-negative number)",
+                value
+            )));
+        }
+        Ok(PenaltyFactor(value))
+    }
+
+    /// Creates a new `PenaltyFactor` value from a nullable SQL argument.
+    /// `NULL` falls back to the `meritrank.penalty_factor` setting.
+    pub fn from_nullable(value: Option<f64>) -> Result<Self, GraphManipulationError> {
+        value.map_or_else(|| Ok(Self::default()), Self::new)
+    }
+
+    /// Returns the multiplier.
+    pub fn get(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Default for PenaltyFactor {
+    fn default() -> Self {
+        PenaltyFactor(guc::PENALTY_FACTOR.get())
+    }
+}
+
+/// Validated probability of a random walk continuing with another step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alpha(f64);
+
+impl Alpha {
+    /// Creates a new `Alpha` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::InvalidAlpha()` if the value is not in `(0, 1)`.
+    pub fn new(value: f64) -> Result<Self, GraphManipulationError> {
+        if !(value > 0.0 && value < 1.0) {
+            return Err(GraphManipulationError::InvalidAlpha(format!(
+                "{} (must be greater than 0 and less than 1)",
+                value
+            )));
+        }
+        Ok(Alpha(value))
+    }
+
+    /// Creates a new `Alpha` value from a nullable SQL argument.
+    /// `NULL` falls back to the `meritrank.alpha` setting.
+    pub fn from_nullable(value: Option<f64>) -> Result<Self, GraphManipulationError> {
+        value.map_or_else(|| Ok(Self::default()), Self::new)
+    }
+
+    /// Returns the probability.
+    pub fn get(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Default for Alpha {
+    fn default() -> Self {
+        Alpha(guc::ALPHA.get())
+    }
+}
+
+/// Default probability of following an edge rather than jumping (or restarting).
+pub const DEFAULT_DAMPING: f64 = 0.85;
+
+/// Validated probability of following an edge in PageRank-style calculations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DampingFactor(f64);
+
+impl DampingFactor {
+    /// Creates a new `DampingFactor` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::InvalidDampingFactor()` if the value is not in `[0, 1)`.
+    pub fn new(value: f64) -> Result<Self, GraphManipulationError> {
+        if !(0.0..1.0).contains(&value) {
+            return Err(GraphManipulationError::InvalidDampingFactor(format!(
+                "{} (must be at least 0 and less than 1)",
+                value
+            )));
+        }
+        Ok(DampingFactor(value))
+    }
+
+    /// Creates a new `DampingFactor` value from a nullable SQL argument.
+    /// `NULL` falls back to `DEFAULT_DAMPING`.
+    pub fn from_nullable(value: Option<f64>) -> Result<Self, GraphManipulationError> {
+        value.map_or_else(|| Ok(Self::default()), Self::new)
+    }
+
+    /// Returns the probability.
+    pub fn get(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Default for DampingFactor {
+    fn default() -> Self {
+        DampingFactor(DEFAULT_DAMPING)
+    }
+}
+
+/// Validated time budget of a calculation, after which it returns the ranks of the walks
+/// generated so far. `None` means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimeBudget(Option<Duration>);
+
+impl TimeBudget {
+    /// Creates a new `TimeBudget` value from a number of milliseconds.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GraphManipulationError::InvalidTimeBudget()` if the value is zero or negative.
+    pub fn new(max_ms: i32) -> Result<Self, GraphManipulationError> {
+        if max_ms <= 0 {
+            return Err(GraphManipulationError::InvalidTimeBudget(format!(
+                "{} (must be a positive number of milliseconds)",
+                max_ms
+            )));
+        }
+        Ok(TimeBudget(Some(Duration::from_millis(max_ms as u64))))
+    }
+
+    /// Creates a new `TimeBudget` value from a nullable SQL argument.
+    /// `NULL` means no budget.
+    pub fn from_nullable(max_ms: Option<i32>) -> Result<Self, GraphManipulationError> {
+        max_ms.map_or_else(|| Ok(Self::default()), Self::new)
+    }
+
+    /// Returns the budget, or `None` if unlimited.
+    pub fn get(&self) -> Option<Duration> {
+        self.0
+    }
+
+    /// Returns the moment a calculation starting now runs out of the budget.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.0.map(|budget| Instant::now() + budget)
+    }
+}
+
+/// Freshness stamp attached to score outputs, so that downstream caches can tell
+/// whether a stored score was computed from the current graph.
+#[derive(Debug, Clone, Copy)]
+pub struct Freshness {
+    pub computed_at: Option<TimestampWithTimeZone>,
+    pub graph_generation: i64,
+}
+
+impl Freshness {
+    /// Stamps a result computed now from the given graph generation.
+    pub fn now(graph_generation: u64) -> Self {
+        let computed_at =
+            TimestampWithTimeZone::try_from(unsafe { pg_sys::GetCurrentTimestamp() }).ok();
+        Freshness {
+            computed_at,
+            graph_generation: graph_generation as i64,
+        }
+    }
+}
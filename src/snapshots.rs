@@ -0,0 +1,190 @@
+// Standard library imports
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+// External crate imports
+use lazy_static::lazy_static;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::lock_graph;
+use crate::lib_graph::Weight;
+
+/// Edges of a graph keyed by `(source, destination)` node names.
+pub type EdgeSet = HashMap<(String, String), Weight>;
+
+lazy_static! {
+    static ref SNAPSHOTS: Mutex<HashMap<String, EdgeSet>> = Mutex::new(HashMap::new());
+}
+
+fn lock_snapshots() -> Result<MutexGuard<'static, HashMap<String, EdgeSet>>, GraphManipulationError>
+{
+    SNAPSHOTS
+        .lock()
+        .map_err(|e| GraphManipulationError::MutexLockFailure(format!("Mutex lock error: {}", e)))
+}
+
+/// Kind of difference of an edge between two edge sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl EdgeChangeKind {
+    /// Returns the name of the change as reported by `meritrank_diff`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeChangeKind::Added => "added",
+            EdgeChangeKind::Removed => "removed",
+            EdgeChangeKind::Changed => "changed",
+        }
+    }
+}
+
+/// A difference of an edge between two edge sets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeChange {
+    pub source: String,
+    pub destination: String,
+    pub kind: EdgeChangeKind,
+    pub old_weight: Option<Weight>,
+    pub new_weight: Option<Weight>,
+}
+
+/// Compares two edge sets, returning the changes from `old` to `new` sorted by
+/// source and destination.
+pub fn diff(old: &EdgeSet, new: &EdgeSet) -> Vec<EdgeChange> {
+    let mut changes: Vec<EdgeChange> = new
+        .iter()
+        .filter_map(|((source, destination), &weight)| {
+            let (kind, old_weight) = match old.get(&(source.clone(), destination.clone())) {
+                None => (EdgeChangeKind::Added, None),
+                Some(&old_weight) if old_weight != weight => {
+                    (EdgeChangeKind::Changed, Some(old_weight))
+                }
+                Some(_) => return None,
+            };
+            Some(EdgeChange {
+                source: source.clone(),
+                destination: destination.clone(),
+                kind,
+                old_weight,
+                new_weight: Some(weight),
+            })
+        })
+        .chain(old.iter().filter(|(edge, _)| !new.contains_key(*edge)).map(
+            |((source, destination), &weight)| EdgeChange {
+                source: source.clone(),
+                destination: destination.clone(),
+                kind: EdgeChangeKind::Removed,
+                old_weight: Some(weight),
+                new_weight: None,
+            },
+        ))
+        .collect();
+
+    changes.sort_by(|a, b| (&a.source, &a.destination).cmp(&(&b.source, &b.destination)));
+    changes
+}
+
+#[pg_extern]
+/// Captures the edges of the in-memory graph under `name`, e.g. before a bulk import,
+/// replacing an earlier snapshot of the same name.
+///
+/// Snapshots are kept in the memory of this backend.
+///
+/// # Returns
+///
+/// The number of edges captured.
+pub fn meritrank_snapshot(name: &str) -> Result<i64, ErrorReport> {
+    let edges = lock_graph()?.named_edges();
+    let count = edges.len() as i64;
+    lock_snapshots()?.insert(name.to_string(), edges);
+    Ok(count)
+}
+
+#[pg_extern]
+/// Drops a snapshot. Returns whether it existed.
+pub fn meritrank_drop_snapshot(name: &str) -> Result<bool, ErrorReport> {
+    Ok(lock_snapshots()?.remove(name).is_some())
+}
+
+#[pg_extern]
+/// Lists the snapshots of this backend with their number of edges.
+pub fn meritrank_snapshots(
+) -> Result<TableIterator<'static, (name!(name, String), name!(edges, i64))>, ErrorReport> {
+    let mut snapshots: Vec<(String, i64)> = lock_snapshots()?
+        .iter()
+        .map(|(name, edges)| (name.clone(), edges.len() as i64))
+        .collect();
+    snapshots.sort();
+    Ok(TableIterator::new(snapshots.into_iter()))
+}
+
+#[pg_extern]
+/// Returns the edges added, removed and changed between two snapshots.
+///
+/// # Arguments
+///
+/// * `snapshot_a` - The name of the earlier snapshot.
+/// * `snapshot_b` - The name of the later snapshot. When `NULL`, the current in-memory
+///   graph is compared instead.
+///
+/// # Returns
+///
+/// One row per differing edge with its change (`added`, `removed` or `changed`) and
+/// its weight in either snapshot, `NULL` where the edge is absent.
+pub fn meritrank_diff(
+    snapshot_a: &str,
+    snapshot_b: default!(Option<&str>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(source, String),
+            name!(destination, String),
+            name!(change, String),
+            name!(old_weight, Option<f64>),
+            name!(new_weight, Option<f64>),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Take the current edges before locking the snapshots
+    let current = match snapshot_b {
+        Some(_) => None,
+        None => Some(lock_graph()?.named_edges()),
+    };
+
+    let snapshots = lock_snapshots()?;
+    let find = |name: &str| {
+        snapshots
+            .get(name)
+            .ok_or_else(|| GraphManipulationError::SnapshotNotFound(name.to_string()))
+    };
+    let old = find(snapshot_a)?;
+    let new = match (snapshot_b, current.as_ref()) {
+        (_, Some(current)) => current,
+        (snapshot_b, None) => find(snapshot_b.unwrap_or_default())?,
+    };
+
+    let changes: Vec<_> = diff(old, new)
+        .into_iter()
+        .map(|change| {
+            (
+                change.source,
+                change.destination,
+                change.kind.name().to_string(),
+                change.old_weight,
+                change.new_weight,
+            )
+        })
+        .collect();
+    Ok(TableIterator::new(changes.into_iter()))
+}
@@ -0,0 +1,358 @@
+// Standard library imports
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Instant;
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::cache;
+use crate::error::GraphManipulationError;
+use crate::graph::{current_timestamp, lock_graph, timestamp_to_pg, GraphSingleton};
+use crate::guc;
+use crate::lib_graph::{MeritRank, NodeId, Weight};
+use crate::rating::{Alpha, Iterations};
+use crate::walks;
+
+// Cumulative counters of this backend, reset by `meritrank_stats_reset()`
+static CALCULATIONS: AtomicU64 = AtomicU64::new(0);
+static WALKS_GENERATED: AtomicU64 = AtomicU64::new(0);
+static WALK_NANOS: AtomicU64 = AtomicU64::new(0);
+static SNAPSHOT_HITS: AtomicU64 = AtomicU64::new(0);
+static SNAPSHOT_MISSES: AtomicU64 = AtomicU64::new(0);
+static EVICTED_WALK_EGOS: AtomicU64 = AtomicU64::new(0);
+static EVICTED_WALKS: AtomicU64 = AtomicU64::new(0);
+
+// Counters of the current epoch of this backend, restarted by `meritrank_epoch_advance()`
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+static EPOCH_STARTED_AT: AtomicI64 = AtomicI64::new(0);
+static EPOCH_CALCULATIONS: AtomicU64 = AtomicU64::new(0);
+static EPOCH_WALKS_GENERATED: AtomicU64 = AtomicU64::new(0);
+static EPOCH_WALK_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Records whether a graph snapshot was reused or had to be copied.
+pub fn record_snapshot(reused: bool) {
+    if reused {
+        SNAPSHOT_HITS.fetch_add(1, Ordering::Relaxed);
+    } else {
+        SNAPSHOT_MISSES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records that the walks of `egos` egos, `walks` walks in total, were dropped to stay
+/// within `meritrank.max_walk_memory`.
+pub fn record_walk_eviction(egos: usize, walks: usize) {
+    EVICTED_WALK_EGOS.fetch_add(egos as u64, Ordering::Relaxed);
+    EVICTED_WALKS.fetch_add(walks as u64, Ordering::Relaxed);
+}
+
+/// Calculates the ranks of `ego`, recording the call in the runtime statistics.
+/// Returns the number of walks used, which is less than `iterations` if the
+/// calculation converged early.
+pub fn calculate(
+    merit_rank: &mut MeritRank,
+    ego: NodeId,
+    iterations: Iterations,
+) -> Result<usize, GraphManipulationError> {
+    let start = Instant::now();
+    let walks = merit_rank.calculate(ego, iterations.get())?;
+    let nanos = start.elapsed().as_nanos() as u64;
+
+    CALCULATIONS.fetch_add(1, Ordering::Relaxed);
+    WALKS_GENERATED.fetch_add(walks as u64, Ordering::Relaxed);
+    WALK_NANOS.fetch_add(nanos, Ordering::Relaxed);
+    EPOCH_CALCULATIONS.fetch_add(1, Ordering::Relaxed);
+    EPOCH_WALKS_GENERATED.fetch_add(walks as u64, Ordering::Relaxed);
+    EPOCH_WALK_NANOS.fetch_add(nanos, Ordering::Relaxed);
+    Ok(walks)
+}
+
+#[pg_extern]
+/// Returns the number of nodes in the in-memory graph of this backend.
+pub fn meritrank_node_count() -> Result<i64, ErrorReport> {
+    let graph = lock_graph()?;
+    Ok(graph.borrow_graph().node_count() as i64)
+}
+
+#[pg_extern]
+/// Returns the number of edges in the in-memory graph of this backend.
+pub fn meritrank_edge_count() -> Result<i64, ErrorReport> {
+    let graph = lock_graph()?;
+    Ok(graph.borrow_graph().edge_count() as i64)
+}
+
+#[pg_extern]
+/// Returns runtime statistics of the extension in this backend.
+///
+/// `max_nodes` and `occupancy` are `NULL` when `meritrank.max_nodes` is unlimited.
+/// `calculations`, `walks_generated` and `walk_time_ms` count rank calculations that were
+/// not served from the rank cache. `snapshot_hits` and `snapshot_misses` count graph
+/// snapshots that were reused and copied, respectively. `evicted_walk_egos` and
+/// `evicted_walks` count the egos and walks dropped to stay within
+/// `meritrank.max_walk_memory`.
+pub fn meritrank_stats() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(nodes, i64),
+            name!(edges, i64),
+            name!(max_nodes, Option<i64>),
+            name!(occupancy, Option<f64>),
+            name!(evicted_nodes, i64),
+            name!(graph_generation, i64),
+            name!(calculations, i64),
+            name!(walks_generated, i64),
+            name!(walk_time_ms, f64),
+            name!(cache_entries, i64),
+            name!(cache_hits, i64),
+            name!(cache_misses, i64),
+            name!(snapshot_hits, i64),
+            name!(snapshot_misses, i64),
+            name!(evicted_walk_egos, i64),
+            name!(evicted_walks, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let graph = lock_graph()?;
+    let cache = cache::lock_cache()?;
+
+    let nodes = graph.borrow_graph().node_count() as i64;
+    let edges = graph.borrow_graph().edge_count() as i64;
+    let max_nodes = Some(guc::MAX_NODES.get() as i64).filter(|&max_nodes| max_nodes > 0);
+    let occupancy = max_nodes.map(|max_nodes| nodes as f64 / max_nodes as f64);
+
+    Ok(TableIterator::once((
+        nodes,
+        edges,
+        max_nodes,
+        occupancy,
+        graph.get_evicted_nodes() as i64,
+        graph.get_generation() as i64,
+        CALCULATIONS.load(Ordering::Relaxed) as i64,
+        WALKS_GENERATED.load(Ordering::Relaxed) as i64,
+        WALK_NANOS.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        cache.len() as i64,
+        cache.hits() as i64,
+        cache.misses() as i64,
+        SNAPSHOT_HITS.load(Ordering::Relaxed) as i64,
+        SNAPSHOT_MISSES.load(Ordering::Relaxed) as i64,
+        EVICTED_WALK_EGOS.load(Ordering::Relaxed) as i64,
+        EVICTED_WALKS.load(Ordering::Relaxed) as i64,
+    )))
+}
+
+#[pg_extern]
+/// Resets the cumulative calculation, cache, snapshot and eviction counters reported by
+/// `meritrank_stats()`.
+pub fn meritrank_stats_reset() -> Result<(), ErrorReport> {
+    CALCULATIONS.store(0, Ordering::Relaxed);
+    WALKS_GENERATED.store(0, Ordering::Relaxed);
+    WALK_NANOS.store(0, Ordering::Relaxed);
+    SNAPSHOT_HITS.store(0, Ordering::Relaxed);
+    SNAPSHOT_MISSES.store(0, Ordering::Relaxed);
+    EVICTED_WALK_EGOS.store(0, Ordering::Relaxed);
+    EVICTED_WALKS.store(0, Ordering::Relaxed);
+    cache::lock_cache()?.reset_stats();
+    Ok(())
+}
+
+#[pg_extern]
+/// Returns the current epoch of this backend and the calculations made during it.
+///
+/// Epochs segment the calculation statistics of long-running backends by time period:
+/// `meritrank_epoch_advance()` ends the current epoch and starts the next one. The first
+/// epoch is 0 and lasts from the start of the backend, so its `started_at` is `NULL`.
+/// Unlike `meritrank_stats()`, the counters are not affected by `meritrank_stats_reset()`.
+pub fn meritrank_epoch_current() -> TableIterator<
+    'static,
+    (
+        name!(epoch, i64),
+        name!(started_at, Option<TimestampWithTimeZone>),
+        name!(calculations, i64),
+        name!(walks_generated, i64),
+        name!(walk_time_ms, f64),
+    ),
+> {
+    let started_at = Some(EPOCH_STARTED_AT.load(Ordering::Relaxed))
+        .filter(|&started_at| started_at > 0)
+        .and_then(timestamp_to_pg);
+
+    TableIterator::once((
+        EPOCH.load(Ordering::Relaxed) as i64,
+        started_at,
+        EPOCH_CALCULATIONS.load(Ordering::Relaxed) as i64,
+        EPOCH_WALKS_GENERATED.load(Ordering::Relaxed) as i64,
+        EPOCH_WALK_NANOS.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+    ))
+}
+
+#[pg_extern]
+/// Ends the current epoch of this backend and starts the next one with zeroed counters.
+///
+/// # Returns
+///
+/// The number of the new epoch.
+pub fn meritrank_epoch_advance() -> i64 {
+    EPOCH_CALCULATIONS.store(0, Ordering::Relaxed);
+    EPOCH_WALKS_GENERATED.store(0, Ordering::Relaxed);
+    EPOCH_WALK_NANOS.store(0, Ordering::Relaxed);
+    EPOCH_STARTED_AT.store(current_timestamp(), Ordering::Relaxed);
+    EPOCH.fetch_add(1, Ordering::Relaxed) as i64 + 1
+}
+
+/// Nanoseconds a walk step is assumed to take while this backend has not timed any walks.
+const DEFAULT_STEP_NANOS: f64 = 250.0;
+
+/// Returns the expected number of nodes of a walk, including the ego, when every step
+/// continues with probability `continuation` and walks are cut off after
+/// `max_walk_length` nodes. `continuation` is less than 1, since alpha is.
+fn expected_walk_length(continuation: f64, max_walk_length: Option<usize>) -> f64 {
+    let truncated = max_walk_length.map_or(0.0, |length| continuation.powi(length as i32));
+    (1.0 - truncated) / (1.0 - continuation)
+}
+
+#[pg_extern]
+/// Estimates the cost of calculating the ranks of `ego` without running any walks, e.g.
+/// so that a scheduler can send expensive requests to background processing.
+///
+/// Walks only reach the subgraph reachable from `ego` over positive edges, so it is
+/// measured first. A walk continues with probability `alpha` at every node with positive
+/// out-edges and ends at the others, which gives the expected length of a walk. The time
+/// is based on the mean time per walk of the calculations this backend made so far, or
+/// on a fixed cost per step before the first one. The memory covers the copy of the
+/// graph, the walks and the hit counters of the calculation.
+///
+/// # Arguments
+///
+/// * `ego` - The name of the ego.
+/// * `iterations` - The number of walks. Defaults to `DEFAULT_ITERATIONS`.
+/// * `alpha` - The probability of a walk continuing with another step.
+pub fn meritrank_estimate(
+    ego: &str,
+    iterations: default!(Option<i32>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(reachable_nodes, i64),
+            name!(reachable_edges, i64),
+            name!(mean_out_degree, f64),
+            name!(expected_walk_length, f64),
+            name!(estimated_ms, f64),
+            name!(estimated_bytes, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+
+    let mut reached: HashSet<NodeId> = HashSet::from([ego_id]);
+    let mut queue: VecDeque<NodeId> = VecDeque::from([ego_id]);
+    let mut edges = 0;
+    let mut walkable = 0;
+    while let Some(node) = queue.pop_front() {
+        let targets: Vec<NodeId> = snapshot
+            .graph
+            .edges(node)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&(_, _, weight)| weight > 0.0)
+            .map(|(_, target, _)| target)
+            .collect();
+        if !targets.is_empty() {
+            walkable += 1;
+        }
+        edges += targets.len();
+        for target in targets {
+            if reached.insert(target) {
+                queue.push_back(target);
+            }
+        }
+    }
+    let nodes = reached.len();
+
+    let max_walk_length = Some(guc::MAX_WALK_LENGTH.get())
+        .filter(|&length| length > 0)
+        .map(|length| length as usize);
+    let continuation = alpha.get() * walkable as f64 / nodes as f64;
+    let walk_length = expected_walk_length(continuation, max_walk_length);
+
+    let walks = iterations.get() as f64;
+    let measured_walks = WALKS_GENERATED.load(Ordering::Relaxed);
+    let walk_nanos = if measured_walks > 0 {
+        WALK_NANOS.load(Ordering::Relaxed) as f64 / measured_walks as f64
+    } else {
+        DEFAULT_STEP_NANOS * walk_length / guc::PARALLELISM.get() as f64
+    };
+
+    // Every step is stored in its walk and indexed under the node it visits
+    let step_bytes = std::mem::size_of::<NodeId>() + 2 * std::mem::size_of::<usize>();
+    let counter_bytes = std::mem::size_of::<NodeId>() + std::mem::size_of::<Weight>() + 1;
+    let bytes = snapshot.graph.memory_usage() as f64
+        + walks * walk_length * step_bytes as f64
+        + (nodes * counter_bytes) as f64;
+
+    Ok(TableIterator::once((
+        nodes as i64,
+        edges as i64,
+        edges as f64 / nodes as f64,
+        walk_length,
+        walks * walk_nanos / 1_000_000.0,
+        bytes as i64,
+    )))
+}
+
+#[pg_extern]
+/// Returns an estimate of the memory held by the extension in this backend, per component.
+///
+/// The components are `graph` (the graph structure), `node_names` (the map from node
+/// names to IDs), `node_properties` (metadata attached to nodes), `csr` (the frozen copy
+/// of the graph built by `meritrank_freeze`), `rank_cache` (the cached rank vectors) and
+/// `walk_storage` (the random walks kept between calculations), followed by their `total`.
+pub fn meritrank_memory_usage(
+) -> Result<TableIterator<'static, (name!(component, String), name!(bytes, i64))>, ErrorReport> {
+    let graph = lock_graph()?;
+    let cache = cache::lock_cache()?;
+
+    let mut rows = vec![
+        ("graph", graph.borrow_graph().memory_usage()),
+        ("node_names", graph.node_names_memory_usage()),
+        ("node_properties", graph.node_properties_memory_usage()),
+        ("csr", graph.csr_memory_usage()),
+        ("rank_cache", cache.memory_usage()),
+    ];
+    drop(cache);
+    drop(graph);
+
+    // The walk store keeps its own copy of the graph next to the walks
+    let store = walks::lock_store()?;
+    rows.push((
+        "walk_storage",
+        store.get().map_or(0, |merit_rank| {
+            merit_rank.get_graph().memory_usage() + merit_rank.walks_memory_usage()
+        }),
+    ));
+    let total = rows.iter().map(|&(_, bytes)| bytes).sum();
+    rows.push(("total", total));
+
+    Ok(TableIterator::new(rows.into_iter().map(
+        |(component, bytes)| (component.to_string(), bytes as i64),
+    )))
+}
+
+extension_sql!(
+    r#"
+CREATE VIEW pg_meritrank_stats AS SELECT * FROM meritrank_stats();
+"#,
+    name = "create_stats_view",
+    requires = [meritrank_stats]
+);
@@ -0,0 +1,362 @@
+// Standard library imports
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// External crate imports
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::lock_graph;
+use crate::lib_graph::{NodeId, Weight};
+
+/// Most pairs of neighbors checked per node when estimating the clustering coefficient;
+/// beyond that, pairs are sampled.
+const MAX_NEIGHBOR_PAIRS: usize = 1000;
+
+/// Which edges of a node `meritrank_neighbors` follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Edges leaving the node: the nodes it voted for.
+    Outgoing,
+    /// Edges entering the node: the nodes that voted for it.
+    Incoming,
+    /// Both.
+    Both,
+}
+
+impl Direction {
+    /// Parses a direction: `out`, `in` or `both`.
+    pub fn parse(direction: &str) -> Result<Self, GraphManipulationError> {
+        match direction.to_ascii_lowercase().as_str() {
+            "out" | "outgoing" => Ok(Direction::Outgoing),
+            "in" | "incoming" => Ok(Direction::Incoming),
+            "both" => Ok(Direction::Both),
+            _ => Err(GraphManipulationError::DataExtractionFailure(format!(
+                "unknown direction '{}' (expected out, in or both)",
+                direction
+            ))),
+        }
+    }
+}
+
+#[pg_extern]
+/// Labels every node with the weakly connected component it belongs to, ignoring edge
+/// direction, to spot vote islands that never receive rank from the main cluster.
+///
+/// Components are numbered from 1 by descending size, so component 1 is the largest.
+///
+/// # Returns
+///
+/// One row per node with its component and the number of nodes in that component,
+/// sorted by component and node name.
+pub fn meritrank_components() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(node, String),
+            name!(component_id, i64),
+            name!(component_size, i64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let graph = lock_graph()?;
+    let components = graph.borrow_graph().connected_components();
+
+    let mut sizes: HashMap<usize, i64> = HashMap::new();
+    for &component in components.values() {
+        *sizes.entry(component).or_insert(0) += 1;
+    }
+
+    let mut rows: Vec<(String, i64, i64)> = components
+        .into_iter()
+        .filter_map(|(node_id, component)| {
+            let name = graph.get_node_name(node_id)?;
+            Some((name.to_string(), component as i64, sizes[&component]))
+        })
+        .collect();
+    rows.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+/// Number and summed weight of the incoming and outgoing edges of a node.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Degrees {
+    pub in_degree: i64,
+    pub out_degree: i64,
+    pub in_weight: f64,
+    pub out_weight: f64,
+}
+
+#[pg_extern]
+/// Returns the in- and out-degree of every node of the in-memory graph, together with
+/// the summed weight of its incoming and outgoing edges, sorted by node name.
+pub fn meritrank_degrees() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(node, String),
+            name!(in_degree, i64),
+            name!(out_degree, i64),
+            name!(in_weight, f64),
+            name!(out_weight, f64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let graph = lock_graph()?;
+
+    let mut degrees: HashMap<NodeId, Degrees> = graph
+        .borrow_graph()
+        .node_ids()
+        .into_iter()
+        .map(|node_id| (node_id, Degrees::default()))
+        .collect();
+    for (source, target, weight) in graph.borrow_graph().all_edges() {
+        let out = degrees.entry(source).or_default();
+        out.out_degree += 1;
+        out.out_weight += weight;
+        let incoming = degrees.entry(target).or_default();
+        incoming.in_degree += 1;
+        incoming.in_weight += weight;
+    }
+
+    let mut rows: Vec<(String, i64, i64, f64, f64)> = degrees
+        .into_iter()
+        .filter_map(|(node_id, d)| {
+            let name = graph.get_node_name(node_id)?;
+            Some((
+                name.to_string(),
+                d.in_degree,
+                d.out_degree,
+                d.in_weight,
+                d.out_weight,
+            ))
+        })
+        .collect();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+#[pg_extern]
+/// Returns the direct neighbors of a node with the weights of the connecting edges,
+/// e.g. to show "who you voted for" (`out`) and "who voted for you" (`in`).
+///
+/// # Arguments
+///
+/// * `node` - The node whose neighbors are returned.
+/// * `direction` - `out`, `in` or `both`.
+///
+/// # Returns
+///
+/// One row per edge with the neighbor, the direction of the edge as seen from `node`
+/// (`out` or `in`) and its weight; outgoing edges come first, each sorted by neighbor name.
+pub fn meritrank_neighbors(
+    node: &str,
+    direction: default!(&str, "'both'"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(neighbor, String),
+            name!(direction, String),
+            name!(weight, f64),
+        ),
+    >,
+    ErrorReport,
+> {
+    let direction = Direction::parse(direction)?;
+    let graph = lock_graph()?;
+    let node_id = graph
+        .borrow_node_names()
+        .get(node)
+        .copied()
+        .ok_or_else(|| GraphManipulationError::NodeNotFound(format!("Node not found: {}", node)))?;
+
+    let mut edges: Vec<(&str, NodeId, f64)> = Vec::new();
+    if direction != Direction::Incoming {
+        let outgoing = graph.borrow_graph().edges(node_id).unwrap_or_default();
+        edges.extend(
+            outgoing
+                .into_iter()
+                .map(|(_, target, weight)| ("out", target, weight)),
+        );
+    }
+    if direction != Direction::Outgoing {
+        let incoming = graph.borrow_graph().incoming_edges(node_id);
+        edges.extend(
+            incoming
+                .into_iter()
+                .map(|(source, weight)| ("in", source, weight)),
+        );
+    }
+
+    let mut rows: Vec<(String, String, f64)> = edges
+        .into_iter()
+        .filter_map(|(direction, neighbor, weight)| {
+            let name = graph.get_node_name(neighbor)?;
+            Some((name.to_string(), direction.to_string(), weight))
+        })
+        .collect();
+    // Outgoing edges first
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    Ok(TableIterator::new(rows.into_iter()))
+}
+
+/// Returns the `q` quantile of sorted values, interpolating linearly between ranks.
+fn quantile(sorted: &[Weight], q: f64) -> Option<Weight> {
+    let last = sorted.len().checked_sub(1)?;
+    let position = q * last as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64))
+}
+
+/// Estimates the average local clustering coefficient of the graph, ignoring edge
+/// direction and weights, over `samples` nodes chosen at random. For nodes with many
+/// neighbors, the share of connected neighbor pairs is estimated from
+/// `MAX_NEIGHBOR_PAIRS` random pairs. Nodes with fewer than two neighbors count as 0.
+fn clustering_coefficient(
+    adjacency: &BTreeMap<NodeId, BTreeSet<NodeId>>,
+    samples: usize,
+    rng: &mut StdRng,
+) -> f64 {
+    let nodes: Vec<&NodeId> = adjacency.keys().collect();
+    let sampled: Vec<&&NodeId> = nodes.choose_multiple(rng, samples).collect();
+    if sampled.is_empty() {
+        return 0.0;
+    }
+
+    let mut total = 0.0;
+    for node in &sampled {
+        let neighbors: Vec<&NodeId> = adjacency[**node].iter().collect();
+        let k = neighbors.len();
+        if k < 2 {
+            continue;
+        }
+        let connected = |a: &NodeId, b: &NodeId| adjacency[a].contains(b);
+
+        let pairs = k * (k - 1) / 2;
+        total += if pairs <= MAX_NEIGHBOR_PAIRS {
+            let mut links = 0;
+            for i in 0..k {
+                for j in (i + 1)..k {
+                    if connected(neighbors[i], neighbors[j]) {
+                        links += 1;
+                    }
+                }
+            }
+            links as f64 / pairs as f64
+        } else {
+            let mut links = 0;
+            for _ in 0..MAX_NEIGHBOR_PAIRS {
+                let i = rng.gen_range(0..k);
+                let j = (i + rng.gen_range(1..k)) % k;
+                if connected(neighbors[i], neighbors[j]) {
+                    links += 1;
+                }
+            }
+            links as f64 / MAX_NEIGHBOR_PAIRS as f64
+        };
+    }
+    total / sampled.len() as f64
+}
+
+#[pg_extern]
+/// Returns summary metrics of the in-memory graph, e.g. to gate features on how mature
+/// the graph is.
+///
+/// `density` is the share of possible directed edges that exist, and `average_degree`
+/// the mean number of outgoing edges per node. Weight quantiles are over all edges,
+/// including negative ones. `clustering_coefficient` is the average local clustering
+/// coefficient of the graph with directions ignored, estimated from `samples` nodes;
+/// results are repeatable for the same `seed`.
+pub fn meritrank_graph_metrics(
+    samples: default!(i32, 1000),
+    seed: default!(i64, 0),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(nodes, i64),
+            name!(edges, i64),
+            name!(density, f64),
+            name!(average_degree, f64),
+            name!(negative_edges, i64),
+            name!(weight_min, Option<f64>),
+            name!(weight_p25, Option<f64>),
+            name!(weight_median, Option<f64>),
+            name!(weight_p75, Option<f64>),
+            name!(weight_p95, Option<f64>),
+            name!(weight_max, Option<f64>),
+            name!(clustering_coefficient, f64),
+        ),
+    >,
+    ErrorReport,
+> {
+    if samples < 1 {
+        return Err(GraphManipulationError::InvalidLimit(format!(
+            "samples must be positive, got {}",
+            samples
+        ))
+        .into());
+    }
+
+    let (node_ids, edges) = {
+        let graph = lock_graph()?;
+        (
+            graph.borrow_graph().node_ids(),
+            graph.borrow_graph().all_edges(),
+        )
+    };
+
+    let nodes = node_ids.len() as f64;
+    let density = if node_ids.len() > 1 {
+        edges.len() as f64 / (nodes * (nodes - 1.0))
+    } else {
+        0.0
+    };
+    let average_degree = if node_ids.is_empty() {
+        0.0
+    } else {
+        edges.len() as f64 / nodes
+    };
+
+    let mut weights: Vec<Weight> = edges.iter().map(|&(_, _, weight)| weight).collect();
+    weights.sort_by(|a, b| a.total_cmp(b));
+    let negative_edges = weights.iter().filter(|&&weight| weight < 0.0).count() as i64;
+
+    let mut adjacency: BTreeMap<NodeId, BTreeSet<NodeId>> = node_ids
+        .iter()
+        .map(|&node_id| (node_id, BTreeSet::new()))
+        .collect();
+    for &(source, target, _) in &edges {
+        adjacency.entry(source).or_default().insert(target);
+        adjacency.entry(target).or_default().insert(source);
+    }
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    let clustering = clustering_coefficient(&adjacency, samples as usize, &mut rng);
+
+    Ok(TableIterator::once((
+        node_ids.len() as i64,
+        edges.len() as i64,
+        density,
+        average_degree,
+        negative_edges,
+        quantile(&weights, 0.0),
+        quantile(&weights, 0.25),
+        quantile(&weights, 0.5),
+        quantile(&weights, 0.75),
+        quantile(&weights, 0.95),
+        quantile(&weights, 1.0),
+        clustering,
+    )))
+}
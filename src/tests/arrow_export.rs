@@ -0,0 +1,74 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::arrow_export::{meritrank_export_arrow, meritrank_export_ranks_arrow};
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use arrow_array::{Float64Array, StringArray};
+    use arrow_ipc::reader::FileReader;
+    use pgx::*;
+    use std::fs::File;
+
+    #[pg_test]
+    fn test_export_arrow() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("arrow_a", "arrow_b", 2.0).unwrap();
+        meritrank_add("arrow_a", "arrow_c", 1.0).unwrap();
+        meritrank_add("arrow_b", "arrow_a", 3.0).unwrap();
+
+        let path = std::env::temp_dir().join("meritrank_test_edges.arrow");
+        let path = path.to_str().unwrap();
+        assert_eq!(meritrank_export_arrow(path).unwrap(), 3);
+
+        let reader = FileReader::try_new(File::open(path).unwrap(), None).unwrap();
+        let batches: Vec<_> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.schema().field(0).name(), "source");
+
+        let sources = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let destinations = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let weights = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(sources.value(0), "arrow_a");
+        assert_eq!(destinations.value(0), "arrow_b");
+        assert_eq!(weights.value(0), 2.0);
+        assert_eq!(sources.value(2), "arrow_b");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[pg_test]
+    fn test_export_ranks_arrow() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("arrow_a", "arrow_b", 1.0).unwrap();
+        meritrank_add("arrow_b", "arrow_a", 1.0).unwrap();
+
+        let path = std::env::temp_dir().join("meritrank_test_ranks.arrow");
+        let path = path.to_str().unwrap();
+        let rows =
+            meritrank_export_ranks_arrow(path, vec!["arrow_a".to_string()], Some(100), false)
+                .unwrap();
+        assert!(rows > 0);
+
+        let reader = FileReader::try_new(File::open(path).unwrap(), None).unwrap();
+        let written: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(written as i64, rows);
+
+        assert!(
+            meritrank_export_ranks_arrow(path, vec!["arrow_x".to_string()], None, false).is_err()
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
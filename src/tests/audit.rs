@@ -0,0 +1,58 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{meritrank_add, meritrank_clear, meritrank_delete};
+    use pgx::*;
+
+    fn audit_rows() -> Vec<(String, Option<f64>, Option<f64>)> {
+        Spi::connect(|client| {
+            client
+                .select(
+                    "SELECT op, old_weight, new_weight FROM meritrank_audit \
+                     WHERE subject = 'audit_a' OR subject IS NULL ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|row| {
+                    (
+                        row.get::<String>(1).unwrap().unwrap(),
+                        row.get::<f64>(2).unwrap(),
+                        row.get::<f64>(3).unwrap(),
+                    )
+                })
+                .collect()
+        })
+    }
+
+    #[pg_test]
+    fn test_audit() {
+        meritrank_clear(true).unwrap();
+        Spi::run("DELETE FROM meritrank_audit").unwrap();
+        meritrank_add("audit_a", "audit_b", 1.0).unwrap();
+        assert!(audit_rows().is_empty());
+
+        Spi::run("SET meritrank.audit = on").unwrap();
+        meritrank_add("audit_a", "audit_b", 2.0).unwrap();
+        meritrank_add("audit_a", "audit_c", 1.0).unwrap();
+        meritrank_delete("audit_a", "audit_c").unwrap();
+        // Deleting a missing edge records nothing
+        meritrank_delete("audit_a", "audit_c").unwrap();
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.audit = off").unwrap();
+
+        assert_eq!(
+            audit_rows(),
+            vec![
+                ("update".to_string(), Some(1.0), Some(2.0)),
+                ("add".to_string(), None, Some(1.0)),
+                ("delete".to_string(), Some(1.0), None),
+                ("clear".to_string(), None, None),
+            ]
+        );
+        let changed_by: Option<bool> =
+            Spi::get_one("SELECT bool_and(changed_by = current_user) FROM meritrank_audit")
+                .unwrap();
+        assert_eq!(changed_by, Some(true));
+    }
+}
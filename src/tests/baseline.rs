@@ -0,0 +1,76 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::baseline::{hits_calculate, pagerank_calculate, ppr_calculate};
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_pagerank() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("pr_a", "pr_b", 1.0).unwrap();
+        meritrank_add("pr_b", "pr_c", 1.0).unwrap();
+        meritrank_add("pr_c", "pr_a", 1.0).unwrap();
+
+        // A cycle spreads the score evenly
+        let scores: Vec<_> = pagerank_calculate(None, None).unwrap().collect();
+        assert_eq!(scores.len(), 3);
+        for (_, score) in scores.iter() {
+            assert!((score - 1.0 / 3.0).abs() < 1e-9);
+        }
+
+        // A node that receives more votes ranks first
+        meritrank_add("pr_a", "pr_c", 1.0).unwrap();
+        let scores: Vec<_> = pagerank_calculate(Some(0.85), Some(50)).unwrap().collect();
+        assert_eq!(scores[0].0, "pr_c");
+        let total: f64 = scores.iter().map(|(_, score)| score).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        assert!(pagerank_calculate(Some(1.5), None).is_err());
+    }
+
+    #[pg_test]
+    fn test_personalized_pagerank() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("ppr_a", "ppr_b", 1.0).unwrap();
+        meritrank_add("ppr_b", "ppr_c", 1.0).unwrap();
+        meritrank_add("ppr_x", "ppr_a", 1.0).unwrap();
+
+        let scores: Vec<_> = ppr_calculate("ppr_a", Some(0.5), Some(1e-12))
+            .unwrap()
+            .collect();
+        let score = |node: &str| {
+            scores
+                .iter()
+                .find(|(name, _)| name == node)
+                .map(|(_, score)| *score)
+        };
+
+        // ppr_c is dangling and restarts at the ego, so a = 1/2 + c/2, b = a/2, c = b/2
+        assert!((score("ppr_a").unwrap() - 4.0 / 7.0).abs() < 1e-9);
+        assert!((score("ppr_b").unwrap() - 2.0 / 7.0).abs() < 1e-9);
+        assert!((score("ppr_c").unwrap() - 1.0 / 7.0).abs() < 1e-9);
+        // Nodes that cannot be reached from the ego get no score
+        assert_eq!(score("ppr_x"), None);
+
+        assert!(ppr_calculate("ppr_a", None, Some(0.0)).is_err());
+        assert!(ppr_calculate("ppr_missing", None, None).is_err());
+    }
+
+    #[pg_test]
+    fn test_hits() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("hits_hub", "hits_x", 1.0).unwrap();
+        meritrank_add("hits_hub", "hits_y", 1.0).unwrap();
+        meritrank_add("hits_other", "hits_x", 1.0).unwrap();
+
+        let scores: Vec<_> = hits_calculate(None).unwrap().collect();
+        // hits_x is voted for by both hubs, so it is the top authority
+        assert_eq!(scores[0].0, "hits_x");
+        let hub = |node: &str| scores.iter().find(|row| row.0 == node).unwrap().1;
+        assert!(hub("hits_hub") > hub("hits_other"));
+        assert_eq!(hub("hits_x"), 0.0);
+
+        assert!(hits_calculate(Some(0)).is_err());
+    }
+}
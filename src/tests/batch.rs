@@ -0,0 +1,115 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::batch::{meritrank_calculate_many, meritrank_matrix, meritrank_scores_many};
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_calculate_many() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("many_a", "many_b", 1.0).unwrap();
+        meritrank_add("many_a", "many_c", 1.0).unwrap();
+        meritrank_add("many_b", "many_c", 1.0).unwrap();
+
+        let egos = vec![
+            "many_a".to_string(),
+            "many_b".to_string(),
+            "many_a".to_string(),
+        ];
+        let rows: Vec<_> = meritrank_calculate_many(egos.clone(), Some(200), None, None, None)
+            .unwrap()
+            .collect();
+        // Repeated egos are calculated once, in the order given
+        let mut calculated: Vec<&str> = rows.iter().map(|(ego, _, _)| ego.as_str()).collect();
+        calculated.dedup();
+        assert_eq!(calculated, vec!["many_a", "many_b"]);
+        assert!(rows
+            .iter()
+            .any(|(ego, node, rank)| ego == "many_a" && node == "many_c" && *rank > 0.0));
+
+        let limited = meritrank_calculate_many(egos, Some(200), Some(1), None, None)
+            .unwrap()
+            .count();
+        assert_eq!(limited, 2);
+
+        assert!(
+            meritrank_calculate_many(vec!["many_x".to_string()], None, None, None, None).is_err()
+        );
+    }
+
+    #[pg_test]
+    fn test_scores_many() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("scores_a", "scores_b", 1.0).unwrap();
+        meritrank_add("scores_a", "scores_c", 1.0).unwrap();
+        meritrank_add("scores_b", "scores_c", 1.0).unwrap();
+
+        let egos = vec!["scores_a".to_string(), "scores_b".to_string()];
+        for parallel in [false, true] {
+            let mut pairs: Vec<(String, String)> =
+                meritrank_scores_many(egos.clone(), Some(200), parallel)
+                    .unwrap()
+                    .filter(|(_, _, rank)| *rank > 0.0)
+                    .map(|(ego, node, _)| (ego, node))
+                    .collect();
+            pairs.sort();
+            // Every ego reaches the peers its edges lead to
+            for (ego, node) in [
+                ("scores_a", "scores_b"),
+                ("scores_a", "scores_c"),
+                ("scores_b", "scores_c"),
+            ] {
+                assert!(pairs.contains(&(ego.to_string(), node.to_string())));
+            }
+            assert!(!pairs.contains(&("scores_b".to_string(), "scores_a".to_string())));
+        }
+
+        // The settings of meritrank_ranks apply, so seeded results match
+        // meritrank_calculate_many whether or not the walks are split between threads
+        Spi::run("SET meritrank.seed = 7").unwrap();
+        let mut expected: Vec<_> =
+            meritrank_calculate_many(egos.clone(), Some(200), None, None, None)
+                .unwrap()
+                .collect();
+        expected.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        for parallel in [false, true] {
+            let mut rows: Vec<_> = meritrank_scores_many(egos.clone(), Some(200), parallel)
+                .unwrap()
+                .collect();
+            rows.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+            assert_eq!(rows, expected);
+        }
+        Spi::run("RESET meritrank.seed").unwrap();
+
+        assert!(meritrank_scores_many(vec!["scores_x".to_string()], None, false).is_err());
+    }
+
+    #[pg_test]
+    fn test_matrix() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("matrix_a", "matrix_b", 1.0).unwrap();
+        meritrank_add("matrix_b", "matrix_c", 1.0).unwrap();
+
+        let nodes = vec![
+            "matrix_a".to_string(),
+            "matrix_b".to_string(),
+            "matrix_c".to_string(),
+        ];
+        let matrix: Vec<_> = meritrank_matrix(nodes, Some(200), None, None)
+            .unwrap()
+            .collect();
+        assert_eq!(matrix.len(), 9);
+        let score = |ego: &str, target: &str| {
+            matrix
+                .iter()
+                .find(|(e, t, _)| e == ego && t == target)
+                .map(|&(_, _, score)| score)
+                .unwrap()
+        };
+        assert!(score("matrix_a", "matrix_c") > 0.0);
+        // Edges only lead forward, so nothing reaches back
+        assert_eq!(score("matrix_b", "matrix_a"), 0.0);
+        assert_eq!(score("matrix_c", "matrix_b"), 0.0);
+    }
+}
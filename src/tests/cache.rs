@@ -0,0 +1,107 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::cache::{meritrank_cache_stats, RankCache, RankCacheEntry, RankCacheKey};
+    use crate::graph::{meritrank_add, meritrank_calculate, meritrank_clear, meritrank_warmup};
+    use crate::lib_graph::NodeId;
+    use crate::rating::{Alpha, Iterations, PenaltyFactor};
+    use pgx::*;
+
+    fn key(ego: usize) -> RankCacheKey {
+        RankCacheKey::new(
+            NodeId::UInt(ego),
+            Iterations::default(),
+            PenaltyFactor::new(1.0).unwrap(),
+            Alpha::default(),
+            false,
+        )
+    }
+
+    #[pg_test]
+    fn test_rank_cache_evicts_least_recently_used() {
+        let mut cache = RankCache::new();
+        cache.insert(
+            key(1),
+            RankCacheEntry::new(vec![(NodeId::UInt(1), 1.0)], 0),
+            2,
+        );
+        cache.insert(
+            key(2),
+            RankCacheEntry::new(vec![(NodeId::UInt(2), 1.0)], 0),
+            2,
+        );
+        assert!(cache.get(&key(1)).is_some());
+        cache.insert(
+            key(3),
+            RankCacheEntry::new(vec![(NodeId::UInt(3), 1.0)], 0),
+            2,
+        );
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key(2)).is_none());
+        assert!(cache.get(&key(1)).is_some());
+    }
+
+    #[pg_test]
+    fn test_rank_cache_invalidates_visited_nodes() {
+        let mut cache = RankCache::new();
+        cache.insert(
+            key(1),
+            RankCacheEntry::new(vec![(NodeId::UInt(1), 0.5), (NodeId::UInt(4), 0.5)], 0),
+            10,
+        );
+        cache.insert(
+            key(2),
+            RankCacheEntry::new(vec![(NodeId::UInt(2), 1.0)], 0),
+            10,
+        );
+
+        assert_eq!(cache.invalidate_node(NodeId::UInt(4)), 1);
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache.get(&key(2)).is_some());
+    }
+
+    #[pg_test]
+    fn test_meritrank_calculate_uses_cache() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("cache_a", "cache_b", 1.0).unwrap();
+
+        let first =
+            meritrank_calculate("cache_a", "cache_b", Some(100), None, None, false, None).unwrap();
+        let second =
+            meritrank_calculate("cache_a", "cache_b", Some(100), None, None, false, None).unwrap();
+        assert_eq!(first, second);
+
+        let (entries, _, hits, _) = meritrank_cache_stats().unwrap().next().unwrap();
+        assert_eq!(entries, 1);
+        assert!(hits >= 1);
+
+        meritrank_add("cache_b", "cache_c", 1.0).unwrap();
+        let (entries, _, _, _) = meritrank_cache_stats().unwrap().next().unwrap();
+        assert_eq!(entries, 0);
+    }
+
+    #[pg_test]
+    fn test_warmup() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("warm_a", "warm_b", 1.0).unwrap();
+        meritrank_add("warm_b", "warm_c", 1.0).unwrap();
+
+        let egos = vec![
+            "warm_a".to_string(),
+            "warm_b".to_string(),
+            "warm_x".to_string(),
+        ];
+        let rows: Vec<_> = meritrank_warmup(egos, Some(100)).unwrap().collect();
+        // Unknown egos are skipped
+        let warmed: Vec<&str> = rows.iter().map(|(ego, _, _)| ego.as_str()).collect();
+        assert_eq!(warmed, vec!["warm_a", "warm_b"]);
+        let (entries, _, hits, _) = meritrank_cache_stats().unwrap().next().unwrap();
+        assert_eq!(entries, 2);
+
+        // The first request is served from the cache
+        meritrank_calculate("warm_a", "warm_c", Some(100), None, None, false, None).unwrap();
+        let (_, _, hits_after, _) = meritrank_cache_stats().unwrap().next().unwrap();
+        assert_eq!(hits_after, hits + 1);
+    }
+}
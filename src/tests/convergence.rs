@@ -0,0 +1,66 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::convergence::{
+        kendall_tau, l1_change, meritrank_calculate_verbose, meritrank_ranks_with_error,
+    };
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use crate::lib_graph::NodeId;
+    use pgx::*;
+    use std::collections::HashMap;
+
+    #[pg_test]
+    fn test_convergence_metrics() {
+        let (a, b, c) = (NodeId::UInt(1), NodeId::UInt(2), NodeId::UInt(3));
+        let previous: HashMap<NodeId, f64> = [(a, 0.5), (b, 0.3)].into_iter().collect();
+        let current: HashMap<NodeId, f64> = [(a, 0.4), (c, 0.1)].into_iter().collect();
+        assert!((l1_change(&previous, &current) - 0.5).abs() < 1e-9);
+
+        assert_eq!(kendall_tau(&previous, &[(a, 0.5), (b, 0.3)]), Some(1.0));
+        assert_eq!(kendall_tau(&previous, &[(b, 0.5), (a, 0.3)]), Some(-1.0));
+        assert_eq!(kendall_tau(&previous, &[(a, 0.5)]), None);
+    }
+
+    #[pg_test]
+    fn test_calculate_verbose() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("verbose_a", "verbose_b", 2.0).unwrap();
+        meritrank_add("verbose_a", "verbose_c", 1.0).unwrap();
+
+        let rows: Vec<_> = meritrank_calculate_verbose("verbose_a", Some(1000), 300, 3)
+            .unwrap()
+            .collect();
+        let walks: Vec<i64> = rows.iter().map(|row| row.1).collect();
+        assert_eq!(walks, vec![300, 600, 900, 1000]);
+        assert_eq!((rows[0].2, rows[0].3), (None, None));
+        assert!(rows[3].2.unwrap() >= 0.0);
+        assert!(rows[3].3.is_some());
+
+        assert!(meritrank_calculate_verbose("verbose_a", None, 0, 3).is_err());
+        assert!(meritrank_calculate_verbose("verbose_x", None, 100, 3).is_err());
+    }
+
+    #[pg_test]
+    fn test_ranks_with_error() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("error_a", "error_b", 2.0).unwrap();
+        meritrank_add("error_a", "error_c", 1.0).unwrap();
+
+        let narrow: Vec<_> = meritrank_ranks_with_error("error_a", Some(10000), None, None, None)
+            .unwrap()
+            .collect();
+        let wide: Vec<_> = meritrank_ranks_with_error("error_a", Some(100), None, None, None)
+            .unwrap()
+            .collect();
+        assert_eq!(narrow.len(), 3);
+        for (_, rank, std_error, ci_low, ci_high) in &narrow {
+            assert!(*std_error >= 0.0);
+            assert!(ci_low <= rank && rank <= ci_high);
+        }
+
+        let error_of = |rows: &[(String, f64, f64, f64, f64)], node: &str| {
+            rows.iter().find(|row| row.0 == node).unwrap().2
+        };
+        assert!(error_of(&narrow, "error_b") < error_of(&wide, "error_b"));
+    }
+}
@@ -0,0 +1,95 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::explain::{meritrank_detect_bridges, meritrank_explain, meritrank_hits};
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_detect_bridges() {
+        meritrank_clear(true).unwrap();
+        for (subject, object) in [
+            ("bridge_ego", "bridge_h1"),
+            ("bridge_ego", "bridge_h2"),
+            ("bridge_h1", "bridge_h2"),
+            ("bridge_h2", "bridge_h1"),
+            ("bridge_h1", "bridge_s1"),
+            ("bridge_s1", "bridge_s2"),
+            ("bridge_s2", "bridge_s1"),
+        ] {
+            meritrank_add(subject, object, 1.0).unwrap();
+        }
+
+        let rows: Vec<_> = meritrank_detect_bridges("bridge_ego", 0.9, Some(2000))
+            .unwrap()
+            .collect();
+        let mut nodes: Vec<&str> = rows.iter().map(|row| row.0.as_str()).collect();
+        nodes.sort();
+        assert_eq!(nodes, vec!["bridge_s1", "bridge_s2"]);
+        for (_, rank, source, target, share) in &rows {
+            assert!(*rank > 0.0);
+            assert_eq!(
+                (source.as_str(), target.as_str()),
+                ("bridge_h1", "bridge_s1")
+            );
+            assert_eq!(*share, 1.0);
+        }
+
+        assert!(meritrank_detect_bridges("bridge_ego", 0.0, None).is_err());
+        assert!(meritrank_detect_bridges("bridge_ego", 1.5, None).is_err());
+    }
+
+    #[pg_test]
+    fn test_hits() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("hits_a", "hits_b", 1.0).unwrap();
+        meritrank_add("hits_a", "hits_c", -1.0).unwrap();
+        meritrank_add("hits_b", "hits_c", 1.0).unwrap();
+
+        let rows: Vec<_> = meritrank_hits("hits_a", Some(500), Some(1.0))
+            .unwrap()
+            .collect();
+        let ego = rows.iter().find(|row| row.0 == "hits_a").unwrap();
+        assert_eq!(ego.1, 500);
+
+        for (node, hits, negative_hits, total_hits, rank) in &rows {
+            let expected = (*hits as f64 + negative_hits) / *total_hits as f64;
+            assert!((rank - expected).abs() < 1e-9, "{}", node);
+        }
+        let c = rows.iter().find(|row| row.0 == "hits_c").unwrap();
+        assert!(c.2 < 0.0);
+    }
+
+    #[pg_test]
+    fn test_explain() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("explain_ego", "explain_mid", 1.0).unwrap();
+        meritrank_add("explain_mid", "explain_target", 2.0).unwrap();
+        meritrank_add("explain_ego", "explain_target", 1.0).unwrap();
+
+        let rows: Vec<_> = meritrank_explain("explain_ego", "explain_target", Some(1000))
+            .unwrap()
+            .collect();
+        let direct = rows
+            .iter()
+            .find(|row| row.0 == vec!["explain_ego", "explain_target"])
+            .unwrap();
+        assert_eq!(direct.2, 1.0);
+        let indirect = rows
+            .iter()
+            .find(|row| row.0 == vec!["explain_ego", "explain_mid", "explain_target"])
+            .unwrap();
+        assert_eq!(indirect.2, 3.0);
+
+        for (path, walks, _, contribution) in &rows {
+            assert_eq!(path.first().unwrap(), "explain_ego");
+            assert_eq!(path.last().unwrap(), "explain_target");
+            assert!(*walks > 0);
+            assert!(*contribution > 0.0 && *contribution <= 1.0);
+        }
+        let total: f64 = rows.iter().map(|row| row.3).sum();
+        assert!(total <= 1.0 + 1e-9);
+
+        assert!(meritrank_explain("explain_ego", "explain_missing", None).is_err());
+    }
+}
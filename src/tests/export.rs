@@ -0,0 +1,58 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::export::{meritrank_export_dot, meritrank_export_graphml};
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_export_graphml() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("graphml_a", "graphml_<b>", 2.0).unwrap();
+
+        let graphml = meritrank_export_graphml().unwrap();
+        assert!(graphml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml "));
+        assert!(graphml.contains("<graph id=\"meritrank\" edgedefault=\"directed\">"));
+        assert!(
+            graphml.contains("    <node id=\"n1\"><data key=\"name\">graphml_a</data></node>\n")
+        );
+        // Names are escaped
+        assert!(graphml
+            .contains("    <node id=\"n2\"><data key=\"name\">graphml_&lt;b&gt;</data></node>\n"));
+        assert!(graphml.contains(
+            "    <edge source=\"n1\" target=\"n2\"><data key=\"weight\">2</data></edge>\n"
+        ));
+        assert!(graphml.ends_with("  </graph>\n</graphml>\n"));
+
+        // Every node and edge is listed once
+        assert_eq!(graphml.matches("<node ").count(), 2);
+        assert_eq!(graphml.matches("<edge ").count(), 1);
+    }
+
+    #[pg_test]
+    fn test_export_dot() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("dot_a", "dot_b", 1.0).unwrap();
+        meritrank_add("dot_b", "dot_\"c\"", 2.0).unwrap();
+
+        let dot = meritrank_export_dot(None).unwrap();
+        assert_eq!(
+            dot,
+            "digraph meritrank {\n\
+             \x20 n1 [label=\"dot_a\"];\n\
+             \x20 n2 [label=\"dot_b\"];\n\
+             \x20 n3 [label=\"dot_\\\"c\\\"\"];\n\
+             \x20 n1 -> n2 [label=\"1\"];\n\
+             \x20 n2 -> n3 [label=\"2\"];\n\
+             }\n"
+        );
+
+        // Ranked nodes are labelled with their rank and filled in green
+        let dot = meritrank_export_dot(Some("dot_a")).unwrap();
+        assert!(dot.contains("  n2 [label=\"dot_b\\n0."));
+        assert!(dot.contains("style=filled, fillcolor=\"0.330 "));
+        assert!(dot.contains("  n1 -> n2 [label=\"1\"];\n"));
+
+        assert!(meritrank_export_dot(Some("dot_missing")).is_err());
+    }
+}
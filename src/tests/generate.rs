@@ -0,0 +1,58 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::generate::meritrank_generate_graph;
+    use crate::graph::meritrank_clear;
+    use crate::persist::flush;
+    use crate::snapshots::{meritrank_diff, meritrank_drop_snapshot, meritrank_snapshot};
+    use crate::stats::{meritrank_edge_count, meritrank_node_count};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_generate_models() {
+        meritrank_clear(true).unwrap();
+        let edges = meritrank_generate_graph(10, "random", 1.0, 2, 4, 0.1, Some(1), "R").unwrap();
+        assert_eq!(edges, 90);
+
+        meritrank_clear(true).unwrap();
+        let edges =
+            meritrank_generate_graph(50, "barabasi_albert", 0.1, 2, 4, 0.1, Some(1), "B").unwrap();
+        assert_eq!(edges, 96);
+        assert_eq!(meritrank_edge_count().unwrap(), 96);
+
+        meritrank_clear(true).unwrap();
+        let edges =
+            meritrank_generate_graph(20, "watts_strogatz", 0.1, 2, 4, 0.2, Some(1), "W").unwrap();
+        assert_eq!(edges, 80);
+        assert_eq!(meritrank_node_count().unwrap(), 20);
+
+        assert!(meritrank_generate_graph(20, "watts_strogatz", 0.1, 2, 3, 0.2, None, "W").is_err());
+        assert!(meritrank_generate_graph(20, "lattice", 0.1, 2, 4, 0.2, None, "W").is_err());
+    }
+
+    #[pg_test]
+    fn test_generate_persisted() {
+        meritrank_clear(true).unwrap();
+        let edges = meritrank_generate_graph(10, "random", 1.0, 2, 4, 0.1, Some(1), "P").unwrap();
+
+        // The edges are mirrored to the graph table like those of meritrank_add
+        flush().unwrap();
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*) FROM graph WHERE source LIKE 'P%'").unwrap(),
+            Some(edges)
+        );
+    }
+
+    #[pg_test]
+    fn test_generate_seed() {
+        meritrank_clear(true).unwrap();
+        meritrank_generate_graph(30, "barabasi_albert", 0.1, 3, 4, 0.1, Some(42), "S").unwrap();
+        meritrank_snapshot("generated").unwrap();
+
+        meritrank_clear(true).unwrap();
+        meritrank_generate_graph(30, "barabasi_albert", 0.1, 3, 4, 0.1, Some(42), "S").unwrap();
+        assert_eq!(meritrank_diff("generated", None).unwrap().count(), 0);
+
+        meritrank_drop_snapshot("generated").unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::meritrank_clear;
+    use crate::graph_type::{meritrank_calculate_on, meritrank_graph, MeritGraph};
+    use crate::stats::meritrank_edge_count;
+    use pgx::*;
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[pg_test]
+    fn test_graph_type() {
+        meritrank_clear(true).unwrap();
+        let graph = meritrank_graph(
+            strings(&["tenant_a", "tenant_b", "tenant_a"]),
+            strings(&["tenant_b", "tenant_c", "tenant_b"]),
+            vec![1.0, 1.0, 2.0],
+        )
+        .unwrap();
+        assert_eq!(
+            graph,
+            MeritGraph::new(vec![
+                ("tenant_a".to_string(), "tenant_b".to_string(), 2.0),
+                ("tenant_b".to_string(), "tenant_c".to_string(), 1.0),
+            ])
+            .unwrap()
+        );
+        assert!(meritrank_graph(strings(&["tenant_a"]), strings(&[]), vec![1.0]).is_err());
+        assert!(
+            meritrank_graph(strings(&["tenant_a"]), strings(&["tenant_a"]), vec![1.0]).is_err()
+        );
+
+        let rank = meritrank_calculate_on(graph.clone(), "tenant_a", "tenant_c", None, None, None)
+            .unwrap();
+        assert!(rank > 0.0);
+        assert!(meritrank_calculate_on(graph, "tenant_a", "tenant_x", None, None, None).is_err());
+
+        // Values live in ordinary columns and round-trip through their text form
+        Spi::run(
+            "CREATE TEMP TABLE tenant_graphs AS \
+             SELECT 't1' AS tenant, meritrank_graph( \
+                 ARRAY['a', 'b'], ARRAY['b', 'c'], ARRAY[1, 1]::float8[] \
+             ) AS graph",
+        )
+        .unwrap();
+        let rank: Option<f64> = Spi::get_one(
+            "SELECT meritrank_calculate_on(graph::text::meritgraph, 'a', 'c') \
+             FROM tenant_graphs WHERE tenant = 't1'",
+        )
+        .unwrap();
+        assert!(rank.unwrap() > 0.0);
+
+        // The in-memory graph is not touched
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+    }
+}
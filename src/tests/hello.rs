@@ -19,4 +19,16 @@ mod tests {
         assert_eq!("Hello, hello_world", result);
         println!("Test hello_hello_world_spi passed.");
     }
+
+    #[pg_test]
+    fn insert_and_trigger_quotes_names() {
+        crate::insert_and_trigger("o'brien", "x', 0); DROP TABLE graph; --", 1.5);
+        let weight: Option<f64> = Spi::get_one(
+            "SELECT weight::float8 FROM graph \
+             WHERE source = 'o''brien' AND destination = 'x'', 0); DROP TABLE graph; --'",
+        )
+        .unwrap();
+        assert_eq!(weight, Some(1.5));
+        Spi::run("DELETE FROM graph WHERE source = 'o''brien'").unwrap();
+    }
 }
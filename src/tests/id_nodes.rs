@@ -0,0 +1,64 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{lock_graph, meritrank_add, meritrank_clear, NodeKey};
+    use crate::id_nodes::{
+        meritrank_add_id, meritrank_calculate_id, meritrank_delete_id, meritrank_ranks_id,
+    };
+    use crate::stats::meritrank_edge_count;
+    use pgx::*;
+
+    #[pg_test]
+    fn test_id_nodes() {
+        assert_eq!(NodeKey::from_name("-42"), Some(NodeKey::Number(-42)));
+        assert_eq!(NodeKey::from_name("007"), None);
+        assert_eq!(NodeKey::from_name("-0"), None);
+        assert_eq!(NodeKey::from_name("node1"), None);
+        assert_eq!(NodeKey::Number(-42).name(), "-42");
+
+        meritrank_clear(true).unwrap();
+        meritrank_add_id(1, 2, 1.0).unwrap();
+        meritrank_add_id(2, 3, 1.0).unwrap();
+        // Nodes added by ID and by its decimal form are the same nodes
+        meritrank_add("1", "3", 1.0).unwrap();
+        meritrank_add("1", "id_plain", 1.0).unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 4);
+
+        assert!(meritrank_calculate_id(1, 3, Some(200), None, None).unwrap() > 0.0);
+
+        // Peers that are not integers are left out
+        let mut peers: Vec<i64> = meritrank_ranks_id(1, Some(200), None, None, None)
+            .unwrap()
+            .map(|(node, _)| node)
+            .collect();
+        peers.retain(|&node| node != 1);
+        peers.sort();
+        assert_eq!(peers, vec![2, 3]);
+
+        meritrank_delete_id(1, 2).unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 3);
+    }
+
+    #[pg_test]
+    fn test_id_ranks_limit() {
+        meritrank_clear(true).unwrap();
+        meritrank_add_id(1, 2, 1.0).unwrap();
+        meritrank_add_id(2, 3, 1.0).unwrap();
+        // A peer without an ID that outranks the others
+        meritrank_add("1", "id_plain", 10.0).unwrap();
+
+        // The nodes are found by ID without looking up their names
+        let graph = lock_graph().unwrap();
+        let node_id = graph.keyed_node_id(NodeKey::Number(3)).unwrap();
+        assert_eq!(graph.get_node_name(node_id), Some("3"));
+        assert_eq!(graph.node_key(node_id), Some(NodeKey::Number(3)));
+        drop(graph);
+
+        let peers: Vec<i64> = meritrank_ranks_id(1, Some(200), Some(2), None, None)
+            .unwrap()
+            .map(|(node, _)| node)
+            .collect();
+        assert_eq!(peers.len(), 2);
+        assert!(peers.iter().all(|&node| node == 2 || node == 3));
+    }
+}
@@ -0,0 +1,27 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::info::meritrank_algorithm_info;
+    use crate::lib_graph::constants::{DEFAULT_ALPHA, SCORING_VERSION};
+    use crate::rating::DEFAULT_ITERATIONS;
+    use pgx::*;
+
+    #[pg_test]
+    fn test_algorithm_info() {
+        let (algorithm, extension_version, scoring_version, alpha, iterations, penalty_factor) =
+            meritrank_algorithm_info().next().unwrap();
+        assert_eq!(algorithm, "meritrank");
+        assert_eq!(extension_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(scoring_version, SCORING_VERSION);
+        assert_eq!(alpha, DEFAULT_ALPHA);
+        assert_eq!(iterations, DEFAULT_ITERATIONS as i64);
+        assert_eq!(penalty_factor, 1.0);
+
+        // The defaults follow the settings
+        Spi::run("SET meritrank.penalty_factor = 2").unwrap();
+        let penalty_factor =
+            Spi::get_one::<f64>("SELECT penalty_factor FROM meritrank_algorithm_info()").unwrap();
+        assert_eq!(penalty_factor, Some(2.0));
+        Spi::run("RESET meritrank.penalty_factor").unwrap();
+    }
+}
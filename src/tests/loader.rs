@@ -0,0 +1,168 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{lock_graph, meritrank_add, meritrank_clear, GraphSingleton, GRAPH};
+    use crate::loader::{
+        meritrank_import_csv, meritrank_load_from, meritrank_write_preload_file, preload_file,
+    };
+    use crate::persist::{
+        meritrank_flush, meritrank_restore, meritrank_sync, KNOWN_VERSION, VERSION_CHECKED_AT,
+    };
+    use pgx::*;
+    use std::sync::atomic::Ordering;
+
+    fn stored_weight(source: &str, destination: &str) -> Option<f64> {
+        Spi::get_one_with_args(
+            "SELECT weight FROM graph WHERE source = $1 AND destination = $2",
+            vec![
+                (PgBuiltInOids::TEXTOID.oid(), source.into_datum()),
+                (PgBuiltInOids::TEXTOID.oid(), destination.into_datum()),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[pg_test]
+    fn test_build_agg() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        Spi::run(
+            "CREATE TABLE agg_votes (subject text, object text, amount float8); \
+             INSERT INTO agg_votes VALUES \
+                 ('agg_a', 'agg_b', 1), ('agg_a', 'agg_c', 2), ('agg_a', 'agg_a', 1), \
+                 (NULL, 'agg_b', 1)",
+        )
+        .unwrap();
+
+        let added = Spi::get_one::<i64>(
+            "SELECT meritrank_build_agg(subject, object, amount) FROM agg_votes",
+        )
+        .unwrap();
+        assert_eq!(added, Some(2));
+        assert!(lock_graph().unwrap().contains_edge("agg_a", "agg_c"));
+
+        // The edges are written to the graph table like those of meritrank_add
+        meritrank_flush().unwrap();
+        assert_eq!(stored_weight("agg_a", "agg_b"), Some(1.0));
+        assert_eq!(stored_weight("agg_a", "agg_c"), Some(2.0));
+    }
+
+    #[pg_test]
+    fn test_load_from() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        Spi::run(
+            "CREATE TABLE load_edges (src text, dst text, w float8, at timestamptz); \
+             INSERT INTO load_edges VALUES \
+                 ('load_a', 'load_b', 1, '2020-01-01'), ('load_b', 'load_c', 2, NULL), \
+                 ('load_c', 'load_c', 1, NULL), ('load_c', NULL, 1, NULL)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            meritrank_load_from("load_edges", "src", "dst", "w", Some("at")).unwrap(),
+            2
+        );
+        meritrank_flush().unwrap();
+        assert_eq!(stored_weight("load_a", "load_b"), Some(1.0));
+        assert_eq!(stored_weight("load_b", "load_c"), Some(2.0));
+
+        // Memory and the table agree, and the edges survive a reload
+        assert_eq!(
+            meritrank_sync("memory-wins").unwrap().next().unwrap(),
+            (0, 0, 0)
+        );
+        assert_eq!(meritrank_restore().unwrap(), 2);
+        assert!(lock_graph().unwrap().contains_edge("load_b", "load_c"));
+    }
+
+    #[pg_test]
+    fn test_import_csv() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        let version = || {
+            Spi::get_one::<i64>("SELECT version FROM meritrank_graph_version")
+                .unwrap()
+                .unwrap()
+        };
+        let before = version();
+
+        let path =
+            std::env::temp_dir().join(format!("meritrank_import_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "source;destination;weight\r\n\
+             \"csv_a\";csv_b;1\r\n\
+             \"csv;quoted\" ; csv_b ; 2\n\
+             csv_b;\"csv \"\"c\"\"\nline\";3\n\
+             \n\
+             csv_c;csv_c;1\n\
+             csv_d;csv_e\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let counts = meritrank_import_csv(&path, ";").unwrap().next().unwrap();
+        assert_eq!(counts, (3, 3));
+        assert!(lock_graph().unwrap().contains_edge("csv;quoted", "csv_b"));
+
+        // Imported edges are written to the table, bumping its version
+        meritrank_flush().unwrap();
+        assert_eq!(stored_weight("csv_a", "csv_b"), Some(1.0));
+        assert_eq!(stored_weight("csv;quoted", "csv_b"), Some(2.0));
+        assert_eq!(stored_weight("csv_b", "csv \"c\"\nline"), Some(3.0));
+        assert!(version() > before);
+        assert_eq!(
+            meritrank_sync("memory-wins").unwrap().next().unwrap(),
+            (0, 0, 0)
+        );
+
+        assert!(meritrank_import_csv(&path, ";;").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[pg_test]
+    fn test_preload_file() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("pre_a", "pre_b", 1.0).unwrap();
+        meritrank_add("pre \"b\"", "pre_c", 2.0).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("meritrank_preload_{}.csv", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        assert_eq!(meritrank_write_preload_file(Some(&path)).unwrap(), 2);
+
+        // Start over as a backend forked from a postmaster that preloaded the file
+        GraphSingleton::clear_graph(true).unwrap();
+        KNOWN_VERSION.store(-1, Ordering::SeqCst);
+        assert_eq!(preload_file(&path).unwrap(), 2);
+        let generation = GRAPH.lock().unwrap().get_generation();
+
+        // The first statement keeps the preloaded graph
+        VERSION_CHECKED_AT.store(0, Ordering::SeqCst);
+        {
+            let graph = lock_graph().unwrap();
+            assert_eq!(graph.get_generation(), generation);
+            assert!(graph.contains_edge("pre \"b\"", "pre_c"));
+        }
+
+        // It is reloaded once the table changes
+        Spi::run("UPDATE meritrank_graph_version SET version = version + 1").unwrap();
+        VERSION_CHECKED_AT.store(0, Ordering::SeqCst);
+        {
+            let graph = lock_graph().unwrap();
+            assert_ne!(graph.get_generation(), generation);
+            assert!(graph.contains_edge("pre_a", "pre_b"));
+        }
+
+        // Files without a header are taken to match the table on first use
+        std::fs::write(&path, "pre_a,pre_b,1\n\"pre \"\"b\"\"\",pre_c,2\n").unwrap();
+        GraphSingleton::clear_graph(true).unwrap();
+        KNOWN_VERSION.store(-1, Ordering::SeqCst);
+        assert_eq!(preload_file(&path).unwrap(), 2);
+        let generation = GRAPH.lock().unwrap().get_generation();
+        VERSION_CHECKED_AT.store(0, Ordering::SeqCst);
+        assert_eq!(lock_graph().unwrap().get_generation(), generation);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
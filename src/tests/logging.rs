@@ -0,0 +1,23 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::guc::LogLevel;
+    use crate::logging;
+    use pgx::*;
+
+    #[pg_test]
+    fn test_log_level() {
+        assert!(logging::enabled(LogLevel::Info));
+        assert!(!logging::enabled(LogLevel::Debug));
+
+        Spi::run("SET meritrank.log_level = 'warning'").unwrap();
+        assert!(logging::enabled(LogLevel::Warning));
+        assert!(!logging::enabled(LogLevel::Info));
+
+        Spi::run("SET meritrank.log_level = 'off'").unwrap();
+        assert!(!logging::enabled(LogLevel::Warning));
+        assert!(!logging::enabled(LogLevel::Off));
+
+        Spi::run("RESET meritrank.log_level").unwrap();
+    }
+}
@@ -1,4 +1,32 @@
 // mod dump;
-// mod generate;
+#[cfg(feature = "arrow")]
+mod arrow_export;
+mod audit;
+mod baseline;
+mod batch;
+mod cache;
+mod convergence;
+mod explain;
+mod export;
+mod generate;
+mod graph_type;
 mod hello;
+mod id_nodes;
+mod info;
+mod loader;
+mod logging;
+mod node_rating;
+mod node_types;
+mod persist;
+mod properties;
+mod queue;
+mod rating;
+mod snapshots;
+mod stats;
+mod structure;
 mod trigger_new;
+mod triggers;
+mod uuid_nodes;
+mod vote;
+mod walks;
+mod weights;
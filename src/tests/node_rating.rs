@@ -0,0 +1,53 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_node_rating_order() {
+        // Ratings are ordered by rank, then by node name
+        let order: Option<String> = Spi::get_one(
+            "SELECT string_agg((r).node, ',' ORDER BY r USING >) FROM (VALUES \
+                 (('a', 0.1)::node_rating), (('b', 0.3)::node_rating), \
+                 (('c', 0.2)::node_rating), (('d', 0.2)::node_rating) \
+             ) AS ratings (r)",
+        )
+        .unwrap();
+        assert_eq!(order, Some("b,d,c,a".to_string()));
+
+        let equal: Option<bool> =
+            Spi::get_one("SELECT ('a', 0.5)::node_rating = ('a', 0.5)::node_rating").unwrap();
+        assert_eq!(equal, Some(true));
+        let less: Option<bool> =
+            Spi::get_one("SELECT ('z', 0.1)::node_rating < ('a', 0.5)::node_rating").unwrap();
+        assert_eq!(less, Some(true));
+    }
+
+    #[pg_test]
+    fn test_node_rating_index() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("rating_a", "rating_b", 1.0).unwrap();
+        meritrank_add("rating_a", "rating_c", 2.0).unwrap();
+
+        Spi::run(
+            "CREATE TEMP TABLE materialized_ratings AS \
+             SELECT r AS rating FROM meritrank_ratings('rating_a') AS r",
+        )
+        .unwrap();
+        Spi::run("CREATE INDEX ON materialized_ratings (rating)").unwrap();
+
+        let best: Option<String> = Spi::get_one(
+            "SELECT (rating).node FROM materialized_ratings ORDER BY rating DESC LIMIT 1",
+        )
+        .unwrap();
+        assert_eq!(best, Some("rating_a".to_string()));
+        let above: Option<i64> = Spi::get_one(
+            "SELECT count(*) FROM materialized_ratings \
+             WHERE rating > (SELECT rating FROM materialized_ratings \
+                             WHERE (rating).node = 'rating_b')",
+        )
+        .unwrap();
+        assert_eq!(above, Some(2));
+    }
+}
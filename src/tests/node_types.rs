@@ -0,0 +1,97 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{meritrank_add, meritrank_clear, meritrank_ranks};
+    use crate::lib_graph::NodeType;
+    use crate::node_types::{meritrank_node_type, meritrank_set_node_type};
+    use pgx::*;
+
+    #[test]
+    fn test_node_type_from_name() {
+        assert_eq!(NodeType::from_name("U1"), NodeType::User);
+        assert_eq!(NodeType::from_name("B42"), NodeType::Beacon);
+        assert_eq!(NodeType::from_name("C7"), NodeType::Comment);
+        assert_eq!(NodeType::from_name("Uber"), NodeType::Other);
+        assert_eq!(NodeType::from_name("U"), NodeType::Other);
+        assert_eq!(NodeType::from_name("alice"), NodeType::Other);
+        assert_eq!("Beacon".parse::<NodeType>().unwrap(), NodeType::Beacon);
+        assert!("robot".parse::<NodeType>().is_err());
+    }
+
+    #[pg_test]
+    fn test_node_types() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("U1", "B1", 1.0).unwrap();
+        meritrank_add("U1", "C1", 1.0).unwrap();
+        meritrank_add("U1", "alice", 1.0).unwrap();
+
+        assert_eq!(meritrank_node_type("U1").unwrap(), "user");
+        assert_eq!(meritrank_node_type("B1").unwrap(), "beacon");
+        assert_eq!(meritrank_node_type("C1").unwrap(), "comment");
+        assert_eq!(meritrank_node_type("alice").unwrap(), "other");
+        assert!(meritrank_node_type("missing").is_err());
+
+        meritrank_set_node_type("alice", "user").unwrap();
+        assert_eq!(meritrank_node_type("alice").unwrap(), "user");
+        assert!(meritrank_set_node_type("alice", "robot").is_err());
+
+        meritrank_clear(true).unwrap();
+    }
+
+    #[pg_test]
+    fn test_ranks_node_type_filter() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("U1", "U2", 1.0).unwrap();
+        meritrank_add("U1", "U3", 1.0).unwrap();
+        meritrank_add("U1", "B1", 1.0).unwrap();
+        meritrank_add("U2", "C1", 1.0).unwrap();
+
+        let users: Vec<_> = meritrank_ranks(
+            "U1",
+            Some(100),
+            None,
+            None,
+            false,
+            Some("user"),
+            None,
+            false,
+            None,
+        )
+        .unwrap()
+        .map(|(node, ..)| node)
+        .collect();
+        assert!(!users.is_empty());
+        assert!(users.iter().all(|node| node.starts_with('U')));
+
+        let beacons: Vec<_> = meritrank_ranks(
+            "U1",
+            Some(100),
+            Some(1),
+            None,
+            false,
+            Some("beacon"),
+            None,
+            false,
+            None,
+        )
+        .unwrap()
+        .map(|(node, ..)| node)
+        .collect();
+        assert_eq!(beacons, vec!["B1".to_string()]);
+
+        assert!(meritrank_ranks(
+            "U1",
+            Some(100),
+            None,
+            None,
+            false,
+            Some("robot"),
+            None,
+            false,
+            None
+        )
+        .is_err());
+
+        meritrank_clear(true).unwrap();
+    }
+}
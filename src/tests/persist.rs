@@ -0,0 +1,466 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{
+        lock_graph, meritrank_add, meritrank_calculate, meritrank_clear, meritrank_delete,
+        meritrank_rank_deltas, meritrank_ranks_json, GraphSingleton, HYDRATED,
+    };
+    use crate::loader::{
+        graph_table_partitions, meritrank_migrate_graph_table, meritrank_reindex, persist_edges,
+    };
+    use crate::outbox::meritrank_outbox_prune;
+    use crate::persist::{
+        discard_changes, meritrank_flush, meritrank_restore, meritrank_sync, VERSION_CHECKED_AT,
+    };
+    use crate::stats::meritrank_edge_count;
+    use pgx::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::Ordering;
+
+    fn stored_weight(source: &str, destination: &str) -> Option<f64> {
+        Spi::get_one_with_args(
+            "SELECT weight FROM graph WHERE source = $1 AND destination = $2",
+            vec![
+                (PgBuiltInOids::TEXTOID.oid(), source.into_datum()),
+                (PgBuiltInOids::TEXTOID.oid(), destination.into_datum()),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[pg_test]
+    fn test_persist_changes() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("persist_a", "persist_b", 1.0).unwrap();
+        meritrank_add("persist_a", "persist_c", 2.0).unwrap();
+        meritrank_add("persist_a", "persist_b", 3.0).unwrap();
+        meritrank_delete("persist_a", "persist_c").unwrap();
+
+        // The last change of each edge is written
+        assert_eq!(meritrank_flush().unwrap(), 2);
+        assert_eq!(stored_weight("persist_a", "persist_b"), Some(3.0));
+        assert_eq!(stored_weight("persist_a", "persist_c"), None);
+        assert_eq!(meritrank_flush().unwrap(), 0);
+
+        // Clearing memory alone leaves the table untouched
+        meritrank_clear(false).unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+        assert_eq!(meritrank_flush().unwrap(), 0);
+        assert_eq!(stored_weight("persist_a", "persist_b"), Some(3.0));
+
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        assert_eq!(stored_weight("persist_a", "persist_b"), None);
+    }
+
+    #[pg_test]
+    fn test_persist_changes_rolled_back() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("rollback_a", "rollback_b", 1.0).unwrap();
+        meritrank_flush().unwrap();
+
+        meritrank_add("rollback_a", "rollback_c", 2.0).unwrap();
+        meritrank_delete("rollback_a", "rollback_b").unwrap();
+        // As on ROLLBACK, which fires the abort callback
+        discard_changes();
+
+        // The graph is loaded again from the table, which never saw the changes
+        assert_eq!(
+            lock_graph().unwrap().named_edges(),
+            HashMap::from([(("rollback_a".to_string(), "rollback_b".to_string()), 1.0)])
+        );
+        assert_eq!(meritrank_flush().unwrap(), 0);
+        assert_eq!(stored_weight("rollback_a", "rollback_b"), Some(1.0));
+    }
+
+    #[pg_test]
+    fn test_persist_changes_off() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        Spi::run("SET meritrank.persist_changes = off").unwrap();
+
+        meritrank_add("ephemeral_a", "ephemeral_b", 1.0).unwrap();
+        assert_eq!(meritrank_flush().unwrap(), 0);
+        assert_eq!(stored_weight("ephemeral_a", "ephemeral_b"), None);
+
+        Spi::run("RESET meritrank.persist_changes").unwrap();
+    }
+
+    #[pg_test]
+    fn test_auto_load() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        Spi::run("INSERT INTO graph (source, destination, weight) VALUES ('load_a', 'load_b', 2)")
+            .unwrap();
+
+        // Simulate a fresh backend
+        GraphSingleton::clear_graph(true).unwrap();
+        HYDRATED.store(false, Ordering::SeqCst);
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+
+        GraphSingleton::clear_graph(true).unwrap();
+        HYDRATED.store(false, Ordering::SeqCst);
+        Spi::run("SET meritrank.auto_load = off").unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+
+        Spi::run("RESET meritrank.auto_load").unwrap();
+    }
+
+    #[pg_test]
+    fn test_migrate_graph_table() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        // The schema of early versions, with a duplicate row of an edge
+        Spi::run("ALTER TABLE graph ALTER COLUMN weight TYPE numeric(10,5)").unwrap();
+        Spi::run("ALTER TABLE graph DROP CONSTRAINT graph_pkey").unwrap();
+        Spi::run("DROP INDEX graph_destination_source_idx").unwrap();
+        Spi::run("ALTER TABLE graph ALTER COLUMN source TYPE varchar(32)").unwrap();
+        Spi::run(
+            "INSERT INTO graph (source, destination, weight, updated_at) VALUES \
+             ('legacy_a', 'legacy_b', 1, now() - interval '1 day'), \
+             ('legacy_a', 'legacy_b', 0.125, now())",
+        )
+        .unwrap();
+
+        let steps: Vec<String> = meritrank_migrate_graph_table().unwrap().collect();
+        assert_eq!(
+            steps,
+            vec![
+                "weight: numeric(10,5) -> double precision".to_string(),
+                "source: character varying(32) -> text".to_string(),
+                "primary key (source, destination), 1 duplicate rows removed".to_string(),
+                "index graph_destination_source_idx".to_string(),
+            ]
+        );
+        assert_eq!(meritrank_migrate_graph_table().unwrap().count(), 0);
+        assert_eq!(stored_weight("legacy_a", "legacy_b"), Some(0.125));
+    }
+
+    #[pg_test]
+    fn test_install_over_existing_graph_table() {
+        // A table made by hand before the extension was installed
+        Spi::run(
+            "CREATE SCHEMA legacy_install; \
+             CREATE TABLE legacy_install.graph ( \
+                 source varchar(32), destination varchar(32), weight numeric(10,5) \
+             )",
+        )
+        .unwrap();
+        Spi::run(
+            "SELECT set_config( \
+                 'search_path', 'legacy_install, ' || current_setting('search_path'), true \
+             )",
+        )
+        .unwrap();
+
+        let created = Spi::get_one::<bool>("SELECT meritrank_create_graph_table()").unwrap();
+        assert_eq!(created, Some(false));
+        // The table is left as it was, and out of the extension
+        let member = Spi::get_one::<bool>(
+            "SELECT EXISTS ( \
+                 SELECT 1 FROM pg_depend \
+                 WHERE classid = 'pg_class'::regclass \
+                   AND objid = 'legacy_install.graph'::regclass AND deptype = 'e' \
+             )",
+        )
+        .unwrap();
+        assert_eq!(member, Some(false));
+        let indexed = Spi::get_one::<bool>(
+            "SELECT to_regclass('legacy_install.graph_destination_source_idx') IS NOT NULL",
+        )
+        .unwrap();
+        assert_eq!(indexed, Some(false));
+    }
+
+    #[pg_test]
+    fn test_partitioned_graph_table() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        // The table created with meritrank.graph_partitions = 2
+        Spi::run("ALTER TABLE graph RENAME TO graph_unpartitioned").unwrap();
+        Spi::run("ALTER INDEX graph_pkey RENAME TO graph_unpartitioned_pkey").unwrap();
+        Spi::run(
+            "CREATE TABLE graph ( \
+                 source text NOT NULL, \
+                 destination text NOT NULL, \
+                 weight float8 NOT NULL, \
+                 updated_at timestamptz NOT NULL DEFAULT now(), \
+                 PRIMARY KEY (source, destination) \
+             ) PARTITION BY HASH (source)",
+        )
+        .unwrap();
+        for remainder in 0..2 {
+            Spi::run(&format!(
+                "CREATE TABLE graph_p{0} PARTITION OF graph \
+                 FOR VALUES WITH (MODULUS 2, REMAINDER {0})",
+                remainder
+            ))
+            .unwrap();
+        }
+        assert_eq!(
+            graph_table_partitions().unwrap(),
+            vec!["graph_p0".to_string(), "graph_p1".to_string()]
+        );
+
+        meritrank_add("partition_a", "partition_b", 1.0).unwrap();
+        meritrank_add("partition_b", "partition_c", 1.0).unwrap();
+        meritrank_add("partition_c", "partition_d", 1.0).unwrap();
+        assert_eq!(meritrank_flush().unwrap(), 3);
+
+        // Simulate a fresh backend
+        GraphSingleton::clear_graph(true).unwrap();
+        HYDRATED.store(false, Ordering::SeqCst);
+        assert_eq!(meritrank_edge_count().unwrap(), 3);
+    }
+
+    #[pg_test]
+    fn test_reindex() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        meritrank_add("reindex_a", "reindex_b", 1.0).unwrap();
+        meritrank_flush().unwrap();
+
+        // The primary key and the index on (destination, source)
+        assert_eq!(meritrank_reindex().unwrap(), 2);
+        assert_eq!(stored_weight("reindex_a", "reindex_b"), Some(1.0));
+    }
+
+    #[pg_test]
+    fn test_upsert_edges() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        meritrank_add("upsert_a", "upsert_b", 1.0).unwrap();
+        meritrank_flush().unwrap();
+        meritrank_add("upsert_a", "upsert_b", 2.0).unwrap();
+        meritrank_flush().unwrap();
+
+        let rows: Option<i64> = Spi::get_one("SELECT count(*) FROM graph").unwrap();
+        assert_eq!(rows, Some(1));
+        assert_eq!(stored_weight("upsert_a", "upsert_b"), Some(2.0));
+
+        // Re-inserting an edge directly replaces its weight as well
+        persist_edges(
+            vec!["upsert_a".to_string(), "upsert_a".to_string()],
+            vec!["upsert_b".to_string(), "upsert_b".to_string()],
+            vec![3.0, 4.0],
+        )
+        .unwrap();
+        assert_eq!(stored_weight("upsert_a", "upsert_b"), Some(4.0));
+    }
+
+    #[pg_test]
+    fn test_sync() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+        Spi::run("INSERT INTO graph (source, destination, weight) VALUES ('sync_a', 'sync_b', 1)")
+            .unwrap();
+        Spi::run("SET meritrank.persist_changes = off").unwrap();
+        meritrank_add("sync_a", "sync_c", 2.0).unwrap();
+
+        let counts = meritrank_sync("table-wins").unwrap().next().unwrap();
+        assert_eq!(counts, (1, 1, 0));
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+
+        meritrank_add("sync_a", "sync_b", 5.0).unwrap();
+        meritrank_add("sync_a", "sync_d", 3.0).unwrap();
+        let counts = meritrank_sync("memory-wins").unwrap().next().unwrap();
+        assert_eq!(counts, (1, 0, 1));
+        assert_eq!(stored_weight("sync_a", "sync_b"), Some(5.0));
+        assert_eq!(stored_weight("sync_a", "sync_d"), Some(3.0));
+
+        assert!(meritrank_sync("both").is_err());
+        Spi::run("RESET meritrank.persist_changes").unwrap();
+    }
+
+    #[pg_test]
+    fn test_sync_evicted() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.max_nodes = 3").unwrap();
+        Spi::run("SET meritrank.node_budget_policy = 'evict'").unwrap();
+        meritrank_add("evict_a", "evict_b", 1.0).unwrap();
+        meritrank_add("evict_b", "evict_a", 1.0).unwrap();
+        meritrank_add("evict_a", "evict_c", 1.0).unwrap();
+        meritrank_add("evict_d", "evict_e", 1.0).unwrap();
+        meritrank_flush().unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+
+        // Evicted edges stay in the table, and syncing in either direction keeps them apart
+        assert_eq!(stored_weight("evict_a", "evict_b"), Some(1.0));
+        let counts = meritrank_sync("memory-wins").unwrap().next().unwrap();
+        assert_eq!(counts, (0, 0, 0));
+        let counts = meritrank_sync("table-wins").unwrap().next().unwrap();
+        assert_eq!(counts, (0, 0, 0));
+        assert_eq!(stored_weight("evict_a", "evict_c"), Some(1.0));
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+
+        Spi::run("RESET meritrank.max_nodes").unwrap();
+        Spi::run("RESET meritrank.node_budget_policy").unwrap();
+        // Restoring starts over and loads them again
+        assert_eq!(meritrank_restore().unwrap(), 4);
+    }
+
+    #[pg_test]
+    fn test_current_ranks() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("current_a", "current_b", 1.0).unwrap();
+        meritrank_add("current_b", "current_c", 1.0).unwrap();
+
+        let rank =
+            meritrank_calculate("current_a", "current_b", Some(100), None, None, false, None)
+                .unwrap();
+        let stored: Option<f64> = Spi::get_one(
+            "SELECT rank FROM meritrank_current_ranks WHERE ego = 'current_a' AND node = 'current_b'",
+        )
+        .unwrap();
+        assert_eq!(stored, Some(rank));
+
+        // Ranks are tagged with the shared version of the table, including pending changes
+        let tagged: Option<bool> = Spi::get_one(
+            "SELECT bool_and(r.graph_version = v.version) \
+             FROM meritrank_current_ranks r, meritrank_graph_version v WHERE r.ego = 'current_a'",
+        )
+        .unwrap();
+        assert_eq!(tagged, Some(true));
+
+        Spi::run("SET meritrank.store_ranks = off").unwrap();
+        meritrank_add("current_c", "current_a", 1.0).unwrap();
+        meritrank_calculate("current_c", "current_a", Some(100), None, None, false, None).unwrap();
+        let stored: Option<i64> =
+            Spi::get_one("SELECT count(*) FROM meritrank_current_ranks WHERE ego = 'current_c'")
+                .unwrap();
+        assert_eq!(stored, Some(0));
+
+        Spi::run("RESET meritrank.store_ranks").unwrap();
+    }
+
+    #[pg_test]
+    fn test_outbox() {
+        meritrank_clear(true).unwrap();
+        Spi::run("DELETE FROM meritrank_outbox").unwrap();
+
+        // Nothing is recorded unless the outbox is enabled
+        meritrank_add("outbox_a", "outbox_b", 1.0).unwrap();
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*) FROM meritrank_outbox").unwrap(),
+            Some(0)
+        );
+
+        Spi::run("SET meritrank.outbox = on").unwrap();
+        meritrank_add("outbox_a", "outbox_b", 2.0).unwrap();
+        meritrank_add("outbox_a", "outbox_c", 1.0).unwrap();
+        meritrank_delete("outbox_a", "outbox_c").unwrap();
+        meritrank_clear(true).unwrap();
+
+        let ops = Spi::get_one::<String>(
+            "SELECT string_agg(op || ':' || coalesce(object, '-'), ',' ORDER BY id) \
+             FROM meritrank_outbox",
+        )
+        .unwrap();
+        assert_eq!(
+            ops.as_deref(),
+            Some("update:outbox_b,add:outbox_c,delete:outbox_c,clear:-")
+        );
+
+        let last = Spi::get_one::<i64>("SELECT max(id) FROM meritrank_outbox")
+            .unwrap()
+            .unwrap();
+        let versions: Option<i64> =
+            Spi::get_one("SELECT count(graph_version) FROM meritrank_outbox").unwrap();
+        assert_eq!(versions, Some(4));
+        assert_eq!(meritrank_outbox_prune(last - 1).unwrap(), 3);
+        assert_eq!(meritrank_outbox_prune(last).unwrap(), 1);
+
+        Spi::run("RESET meritrank.outbox").unwrap();
+    }
+
+    #[pg_test]
+    fn test_sync_backends() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("sync_a", "sync_b", 1.0).unwrap();
+        meritrank_flush().unwrap();
+
+        // Another backend adds an edge and bumps the version
+        Spi::run(
+            "INSERT INTO graph (source, destination, weight) VALUES ('sync_b', 'sync_c', 2.0)",
+        )
+        .unwrap();
+        Spi::run("UPDATE meritrank_graph_version SET version = version + 1").unwrap();
+
+        // The version is checked once per statement; pretend a new one started
+        Spi::run("SET meritrank.sync_backends = off").unwrap();
+        VERSION_CHECKED_AT.store(0, Ordering::SeqCst);
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+
+        Spi::run("RESET meritrank.sync_backends").unwrap();
+        VERSION_CHECKED_AT.store(0, Ordering::SeqCst);
+        assert_eq!(meritrank_edge_count().unwrap(), 2);
+        assert!(GraphSingleton::node_name_to_id("sync_c").is_ok());
+    }
+
+    #[pg_test]
+    fn test_restore() {
+        meritrank_clear(true).unwrap();
+        meritrank_flush().unwrap();
+
+        // The state of a restored database: rows in the tables, nothing in memory
+        Spi::run(
+            "INSERT INTO graph (source, destination, weight) \
+             VALUES ('restore_a', 'restore_b', 1.0), ('restore_b', 'restore_c', 2.0)",
+        )
+        .unwrap();
+        Spi::run("INSERT INTO meritrank_config VALUES ('meritrank.penalty_factor', '2.5')")
+            .unwrap();
+
+        assert_eq!(meritrank_restore().unwrap(), 2);
+        assert_eq!(meritrank_edge_count().unwrap(), 2);
+        assert_eq!(
+            Spi::get_one::<String>("SELECT current_setting('meritrank.penalty_factor')").unwrap(),
+            Some("2.5".to_string())
+        );
+
+        Spi::run("DELETE FROM meritrank_config").unwrap();
+        Spi::run("RESET meritrank.penalty_factor").unwrap();
+    }
+
+    #[pg_test]
+    fn test_rank_deltas() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.rank_history = on").unwrap();
+        Spi::run("SET meritrank.seed = 7").unwrap();
+        meritrank_add("delta_a", "delta_b", 1.0).unwrap();
+        meritrank_ranks_json("delta_a", Some(500)).unwrap();
+        let since = Spi::get_one::<i64>(
+            "SELECT max(graph_version) FROM meritrank_rank_history WHERE ego = 'delta_a'",
+        )
+        .unwrap()
+        .unwrap();
+        // The vector is keyed by the shared version of the table, not a backend's generation
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT version FROM meritrank_graph_version").unwrap(),
+            Some(since)
+        );
+
+        // Nothing changed since then
+        assert_eq!(
+            meritrank_rank_deltas("delta_a", since, 0.01, Some(500))
+                .unwrap()
+                .count(),
+            0
+        );
+
+        meritrank_add("delta_a", "delta_c", 1.0).unwrap();
+        let deltas: Vec<_> = meritrank_rank_deltas("delta_a", since, 0.01, Some(500))
+            .unwrap()
+            .collect();
+        let new_node = deltas.iter().find(|row| row.0 == "delta_c").unwrap();
+        assert_eq!(new_node.1, None);
+        assert!(new_node.3 > 0.0);
+        let diluted = deltas.iter().find(|row| row.0 == "delta_b").unwrap();
+        assert!(diluted.3 < 0.0);
+
+        assert!(meritrank_rank_deltas("delta_a", since, -1.0, None).is_err());
+
+        Spi::run("RESET meritrank.seed").unwrap();
+        Spi::run("RESET meritrank.rank_history").unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use crate::properties::{
+        meritrank_get_node_properties, meritrank_get_node_property, meritrank_set_node_property,
+    };
+    use pgx::*;
+
+    #[pg_test]
+    fn test_node_properties() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("props_a", "props_b", 1.0).unwrap();
+
+        meritrank_set_node_property("props_a", "display_name", Some("Alice")).unwrap();
+        meritrank_set_node_property("props_a", "flag", Some("vip")).unwrap();
+        assert_eq!(
+            meritrank_get_node_property("props_a", "display_name").unwrap(),
+            Some("Alice".to_string())
+        );
+        assert_eq!(
+            meritrank_get_node_property("props_b", "display_name").unwrap(),
+            None
+        );
+        assert_eq!(
+            meritrank_get_node_property("props_missing", "display_name").unwrap(),
+            None
+        );
+
+        meritrank_set_node_property("props_a", "flag", None).unwrap();
+        let properties: Vec<_> = meritrank_get_node_properties("props_a").unwrap().collect();
+        assert_eq!(
+            properties,
+            vec![("display_name".to_string(), "Alice".to_string())]
+        );
+
+        meritrank_clear(true).unwrap();
+        assert_eq!(
+            meritrank_get_node_property("props_a", "display_name").unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use crate::queue::{
+        meritrank_enqueue, meritrank_job_status, meritrank_process_queue, meritrank_result,
+    };
+    use pgx::*;
+
+    fn status(id: i64) -> (String, Option<String>) {
+        let (status, _, error, _, _, _) = meritrank_job_status(id).unwrap().next().unwrap();
+        (status, error)
+    }
+
+    #[pg_test]
+    fn test_queue() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("queue_a", "queue_b", 1.0).unwrap();
+        meritrank_add("queue_b", "queue_c", 1.0).unwrap();
+
+        let done = meritrank_enqueue("queue_a", Some(200)).unwrap();
+        let failed = meritrank_enqueue("queue_x", None).unwrap();
+        assert!(meritrank_enqueue("queue_a", Some(-1)).is_err());
+        assert_eq!(status(done).0, "queued");
+        assert_eq!(meritrank_result("queue_a").unwrap().count(), 0);
+
+        assert_eq!(meritrank_process_queue(100).unwrap(), 2);
+        assert_eq!(meritrank_process_queue(100).unwrap(), 0);
+
+        assert_eq!(status(done), ("done".to_string(), None));
+        let ranks: Vec<_> = meritrank_result("queue_a").unwrap().collect();
+        assert!(ranks
+            .iter()
+            .any(|(node, rank, _, _)| node == "queue_c" && *rank > 0.0));
+
+        let (status, error) = status(failed);
+        assert_eq!(status, "failed");
+        assert!(error.unwrap().contains("queue_x"));
+
+        assert!(meritrank_job_status(-1).is_err());
+    }
+}
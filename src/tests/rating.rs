@@ -0,0 +1,383 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::cache::{meritrank_cache_clear, meritrank_cache_stats};
+    use crate::error::GraphManipulationError;
+    use crate::graph::{
+        meritrank_add, meritrank_calculate, meritrank_calculate_window, meritrank_can_rank,
+        meritrank_clear, meritrank_ranks, meritrank_ranks_json, meritrank_similarity,
+        timestamp_from_pg, GRAPH,
+    };
+    use crate::rating::{Alpha, Iterations, Limit, PenaltyFactor, TimeBudget, DEFAULT_ITERATIONS};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_iterations_validation() {
+        assert!(Iterations::new(0).is_err());
+        assert!(Iterations::new(-5).is_err());
+        assert_eq!(Iterations::new(10).unwrap().get(), 10);
+        assert_eq!(
+            Iterations::from_nullable(None).unwrap().get(),
+            DEFAULT_ITERATIONS
+        );
+    }
+
+    #[pg_test]
+    fn test_limit_validation() {
+        assert!(Limit::new(0).is_err());
+        assert!(Limit::new(-1).is_err());
+        assert_eq!(Limit::new(3).unwrap().get(), Some(3));
+        assert_eq!(Limit::from_nullable(None).unwrap().get(), None);
+    }
+
+    #[pg_test]
+    fn test_penalty_factor_validation() {
+        assert!(PenaltyFactor::new(-0.5).is_err());
+        assert!(PenaltyFactor::new(f64::NAN).is_err());
+        assert_eq!(PenaltyFactor::new(2.0).unwrap().get(), 2.0);
+        assert_eq!(PenaltyFactor::from_nullable(None).unwrap().get(), 1.0);
+    }
+
+    #[pg_test]
+    fn test_alpha_validation() {
+        assert!(Alpha::new(0.0).is_err());
+        assert!(Alpha::new(1.0).is_err());
+        assert!(Alpha::new(f64::NAN).is_err());
+        assert_eq!(Alpha::new(0.5).unwrap().get(), 0.5);
+        assert_eq!(Alpha::from_nullable(None).unwrap().get(), 0.85);
+    }
+
+    #[pg_test]
+    fn test_time_budget_validation() {
+        assert!(TimeBudget::new(0).is_err());
+        assert!(TimeBudget::new(-1).is_err());
+        assert_eq!(
+            TimeBudget::new(250).unwrap().get(),
+            Some(std::time::Duration::from_millis(250))
+        );
+        assert!(TimeBudget::from_nullable(None)
+            .unwrap()
+            .deadline()
+            .is_none());
+    }
+
+    #[pg_test]
+    fn test_alpha_controls_walk_depth() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("alpha_a", "alpha_b", 1.0).unwrap();
+        meritrank_add("alpha_b", "alpha_c", 1.0).unwrap();
+        meritrank_add("alpha_c", "alpha_d", 1.0).unwrap();
+
+        let rank_of_d = |alpha| {
+            meritrank_ranks(
+                "alpha_a",
+                Some(2000),
+                None,
+                None,
+                false,
+                None,
+                Some(alpha),
+                false,
+                None,
+            )
+            .unwrap()
+            .find(|(node, ..)| node == "alpha_d")
+            .map_or(0.0, |(_, rank, ..)| rank)
+        };
+        assert!(rank_of_d(0.95) > rank_of_d(0.1));
+
+        assert!(
+            meritrank_calculate("alpha_a", "alpha_b", None, None, Some(1.0), false, None).is_err()
+        );
+        meritrank_clear(true).unwrap();
+    }
+
+    #[pg_test]
+    fn test_reverse_ranks() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("reverse_a", "reverse_c", 1.0).unwrap();
+        meritrank_add("reverse_b", "reverse_c", 1.0).unwrap();
+
+        let trusted_by = || -> Vec<String> {
+            let mut nodes: Vec<String> = meritrank_ranks(
+                "reverse_c",
+                Some(200),
+                None,
+                None,
+                false,
+                None,
+                None,
+                true,
+                None,
+            )
+            .unwrap()
+            .map(|(node, ..)| node)
+            .collect();
+            nodes.sort();
+            nodes
+        };
+        assert_eq!(trusted_by(), vec!["reverse_a", "reverse_b", "reverse_c"]);
+        assert_eq!(
+            meritrank_ranks(
+                "reverse_c",
+                Some(200),
+                None,
+                None,
+                false,
+                None,
+                None,
+                false,
+                None
+            )
+            .unwrap()
+            .count(),
+            0
+        );
+
+        // A new voter invalidates the cached reverse ranks
+        meritrank_add("reverse_d", "reverse_c", 1.0).unwrap();
+        assert_eq!(trusted_by().len(), 4);
+
+        assert!(
+            meritrank_calculate("reverse_c", "reverse_a", Some(200), None, None, true, None)
+                .unwrap()
+                > 0.0
+        );
+        assert!(
+            meritrank_calculate("reverse_a", "reverse_c", Some(200), None, None, true, None)
+                .is_err()
+        );
+        meritrank_clear(true).unwrap();
+    }
+
+    #[pg_test]
+    fn test_ranks_json() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("json_a", "json_b", 1.0).unwrap();
+        meritrank_add("json_a", "json_c", 1.0).unwrap();
+
+        let JsonB(scores) = meritrank_ranks_json("json_a", Some(200)).unwrap();
+        let scores = scores.as_object().unwrap();
+        assert_eq!(scores.len(), 3);
+        assert!(scores["json_b"].as_f64().unwrap() > 0.0);
+
+        let JsonB(empty) = meritrank_ranks_json("json_b", Some(200)).unwrap();
+        assert_eq!(empty, serde_json::json!({}));
+        assert!(meritrank_ranks_json("json_missing", None).is_err());
+    }
+
+    #[pg_test]
+    fn test_similarity() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("sim_a", "sim_x", 1.0).unwrap();
+        meritrank_add("sim_b", "sim_x", 1.0).unwrap();
+        meritrank_add("sim_c", "sim_y", 1.0).unwrap();
+
+        let similarity = |a: &str, b: &str| {
+            meritrank_similarity(a, b, Some(200))
+                .unwrap()
+                .next()
+                .unwrap()
+        };
+        let (cosine, jensen_shannon, shared_peers) = similarity("sim_a", "sim_b");
+        assert!((cosine.unwrap() - 1.0).abs() < 1e-9);
+        assert!((jensen_shannon.unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(shared_peers, 1);
+
+        assert_eq!(similarity("sim_a", "sim_c"), (Some(0.0), Some(0.0), 0));
+        assert_eq!(similarity("sim_a", "sim_x"), (None, None, 0));
+        assert!(meritrank_similarity("sim_a", "sim_missing", None).is_err());
+    }
+
+    #[pg_test]
+    fn test_meritrank_calculate_rejects_invalid_iterations() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("rating_a", "rating_b", 1.0).unwrap();
+
+        assert!(
+            meritrank_calculate("rating_a", "rating_b", Some(0), None, None, false, None).is_err()
+        );
+        assert!(
+            meritrank_calculate("rating_a", "rating_b", Some(-10), None, None, false, None)
+                .is_err()
+        );
+        assert!(meritrank_calculate("rating_a", "rating_b", None, None, None, false, None).is_ok());
+    }
+
+    #[pg_test]
+    fn test_meritrank_ranks_limit() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("rating_a", "rating_b", 1.0).unwrap();
+        meritrank_add("rating_a", "rating_c", 1.0).unwrap();
+
+        let ranks: Vec<_> = meritrank_ranks(
+            "rating_a",
+            Some(100),
+            Some(2),
+            None,
+            false,
+            None,
+            None,
+            false,
+            None,
+        )
+        .unwrap()
+        .collect();
+        assert_eq!(ranks.len(), 2);
+
+        assert!(meritrank_ranks(
+            "rating_a",
+            Some(100),
+            Some(0),
+            None,
+            false,
+            None,
+            None,
+            false,
+            None
+        )
+        .is_err());
+    }
+
+    #[pg_test]
+    fn test_ego_without_outgoing_edges() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("rating_a", "rating_b", 1.0).unwrap();
+
+        assert!(meritrank_can_rank("rating_a").unwrap());
+        assert!(!meritrank_can_rank("rating_b").unwrap());
+        assert!(!meritrank_can_rank("rating_missing").unwrap());
+
+        let ranks: Vec<_> = meritrank_ranks(
+            "rating_b",
+            Some(100),
+            None,
+            None,
+            false,
+            None,
+            None,
+            false,
+            None,
+        )
+        .unwrap()
+        .collect();
+        assert!(ranks.is_empty());
+        assert!(
+            meritrank_calculate("rating_b", "rating_a", Some(100), None, None, false, None)
+                .is_err()
+        );
+    }
+
+    #[pg_test]
+    fn test_time_budget() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("budget_a", "budget_b", 1.0).unwrap();
+        meritrank_add("budget_b", "budget_c", 1.0).unwrap();
+        meritrank_add("budget_c", "budget_a", 1.0).unwrap();
+        meritrank_cache_clear().unwrap();
+
+        // The budget runs out long before all walks are generated
+        let start = std::time::Instant::now();
+        let ranks: Vec<_> = meritrank_ranks(
+            "budget_a",
+            Some(i32::MAX),
+            None,
+            None,
+            false,
+            None,
+            None,
+            false,
+            Some(1),
+        )
+        .unwrap()
+        .collect();
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        assert!(!ranks.is_empty());
+
+        // Partial results are not cached
+        let (entries, ..) = meritrank_cache_stats().unwrap().next().unwrap();
+        assert_eq!(entries, 0);
+
+        assert!(
+            meritrank_calculate("budget_a", "budget_b", None, None, None, false, Some(0)).is_err()
+        );
+    }
+
+    #[pg_test]
+    fn test_calculate_window() {
+        meritrank_clear(true).unwrap();
+        let day = 86_400;
+        {
+            let mut graph = GRAPH.lock().unwrap();
+            graph
+                .add_edge_at("window_a", "window_old", 1.0, 100 * day)
+                .unwrap();
+            graph
+                .add_edge_at("window_a", "window_new", 1.0, 200 * day)
+                .unwrap();
+        }
+
+        let at = |days: i64| {
+            let micros = (days * day - 946_684_800) * 1_000_000;
+            let timestamp = TimestampWithTimeZone::try_from(micros).unwrap();
+            assert_eq!(timestamp_from_pg(timestamp), days * day);
+            TimestampWithTimeZone::try_from(micros).unwrap()
+        };
+
+        let recent = |target| {
+            meritrank_calculate_window(
+                "window_a",
+                target,
+                Some(at(150)),
+                None,
+                Some(200),
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        assert_eq!(recent("window_old"), 0.0);
+        assert!(recent("window_new") > 0.0);
+
+        let all =
+            meritrank_calculate_window("window_a", "window_old", None, None, Some(200), None, None)
+                .unwrap();
+        assert!(all > 0.0);
+
+        assert!(meritrank_calculate_window(
+            "window_a",
+            "window_new",
+            None,
+            Some(at(50)),
+            Some(200),
+            None,
+            None
+        )
+        .is_err());
+
+        meritrank_clear(true).unwrap();
+    }
+
+    #[pg_test]
+    fn test_error_sqlstate() {
+        let not_found = GraphManipulationError::NodeNotFound("sqlstate_a".to_string());
+        assert_eq!(
+            not_found.sqlstate(),
+            PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT
+        );
+        assert!(not_found.hint().is_some());
+
+        let invalid = Iterations::new(0).unwrap_err();
+        assert_eq!(
+            invalid.sqlstate(),
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE
+        );
+
+        let no_edges = GraphManipulationError::NoOutgoingEdges("sqlstate_a".to_string());
+        assert_eq!(
+            no_edges.sqlstate(),
+            PgSqlErrorCode::ERRCODE_OBJECT_NOT_IN_PREREQUISITE_STATE
+        );
+        assert!(no_edges.to_string().ends_with("sqlstate_a"));
+    }
+}
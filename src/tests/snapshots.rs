@@ -0,0 +1,55 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{meritrank_add, meritrank_clear, meritrank_delete};
+    use crate::snapshots::{meritrank_diff, meritrank_drop_snapshot, meritrank_snapshot};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_snapshot_diff() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("snap_a", "snap_b", 1.0).unwrap();
+        meritrank_add("snap_a", "snap_c", 2.0).unwrap();
+        assert_eq!(meritrank_snapshot("before").unwrap(), 2);
+
+        meritrank_add("snap_a", "snap_b", 3.0).unwrap();
+        meritrank_delete("snap_a", "snap_c").unwrap();
+        meritrank_add("snap_b", "snap_c", 1.0).unwrap();
+        meritrank_snapshot("after").unwrap();
+
+        let changes: Vec<_> = meritrank_diff("before", Some("after")).unwrap().collect();
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    "snap_a".to_string(),
+                    "snap_b".to_string(),
+                    "changed".to_string(),
+                    Some(1.0),
+                    Some(3.0)
+                ),
+                (
+                    "snap_a".to_string(),
+                    "snap_c".to_string(),
+                    "removed".to_string(),
+                    Some(2.0),
+                    None
+                ),
+                (
+                    "snap_b".to_string(),
+                    "snap_c".to_string(),
+                    "added".to_string(),
+                    None,
+                    Some(1.0)
+                ),
+            ]
+        );
+
+        // Without a second snapshot, the current graph is compared
+        assert_eq!(meritrank_diff("after", None).unwrap().count(), 0);
+
+        assert!(meritrank_drop_snapshot("before").unwrap());
+        assert!(meritrank_diff("before", None).is_err());
+        meritrank_drop_snapshot("after").unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{
+        lock_graph, meritrank_add, meritrank_add_batch, meritrank_add_jsonb, meritrank_add_node,
+        meritrank_calculate, meritrank_clear, meritrank_delete, meritrank_generation,
+        GraphSingleton, DATABASE, GRAPH,
+    };
+    use crate::persist::flush;
+    use crate::properties::meritrank_set_node_property;
+    use crate::stats::{
+        meritrank_edge_count, meritrank_epoch_advance, meritrank_epoch_current, meritrank_estimate,
+        meritrank_memory_usage, meritrank_node_count, meritrank_stats, meritrank_stats_reset,
+    };
+    use crate::walks::meritrank_walk_stats;
+    use pgx::*;
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    #[pg_test]
+    fn test_node_budget_reject() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.max_nodes = 2").unwrap();
+        Spi::run("SET meritrank.node_budget_policy = 'reject'").unwrap();
+
+        meritrank_add("budget_a", "budget_b", 1.0).unwrap();
+        assert!(meritrank_add("budget_a", "budget_c", 1.0).is_err());
+
+        let (nodes, _, max_nodes, occupancy, ..) = meritrank_stats().unwrap().next().unwrap();
+        assert_eq!(nodes, 2);
+        assert_eq!(max_nodes, Some(2));
+        assert_eq!(occupancy, Some(1.0));
+
+        Spi::run("RESET meritrank.max_nodes").unwrap();
+    }
+
+    #[pg_test]
+    fn test_strict_nodes() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.strict_nodes = on").unwrap();
+
+        assert!(meritrank_add("strict_a", "strict_b", 1.0).is_err());
+        assert!(meritrank_delete("strict_a", "strict_b").is_err());
+        assert_eq!(meritrank_node_count().unwrap(), 0);
+
+        assert!(meritrank_add_node("strict_a").unwrap());
+        assert!(!meritrank_add_node("strict_a").unwrap());
+        assert!(meritrank_add("strict_a", "strict_b", 1.0).is_err());
+
+        assert!(meritrank_add_node("strict_b").unwrap());
+        meritrank_add("strict_a", "strict_b", 1.0).unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+
+        Spi::run("RESET meritrank.strict_nodes").unwrap();
+        meritrank_add("strict_a", "strict_c", 1.0).unwrap();
+        assert_eq!(meritrank_node_count().unwrap(), 3);
+    }
+
+    #[pg_test]
+    fn test_add_batch() {
+        meritrank_clear(true).unwrap();
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+
+        assert!(meritrank_add_batch(names(&["batch_a"]), names(&["batch_b"]), vec![]).is_err());
+        assert!(meritrank_add_batch(
+            names(&["batch_a", "batch_b"]),
+            names(&["batch_b", "batch_b"]),
+            vec![1.0, 1.0]
+        )
+        .is_err());
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+
+        assert_eq!(
+            meritrank_add_batch(
+                names(&["batch_a", "batch_a", "batch_b"]),
+                names(&["batch_b", "batch_c", "batch_c"]),
+                vec![1.0, 2.0, 3.0]
+            )
+            .unwrap(),
+            3
+        );
+        assert_eq!(meritrank_edge_count().unwrap(), 3);
+
+        // A zero weight removes the edge, and a zero weight on a missing edge does nothing
+        assert_eq!(
+            meritrank_add_batch(
+                names(&["batch_a", "batch_c"]),
+                names(&["batch_b", "batch_a"]),
+                vec![0.0, 0.0]
+            )
+            .unwrap(),
+            1
+        );
+        assert_eq!(meritrank_edge_count().unwrap(), 2);
+    }
+
+    #[pg_test]
+    fn test_add_jsonb() {
+        meritrank_clear(true).unwrap();
+
+        assert!(meritrank_add_jsonb(JsonB(json!({"src": "jsonb_a"}))).is_err());
+        assert!(meritrank_add_jsonb(JsonB(json!([
+            {"src": "jsonb_a", "dst": "jsonb_b", "w": 1.0},
+            {"src": "jsonb_a", "dst": "jsonb_c", "w": "heavy"},
+        ])))
+        .is_err());
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+
+        assert_eq!(
+            meritrank_add_jsonb(JsonB(json!([
+                {"src": "jsonb_a", "dst": "jsonb_b", "w": 1.0},
+                {"src": "jsonb_b", "dst": "jsonb_c", "w": -2},
+            ])))
+            .unwrap(),
+            2
+        );
+        assert_eq!(meritrank_edge_count().unwrap(), 2);
+    }
+
+    #[pg_test]
+    fn test_node_budget_evict() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.max_nodes = 3").unwrap();
+        Spi::run("SET meritrank.node_budget_policy = 'evict'").unwrap();
+
+        meritrank_add("budget_a", "budget_b", 1.0).unwrap();
+        meritrank_add("budget_b", "budget_a", 1.0).unwrap();
+        meritrank_add("budget_a", "budget_c", 1.0).unwrap();
+        meritrank_add("budget_d", "budget_e", 1.0).unwrap();
+
+        let (nodes, _, _, _, evicted_nodes, ..) = meritrank_stats().unwrap().next().unwrap();
+        assert_eq!(nodes, 3);
+        assert_eq!(evicted_nodes, 2);
+
+        // The reverse name index follows evictions
+        let graph = GRAPH.lock().unwrap();
+        assert_eq!(graph.node_ids_to_names().len(), 3);
+        for (name, &node_id) in graph.borrow_node_names() {
+            assert_eq!(graph.get_node_name(node_id), Some(name.as_str()));
+        }
+        drop(graph);
+
+        Spi::run("RESET meritrank.max_nodes").unwrap();
+        Spi::run("RESET meritrank.node_budget_policy").unwrap();
+    }
+
+    #[pg_test]
+    fn test_edge_budget() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.max_edges = 2").unwrap();
+
+        meritrank_add("edges_a", "edges_b", 1.0).unwrap();
+        meritrank_add("edges_b", "edges_c", 1.0).unwrap();
+        assert!(meritrank_add("edges_c", "edges_a", 1.0).is_err());
+        // Updating an existing edge needs no room
+        meritrank_add("edges_a", "edges_b", 2.0).unwrap();
+
+        Spi::run("SET meritrank.node_budget_policy = 'evict'").unwrap();
+        meritrank_add("edges_d", "edges_e", 1.0).unwrap();
+        assert!(meritrank_edge_count().unwrap() <= 2);
+        assert!(GraphSingleton::node_name_to_id("edges_d").is_ok());
+
+        Spi::run("SET meritrank.node_budget_policy = 'spill'").unwrap();
+        meritrank_add("edges_d", "edges_f", 1.0).unwrap();
+        meritrank_calculate("edges_d", "edges_e", Some(50), None, None, false, None).unwrap();
+        meritrank_add("edges_e", "edges_f", 1.0).unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 4);
+        let (walks, ..) = meritrank_walk_stats("edges_d").unwrap().next().unwrap();
+        assert_eq!(walks, 0);
+
+        Spi::run("RESET meritrank.max_edges").unwrap();
+        Spi::run("RESET meritrank.node_budget_policy").unwrap();
+    }
+
+    #[pg_test]
+    fn test_edge_budget_batch() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.max_edges = 2").unwrap();
+        Spi::run("SET meritrank.node_budget_policy = 'reject'").unwrap();
+        meritrank_add("batch_budget_a", "batch_budget_b", 1.0).unwrap();
+
+        // The third edge exceeds the budget, so none of the batch is applied
+        assert!(meritrank_add_batch(
+            vec![
+                "batch_budget_a".to_string(),
+                "batch_budget_b".to_string(),
+                "batch_budget_c".to_string()
+            ],
+            vec![
+                "batch_budget_b".to_string(),
+                "batch_budget_c".to_string(),
+                "batch_budget_d".to_string()
+            ],
+            vec![3.0, 1.0, 1.0],
+        )
+        .is_err());
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+        assert_eq!(meritrank_node_count().unwrap(), 2);
+        assert_eq!(
+            lock_graph()
+                .unwrap()
+                .edge_weight("batch_budget_a", "batch_budget_b"),
+            Some(1.0)
+        );
+
+        flush().unwrap();
+        assert_eq!(
+            Spi::get_one::<i64>(
+                "SELECT count(*) FROM graph WHERE source LIKE 'batch_budget_%' AND weight = 1"
+            )
+            .unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*) FROM graph WHERE source LIKE 'batch_budget_%'")
+                .unwrap(),
+            Some(1)
+        );
+
+        Spi::run("RESET meritrank.max_edges").unwrap();
+        Spi::run("RESET meritrank.node_budget_policy").unwrap();
+    }
+
+    #[pg_test]
+    fn test_stats_count_calculations() {
+        meritrank_clear(true).unwrap();
+        meritrank_stats_reset().unwrap();
+        meritrank_add("stats_a", "stats_b", 1.0).unwrap();
+
+        meritrank_calculate("stats_a", "stats_b", Some(50), None, None, false, None).unwrap();
+        meritrank_calculate("stats_a", "stats_b", Some(50), None, None, false, None).unwrap();
+
+        let (nodes, edges, _, _, _, _, calculations, walks_generated, _, _, cache_hits, ..) =
+            meritrank_stats().unwrap().next().unwrap();
+        assert_eq!((nodes, edges), (2, 1));
+        // The second call is served from the rank cache
+        assert_eq!(calculations, 1);
+        assert_eq!(walks_generated, 50);
+        assert_eq!(cache_hits, 1);
+    }
+
+    #[pg_test]
+    fn test_generation() {
+        meritrank_clear(true).unwrap();
+        let cleared = meritrank_generation().unwrap();
+
+        meritrank_add("generation_a", "generation_b", 1.0).unwrap();
+        let added = meritrank_generation().unwrap();
+        assert!(added > cleared);
+
+        // Reads leave it unchanged
+        meritrank_calculate(
+            "generation_a",
+            "generation_b",
+            Some(50),
+            None,
+            None,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(meritrank_generation().unwrap(), added);
+
+        meritrank_delete("generation_a", "generation_b").unwrap();
+        assert!(meritrank_generation().unwrap() > added);
+        meritrank_clear(true).unwrap();
+        assert!(meritrank_generation().unwrap() > added);
+    }
+
+    #[pg_test]
+    fn test_estimate() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("estimate_a", "estimate_b", 1.0).unwrap();
+        meritrank_add("estimate_b", "estimate_c", 1.0).unwrap();
+        meritrank_add("estimate_a", "estimate_x", -1.0).unwrap();
+        meritrank_add("estimate_y", "estimate_a", 1.0).unwrap();
+
+        let (nodes, edges, mean_out_degree, walk_length, ms, bytes) =
+            meritrank_estimate("estimate_a", Some(1000), Some(0.9))
+                .unwrap()
+                .next()
+                .unwrap();
+        // Negative edges and nodes only pointing at the ego are not walked
+        assert_eq!((nodes, edges), (3, 2));
+        assert!((mean_out_degree - 2.0 / 3.0).abs() < 1e-9);
+        // Two of three nodes continue walks, with probability 0.9 each
+        assert!((walk_length - 2.5).abs() < 1e-9);
+        assert!(ms > 0.0);
+        assert!(bytes > 0);
+
+        let (.., more_ms, more_bytes) = meritrank_estimate("estimate_a", Some(10_000), Some(0.9))
+            .unwrap()
+            .next()
+            .unwrap();
+        assert!(more_ms > ms);
+        assert!(more_bytes > bytes);
+
+        assert!(meritrank_estimate("estimate_z", None, None).is_err());
+    }
+
+    #[pg_test]
+    fn test_node_and_edge_count() {
+        meritrank_clear(true).unwrap();
+        assert_eq!(meritrank_node_count().unwrap(), 0);
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+
+        meritrank_add("count_a", "count_b", 1.0).unwrap();
+        meritrank_add("count_a", "count_c", -1.0).unwrap();
+        assert_eq!(meritrank_node_count().unwrap(), 3);
+        assert_eq!(meritrank_edge_count().unwrap(), 2);
+
+        meritrank_clear(true).unwrap();
+        assert_eq!(meritrank_node_count().unwrap(), 0);
+    }
+
+    #[pg_test]
+    fn test_zero_weight_removes_edge() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("zero_a", "zero_b", 1.0).unwrap();
+        meritrank_add("zero_a", "zero_b", 2.0).unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+
+        meritrank_add("zero_a", "zero_b", 0.0).unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+
+        // A zero weight for a missing edge does not create nodes
+        meritrank_add("zero_c", "zero_d", 0.0).unwrap();
+        assert_eq!(meritrank_node_count().unwrap(), 2);
+
+        meritrank_clear(true).unwrap();
+    }
+
+    #[pg_test]
+    fn test_graph_of_another_database() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("db_a", "db_b", 1.0).unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 1);
+
+        // Pretend the graph was built while connected to another database
+        let database = unsafe { pg_sys::MyDatabaseId }.as_u32();
+        DATABASE.store(database + 1, Ordering::SeqCst);
+        Spi::run("SET meritrank.auto_load = off").unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+        assert_eq!(DATABASE.load(Ordering::SeqCst), database);
+
+        Spi::run("RESET meritrank.auto_load").unwrap();
+    }
+
+    #[pg_test]
+    fn test_snapshot_reuse() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("snap_a", "snap_b", 1.0).unwrap();
+        meritrank_stats_reset().unwrap();
+
+        let first = GraphSingleton::get_snapshot().unwrap();
+        let second = GraphSingleton::get_snapshot().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        meritrank_add("snap_b", "snap_c", 1.0).unwrap();
+        let third = GraphSingleton::get_snapshot().unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert!(third.node_names.contains_key("snap_c"));
+
+        // Reusing the snapshot does not lock the graph
+        {
+            let _graph = GRAPH.lock().unwrap();
+            let fourth = GraphSingleton::get_snapshot().unwrap();
+            assert!(Arc::ptr_eq(&third, &fourth));
+        }
+
+        let (.., snapshot_hits, snapshot_misses, _, _) = meritrank_stats().unwrap().next().unwrap();
+        assert_eq!((snapshot_hits, snapshot_misses), (2, 2));
+    }
+
+    #[pg_test]
+    fn test_epochs() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("epoch_a", "epoch_b", 1.0).unwrap();
+
+        let epoch = meritrank_epoch_advance();
+        let (current, started_at, calculations, walks, _) =
+            meritrank_epoch_current().next().unwrap();
+        assert_eq!(current, epoch);
+        assert!(started_at.is_some());
+        assert_eq!((calculations, walks), (0, 0));
+
+        meritrank_calculate("epoch_a", "epoch_b", Some(100), None, None, false, None).unwrap();
+        meritrank_stats_reset().unwrap();
+        let (_, _, calculations, walks, _) = meritrank_epoch_current().next().unwrap();
+        assert_eq!((calculations, walks), (1, 100));
+
+        assert_eq!(meritrank_epoch_advance(), epoch + 1);
+        let (_, _, calculations, _, _) = meritrank_epoch_current().next().unwrap();
+        assert_eq!(calculations, 0);
+    }
+
+    #[pg_test]
+    fn test_memory_usage() {
+        meritrank_clear(true).unwrap();
+        let usage = || -> Vec<(String, i64)> { meritrank_memory_usage().unwrap().collect() };
+        let bytes = |rows: &[(String, i64)], component: &str| {
+            rows.iter()
+                .find(|(name, _)| name == component)
+                .map(|&(_, bytes)| bytes)
+                .unwrap()
+        };
+
+        let empty = usage();
+        let components: Vec<&str> = empty.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            components,
+            vec![
+                "graph",
+                "node_names",
+                "node_properties",
+                "csr",
+                "rank_cache",
+                "walk_storage",
+                "total"
+            ]
+        );
+        assert_eq!(bytes(&empty, "walk_storage"), 0);
+
+        for i in 0..100 {
+            meritrank_add(&format!("mem_{}", i), &format!("mem_{}", i + 1), 1.0).unwrap();
+        }
+        meritrank_set_node_property("mem_0", "label", Some("first")).unwrap();
+        Spi::run("SELECT count(*) FROM meritrank_ranks('mem_0')").unwrap();
+
+        let used = usage();
+        assert!(bytes(&used, "graph") > bytes(&empty, "graph"));
+        assert!(bytes(&used, "node_names") > bytes(&empty, "node_names"));
+        assert!(bytes(&used, "node_properties") > bytes(&empty, "node_properties"));
+        assert!(bytes(&used, "walk_storage") > 0);
+        let components: i64 = used[..used.len() - 1].iter().map(|&(_, bytes)| bytes).sum();
+        assert_eq!(bytes(&used, "total"), components);
+    }
+}
@@ -0,0 +1,303 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{
+        lock_graph, meritrank_add, meritrank_add_batch, meritrank_clear, meritrank_compact,
+        meritrank_merge_nodes, meritrank_rename_node, GraphSingleton, NodeId, MAX_NODE_NAME_BYTES,
+    };
+    use crate::persist::meritrank_flush;
+    use crate::structure::{
+        meritrank_components, meritrank_degrees, meritrank_graph_metrics, meritrank_neighbors,
+    };
+    use pgx::*;
+    use std::collections::HashMap;
+
+    fn named_edges() -> HashMap<(String, String), f64> {
+        lock_graph().unwrap().named_edges()
+    }
+
+    fn edge(source: &str, target: &str, weight: f64) -> ((String, String), f64) {
+        ((source.to_string(), target.to_string()), weight)
+    }
+
+    #[pg_test]
+    fn test_components() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("main_a", "main_b", 1.0).unwrap();
+        meritrank_add("main_c", "main_b", 1.0).unwrap();
+        meritrank_add("island_a", "island_b", 1.0).unwrap();
+
+        let components: Vec<_> = meritrank_components().unwrap().collect();
+        assert_eq!(
+            components,
+            vec![
+                ("main_a".to_string(), 1, 3),
+                ("main_b".to_string(), 1, 3),
+                ("main_c".to_string(), 1, 3),
+                ("island_a".to_string(), 2, 2),
+                ("island_b".to_string(), 2, 2),
+            ]
+        );
+    }
+
+    #[pg_test]
+    fn test_degrees() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("deg_a", "deg_b", 2.0).unwrap();
+        meritrank_add("deg_a", "deg_c", -1.0).unwrap();
+        meritrank_add("deg_c", "deg_b", 0.5).unwrap();
+
+        let degrees: Vec<_> = meritrank_degrees().unwrap().collect();
+        assert_eq!(
+            degrees,
+            vec![
+                ("deg_a".to_string(), 0, 2, 0.0, 1.0),
+                ("deg_b".to_string(), 2, 0, 2.5, 0.0),
+                ("deg_c".to_string(), 1, 1, -1.0, 0.5),
+            ]
+        );
+    }
+
+    #[pg_test]
+    fn test_graph_metrics() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("metrics_a", "metrics_b", 1.0).unwrap();
+        meritrank_add("metrics_b", "metrics_c", 1.0).unwrap();
+        meritrank_add("metrics_c", "metrics_a", 1.0).unwrap();
+        meritrank_add("metrics_d", "metrics_a", -1.0).unwrap();
+
+        let (
+            nodes,
+            edges,
+            density,
+            average_degree,
+            negative_edges,
+            weight_min,
+            _,
+            weight_median,
+            _,
+            _,
+            weight_max,
+            clustering,
+        ) = meritrank_graph_metrics(1000, 0).unwrap().next().unwrap();
+        assert_eq!((nodes, edges, negative_edges), (4, 4, 1));
+        assert!((density - 4.0 / 12.0).abs() < 1e-9);
+        assert!((average_degree - 1.0).abs() < 1e-9);
+        assert_eq!(
+            (weight_min, weight_median, weight_max),
+            (Some(-1.0), Some(1.0), Some(1.0))
+        );
+        // a closes one of its three neighbor pairs, b and c all of theirs, d has one neighbor
+        assert!((clustering - 7.0 / 12.0).abs() < 1e-9);
+
+        meritrank_clear(true).unwrap();
+        let (nodes, _, density, _, _, weight_min, ..) =
+            meritrank_graph_metrics(1000, 0).unwrap().next().unwrap();
+        assert_eq!((nodes, density, weight_min), (0, 0.0, None));
+    }
+
+    #[pg_test]
+    fn test_rename_node() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("rename_a", "rename_b", 1.0).unwrap();
+        meritrank_add("rename_c", "rename_a", 2.0).unwrap();
+
+        meritrank_rename_node("rename_a", "rename_z").unwrap();
+        assert_eq!(
+            named_edges(),
+            HashMap::from([
+                edge("rename_z", "rename_b", 1.0),
+                edge("rename_c", "rename_z", 2.0),
+            ])
+        );
+
+        assert!(meritrank_rename_node("rename_a", "rename_y").is_err());
+        assert!(meritrank_rename_node("rename_z", "rename_b").is_err());
+    }
+
+    #[pg_test]
+    fn test_add_returns_change() {
+        meritrank_clear(true).unwrap();
+        let change = |amount: f64| {
+            meritrank_add("change_a", "change_b", amount)
+                .unwrap()
+                .next()
+                .unwrap()
+        };
+
+        assert_eq!(change(1.0), (None, Some(1.0), true));
+        assert_eq!(change(2.5), (Some(1.0), Some(2.5), false));
+        assert_eq!(change(0.0), (Some(2.5), None, false));
+        assert_eq!(change(0.0), (None, None, false));
+
+        let created: Option<bool> =
+            Spi::get_one("SELECT created FROM meritrank_add('change_a', 'change_c', 3)").unwrap();
+        assert_eq!(created, Some(true));
+    }
+
+    #[pg_test]
+    fn test_node_names() {
+        meritrank_clear(true).unwrap();
+        let longest = "n".repeat(MAX_NODE_NAME_BYTES);
+        let too_long = "n".repeat(MAX_NODE_NAME_BYTES + 1);
+
+        // Names up to the limit are kept in memory and in the graph table alike
+        meritrank_add(&longest, &longest.replace('n', "m"), 1.0).unwrap();
+        meritrank_flush().unwrap();
+
+        assert!(meritrank_add("names_a", &too_long, 1.0).is_err());
+        assert!(meritrank_add("", "names_a", 1.0).is_err());
+        assert!(meritrank_rename_node(&longest, &too_long).is_err());
+        // A batch with an invalid name is rejected as a whole
+        assert!(meritrank_add_batch(
+            vec!["names_a".to_string(), "names_b".to_string()],
+            vec!["names_b".to_string(), too_long],
+            vec![1.0, 1.0],
+        )
+        .is_err());
+        assert_eq!(named_edges().len(), 1);
+    }
+
+    #[pg_test]
+    fn test_merge_nodes() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("merge_a", "merge_c", 1.0).unwrap();
+        meritrank_add("merge_b", "merge_c", 2.0).unwrap();
+        meritrank_add("merge_a", "merge_b", 1.0).unwrap();
+        meritrank_add("merge_d", "merge_a", 3.0).unwrap();
+        meritrank_add("merge_d", "merge_b", 1.0).unwrap();
+        meritrank_add("merge_e", "merge_a", -1.0).unwrap();
+        meritrank_add("merge_e", "merge_b", 1.0).unwrap();
+
+        assert_eq!(meritrank_merge_nodes("merge_a", "merge_b").unwrap(), 4);
+        assert_eq!(
+            named_edges(),
+            HashMap::from([
+                edge("merge_b", "merge_c", 3.0),
+                edge("merge_d", "merge_b", 4.0),
+            ])
+        );
+
+        assert!(meritrank_merge_nodes("merge_a", "merge_b").is_err());
+        assert!(meritrank_merge_nodes("merge_b", "merge_b").is_err());
+    }
+
+    #[pg_test]
+    fn test_merge_nodes_within_budget() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.max_edges = 2").unwrap();
+        Spi::run("SET meritrank.node_budget_policy = 'evict'").unwrap();
+        meritrank_add("budget_merge_x", "budget_merge_a", 1.0).unwrap();
+        meritrank_add("budget_merge_b", "budget_merge_y", 2.0).unwrap();
+
+        // Re-pointing an edge briefly exceeds the budget, which must not evict anything
+        assert_eq!(
+            meritrank_merge_nodes("budget_merge_a", "budget_merge_b").unwrap(),
+            1
+        );
+        assert_eq!(
+            named_edges(),
+            HashMap::from([
+                edge("budget_merge_x", "budget_merge_b", 1.0),
+                edge("budget_merge_b", "budget_merge_y", 2.0),
+            ])
+        );
+
+        Spi::run("RESET meritrank.max_edges").unwrap();
+        Spi::run("RESET meritrank.node_budget_policy").unwrap();
+    }
+
+    #[pg_test]
+    fn test_compact() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("compact_a", "compact_b", 1.0).unwrap();
+        meritrank_add("compact_b", "compact_c", 2.0).unwrap();
+        meritrank_add("compact_c", "compact_a", 3.0).unwrap();
+        meritrank_merge_nodes("compact_a", "compact_c").unwrap();
+
+        let (nodes, renumbered, dropped, _) = meritrank_compact().unwrap().next().unwrap();
+        assert_eq!((nodes, renumbered, dropped), (2, 2, 0));
+        assert!(GraphSingleton::node_name_to_id("compact_b").unwrap() == NodeId::UInt(1));
+        assert_eq!(
+            named_edges(),
+            HashMap::from([
+                edge("compact_b", "compact_c", 2.0),
+                edge("compact_c", "compact_b", 1.0),
+            ])
+        );
+
+        meritrank_add("compact_d", "compact_b", 1.0).unwrap();
+        assert!(GraphSingleton::node_name_to_id("compact_d").unwrap() == NodeId::UInt(3));
+    }
+
+    #[pg_test]
+    fn test_node_id_to_name() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("reverse_a", "reverse_b", 1.0).unwrap();
+        meritrank_add("reverse_b", "reverse_c", 2.0).unwrap();
+        let id = |name: &str| GraphSingleton::node_name_to_id(name).unwrap();
+        let name = |node_id: NodeId| GraphSingleton::node_id_to_name(node_id).unwrap();
+
+        let (a, b, c) = (id("reverse_a"), id("reverse_b"), id("reverse_c"));
+        assert_eq!(name(a), "reverse_a");
+        assert_eq!(name(b), "reverse_b");
+        assert_eq!(name(c), "reverse_c");
+
+        // Renamed nodes keep their ID under the new name
+        meritrank_rename_node("reverse_a", "reverse_z").unwrap();
+        assert_eq!(name(a), "reverse_z");
+
+        // Merged nodes no longer resolve
+        meritrank_merge_nodes("reverse_c", "reverse_b").unwrap();
+        assert!(GraphSingleton::node_id_to_name(c).is_err());
+        assert_eq!(name(b), "reverse_b");
+        assert!(GraphSingleton::node_id_to_name(NodeId::UInt(1_000_000)).is_err());
+
+        // Compaction renumbers the index along with the nodes
+        meritrank_compact().unwrap().next().unwrap();
+        let graph = lock_graph().unwrap();
+        assert_eq!(graph.node_ids_to_names().len(), 2);
+        for (name, &node_id) in graph.borrow_node_names() {
+            assert_eq!(graph.get_node_name(node_id), Some(name.as_str()));
+        }
+    }
+
+    #[pg_test]
+    fn test_neighbors() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("neighbors_a", "neighbors_c", 2.0).unwrap();
+        meritrank_add("neighbors_a", "neighbors_b", 1.0).unwrap();
+        meritrank_add("neighbors_d", "neighbors_a", -1.0).unwrap();
+
+        let row = |neighbor: &str, direction: &str, weight: f64| {
+            (neighbor.to_string(), direction.to_string(), weight)
+        };
+        assert_eq!(
+            meritrank_neighbors("neighbors_a", "both")
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![
+                row("neighbors_b", "out", 1.0),
+                row("neighbors_c", "out", 2.0),
+                row("neighbors_d", "in", -1.0),
+            ]
+        );
+        assert_eq!(
+            meritrank_neighbors("neighbors_a", "out").unwrap().count(),
+            2
+        );
+        assert_eq!(
+            meritrank_neighbors("neighbors_c", "in")
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![row("neighbors_a", "in", 2.0)]
+        );
+        assert_eq!(
+            meritrank_neighbors("neighbors_c", "out").unwrap().count(),
+            0
+        );
+
+        assert!(meritrank_neighbors("neighbors_a", "sideways").is_err());
+        assert!(meritrank_neighbors("neighbors_missing", "both").is_err());
+    }
+}
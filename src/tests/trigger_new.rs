@@ -43,7 +43,7 @@ mod tests {
     // #[pg_test]
     fn test_meritrank_calculate() {
         println!("Test meritrank_calculate started.");
-        let result = meritrank_calculate("node1", "node2", 100);
+        let result = meritrank_calculate("node1", "node2", Some(100), None, None, false, None);
         assert!(result.is_ok());
         let calculated_rank = result.unwrap();
         println!("Calculated rank for node1 -> node2: {}", calculated_rank);
@@ -76,7 +76,7 @@ mod tests {
         println!("Test trigger functionality started.");
         let result = meritrank_add("node1", "node2", 42.0);
         assert!(result.is_ok());
-        let result = meritrank_calculate("node1", "node2", 100);
+        let result = meritrank_calculate("node1", "node2", Some(100), None, None, false, None);
         assert!(result.is_ok());
         let result = meritrank_delete("node1", "node2");
         assert!(result.is_ok());
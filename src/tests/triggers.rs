@@ -0,0 +1,87 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{meritrank_add, meritrank_clear};
+    use crate::stats::meritrank_edge_count;
+    use crate::structure::meritrank_neighbors;
+    use crate::triggers::{meritrank_bootstrap, meritrank_install_triggers};
+    use crate::weights::meritrank_set_weight;
+    use pgx::*;
+
+    fn weight(subject: &str, object: &str) -> Option<f64> {
+        meritrank_neighbors(subject, "out")
+            .ok()?
+            .find(|(neighbor, _, _)| neighbor == object)
+            .map(|(_, _, weight)| weight)
+    }
+
+    #[pg_test]
+    fn test_install_triggers() {
+        meritrank_clear(true).unwrap();
+        Spi::run("CREATE TABLE likes (liker text, liked text, stars integer)").unwrap();
+        assert!(meritrank_install_triggers("likes", "liker", "liked", "amount").is_err());
+        assert!(meritrank_install_triggers("missing_likes", "liker", "liked", "stars").is_err());
+        meritrank_install_triggers("likes", "liker", "liked", "stars").unwrap();
+        // Installing again replaces the trigger
+        meritrank_install_triggers("likes", "liker", "liked", "stars").unwrap();
+        meritrank_set_weight("likes", 5.0, 2.0).unwrap();
+
+        Spi::run("INSERT INTO likes VALUES ('like_a', 'like_b', 5), ('like_a', 'like_c', 1)")
+            .unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 2);
+        assert_eq!(weight("like_a", "like_b"), Some(2.0));
+
+        Spi::run("UPDATE likes SET liked = 'like_d' WHERE liked = 'like_c'").unwrap();
+        assert_eq!(weight("like_a", "like_c"), None);
+        assert_eq!(weight("like_a", "like_d"), Some(1.0));
+
+        Spi::run("DELETE FROM likes").unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 0);
+    }
+
+    #[pg_test]
+    fn test_bootstrap() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("boot_x", "boot_y", 1.0).unwrap();
+        Spi::run(
+            "CREATE TABLE boot_vote_user (subject text, object text, amount integer); \
+             CREATE TABLE boot_vote_post (subject text, object text, amount integer); \
+             CREATE TABLE boot_vote_log (message text); \
+             INSERT INTO boot_vote_user VALUES ('boot_a', 'boot_b', 1), ('boot_b', 'boot_c', 1); \
+             INSERT INTO boot_vote_post VALUES ('boot_a', 'boot_p', 2)",
+        )
+        .unwrap();
+
+        let tables: Vec<(String, i64)> = meritrank_bootstrap(Some("boot_vote_%"), true)
+            .unwrap()
+            .map(|(table, edges)| (table.rsplit('.').next().unwrap().to_string(), edges))
+            .collect();
+        // Tables without vote columns are skipped
+        assert_eq!(
+            tables,
+            vec![
+                ("boot_vote_post".to_string(), 1),
+                ("boot_vote_user".to_string(), 2)
+            ]
+        );
+        // Replacing drops the edges that are not backed by a vote
+        assert_eq!(meritrank_edge_count().unwrap(), 3);
+        assert_eq!(weight("boot_x", "boot_y"), None);
+        assert_eq!(weight("boot_a", "boot_p"), Some(2.0));
+
+        // The discovered tables stay in sync
+        Spi::run("INSERT INTO boot_vote_post VALUES ('boot_b', 'boot_p', 1)").unwrap();
+        assert_eq!(meritrank_edge_count().unwrap(), 4);
+
+        // Without replace, edges that are not backed by a vote are kept
+        meritrank_add("boot_x", "boot_y", 1.0).unwrap();
+        assert_eq!(
+            meritrank_bootstrap(Some("boot_vote_%"), false)
+                .unwrap()
+                .count(),
+            2
+        );
+        assert_eq!(weight("boot_x", "boot_y"), Some(1.0));
+        assert_eq!(meritrank_edge_count().unwrap(), 5);
+    }
+}
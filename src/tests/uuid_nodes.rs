@@ -0,0 +1,116 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{lock_graph, meritrank_add, meritrank_clear, NodeKey};
+    use crate::structure::meritrank_neighbors;
+    use crate::uuid_nodes::{meritrank_add_uuid, uuid_key};
+    use pgx::*;
+
+    const A: &str = "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11";
+    const B: &str = "b0eebc99-9c0b-4ef8-bb6d-6bb9bd380a12";
+    const C: &str = "c0eebc99-9c0b-4ef8-bb6d-6bb9bd380a13";
+
+    #[pg_test]
+    fn test_uuid_names() {
+        let key = NodeKey::from_name(A).unwrap();
+        assert_eq!(key.name(), A);
+        assert!(NodeKey::from_name("A0EEBC99-9C0B-4EF8-BB6D-6BB9BD380A11").is_none());
+        assert!(NodeKey::from_name("a0eebc999c0b4ef8bb6d6bb9bd380a11").is_none());
+        assert!(NodeKey::from_name("node1").is_none());
+
+        let uuid = Uuid::from_bytes([
+            0xa0, 0xee, 0xbc, 0x99, 0x9c, 0x0b, 0x4e, 0xf8, 0xbb, 0x6d, 0x6b, 0xb9, 0xbd, 0x38,
+            0x0a, 0x11,
+        ]);
+        assert_eq!(uuid_key(&uuid), key);
+    }
+
+    #[pg_test]
+    fn test_invalid_edges() {
+        meritrank_clear(true).unwrap();
+        let uuid = Uuid::from_bytes([
+            0xa0, 0xee, 0xbc, 0x99, 0x9c, 0x0b, 0x4e, 0xf8, 0xbb, 0x6d, 0x6b, 0xb9, 0xbd, 0x38,
+            0x0a, 0x11,
+        ]);
+
+        // Self-references and weights that are not finite are refused by name and by key
+        assert!(meritrank_add(A, A, 1.0).is_err());
+        assert!(meritrank_add(A, B, f64::NAN).is_err());
+        assert!(meritrank_add(A, B, f64::INFINITY).is_err());
+        assert!(meritrank_add_uuid(uuid, uuid, 1.0).is_err());
+        meritrank_add(A, B, 1.0).unwrap();
+        let other = Uuid::from_bytes([
+            0xb0, 0xee, 0xbc, 0x99, 0x9c, 0x0b, 0x4e, 0xf8, 0xbb, 0x6d, 0x6b, 0xb9, 0xbd, 0x38,
+            0x0a, 0x12,
+        ]);
+        assert!(meritrank_add_uuid(uuid, other, f64::NAN).is_err());
+        assert_eq!(lock_graph().unwrap().edge_weight(A, B), Some(1.0));
+    }
+
+    #[pg_test]
+    fn test_uuid_nodes() {
+        meritrank_clear(true).unwrap();
+        Spi::run(&format!(
+            "SELECT meritrank_add('{A}'::uuid, '{B}'::uuid, 1); \
+             SELECT meritrank_add('{B}'::uuid, '{C}'::uuid, 1);"
+        ))
+        .unwrap();
+        // Nodes added by UUID and by its text form are the same nodes
+        meritrank_add(A, C, 1.0).unwrap();
+        meritrank_add(A, "uuid_plain", 1.0).unwrap();
+
+        let rank: Option<f64> = Spi::get_one(&format!(
+            "SELECT meritrank_calculate('{A}'::uuid, '{C}'::uuid, 200)"
+        ))
+        .unwrap();
+        assert!(rank.unwrap() > 0.0);
+
+        // Peers that are not UUIDs are left out
+        let peers: Option<i64> = Spi::get_one(&format!(
+            "SELECT count(*) FROM meritrank_ranks('{A}'::uuid, 200) \
+             WHERE node IN ('{B}'::uuid, '{C}'::uuid)"
+        ))
+        .unwrap();
+        assert_eq!(peers, Some(2));
+        let all: Option<i64> = Spi::get_one(&format!(
+            "SELECT count(*) FROM meritrank_ranks('{A}'::uuid, 200)"
+        ))
+        .unwrap();
+        assert_eq!(all, peers);
+
+        Spi::run(&format!(
+            "SELECT meritrank_delete('{A}'::uuid, '{B}'::uuid)"
+        ))
+        .unwrap();
+        assert!(!meritrank_neighbors(A, "out")
+            .unwrap()
+            .any(|(neighbor, _, _)| neighbor == B));
+    }
+
+    #[pg_test]
+    fn test_uuid_ranks_limit() {
+        meritrank_clear(true).unwrap();
+        Spi::run(&format!(
+            "SELECT meritrank_add('{A}'::uuid, '{B}'::uuid, 1); \
+             SELECT meritrank_add('{B}'::uuid, '{C}'::uuid, 1);"
+        ))
+        .unwrap();
+        // A peer without a UUID that outranks the others
+        meritrank_add(A, "uuid_plain", 10.0).unwrap();
+
+        // Nodes named by UUID are indexed by key, whichever API added them
+        let key = NodeKey::from_name(C).unwrap();
+        let graph = lock_graph().unwrap();
+        assert_eq!(
+            graph.keyed_node_id(key),
+            graph.borrow_node_names().get(C).copied()
+        );
+        drop(graph);
+
+        let peers: Option<i64> = Spi::get_one(&format!(
+            "SELECT count(*) FROM meritrank_ranks('{A}'::uuid, 200, 2)"
+        ))
+        .unwrap();
+        assert_eq!(peers, Some(2));
+    }
+}
@@ -0,0 +1,157 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::{lock_graph, meritrank_clear};
+    use crate::vote::{meritrank_vote, meritrank_voter};
+    use pgx::*;
+
+    #[pg_test]
+    fn test_voter_defaults_to_current_role() {
+        let role = Spi::get_one::<String>("SELECT current_user::text")
+            .unwrap()
+            .unwrap();
+        assert_eq!(meritrank_voter().unwrap(), role);
+    }
+
+    #[pg_test]
+    fn test_vote_as_session_voter() {
+        meritrank_clear(true).unwrap();
+        Spi::run("SET meritrank.voter_setting = 'app.user_name'").unwrap();
+        Spi::run("SET app.user_name = 'vote_alice'").unwrap();
+
+        meritrank_vote("vote_bob", 2.0).unwrap();
+        assert!(lock_graph()
+            .unwrap()
+            .contains_edge("vote_alice", "vote_bob"));
+
+        // Retracting the vote
+        meritrank_vote("vote_bob", 0.0).unwrap();
+        assert!(!lock_graph()
+            .unwrap()
+            .contains_edge("vote_alice", "vote_bob"));
+
+        // Without a voter in the session, nothing can be voted
+        Spi::run("SET app.user_name = ''").unwrap();
+        assert!(meritrank_vote("vote_bob", 1.0).is_err());
+
+        Spi::run("RESET app.user_name").unwrap();
+        Spi::run("RESET meritrank.voter_setting").unwrap();
+    }
+
+    #[pg_test]
+    fn test_vote_as_unprivileged_role() {
+        meritrank_clear(true).unwrap();
+        Spi::run("CREATE ROLE vote_mallory").unwrap();
+        Spi::run("SET ROLE vote_mallory").unwrap();
+
+        // Neither writing edges directly nor picking the voter is allowed
+        Spi::run(
+            "DO $$ BEGIN \
+                 PERFORM meritrank_add('vote_victim', 'vote_target', 1.0); \
+                 RAISE EXCEPTION 'meritrank_add is executable by PUBLIC'; \
+             EXCEPTION WHEN insufficient_privilege THEN NULL; \
+             END $$",
+        )
+        .unwrap();
+        Spi::run(
+            "DO $$ BEGIN \
+                 SET meritrank.voter_setting = 'app.user_name'; \
+                 RAISE EXCEPTION 'meritrank.voter_setting is settable by PUBLIC'; \
+             EXCEPTION WHEN insufficient_privilege THEN NULL; \
+             END $$",
+        )
+        .unwrap();
+        Spi::run(
+            "DO $$ BEGIN \
+                 SET meritrank.persist_changes = off; \
+                 RAISE EXCEPTION 'meritrank.persist_changes is settable by PUBLIC'; \
+             EXCEPTION WHEN insufficient_privilege THEN NULL; \
+             END $$",
+        )
+        .unwrap();
+
+        // Voting still works, as the role itself
+        Spi::run("SELECT * FROM meritrank_vote('vote_target', 1.0)").unwrap();
+        Spi::run("RESET ROLE").unwrap();
+
+        let graph = lock_graph().unwrap();
+        assert!(graph.contains_edge("vote_mallory", "vote_target"));
+        assert!(!graph.contains_edge("vote_victim", "vote_target"));
+    }
+
+    #[pg_test]
+    fn test_graph_writes_restricted() {
+        let writes = [
+            "meritrank_add",
+            "meritrank_add_id",
+            "meritrank_delete",
+            "meritrank_delete_id",
+            "meritrank_add_batch",
+            "meritrank_add_jsonb",
+            "meritrank_add_node",
+            "meritrank_add_vote",
+            "meritrank_clear",
+            "meritrank_rename_node",
+            "meritrank_merge_nodes",
+            "meritrank_compact",
+            "meritrank_freeze",
+            "meritrank_thaw",
+            "meritrank_drop_walks",
+            "meritrank_set_node_type",
+            "meritrank_set_node_property",
+            "meritrank_generate_graph",
+            "meritrank_load_from",
+            "meritrank_import_csv",
+            "meritrank_build_agg",
+            "meritrank_rebuild_from_votes",
+            "meritrank_bootstrap",
+            "meritrank_install_triggers",
+            "meritrank_sync",
+            "meritrank_restore",
+            "meritrank_flush",
+            "meritrank_outbox_prune",
+            "meritrank_create_graph_table",
+            "insert_and_trigger",
+            "meritrank_vote_trigger",
+        ];
+        let names = format!("ARRAY['{}']", writes.join("', '"));
+
+        // Every function in the list exists, including both overloads of meritrank_add
+        // and meritrank_delete, and none of them is executable by PUBLIC
+        assert_eq!(
+            Spi::get_one::<i64>(&format!(
+                "SELECT count(DISTINCT proname) FROM pg_proc WHERE proname = ANY ({})",
+                names
+            ))
+            .unwrap(),
+            Some(writes.len() as i64)
+        );
+        assert_eq!(
+            Spi::get_one::<i64>(
+                "SELECT count(*) FROM pg_proc \
+                 WHERE proname IN ('meritrank_add', 'meritrank_delete') \
+                   AND 'uuid'::regtype = ANY (proargtypes)"
+            )
+            .unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            Spi::get_one::<i64>(&format!(
+                "SELECT count(*) FROM pg_proc p, aclexplode(p.proacl) a \
+                 WHERE p.proname = ANY ({}) AND a.grantee = 0 \
+                   AND a.privilege_type = 'EXECUTE'",
+                names
+            ))
+            .unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            Spi::get_one::<i64>(&format!(
+                "SELECT count(*) FROM pg_proc WHERE proname = ANY ({}) AND proacl IS NULL",
+                names
+            ))
+            .unwrap(),
+            Some(0)
+        );
+    }
+}
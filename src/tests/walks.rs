@@ -0,0 +1,523 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::cache::meritrank_cache_clear;
+    use crate::graph::{
+        meritrank_add, meritrank_calculate, meritrank_clear, meritrank_freeze, meritrank_ranks,
+        meritrank_thaw, GraphSingleton,
+    };
+    use crate::info::meritrank_algorithm_info;
+    use crate::lib_graph::simd;
+    use crate::lib_graph::walk::AliasTable;
+    use crate::lib_graph::{Connectivity, Convergence, EdgeDecay, MeritRank, MyGraph, NodeId};
+    use crate::walks::{
+        lock_store, meritrank_debug_dump, meritrank_drop_walks, meritrank_walk_stats,
+    };
+    use pgx::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[pg_test]
+    fn test_drop_walks() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("walks_a", "walks_b", 1.0).unwrap();
+        meritrank_add("walks_b", "walks_a", 1.0).unwrap();
+
+        meritrank_calculate("walks_a", "walks_b", Some(100), None, None, false, None).unwrap();
+        meritrank_calculate("walks_b", "walks_a", Some(50), None, None, false, None).unwrap();
+
+        assert_eq!(meritrank_drop_walks(Some("walks_a")).unwrap(), 100);
+        assert_eq!(meritrank_drop_walks(Some("walks_a")).unwrap(), 0);
+        assert_eq!(meritrank_drop_walks(None).unwrap(), 50);
+        assert!(meritrank_drop_walks(Some("walks_missing")).is_err());
+    }
+
+    #[pg_test]
+    fn test_walk_stats() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("walks_a", "walks_b", 1.0).unwrap();
+        meritrank_add("walks_b", "walks_a", 1.0).unwrap();
+        meritrank_add("walks_c", "walks_a", 1.0).unwrap();
+
+        let (walks, average_length, generated_at, _, current) =
+            meritrank_walk_stats("walks_a").unwrap().next().unwrap();
+        assert_eq!((walks, average_length, current), (0, None, false));
+        assert!(generated_at.is_none());
+
+        meritrank_calculate("walks_a", "walks_b", Some(100), None, None, false, None).unwrap();
+        let (walks, average_length, generated_at, _, current) =
+            meritrank_walk_stats("walks_a").unwrap().next().unwrap();
+        assert_eq!(walks, 100);
+        assert!(average_length.unwrap() >= 1.0);
+        assert!(generated_at.is_some());
+        assert!(current);
+
+        let (walks, ..) = meritrank_walk_stats("walks_c").unwrap().next().unwrap();
+        assert_eq!(walks, 0);
+
+        meritrank_add("walks_b", "walks_c", 1.0).unwrap();
+        let (.., current) = meritrank_walk_stats("walks_a").unwrap().next().unwrap();
+        assert!(!current);
+
+        assert!(meritrank_walk_stats("walks_missing").is_err());
+    }
+
+    #[pg_test]
+    fn test_seeded_walks_do_not_depend_on_parallelism() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("walks_a", "walks_b", 1.0).unwrap();
+        meritrank_add("walks_a", "walks_c", 2.0).unwrap();
+        meritrank_add("walks_b", "walks_c", 1.0).unwrap();
+        meritrank_add("walks_c", "walks_a", 1.0).unwrap();
+        Spi::run("SET meritrank.seed = 42").unwrap();
+
+        let mut results = Vec::new();
+        for parallelism in [1, 4] {
+            Spi::run(&format!("SET meritrank.parallelism = {}", parallelism)).unwrap();
+            meritrank_cache_clear().unwrap();
+            let mut ranks: Vec<(String, f64)> = meritrank_ranks(
+                "walks_a",
+                Some(1000),
+                None,
+                None,
+                false,
+                None,
+                None,
+                false,
+                None,
+            )
+            .unwrap()
+            .map(|(node, rank, _, _)| (node, rank))
+            .collect();
+            ranks.sort_by(|a, b| a.0.cmp(&b.0));
+            results.push(ranks);
+        }
+        assert_eq!(results[0], results[1]);
+
+        Spi::run("RESET meritrank.seed").unwrap();
+        Spi::run("RESET meritrank.parallelism").unwrap();
+    }
+
+    #[pg_test]
+    fn test_seeded_walks_do_not_depend_on_parallel_workers() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("workers_a", "workers_b", 1.0).unwrap();
+        meritrank_add("workers_a", "workers_c", 2.0).unwrap();
+        meritrank_add("workers_b", "workers_c", 1.0).unwrap();
+        meritrank_add("workers_c", "workers_a", 1.0).unwrap();
+        meritrank_add("workers_c", "workers_d", -1.0).unwrap();
+        Spi::run("SET meritrank.seed = 42").unwrap();
+
+        let mut results = Vec::new();
+        for workers in [0, 2] {
+            Spi::run(&format!("SET meritrank.parallel_workers = {}", workers)).unwrap();
+            meritrank_cache_clear().unwrap();
+            meritrank_drop_walks(None).unwrap();
+            let mut ranks: Vec<(String, f64)> = meritrank_ranks(
+                "workers_a",
+                Some(3000),
+                None,
+                None,
+                false,
+                None,
+                None,
+                false,
+                None,
+            )
+            .unwrap()
+            .map(|(node, rank, _, _)| (node, rank))
+            .collect();
+            ranks.sort_by(|a, b| a.0.cmp(&b.0));
+            results.push(ranks);
+        }
+        assert_eq!(results[0], results[1]);
+
+        Spi::run("RESET meritrank.seed").unwrap();
+        Spi::run("RESET meritrank.parallel_workers").unwrap();
+    }
+
+    #[pg_test]
+    fn test_walk_edges() {
+        let mut graph = MyGraph::new();
+        for id in 1..=5 {
+            graph.add_node(NodeId::UInt(id).into());
+        }
+        graph
+            .add_edge(NodeId::UInt(1), NodeId::UInt(3), 2.0)
+            .unwrap();
+        graph
+            .add_edge(NodeId::UInt(1), NodeId::UInt(2), 1.0)
+            .unwrap();
+        graph
+            .add_edge(NodeId::UInt(2), NodeId::UInt(4), -1.0)
+            .unwrap();
+        graph
+            .add_edge(NodeId::UInt(3), NodeId::UInt(1), 1.0)
+            .unwrap();
+        graph
+            .add_edge(NodeId::UInt(5), NodeId::UInt(1), 1.0)
+            .unwrap();
+        let merit_rank = MeritRank::new(graph).unwrap();
+
+        // Negative edges and nodes that cannot be reached from the ego are left out
+        assert_eq!(
+            merit_rank.walk_edges(NodeId::UInt(1)),
+            vec![
+                (
+                    NodeId::UInt(1),
+                    vec![(NodeId::UInt(2), 1.0), (NodeId::UInt(3), 2.0)]
+                ),
+                (NodeId::UInt(3), vec![(NodeId::UInt(1), 1.0)]),
+            ]
+        );
+    }
+
+    #[pg_test]
+    fn test_frozen_graph() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("frozen_a", "frozen_b", 1.0).unwrap();
+        meritrank_add("frozen_a", "frozen_c", 2.0).unwrap();
+        meritrank_add("frozen_b", "frozen_c", 1.0).unwrap();
+        meritrank_add("frozen_c", "frozen_a", 1.0).unwrap();
+        meritrank_add("frozen_c", "frozen_b", -1.0).unwrap();
+        Spi::run("SET meritrank.seed = 42").unwrap();
+
+        let ranks = || {
+            meritrank_cache_clear().unwrap();
+            let mut ranks: Vec<(String, f64)> = meritrank_ranks(
+                "frozen_a",
+                Some(1000),
+                None,
+                None,
+                false,
+                None,
+                None,
+                false,
+                None,
+            )
+            .unwrap()
+            .map(|(node, rank, _, _)| (node, rank))
+            .collect();
+            ranks.sort_by(|a, b| a.0.cmp(&b.0));
+            ranks
+        };
+        let unfrozen = ranks();
+        // Negative edges are left out of the copy
+        assert_eq!(meritrank_freeze().unwrap().next().unwrap().0, 3);
+        assert_eq!(meritrank_freeze().unwrap().next().unwrap().1, 4);
+        assert_eq!(ranks(), unfrozen);
+
+        // Writes to a frozen graph rebuild the copy
+        meritrank_add("frozen_c", "frozen_d", 1.0).unwrap();
+        assert!(ranks().iter().any(|(node, _)| node == "frozen_d"));
+
+        assert!(meritrank_thaw().unwrap());
+        assert!(!meritrank_thaw().unwrap());
+        Spi::run("RESET meritrank.seed").unwrap();
+    }
+
+    #[pg_test]
+    fn test_alias_table_sampling() {
+        assert!(AliasTable::new(Vec::<u32>::new(), &[]).is_none());
+        assert!(AliasTable::new(vec![1, 2], &[0.0, 0.0]).is_none());
+
+        let table = AliasTable::new(vec![0usize, 1, 2], &[1.0, 3.0, 0.0]).unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = [0usize; 3];
+        for _ in 0..40_000 {
+            counts[table.sample(&mut rng)] += 1;
+        }
+
+        assert_eq!(counts[2], 0);
+        let ratio = counts[1] as f64 / counts[0] as f64;
+        assert!((ratio - 3.0).abs() < 0.2, "unexpected ratio {}", ratio);
+    }
+
+    #[pg_test]
+    fn test_simd_kernels() {
+        // Lengths around multiples of the lane count exercise the remainders
+        for len in [0, 1, simd::LANES - 1, simd::LANES, 3 * simd::LANES + 5] {
+            let hits: Vec<f64> = (0..len).map(|i| i as f64).collect();
+            let penalties: Vec<f64> = (0..len).map(|i| -((i % 3) as f64)).collect();
+            assert_eq!(simd::sum(&hits), hits.iter().sum::<f64>());
+
+            let mut scores = hits.clone();
+            simd::penalize_and_normalize(&mut scores, &penalties, 0.5, 4.0);
+            let expected: Vec<f64> = hits
+                .iter()
+                .zip(&penalties)
+                .map(|(hit, penalty)| (hit + 0.5 * penalty) / 4.0)
+                .collect();
+            assert_eq!(scores, expected);
+        }
+    }
+
+    #[pg_test]
+    fn test_ranks_match_node_scores() {
+        let nodes: Vec<NodeId> = (1..=20).map(NodeId::UInt).collect();
+        let mut graph = MyGraph::new();
+        for &node in &nodes {
+            graph.add_node(node.into());
+        }
+        for (i, &source) in nodes.iter().enumerate() {
+            for &target in &nodes[i + 1..(i + 4).min(nodes.len())] {
+                graph.add_edge(source, target, 1.0).unwrap();
+            }
+        }
+        graph.add_edge(nodes[0], nodes[19], -1.0).unwrap();
+
+        let mut merit_rank = MeritRank::new(graph).unwrap();
+        merit_rank.set_seed(Some(3));
+        merit_rank.calculate(nodes[0], 1_000).unwrap();
+
+        let ranks = merit_rank.get_ranks(nodes[0], None).unwrap();
+        assert!(ranks.len() > simd::LANES);
+        for (peer, rank) in ranks {
+            let score = merit_rank.get_node_score(nodes[0], peer).unwrap();
+            assert!((rank - score).abs() < 1e-12);
+        }
+    }
+
+    #[pg_test]
+    fn test_edge_decay() {
+        let day = 86_400;
+        let decay = EdgeDecay::new(10.0 * day as f64, 100 * day).unwrap();
+        assert_eq!(decay.apply(1.0, 100 * day), 1.0);
+        assert_eq!(decay.apply(1.0, 90 * day), 0.5);
+        assert_eq!(decay.apply(-2.0, 80 * day), -0.5);
+        assert_eq!(decay.apply(1.0, 110 * day), 1.0);
+        assert!(EdgeDecay::new(0.0, 0).is_none());
+
+        let (a, b, c) = (NodeId::UInt(1), NodeId::UInt(2), NodeId::UInt(3));
+        let mut graph = MyGraph::new();
+        for node in [a, b, c] {
+            graph.add_node(node.into());
+        }
+        graph.add_edge(a, b, 1.0).unwrap();
+        graph.add_edge(a, c, 1.0).unwrap();
+        graph.set_edge_timestamp(a, b, 100 * day);
+        graph.set_edge_timestamp(a, c, 90 * day);
+
+        let mut merit_rank = MeritRank::new(graph).unwrap();
+        merit_rank.calculate(a, 100).unwrap();
+        merit_rank.set_decay(Some(decay));
+        assert!(merit_rank.get_calculated_egos().is_empty());
+
+        let neighbors = merit_rank.neighbors_weighted(a, true).unwrap();
+        assert_eq!(neighbors[&b], 1.0);
+        assert_eq!(neighbors[&c], 0.5);
+    }
+
+    #[pg_test]
+    fn test_convergence_stops_early() {
+        let (a, b, c) = (NodeId::UInt(1), NodeId::UInt(2), NodeId::UInt(3));
+        let mut graph = MyGraph::new();
+        for node in [a, b, c] {
+            graph.add_node(node.into());
+        }
+        graph.add_edge(a, b, 3.0).unwrap();
+        graph.add_edge(a, c, 1.0).unwrap();
+
+        let mut merit_rank = MeritRank::new(graph).unwrap();
+        merit_rank.set_seed(Some(1));
+        assert_eq!(merit_rank.calculate(a, 2_000).unwrap(), 2_000);
+
+        merit_rank.set_convergence(Some(Convergence {
+            batch_size: 100,
+            top_k: 3,
+            stable_checks: 2,
+        }));
+        let walks = merit_rank.calculate(a, 100_000).unwrap();
+        assert!(walks < 100_000);
+        assert_eq!(walks % 100, 0);
+
+        let ranks = merit_rank.get_ranks(a, None).unwrap();
+        assert_eq!(ranks[0].0, a);
+        assert_eq!(ranks[1].0, b);
+    }
+
+    #[pg_test]
+    fn test_max_walk_length() {
+        let (a, b, c, d) = (
+            NodeId::UInt(1),
+            NodeId::UInt(2),
+            NodeId::UInt(3),
+            NodeId::UInt(4),
+        );
+        let mut graph = MyGraph::new();
+        for node in [a, b, c, d] {
+            graph.add_node(node.into());
+        }
+        graph.add_edge(a, b, 1.0).unwrap();
+        graph.add_edge(b, c, 1.0).unwrap();
+        graph.add_edge(c, d, 1.0).unwrap();
+
+        let mut merit_rank = MeritRank::new(graph).unwrap();
+        merit_rank.calculate(a, 500).unwrap();
+        merit_rank.set_max_walk_length(Some(2));
+        assert!(merit_rank.get_calculated_egos().is_empty());
+
+        merit_rank.calculate(a, 500).unwrap();
+        let ranked: Vec<NodeId> = merit_rank
+            .get_ranks(a, None)
+            .unwrap()
+            .into_iter()
+            .map(|(node, _)| node)
+            .collect();
+        assert_eq!(ranked.len(), 2);
+        assert!(!ranked.contains(&c) && !ranked.contains(&d));
+    }
+
+    #[pg_test]
+    fn test_sybil_settings() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("sybil_a", "sybil_b", 1.0).unwrap();
+        meritrank_add("sybil_b", "sybil_c", 1.0).unwrap();
+
+        Spi::run("SET meritrank.max_walk_length = 2").unwrap();
+        assert!(
+            meritrank_calculate("sybil_a", "sybil_c", Some(500), None, None, false, None).is_err()
+        );
+        Spi::run("RESET meritrank.max_walk_length").unwrap();
+        assert!(
+            meritrank_calculate("sybil_a", "sybil_c", Some(500), None, None, false, None).unwrap()
+                > 0.0
+        );
+
+        Spi::run("SET meritrank.alpha = 0.5").unwrap();
+        let (_, _, _, alpha, _, _) = meritrank_algorithm_info().next().unwrap();
+        assert_eq!(alpha, 0.5);
+        Spi::run("RESET meritrank.alpha").unwrap();
+    }
+
+    #[pg_test]
+    fn test_connectivity_decay() {
+        let nodes: Vec<NodeId> = (1..=5).map(NodeId::UInt).collect();
+        let (a, b, c, d, e) = (nodes[0], nodes[1], nodes[2], nodes[3], nodes[4]);
+        let mut graph = MyGraph::new();
+        for &node in &nodes {
+            graph.add_node(node.into());
+        }
+        for (source, target) in [(a, b), (a, c), (b, d), (c, d), (b, e)] {
+            graph.add_edge(source, target, 1.0).unwrap();
+        }
+
+        let mut merit_rank = MeritRank::new(graph).unwrap();
+        merit_rank.set_seed(Some(7));
+        merit_rank.calculate(a, 2_000).unwrap();
+        assert_eq!(merit_rank.count_predecessors(a, d), 2);
+        assert_eq!(merit_rank.count_predecessors(a, e), 1);
+
+        let (score_d, score_e) = (
+            merit_rank.get_node_score(a, d).unwrap(),
+            merit_rank.get_node_score(a, e).unwrap(),
+        );
+        merit_rank.set_connectivity(Some(Connectivity {
+            min_predecessors: 2,
+            decay: 0.5,
+        }));
+        assert_eq!(merit_rank.get_node_score(a, d).unwrap(), score_d);
+        assert_eq!(merit_rank.get_node_score(a, e).unwrap(), score_e * 0.5);
+        assert_eq!(
+            merit_rank.get_node_score(a, a).unwrap(),
+            merit_rank.get_ranks(a, None).unwrap()[0].1
+        );
+
+        // Incremental updates keep the predecessors up to date
+        merit_rank.add_edge(c, e, 1.0);
+        assert_eq!(merit_rank.count_predecessors(a, e), 2);
+        merit_rank.add_edge(c, e, 0.0);
+        assert_eq!(merit_rank.count_predecessors(a, e), 1);
+    }
+
+    #[pg_test]
+    fn test_debug_dump() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("dump_a", "dump_b", 1.0).unwrap();
+        meritrank_add("dump_a", "dump_c", -1.0).unwrap();
+        meritrank_add("dump_b", "dump_c", 1.0).unwrap();
+        meritrank_calculate("dump_a", "dump_b", Some(50), None, None, false, None).unwrap();
+
+        let JsonB(dump) = meritrank_debug_dump("dump_a").unwrap();
+        assert_eq!(dump["ego"], "dump_a");
+        assert!(dump["params"].is_u64());
+
+        let walks = dump["walks"].as_array().unwrap();
+        assert_eq!(walks.len(), 50);
+        assert!(walks.iter().all(|walk| walk["nodes"][0] == "dump_a"));
+        assert_eq!(dump["positions"]["dump_a"].as_array().unwrap().len(), 50);
+        assert_eq!(dump["hits"]["dump_a"], 50.0);
+        assert!(dump["negative_hits"]["dump_c"].as_f64().unwrap() < 0.0);
+
+        assert!(meritrank_debug_dump("dump_missing").is_err());
+    }
+
+    #[pg_test]
+    fn test_evict_walks() {
+        let (a, b, c) = (NodeId::UInt(1), NodeId::UInt(2), NodeId::UInt(3));
+        let mut graph = MyGraph::new();
+        for node in [a, b, c] {
+            graph.add_node(node.into());
+        }
+        graph.add_edge(a, c, 1.0).unwrap();
+        graph.add_edge(b, c, 1.0).unwrap();
+
+        let mut merit_rank = MeritRank::new(graph).unwrap();
+        merit_rank.calculate(a, 100).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        merit_rank.calculate(b, 100).unwrap();
+
+        let usage = merit_rank.walks_memory_usage();
+        assert_eq!(merit_rank.evict_walks(usage), (0, 0));
+
+        assert_eq!(merit_rank.evict_walks(0), (1, 100));
+        assert_eq!(merit_rank.get_calculated_egos(), vec![b]);
+        assert!(merit_rank.walks_memory_usage() < usage);
+        assert!(merit_rank.get_ranks(a, None).is_err());
+        assert!(merit_rank.get_ranks(b, None).is_ok());
+    }
+
+    #[pg_test]
+    fn test_evict_walks_used_least_recently() {
+        let (a, b, c) = (NodeId::UInt(1), NodeId::UInt(2), NodeId::UInt(3));
+        let mut graph = MyGraph::new();
+        for node in [a, b, c] {
+            graph.add_node(node.into());
+        }
+        graph.add_edge(a, c, 1.0).unwrap();
+        graph.add_edge(b, c, 1.0).unwrap();
+
+        // The walks of `a` were generated first, but used last
+        let mut merit_rank = MeritRank::new(graph).unwrap();
+        merit_rank.calculate(a, 100).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        merit_rank.calculate(b, 100).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        merit_rank.mark_used(a);
+
+        assert_eq!(merit_rank.evict_walks(0), (1, 100));
+        assert_eq!(merit_rank.get_calculated_egos(), vec![a]);
+    }
+
+    #[pg_test]
+    fn test_cached_ranks_use_walks() {
+        meritrank_clear(true).unwrap();
+        meritrank_add("lru_a", "lru_c", 1.0).unwrap();
+        meritrank_add("lru_b", "lru_c", 1.0).unwrap();
+        meritrank_cache_clear().unwrap();
+
+        Spi::run("SELECT count(*) FROM meritrank_ranks('lru_a')").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        Spi::run("SELECT count(*) FROM meritrank_ranks('lru_b')").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        // Served from the rank cache
+        Spi::run("SELECT count(*) FROM meritrank_ranks('lru_a')").unwrap();
+
+        let a = GraphSingleton::node_name_to_id("lru_a").unwrap();
+        let b = GraphSingleton::node_name_to_id("lru_b").unwrap();
+        let store = lock_store().unwrap();
+        let storage = store.get().unwrap().get_walk_storage();
+        assert!(storage.get_last_used(a).unwrap() > storage.get_last_used(b).unwrap());
+        assert!(storage.get_generated_at(a).unwrap() < storage.get_generated_at(b).unwrap());
+    }
+}
@@ -0,0 +1,53 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[crate::pg_schema]
+mod tests {
+    use crate::graph::meritrank_add;
+    use crate::persist::meritrank_flush;
+    use crate::stats::meritrank_edge_count;
+    use crate::weights::{
+        meritrank_set_weight, meritrank_translate_weight, meritrank_unset_weight,
+    };
+    use pgx::*;
+
+    #[pg_test]
+    fn test_weight_translation() {
+        meritrank_set_weight("vote_test", 3.0, 5.0).unwrap();
+
+        assert_eq!(meritrank_translate_weight("vote_test", 3.0).unwrap(), 5.0);
+        // Unmapped values and other source types pass through unchanged
+        assert_eq!(meritrank_translate_weight("vote_test", -1.0).unwrap(), -1.0);
+        assert_eq!(meritrank_translate_weight("vote_other", 3.0).unwrap(), 3.0);
+
+        meritrank_set_weight("vote_test", 3.0, 2.0).unwrap();
+        assert_eq!(meritrank_translate_weight("vote_test", 3.0).unwrap(), 2.0);
+
+        assert!(meritrank_unset_weight("vote_test", 3.0).unwrap());
+        assert!(!meritrank_unset_weight("vote_test", 3.0).unwrap());
+        assert_eq!(meritrank_translate_weight("vote_test", 3.0).unwrap(), 3.0);
+    }
+
+    #[pg_test]
+    fn test_update_graph_procedure() {
+        meritrank_add("stale_a", "stale_b", 1.0).unwrap();
+        Spi::run(
+            "CREATE TABLE vote_rebuild (subject text, object text, amount integer); \
+             INSERT INTO vote_rebuild VALUES \
+                 ('rebuild_a', 'rebuild_b', 3), ('rebuild_b', 'rebuild_c', 1), \
+                 ('rebuild_c', 'rebuild_c', 1), ('rebuild_c', NULL, 1)",
+        )
+        .unwrap();
+        meritrank_set_weight("vote_rebuild", 3.0, 5.0).unwrap();
+
+        Spi::run("CALL update_graph_procedure(ARRAY['public.vote_rebuild'])").unwrap();
+
+        // The graph is rebuilt from the votes, skipping the self-reference and the NULL
+        assert_eq!(meritrank_edge_count().unwrap(), 2);
+        // The edges are mirrored to the graph table like those of meritrank_add
+        meritrank_flush().unwrap();
+        let weight: Option<f64> = Spi::get_one(
+            "SELECT weight FROM graph WHERE source = 'rebuild_a' AND destination = 'rebuild_b'",
+        )
+        .unwrap();
+        assert_eq!(weight, Some(5.0));
+    }
+}
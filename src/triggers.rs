@@ -0,0 +1,209 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+#[allow(unused_imports)]
+use crate::graph::meritrank_add;
+use crate::graph::meritrank_clear;
+use crate::guc;
+use crate::loader::quote_literal;
+#[allow(unused_imports)]
+use crate::persist::meritrank_flush;
+use crate::weights::apply_votes;
+#[allow(unused_imports)]
+use crate::weights::meritrank_add_vote;
+
+// The trigger function receives the names of the subject, object and amount columns as
+// arguments, so one function serves vote tables of any layout. Votes are translated
+// with meritrank_weight_map, with the table name as the source type. Removing an edge
+// is an update to a weight of zero, which does not fail for edges that are missing.
+extension_sql!(
+    r#"
+CREATE FUNCTION meritrank_vote_trigger() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+    new_row jsonb := CASE WHEN TG_OP <> 'DELETE' THEN to_jsonb(NEW) END;
+    old_row jsonb := CASE WHEN TG_OP <> 'INSERT' THEN to_jsonb(OLD) END;
+BEGIN
+    IF TG_OP = 'DELETE' OR (TG_OP = 'UPDATE'
+        AND (old_row ->> TG_ARGV[0], old_row ->> TG_ARGV[1])
+            IS DISTINCT FROM (new_row ->> TG_ARGV[0], new_row ->> TG_ARGV[1])) THEN
+        PERFORM meritrank_add(old_row ->> TG_ARGV[0], old_row ->> TG_ARGV[1], 0);
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        PERFORM meritrank_add_vote(
+            TG_TABLE_NAME,
+            new_row ->> TG_ARGV[0],
+            new_row ->> TG_ARGV[1],
+            (new_row ->> TG_ARGV[2])::float8
+        );
+    END IF;
+    -- Written while the rights of the function owner apply, see restrict_graph_writes
+    PERFORM meritrank_flush();
+    RETURN NULL;
+END;
+$$;
+"#,
+    name = "create_vote_trigger_function",
+    requires = [meritrank_add, meritrank_add_vote, meritrank_flush]
+);
+
+#[pg_extern]
+/// Installs a trigger that mirrors the votes of a table to the graph, for tables of any
+/// naming scheme. Inserted votes add an edge, updated ones change it, and deleted ones
+/// remove it; vote values are translated with `meritrank_weight_map`, with the table
+/// name as the source type.
+///
+/// Installing again replaces the trigger, e.g. to change the columns.
+///
+/// # Arguments
+///
+/// * `table` - The vote table, optionally schema-qualified.
+/// * `subject_col` - The column holding the voter.
+/// * `object_col` - The column holding the node voted for.
+/// * `amount_col` - The column holding the vote value.
+pub fn meritrank_install_triggers(
+    table: &str,
+    subject_col: default!(&str, "'subject'"),
+    object_col: default!(&str, "'object'"),
+    amount_col: default!(&str, "'amount'"),
+) -> Result<(), ErrorReport> {
+    Ok(install_triggers(
+        table,
+        subject_col,
+        object_col,
+        amount_col,
+    )?)
+}
+
+/// Installs the sync trigger on `table` as `meritrank_install_triggers` does.
+fn install_triggers(
+    table: &str,
+    subject_col: &str,
+    object_col: &str,
+    amount_col: &str,
+) -> Result<(), GraphManipulationError> {
+    // Resolving through regclass checks the table and quotes its name
+    let relation = Spi::get_one_with_args::<String>(
+        "SELECT $1::regclass::text",
+        vec![(PgBuiltInOids::TEXTOID.oid(), table.into_datum())],
+    )
+    .map_err(|e| GraphManipulationError::DataExtractionFailure(e.to_string()))?
+    .unwrap_or_default();
+
+    for column in [subject_col, object_col, amount_col] {
+        let exists = Spi::get_one_with_args::<bool>(
+            "SELECT EXISTS ( \
+                 SELECT 1 FROM pg_attribute \
+                 WHERE attrelid = $1::regclass AND attname = $2 \
+                   AND attnum > 0 AND NOT attisdropped \
+             )",
+            vec![
+                (PgBuiltInOids::TEXTOID.oid(), table.into_datum()),
+                (PgBuiltInOids::TEXTOID.oid(), column.into_datum()),
+            ],
+        )
+        .map_err(|e| GraphManipulationError::DataExtractionFailure(e.to_string()))?;
+        if exists != Some(true) {
+            return Err(GraphManipulationError::ColumnNotFound(format!(
+                "{}.{}",
+                relation, column
+            )));
+        }
+    }
+
+    Spi::run(&format!(
+        "DROP TRIGGER IF EXISTS meritrank_sync ON {relation}; \
+         CREATE TRIGGER meritrank_sync AFTER INSERT OR UPDATE OR DELETE ON {relation} \
+         FOR EACH ROW EXECUTE FUNCTION meritrank_vote_trigger({}, {}, {})",
+        quote_literal(subject_col),
+        quote_literal(object_col),
+        quote_literal(amount_col),
+    ))
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))
+}
+
+#[pg_extern]
+/// Discovers the vote tables of the database, installs the sync trigger on them and
+/// rebuilds the graph from their votes, to set up a database in one call.
+///
+/// Vote tables are the tables outside the system schemas whose name matches `pattern`
+/// and that have `subject`, `object` and `amount` columns; other matching tables are
+/// skipped with a notice. The votes are added on top of the current graph; with `replace`,
+/// the graph is cleared first, in memory and in the `graph` table, as by
+/// `update_graph_procedure`, which drops every edge that is not backed by a vote.
+///
+/// # Arguments
+///
+/// * `pattern` - A `LIKE` pattern of table names. Defaults to `meritrank.vote_table_pattern`.
+/// * `replace` - Whether to delete the existing edges first. Defaults to `false`.
+///
+/// # Returns
+///
+/// One row per vote table with the number of edges added or updated from it.
+pub fn meritrank_bootstrap(
+    pattern: default!(Option<&str>, "NULL"),
+    replace: default!(bool, false),
+) -> Result<TableIterator<'static, (name!(table_name, String), name!(edges, i64))>, ErrorReport> {
+    let pattern = match pattern {
+        Some(pattern) => pattern.to_string(),
+        None => guc::VOTE_TABLE_PATTERN
+            .get()
+            .unwrap_or_default()
+            .to_string(),
+    };
+
+    let candidates = Spi::connect(|client| -> Result<_, GraphManipulationError> {
+        let rows = client
+            .select(
+                "SELECT format('%I.%I', n.nspname, c.relname), c.relname, \
+                     (SELECT count(*) FROM pg_attribute a \
+                      WHERE a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped \
+                        AND a.attname IN ('subject', 'object', 'amount')) = 3 \
+                 FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+                 WHERE c.relkind IN ('r', 'p') AND NOT c.relispartition \
+                   AND c.relname LIKE $1 \
+                   AND n.nspname NOT IN ('pg_catalog', 'information_schema') \
+                   AND n.nspname NOT LIKE 'pg_toast%' \
+                 ORDER BY 1",
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), pattern.into_datum())]),
+            )
+            .map_err(|e| GraphManipulationError::DataExtractionFailure(e.to_string()))?;
+
+        let mut candidates = Vec::new();
+        for row in rows {
+            if let (Some(relation), Some(name), Some(has_columns)) = (
+                row.get::<String>(1)?,
+                row.get::<String>(2)?,
+                row.get::<bool>(3)?,
+            ) {
+                candidates.push((relation, name, has_columns));
+            }
+        }
+        Ok(candidates)
+    })?;
+
+    if replace {
+        meritrank_clear(true)?;
+    }
+
+    let mut tables = Vec::new();
+    for (relation, name, has_columns) in candidates {
+        if !has_columns {
+            notice!(
+                "meritrank: skipping {}, which lacks subject, object or amount columns",
+                relation
+            );
+            continue;
+        }
+        install_triggers(&relation, "subject", "object", "amount")?;
+        let edges = apply_votes(&relation, &name)?;
+        tables.push((relation, edges));
+    }
+
+    Ok(TableIterator::new(tables.into_iter()))
+}
@@ -0,0 +1,105 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::graph::{self, NodeKey};
+use crate::rating::{Alpha, Iterations, Limit, PenaltyFactor, TimeBudget};
+
+/// Returns the native key of a node identified by a UUID.
+pub fn uuid_key(uuid: &Uuid) -> NodeKey {
+    NodeKey::Uuid(*uuid.as_bytes())
+}
+
+#[pg_extern(name = "meritrank_add")]
+/// Sets the weight of the edge between two nodes identified by UUIDs, as
+/// `meritrank_add(text, text, float8)` does for their canonical text form, and returns
+/// what changed.
+///
+/// The nodes are looked up by their 16-byte keys rather than by name, so nodes added by
+/// UUID and by the text form of the UUID are the same nodes.
+pub fn meritrank_add_uuid(
+    subject: Uuid,
+    object: Uuid,
+    amount: f64,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(previous_weight, Option<f64>),
+            name!(weight, Option<f64>),
+            name!(created, bool),
+        ),
+    >,
+    ErrorReport,
+> {
+    let change = graph::set_keyed_edge(uuid_key(&subject), uuid_key(&object), amount)?;
+
+    let previous_weight = change.and_then(|change| change.previous_weight);
+    let weight = Some(amount).filter(|&amount| amount != 0.0);
+    let created = previous_weight.is_none() && weight.is_some();
+    Ok(TableIterator::once((previous_weight, weight, created)))
+}
+
+#[pg_extern(name = "meritrank_delete")]
+/// Removes the edge between two nodes identified by UUIDs.
+pub fn meritrank_delete_uuid(subject: Uuid, object: Uuid) -> Result<(), ErrorReport> {
+    graph::set_keyed_edge(uuid_key(&subject), uuid_key(&object), 0.0)?;
+    Ok(())
+}
+
+#[pg_extern(name = "meritrank_calculate")]
+/// Calculates the rank of `object` from the perspective of `subject`, both identified
+/// by UUIDs.
+pub fn meritrank_calculate_uuid(
+    subject: Uuid,
+    object: Uuid,
+    iterations: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<f64, ErrorReport> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let (rank, _) = graph::calculate_rank(
+        uuid_key(&subject),
+        uuid_key(&object),
+        iterations,
+        penalty_factor,
+        alpha,
+        false,
+        TimeBudget::default(),
+    )?;
+    Ok(rank)
+}
+
+#[pg_extern(name = "meritrank_ranks")]
+/// Returns the ranks of the peers of `ego`, identified by UUIDs, sorted in descending
+/// order. Peers whose names are not UUIDs, e.g. added by name, are left out before
+/// `limit` is applied.
+pub fn meritrank_ranks_uuid(
+    ego: Uuid,
+    iterations: default!(Option<i32>, "NULL"),
+    limit: default!(Option<i32>, "NULL"),
+    penalty_factor: default!(Option<f64>, "NULL"),
+    alpha: default!(Option<f64>, "NULL"),
+) -> Result<TableIterator<'static, (name!(node, Uuid), name!(rank, f64))>, ErrorReport> {
+    // Validate arguments before touching the graph
+    let iterations = Iterations::from_nullable(iterations)?;
+    let limit = Limit::from_nullable(limit)?;
+    let penalty_factor = PenaltyFactor::from_nullable(penalty_factor)?;
+    let alpha = Alpha::from_nullable(alpha)?;
+
+    let ranks: Vec<(Uuid, f64)> =
+        graph::calculate_keyed_ranks(uuid_key(&ego), iterations, limit, penalty_factor, alpha)?
+            .into_iter()
+            .filter_map(|(key, rank)| match key {
+                NodeKey::Uuid(bytes) => Some((Uuid::from_bytes(bytes), rank)),
+                _ => None,
+            })
+            .collect();
+
+    Ok(TableIterator::new(ranks.into_iter()))
+}
@@ -0,0 +1,124 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::audit::acting_role;
+use crate::error::GraphManipulationError;
+use crate::graph::meritrank_add;
+use crate::guc;
+use crate::persist;
+
+/// Returns the node name the current session votes as.
+///
+/// That is the value of the setting named by `meritrank.voter_setting`, or the name of
+/// the current role when it is empty. Any role can change a custom setting such as
+/// `request.jwt.claims.sub`, so it must only name the voter when a trusted layer, e.g. a
+/// connection pooler or PostgREST, sets it and application roles cannot issue `SET`.
+pub fn current_voter() -> Result<String, GraphManipulationError> {
+    let voter = match guc::VOTER_SETTING
+        .get()
+        .filter(|setting| !setting.is_empty())
+    {
+        Some(setting) => Spi::get_one_with_args::<String>(
+            "SELECT current_setting($1, true)",
+            vec![(PgBuiltInOids::TEXTOID.oid(), setting.into_datum())],
+        )?
+        .filter(|voter| !voter.is_empty())
+        .ok_or_else(|| {
+            GraphManipulationError::PermissionDenied(format!(
+                "{} does not name a voter in this session",
+                setting
+            ))
+        })?,
+        None => acting_role()?,
+    };
+    Ok(voter)
+}
+
+#[pg_extern]
+/// Returns the node name `meritrank_vote` votes as in this session.
+pub fn meritrank_voter() -> Result<String, ErrorReport> {
+    Ok(current_voter()?)
+}
+
+#[pg_extern]
+/// Sets the weight of the edge from the current voter to `object`, like `meritrank_add`.
+///
+/// The subject is never taken from the caller: it is derived from the session with
+/// `meritrank_voter()`, so application roles that may only execute this function can
+/// only create edges originating from themselves. A weight of zero retracts the vote.
+///
+/// The function runs with the rights of the extension owner, since `meritrank_add` and
+/// the other functions that write arbitrary edges are not executable by `PUBLIC`. The
+/// vote is written to the `graph` table before it returns, while those rights apply.
+///
+/// # Arguments
+///
+/// * `object` - The name of the node voted for.
+/// * `amount` - The weight of the edge.
+///
+/// # Returns
+///
+/// What changed, as returned by `meritrank_add`.
+pub fn meritrank_vote(
+    object: &str,
+    amount: f64,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(previous_weight, Option<f64>),
+            name!(weight, Option<f64>),
+            name!(created, bool),
+        ),
+    >,
+    ErrorReport,
+> {
+    let subject = current_voter()?;
+    let changes = meritrank_add(&subject, object, amount)?;
+    persist::flush()?;
+    Ok(changes)
+}
+
+// Functions that write arbitrary edges or otherwise change the graph, including the UUID
+// overloads of meritrank_add and meritrank_delete, are restricted to the roles they are
+// granted to, so that roles allowed to vote cannot forge the votes of others. meritrank_vote
+// and the vote trigger run with the rights of the extension owner to call them.
+extension_sql!(
+    r#"
+DO $$
+DECLARE
+    primitive regprocedure;
+BEGIN
+    FOR primitive IN
+        SELECT p.oid::regprocedure FROM pg_catalog.pg_proc p
+        WHERE p.pronamespace = '@extschema@'::regnamespace
+          AND (p.proname = ANY (ARRAY[
+                  'meritrank_add', 'meritrank_add_id', 'meritrank_delete', 'meritrank_delete_id',
+                  'meritrank_add_batch', 'meritrank_add_jsonb', 'meritrank_add_node',
+                  'meritrank_add_vote', 'meritrank_clear', 'meritrank_rename_node',
+                  'meritrank_merge_nodes', 'meritrank_compact', 'meritrank_freeze',
+                  'meritrank_thaw', 'meritrank_drop_walks', 'meritrank_set_node_type',
+                  'meritrank_set_node_property', 'meritrank_generate_graph',
+                  'meritrank_load_from', 'meritrank_import_csv', 'meritrank_build_agg',
+                  'meritrank_rebuild_from_votes', 'meritrank_bootstrap',
+                  'meritrank_install_triggers', 'meritrank_sync', 'meritrank_restore',
+                  'meritrank_flush', 'meritrank_outbox_prune', 'meritrank_create_graph_table',
+                  'insert_and_trigger', 'meritrank_vote_trigger'
+              ])
+              OR p.proname LIKE '%build_agg_state')
+    LOOP
+        EXECUTE format('REVOKE EXECUTE ON FUNCTION %s FROM PUBLIC', primitive);
+    END LOOP;
+END
+$$;
+
+ALTER FUNCTION meritrank_vote(text, float8)
+    SECURITY DEFINER SET search_path = @extschema@, pg_temp;
+ALTER FUNCTION meritrank_vote_trigger()
+    SECURITY DEFINER SET search_path = @extschema@, pg_temp;
+"#,
+    name = "restrict_graph_writes",
+    finalize
+);
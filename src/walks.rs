@@ -0,0 +1,410 @@
+// Standard library imports
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::UNIX_EPOCH;
+
+// External crate imports
+use lazy_static::lazy_static;
+use serde_json::{json, Map, Value};
+
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::{current_timestamp, lock_graph, timestamp_to_pg, GraphSingleton};
+use crate::guc;
+use crate::lib_graph::{
+    Connectivity, Convergence, EdgeDecay, MeritRank, NodeId, Timestamp, WalkOffload,
+};
+use crate::loader::require_superuser;
+use crate::parallel;
+use crate::stats;
+
+/// Number of seconds in a day, the unit of `meritrank.decay_half_life`.
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Number of bytes in a megabyte, the unit of `meritrank.max_walk_memory`.
+const BYTES_PER_MEGABYTE: usize = 1024 * 1024;
+
+/// Whether the stored walks are to be dropped, see `request_spill()`.
+static SPILL_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Number of consecutive convergence checks the top of the ranking must be stable for.
+const CONVERGENCE_STABLE_CHECKS: usize = 2;
+
+// Singleton instance
+lazy_static! {
+    pub static ref WALK_STORE: Mutex<WalkStore> = Mutex::new(WalkStore::new());
+}
+
+/// Random walks kept between calculations, valid for a single generation of the graph.
+///
+/// Lock order: `WALK_STORE` is never locked while holding `GRAPH`.
+pub struct WalkStore {
+    merit_rank: Option<MeritRank>,
+    generation: u64,
+}
+
+impl WalkStore {
+    /// Creates a new empty store.
+    pub fn new() -> Self {
+        WalkStore {
+            merit_rank: None,
+            generation: 0,
+        }
+    }
+
+    /// Returns the stored `MeritRank` instance if it was built from the given generation.
+    pub fn current(&mut self, generation: u64) -> Option<&mut MeritRank> {
+        match self.merit_rank {
+            Some(ref mut merit_rank) if self.generation == generation => Some(merit_rank),
+            _ => None,
+        }
+    }
+
+    /// Replaces the stored instance, dropping all walks of the previous generation.
+    pub fn replace(&mut self, merit_rank: MeritRank, generation: u64) {
+        self.merit_rank = Some(merit_rank);
+        self.generation = generation;
+    }
+
+    /// Returns the stored instance, whatever generation it was built from.
+    pub fn get(&self) -> Option<&MeritRank> {
+        self.merit_rank.as_ref()
+    }
+
+    /// Returns the stored instance mutably, whatever generation it was built from.
+    pub fn get_mut(&mut self) -> Option<&mut MeritRank> {
+        self.merit_rank.as_mut()
+    }
+
+    /// Returns the generation of the graph the stored instance was built from.
+    pub fn get_generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Drops the stored instance with all of its walks.
+    pub fn clear(&mut self) {
+        self.merit_rank = None;
+    }
+}
+
+impl Default for WalkStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Locks the walk store.
+///
+/// A calculation aborted by query cancellation leaves the store poisoned with partially
+/// updated walks, so they are dropped and the store is unlocked normally again.
+pub fn lock_store() -> Result<MutexGuard<'static, WalkStore>, GraphManipulationError> {
+    let mut store = WALK_STORE.lock().unwrap_or_else(|poisoned| {
+        WALK_STORE.clear_poison();
+        let mut store = poisoned.into_inner();
+        store.clear();
+        store
+    });
+    if SPILL_REQUESTED.swap(false, Ordering::Relaxed) {
+        store.clear();
+    }
+    Ok(store)
+}
+
+/// Has the stored walks dropped the next time the store is locked, to free memory when
+/// the graph exceeds its budget. Can be called while holding `GRAPH`.
+pub fn request_spill() {
+    SPILL_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Drops the stored walks now if that was requested. Must be called without holding `GRAPH`.
+pub fn spill_if_requested() -> Result<(), GraphManipulationError> {
+    if SPILL_REQUESTED.load(Ordering::Relaxed) {
+        lock_store()?;
+    }
+    Ok(())
+}
+
+/// Records that the stored walks of `ego` were used now, so that they are kept longer when
+/// `meritrank.max_walk_memory` is exceeded. Must be called without holding `GRAPH`.
+pub fn mark_used(ego: NodeId) -> Result<(), GraphManipulationError> {
+    if let Some(merit_rank) = lock_store()?.get_mut() {
+        merit_rank.mark_used(ego);
+    }
+    Ok(())
+}
+
+/// Runs `f` on the stored `MeritRank` instance, rebuilding it first if the graph has
+/// changed since it was built. Walks generated by `f` stay available to later calls.
+pub fn with_current_rank<R>(
+    generation: u64,
+    f: impl FnOnce(&mut MeritRank, u64) -> Result<R, GraphManipulationError>,
+) -> Result<R, GraphManipulationError> {
+    let mut store = lock_store()?;
+    if store.current(generation).is_none() {
+        // Build the new instance without holding the store, to respect the lock order
+        drop(store);
+        let (merit_rank, generation) = GraphSingleton::get_rank_with_generation()?;
+        store = lock_store()?;
+        store.replace(merit_rank, generation);
+    }
+
+    let generation = store.get_generation();
+    let merit_rank = store.get_mut().expect("walk store was populated above");
+    configure(merit_rank);
+
+    let result = f(merit_rank, generation);
+    enforce_memory_limit(merit_rank);
+    result
+}
+
+/// Drops the walks of the egos used least recently while the stored walks exceed
+/// `meritrank.max_walk_memory`, recording the evictions in the runtime statistics.
+fn enforce_memory_limit(merit_rank: &mut MeritRank) {
+    let max_walk_memory = guc::MAX_WALK_MEMORY.get();
+    if max_walk_memory <= 0 {
+        return;
+    }
+
+    let (egos, walks) = merit_rank.evict_walks(max_walk_memory as usize * BYTES_PER_MEGABYTE);
+    if egos > 0 {
+        stats::record_walk_eviction(egos, walks);
+    }
+}
+
+/// Applies the walk settings (`meritrank.parallelism`, `meritrank.seed` and
+/// `meritrank.decay_half_life`) to a `MeritRank` instance, and has it check for query
+/// cancellation while generating walks, and generate them on background workers if
+/// `meritrank.parallel_workers` is set.
+pub fn configure(merit_rank: &mut MeritRank) {
+    merit_rank.set_parallelism(guc::PARALLELISM.get() as usize);
+    merit_rank.set_seed(
+        Some(guc::SEED.get())
+            .filter(|&seed| seed >= 0)
+            .map(|seed| seed as u64),
+    );
+
+    // Ages are measured up to the time the instance was built, so that walks stay
+    // valid until the graph or the half-life changes
+    let half_life = guc::DECAY_HALF_LIFE.get() * SECONDS_PER_DAY;
+    let reference_time = merit_rank
+        .get_decay()
+        .filter(|decay| decay.get_half_life() == half_life)
+        .map_or_else(current_timestamp, |decay| decay.get_reference_time());
+    merit_rank.set_decay(EdgeDecay::new(half_life, reference_time));
+    merit_rank.set_max_walk_length(
+        Some(guc::MAX_WALK_LENGTH.get())
+            .filter(|&max_walk_length| max_walk_length > 0)
+            .map(|max_walk_length| max_walk_length as usize),
+    );
+    merit_rank.set_connectivity(
+        Some(guc::CONNECTIVITY_DECAY.get())
+            .filter(|&decay| decay > 0.0)
+            .map(|decay| Connectivity {
+                min_predecessors: guc::CONNECTIVITY_MIN_PREDECESSORS.get() as usize,
+                decay,
+            }),
+    );
+
+    let top_k = guc::CONVERGENCE_TOP_K.get();
+    merit_rank.set_convergence(
+        Some(top_k)
+            .filter(|&top_k| top_k > 0)
+            .map(|top_k| Convergence {
+                batch_size: guc::CONVERGENCE_BATCH_SIZE.get() as usize,
+                top_k: top_k as usize,
+                stable_checks: CONVERGENCE_STABLE_CHECKS,
+            }),
+    );
+
+    // Query cancellation and statement_timeout abort calculations between batches; a time
+    // budget applies to a single call and is set by the caller
+    merit_rank.set_interrupt_check(Some(|| {
+        check_for_interrupts!();
+    }));
+    merit_rank.set_deadline(None);
+    merit_rank.set_walk_offload(
+        Some(parallel::offload_walks as WalkOffload).filter(|_| guc::PARALLEL_WORKERS.get() > 0),
+    );
+}
+
+#[pg_extern]
+/// Discards stored random walks, for one ego or for everyone.
+///
+/// # Arguments
+///
+/// * `ego` - The ego whose walks are dropped. `NULL` drops the walks of every ego.
+///
+/// # Returns
+///
+/// The number of dropped walks.
+pub fn meritrank_drop_walks(ego: default!(Option<&str>, "NULL")) -> Result<i64, ErrorReport> {
+    let ego_id = ego.map(GraphSingleton::node_name_to_id).transpose()?;
+
+    let mut store = lock_store()?;
+    let merit_rank = match store.get_mut() {
+        Some(merit_rank) => merit_rank,
+        None => return Ok(0),
+    };
+
+    let dropped = match ego_id {
+        Some(ego_id) => merit_rank.drop_walks(ego_id),
+        None => {
+            let dropped = merit_rank
+                .get_calculated_egos()
+                .into_iter()
+                .map(|ego_id| merit_rank.drop_walks(ego_id))
+                .sum();
+            store.clear();
+            dropped
+        }
+    };
+
+    Ok(dropped as i64)
+}
+
+#[pg_extern]
+/// Describes the random walks stored for an ego, to check whether walks are being
+/// reused between calculations.
+///
+/// # Arguments
+///
+/// * `ego` - The ego whose walks are described.
+///
+/// # Returns
+///
+/// A single row with the number of stored walks starting from the ego, their average
+/// length in nodes, when they were generated, the generation of the graph they were
+/// generated from, and whether that is still the current generation, i.e. whether the
+/// next calculation can reuse them. `average_length` and `generated_at` are `NULL` when
+/// no walks are stored.
+pub fn meritrank_walk_stats(
+    ego: &str,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(walks, i64),
+            name!(average_length, Option<f64>),
+            name!(generated_at, Option<TimestampWithTimeZone>),
+            name!(graph_generation, i64),
+            name!(current, bool),
+        ),
+    >,
+    ErrorReport,
+> {
+    let ego_id = GraphSingleton::node_name_to_id(ego)?;
+    let current_generation = lock_graph()?.get_generation();
+
+    let store = lock_store()?;
+    let row = match store.get() {
+        Some(merit_rank) => {
+            let storage = merit_rank.get_walk_storage();
+            let walks = storage.count_walks_from_node(ego_id);
+            let generated_at = storage
+                .get_generated_at(ego_id)
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .and_then(|elapsed| timestamp_to_pg(elapsed.as_secs() as Timestamp));
+            (
+                walks as i64,
+                storage.average_walk_length(ego_id),
+                generated_at,
+                store.get_generation() as i64,
+                walks > 0 && store.get_generation() == current_generation,
+            )
+        }
+        None => (0, None, None, current_generation as i64, false),
+    };
+
+    Ok(TableIterator::new(std::iter::once(row)))
+}
+
+#[pg_extern]
+/// Serializes the random walks stored for an ego, with the hits counted from them, to
+/// reproduce ranking bugs that only manifest with specific stored walks offline.
+///
+/// The object has the keys `ego`, `graph_generation` (of the graph the walks were
+/// generated from), `params` (the fingerprint of the walk parameters), `walks` (the id and
+/// node names of every walk starting from the ego), `positions` (for every node, the
+/// walks of the ego passing it and the position the node is indexed at), `hits` and
+/// `negative_hits`. Without stored walks, `walks` is empty and `params` is `null`.
+///
+/// Only superusers may call this function, since the walks reveal the whole neighbourhood
+/// of the ego.
+pub fn meritrank_debug_dump(ego: &str) -> Result<JsonB, ErrorReport> {
+    require_superuser("meritrank_debug_dump")?;
+
+    let snapshot = GraphSingleton::get_snapshot()?;
+    let ego_id = snapshot.node_id(ego)?;
+    let names: HashMap<NodeId, &str> = snapshot
+        .node_names
+        .iter()
+        .map(|(name, &id)| (id, name.as_str()))
+        .collect();
+    let name = |node: &NodeId| names.get(node).copied().unwrap_or_default().to_string();
+
+    let store = lock_store()?;
+    let mut walks = Vec::new();
+    let mut positions = Map::new();
+    let mut hits = Map::new();
+    let mut negative_hits = Map::new();
+    let mut params = Value::Null;
+
+    if let Some(merit_rank) = store.get() {
+        let storage = merit_rank.get_walk_storage();
+        params = storage.get_params(ego_id).map_or(Value::Null, Value::from);
+
+        for (node, pos_walks) in storage.get_walks() {
+            let entries: Vec<Value> = pos_walks
+                .iter()
+                .filter(|(_, pos_walk)| pos_walk.get_walk().first_node() == Some(ego_id))
+                .map(|(id, pos_walk)| {
+                    json!({
+                        "walk": id.to_string(),
+                        "pos": pos_walk.get_pos(),
+                    })
+                })
+                .collect();
+            if entries.is_empty() {
+                continue;
+            }
+            if *node == ego_id {
+                walks.extend(
+                    pos_walks
+                        .iter()
+                        .filter(|(_, pos_walk)| pos_walk.get_pos() == 0)
+                        .map(|(id, pos_walk)| {
+                            let nodes: Vec<String> = pos_walk.get_walk().iter().map(name).collect();
+                            json!({"id": id.to_string(), "nodes": nodes})
+                        }),
+                );
+            }
+            positions.insert(name(node), Value::from(entries));
+        }
+
+        if let Some(counter) = merit_rank.get_personal_hits().get(&ego_id) {
+            for (node, count) in counter.get_tree_map() {
+                hits.insert(name(node), Value::from(*count));
+            }
+        }
+        if let Some(penalties) = merit_rank.get_neg_hits().get(&ego_id) {
+            for (node, penalty) in penalties {
+                negative_hits.insert(name(node), Value::from(*penalty));
+            }
+        }
+    }
+
+    Ok(JsonB(json!({
+        "ego": ego,
+        "graph_generation": store.get_generation(),
+        "params": params,
+        "walks": walks,
+        "positions": positions,
+        "hits": hits,
+        "negative_hits": negative_hits,
+    })))
+}
@@ -0,0 +1,204 @@
+// Library for PostgreSQL extensions
+use pgx::pg_sys::panic::ErrorReport;
+use pgx::*;
+
+// Current crate (`crate::`) imports
+use crate::error::GraphManipulationError;
+use crate::graph::{add_edges, meritrank_add, meritrank_clear};
+use crate::loader::{quote_qualified_identifier, LOAD_BATCH_SIZE};
+
+extension_sql!(
+    r#"
+CREATE TABLE meritrank_weight_map (
+    source_type TEXT   NOT NULL,
+    vote_value  FLOAT8 NOT NULL,
+    weight      FLOAT8 NOT NULL,
+    PRIMARY KEY (source_type, vote_value)
+);
+COMMENT ON TABLE meritrank_weight_map IS
+    'Translation of application vote values to edge weights, per source table or vote type.';
+SELECT pg_catalog.pg_extension_config_dump('meritrank_weight_map', '');
+"#,
+    name = "create_weight_map"
+);
+
+#[pg_extern]
+/// Sets the edge weight used for a vote value of the given source type.
+///
+/// # Arguments
+///
+/// * `source_type` - The source table or vote type, e.g. `vote_user`.
+/// * `vote_value` - The value stored by the application, e.g. `1` for a like.
+/// * `weight` - The weight of the edge created for that value.
+pub fn meritrank_set_weight(
+    source_type: &str,
+    vote_value: f64,
+    weight: f64,
+) -> Result<(), ErrorReport> {
+    if !weight.is_finite() {
+        return Err(GraphManipulationError::WeightExtractionFailure(format!(
+            "weight for {} = {} must be a finite number",
+            source_type, vote_value
+        ))
+        .into());
+    }
+
+    Spi::run_with_args(
+        "INSERT INTO meritrank_weight_map (source_type, vote_value, weight) VALUES ($1, $2, $3) \
+         ON CONFLICT (source_type, vote_value) DO UPDATE SET weight = EXCLUDED.weight",
+        Some(vec![
+            (PgBuiltInOids::TEXTOID.oid(), source_type.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), vote_value.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), weight.into_datum()),
+        ]),
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()).into())
+}
+
+#[pg_extern]
+/// Removes the weight translation of a vote value. Returns `true` if a mapping was removed.
+pub fn meritrank_unset_weight(source_type: &str, vote_value: f64) -> Result<bool, ErrorReport> {
+    let removed = Spi::get_one_with_args::<bool>(
+        "WITH removed AS ( \
+             DELETE FROM meritrank_weight_map WHERE source_type = $1 AND vote_value = $2 \
+             RETURNING 1 \
+         ) SELECT count(*) > 0 FROM removed",
+        vec![
+            (PgBuiltInOids::TEXTOID.oid(), source_type.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), vote_value.into_datum()),
+        ],
+    )
+    .map_err(|e| GraphManipulationError::GraphWriteFailure(e.to_string()))?;
+
+    Ok(removed.unwrap_or(false))
+}
+
+#[pg_extern]
+/// Translates a vote value of the given source type into an edge weight.
+///
+/// Values without a mapping in `meritrank_weight_map` are used as weights unchanged.
+pub fn meritrank_translate_weight(source_type: &str, vote_value: f64) -> Result<f64, ErrorReport> {
+    let weight = Spi::get_one_with_args::<f64>(
+        "SELECT weight FROM meritrank_weight_map WHERE source_type = $1 AND vote_value = $2",
+        vec![
+            (PgBuiltInOids::TEXTOID.oid(), source_type.into_datum()),
+            (PgBuiltInOids::FLOAT8OID.oid(), vote_value.into_datum()),
+        ],
+    )
+    .map_err(|e| GraphManipulationError::WeightExtractionFailure(e.to_string()))?;
+
+    Ok(weight.unwrap_or(vote_value))
+}
+
+#[pg_extern]
+/// Adds an edge for an application vote, translating its value with `meritrank_translate_weight`.
+///
+/// Intended to be called from the sync triggers with `TG_TABLE_NAME` as the source type.
+///
+/// # Returns
+///
+/// The weight of the added edge.
+pub fn meritrank_add_vote(
+    source_type: &str,
+    subject: &str,
+    object: &str,
+    vote_value: f64,
+) -> Result<f64, ErrorReport> {
+    let weight = meritrank_translate_weight(source_type, vote_value)?;
+
+    meritrank_add(subject, object, weight)?;
+
+    Ok(weight)
+}
+
+#[pg_extern]
+/// Rebuilds the in-memory graph from application vote tables, translating vote values
+/// as `meritrank_add_vote` does with the unqualified table name as the source type.
+///
+/// The graph is cleared first, in memory and in the `graph` table, and the votes are
+/// applied with `apply_votes`, so the table ends up holding exactly the voted edges. The
+/// tables need `subject`, `object` and `amount` columns; rows with `NULL` values or a
+/// self-reference are skipped.
+///
+/// # Returns
+///
+/// The number of edges added or updated while rebuilding.
+pub fn meritrank_rebuild_from_votes(tables: Vec<String>) -> Result<i64, ErrorReport> {
+    meritrank_clear(true)?;
+
+    let mut applied: i64 = 0;
+    for table in &tables {
+        let source_type = table.rsplit('.').next().unwrap_or(table);
+        applied += apply_votes(&quote_qualified_identifier(table), source_type)?;
+    }
+
+    Ok(applied)
+}
+
+/// Adds an edge for every vote of a table with `subject`, `object` and `amount` columns,
+/// translating vote values of `source_type` as `meritrank_add_vote` does. The edges are
+/// applied like `meritrank_add_batch` in batches of `LOAD_BATCH_SIZE` rows; rows with
+/// `NULL` values or a self-reference are skipped.
+///
+/// # Returns
+///
+/// The number of edges added or updated.
+pub fn apply_votes(relation: &str, source_type: &str) -> Result<i64, GraphManipulationError> {
+    let query = format!(
+        "SELECT v.subject::text, v.object::text, coalesce(m.weight, v.amount::float8) \
+         FROM {} v \
+         LEFT JOIN meritrank_weight_map m \
+             ON m.source_type = $1 AND m.vote_value = v.amount::float8 \
+         WHERE v.amount IS NOT NULL AND v.subject::text <> v.object::text",
+        relation
+    );
+
+    Spi::connect(|client| -> Result<i64, GraphManipulationError> {
+        let mut cursor = client.open_cursor(
+            &query,
+            Some(vec![(
+                PgBuiltInOids::TEXTOID.oid(),
+                source_type.into_datum(),
+            )]),
+        );
+        let mut applied: i64 = 0;
+        loop {
+            let batch = cursor
+                .fetch(LOAD_BATCH_SIZE)
+                .map_err(|e| GraphManipulationError::FetchRecordsFailure(e.to_string()))?;
+            if batch.is_empty() {
+                break;
+            }
+
+            let mut votes: Vec<(String, String, f64)> = Vec::new();
+            for row in batch {
+                if let (Some(subject), Some(object), Some(weight)) = (
+                    row.get::<String>(1)?,
+                    row.get::<String>(2)?,
+                    row.get::<f64>(3)?,
+                ) {
+                    votes.push((subject, object, weight));
+                }
+            }
+            let edges: Vec<(&str, &str, f64)> = votes
+                .iter()
+                .map(|(subject, object, weight)| (subject.as_str(), object.as_str(), *weight))
+                .collect();
+            applied += add_edges(&edges)? as i64;
+        }
+        Ok(applied)
+    })
+}
+
+// Replaces the PL/pgSQL procedure that deployments used to maintain by hand
+extension_sql!(
+    r#"
+CREATE PROCEDURE update_graph_procedure(
+    tables text[] DEFAULT ARRAY['vote_beacon', 'vote_comment', 'vote_user']
+)
+    LANGUAGE sql
+    AS $$ SELECT meritrank_rebuild_from_votes(tables) $$;
+"#,
+    name = "create_update_graph_procedure",
+    requires = [meritrank_rebuild_from_votes]
+);